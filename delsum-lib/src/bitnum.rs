@@ -1,5 +1,7 @@
 use num_traits::Num;
 use std::ops;
+mod wide;
+pub use wide::U512;
 /// Me: can I have a trait for either u8, u16, u32, u64 or u128?
 /// Mom: We have a trait for either u8, u16, u32, u64 or u128 at home
 /// trait for either u8, u16, u32, u64 or u128 at home:
@@ -21,6 +23,8 @@ pub trait BitNum:
     + Eq
     + Ord
     + From<u8>
+    + num_traits::FromPrimitive
+    + num_traits::ToPrimitive
     + std::convert::TryInto<u8>
     + std::fmt::Debug
     + std::fmt::LowerHex
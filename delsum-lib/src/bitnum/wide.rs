@@ -0,0 +1,444 @@
+//! A fixed-size 512 bit unsigned integer, for CRCs that are wider than 128 bits.
+//!
+//! It only implements what is needed for `BitNum`, with the arithmetic being
+//! done limb by limb, so don't expect it to be as fast as the primitive types.
+use super::BitNum;
+use num_traits::{
+    CheckedAdd, CheckedMul, CheckedSub, FromPrimitive, Num, One, ToPrimitive, WrappingAdd,
+    WrappingMul, WrappingSub, Zero,
+};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops;
+
+const LIMBS: usize = 8;
+
+/// An unsigned integer with 512 bits, stored as little endian 64 bit limbs.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct U512([u64; LIMBS]);
+
+/// The error when parsing a `U512` from a string fails.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseU512Error;
+
+impl fmt::Display for ParseU512Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid 512 bit integer")
+    }
+}
+
+impl std::error::Error for ParseU512Error {}
+
+impl U512 {
+    /// The number of bits of the type.
+    pub const BITS: usize = 64 * LIMBS;
+    fn overflowing_add(self, rhs: Self) -> (Self, bool) {
+        let mut ret = [0u64; LIMBS];
+        let mut carry = false;
+        for (i, r) in ret.iter_mut().enumerate() {
+            let (s, c1) = self.0[i].overflowing_add(rhs.0[i]);
+            let (s, c2) = s.overflowing_add(carry as u64);
+            *r = s;
+            carry = c1 || c2;
+        }
+        (U512(ret), carry)
+    }
+    fn overflowing_sub(self, rhs: Self) -> (Self, bool) {
+        let mut ret = [0u64; LIMBS];
+        let mut borrow = false;
+        for (i, r) in ret.iter_mut().enumerate() {
+            let (s, b1) = self.0[i].overflowing_sub(rhs.0[i]);
+            let (s, b2) = s.overflowing_sub(borrow as u64);
+            *r = s;
+            borrow = b1 || b2;
+        }
+        (U512(ret), borrow)
+    }
+    fn overflowing_mul(self, rhs: Self) -> (Self, bool) {
+        let mut ret = [0u64; LIMBS];
+        let mut overflow = false;
+        for i in 0..LIMBS {
+            if self.0[i] == 0 {
+                continue;
+            }
+            let mut carry = 0u128;
+            for j in 0..LIMBS {
+                let prod = u128::from(self.0[i]) * u128::from(rhs.0[j]) + carry;
+                if i + j < LIMBS {
+                    let sum = prod + u128::from(ret[i + j]);
+                    ret[i + j] = sum as u64;
+                    carry = sum >> 64;
+                } else {
+                    // everything that lands above the top limb is lost
+                    overflow |= prod != 0;
+                    carry = prod >> 64;
+                }
+            }
+            overflow |= carry != 0;
+        }
+        (U512(ret), overflow)
+    }
+    fn bit(&self, i: usize) -> bool {
+        self.0[i / 64] >> (i % 64) & 1 == 1
+    }
+    fn leading_zeros(&self) -> usize {
+        for i in (0..LIMBS).rev() {
+            if self.0[i] != 0 {
+                return (LIMBS - 1 - i) * 64 + self.0[i].leading_zeros() as usize;
+            }
+        }
+        Self::BITS
+    }
+    // simple binary long division
+    fn div_rem(self, rhs: Self) -> (Self, Self) {
+        if rhs.is_zero() {
+            panic!("attempt to divide by zero");
+        }
+        let mut quot = U512::zero();
+        let mut rem = U512::zero();
+        for i in (0..Self::BITS - self.leading_zeros()).rev() {
+            rem = rem << 1;
+            rem.0[0] |= self.bit(i) as u64;
+            if rem >= rhs {
+                rem = rem.overflowing_sub(rhs).0;
+                quot.0[i / 64] |= 1 << (i % 64);
+            }
+        }
+        (quot, rem)
+    }
+}
+
+impl Ord for U512 {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.iter().rev().cmp(other.0.iter().rev())
+    }
+}
+
+impl PartialOrd for U512 {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl ops::Add for U512 {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self {
+        self.checked_add(&rhs)
+            .expect("attempt to add with overflow")
+    }
+}
+
+impl ops::Sub for U512 {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self {
+        self.checked_sub(&rhs)
+            .expect("attempt to subtract with overflow")
+    }
+}
+
+impl ops::Mul for U512 {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self {
+        self.checked_mul(&rhs)
+            .expect("attempt to multiply with overflow")
+    }
+}
+
+impl ops::Div for U512 {
+    type Output = Self;
+    fn div(self, rhs: Self) -> Self {
+        self.div_rem(rhs).0
+    }
+}
+
+impl ops::Rem for U512 {
+    type Output = Self;
+    fn rem(self, rhs: Self) -> Self {
+        self.div_rem(rhs).1
+    }
+}
+
+impl ops::BitXor for U512 {
+    type Output = Self;
+    fn bitxor(mut self, rhs: Self) -> Self {
+        for (a, b) in self.0.iter_mut().zip(rhs.0.iter()) {
+            *a ^= b;
+        }
+        self
+    }
+}
+
+impl ops::BitAnd for U512 {
+    type Output = Self;
+    fn bitand(mut self, rhs: Self) -> Self {
+        for (a, b) in self.0.iter_mut().zip(rhs.0.iter()) {
+            *a &= b;
+        }
+        self
+    }
+}
+
+impl ops::Not for U512 {
+    type Output = Self;
+    fn not(mut self) -> Self {
+        for a in self.0.iter_mut() {
+            *a = !*a;
+        }
+        self
+    }
+}
+
+// shifting by the width or more gives zero instead of panicking
+impl ops::Shl<usize> for U512 {
+    type Output = Self;
+    fn shl(self, rhs: usize) -> Self {
+        let mut ret = [0u64; LIMBS];
+        let (limbs, bits) = (rhs / 64, rhs % 64);
+        for (i, r) in ret.iter_mut().enumerate().skip(limbs) {
+            *r = self.0[i - limbs] << bits;
+            if bits > 0 && i > limbs {
+                *r |= self.0[i - limbs - 1] >> (64 - bits);
+            }
+        }
+        U512(ret)
+    }
+}
+
+impl ops::Shr<usize> for U512 {
+    type Output = Self;
+    fn shr(self, rhs: usize) -> Self {
+        let mut ret = [0u64; LIMBS];
+        let (limbs, bits) = (rhs / 64, rhs % 64);
+        for (i, r) in ret.iter_mut().enumerate().take(LIMBS.saturating_sub(limbs)) {
+            *r = self.0[i + limbs] >> bits;
+            if bits > 0 && i + limbs + 1 < LIMBS {
+                *r |= self.0[i + limbs + 1] << (64 - bits);
+            }
+        }
+        U512(ret)
+    }
+}
+
+impl Zero for U512 {
+    fn zero() -> Self {
+        U512([0; LIMBS])
+    }
+    fn is_zero(&self) -> bool {
+        self.0.iter().all(|&x| x == 0)
+    }
+}
+
+impl One for U512 {
+    fn one() -> Self {
+        U512::from(1u8)
+    }
+}
+
+impl Num for U512 {
+    type FromStrRadixErr = ParseU512Error;
+    fn from_str_radix(s: &str, radix: u32) -> Result<Self, ParseU512Error> {
+        if s.is_empty() {
+            return Err(ParseU512Error);
+        }
+        let radix_num = U512::from_u32(radix).ok_or(ParseU512Error)?;
+        let mut ret = U512::zero();
+        for c in s.chars() {
+            let digit = c.to_digit(radix).ok_or(ParseU512Error)?;
+            ret = ret
+                .checked_mul(&radix_num)
+                .and_then(|x| x.checked_add(&U512::from(digit as u8)))
+                .ok_or(ParseU512Error)?;
+        }
+        Ok(ret)
+    }
+}
+
+impl WrappingAdd for U512 {
+    fn wrapping_add(&self, v: &Self) -> Self {
+        self.overflowing_add(*v).0
+    }
+}
+
+impl WrappingSub for U512 {
+    fn wrapping_sub(&self, v: &Self) -> Self {
+        self.overflowing_sub(*v).0
+    }
+}
+
+impl WrappingMul for U512 {
+    fn wrapping_mul(&self, v: &Self) -> Self {
+        self.overflowing_mul(*v).0
+    }
+}
+
+impl CheckedAdd for U512 {
+    fn checked_add(&self, v: &Self) -> Option<Self> {
+        match self.overflowing_add(*v) {
+            (x, false) => Some(x),
+            (_, true) => None,
+        }
+    }
+}
+
+impl CheckedSub for U512 {
+    fn checked_sub(&self, v: &Self) -> Option<Self> {
+        match self.overflowing_sub(*v) {
+            (x, false) => Some(x),
+            (_, true) => None,
+        }
+    }
+}
+
+impl CheckedMul for U512 {
+    fn checked_mul(&self, v: &Self) -> Option<Self> {
+        match self.overflowing_mul(*v) {
+            (x, false) => Some(x),
+            (_, true) => None,
+        }
+    }
+}
+
+impl From<u8> for U512 {
+    fn from(x: u8) -> Self {
+        U512::from(u128::from(x))
+    }
+}
+
+impl From<u128> for U512 {
+    fn from(x: u128) -> Self {
+        let mut ret = [0u64; LIMBS];
+        ret[0] = x as u64;
+        ret[1] = (x >> 64) as u64;
+        U512(ret)
+    }
+}
+
+impl TryFrom<U512> for u8 {
+    type Error = ParseU512Error;
+    fn try_from(x: U512) -> Result<u8, ParseU512Error> {
+        x.to_u8().ok_or(ParseU512Error)
+    }
+}
+
+impl FromPrimitive for U512 {
+    fn from_i64(n: i64) -> Option<Self> {
+        u64::try_from(n).ok().and_then(Self::from_u64)
+    }
+    fn from_u64(n: u64) -> Option<Self> {
+        Some(U512::from(u128::from(n)))
+    }
+    fn from_u128(n: u128) -> Option<Self> {
+        Some(U512::from(n))
+    }
+}
+
+impl ToPrimitive for U512 {
+    fn to_i64(&self) -> Option<i64> {
+        self.to_u64().and_then(|x| i64::try_from(x).ok())
+    }
+    fn to_u64(&self) -> Option<u64> {
+        self.to_u128().and_then(|x| u64::try_from(x).ok())
+    }
+    fn to_u128(&self) -> Option<u128> {
+        if self.0[2..].iter().any(|&x| x != 0) {
+            return None;
+        }
+        Some(u128::from(self.0[0]) | u128::from(self.0[1]) << 64)
+    }
+}
+
+impl U512 {
+    fn hex_digits(&self, upper: bool) -> String {
+        let digits: String = self.0.iter().rev().map(|x| format!("{:016x}", x)).collect();
+        let digits = digits.trim_start_matches('0');
+        let digits = if digits.is_empty() { "0" } else { digits };
+        if upper {
+            digits.to_ascii_uppercase()
+        } else {
+            digits.to_owned()
+        }
+    }
+}
+
+impl fmt::LowerHex for U512 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad_integral(true, "0x", &self.hex_digits(false))
+    }
+}
+
+impl fmt::UpperHex for U512 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad_integral(true, "0x", &self.hex_digits(true))
+    }
+}
+
+impl fmt::Debug for U512 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#x}", self)
+    }
+}
+
+impl BitNum for U512 {
+    fn revbits(self) -> Self {
+        let mut ret = [0u64; LIMBS];
+        for (r, x) in ret.iter_mut().zip(self.0.iter().rev()) {
+            *r = x.reverse_bits();
+        }
+        U512(ret)
+    }
+    fn bits(&self) -> usize {
+        Self::BITS
+    }
+    fn trail_zeros(&self) -> u32 {
+        match self.0.iter().position(|&x| x != 0) {
+            Some(i) => 64 * i as u32 + self.0[i].trailing_zeros(),
+            None => Self::BITS as u32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn hex(s: &str) -> U512 {
+        U512::from_hex(s).unwrap()
+    }
+    #[test]
+    fn arith() {
+        let a = hex("123456789abcdef0123456789abcdef0123456789abcdef");
+        let b = hex("fedcba9876543210fedcba9876543210");
+        assert_eq!(a + b - b, a);
+        assert_eq!((a * b) / b, a);
+        assert_eq!((a * b + hex("1234")) % b, hex("1234"));
+        assert_eq!(U512::zero().wrapping_sub(&U512::one()), !U512::zero());
+        assert_eq!(U512::zero().checked_sub(&U512::one()), None);
+        assert_eq!((U512::one() << 511).checked_mul(&hex("2")), None);
+        assert_eq!(U512::from(0xffu8).to_u128(), Some(0xff));
+        assert_eq!((U512::one() << 128).to_u128(), None);
+    }
+    #[test]
+    fn bits() {
+        let a = hex("80000000000000000000000000000000000000001");
+        assert_eq!(a >> 160 << 160, U512::one() << 163);
+        assert_eq!(a << 512, U512::zero());
+        assert_eq!((a << 100) >> 100, a);
+        assert_eq!(a.revbits().revbits(), a);
+        assert_eq!(U512::one().revbits(), U512::one() << 511);
+        assert_eq!((a << 70).trail_zeros(), 70);
+        assert!(hex("10000000000000000000000000000000000000000") > hex("ffffffff"));
+    }
+    #[test]
+    fn format() {
+        let a = hex("0x1234abcd00000000000000000000000000000000");
+        assert_eq!(
+            format!("{:x}", a),
+            "1234abcd00000000000000000000000000000000"
+        );
+        assert_eq!(format!("{:#X}", hex("ab")), "0xAB");
+        assert_eq!(format!("{:08x}", hex("ab")), "000000ab");
+        assert_eq!(format!("{:x}", U512::zero()), "0");
+        assert!(U512::from_hex("12g").is_err());
+        assert!(U512::from_hex(&"f".repeat(129)).is_err());
+    }
+}
@@ -0,0 +1,272 @@
+//! An arbitrary affine map over GF(2) from the bits of a text with a fixed length to the checksum
+//!
+//! Each set bit of the text flips a fixed set of bits of the checksum, so the checksum is the checksum of the
+//! all-zero text xored with one column of a matrix for each set bit of the text.
+//! Custom LFSRs, xors of shifted words and syndromes of error-correcting codes are of this form,
+//! and so are CRCs and xorsums if the length of the text is fixed.
+//! Since the map is only defined for texts of one length, it does not implement `Digest`,
+//! and checksums are calculated with `Affine::checksum` instead.
+//!
+//! There are a number of parameters:
+//! * width: The number of bits of the checksum, at most 128
+//! * len: The number of bytes of the texts
+//! * init: The checksum of the text consisting of `len` zero bytes (default 0)
+//! * matrix: The columns of the matrix, as hexadecimal numbers which are padded to the number of digits of `width` bits and written one after another.
+//!   The first column belongs to the most significant bit of the first byte and the last one to the least significant bit of the last byte.
+//! * name: An optional name that gets used for display purposes
+//!
+//! There is no `check` parameter, since "123456789" usually has a different length than the texts.
+pub mod rev;
+use crate::bitnum::BitNum;
+use crate::checksum::CheckBuilderErr;
+use crate::keyval::KeyValIter;
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// A builder to set the various parameters for an affine map.
+///
+/// Example:
+/// ```
+/// # use delsum_lib::checksum::affine::Affine;
+/// // swaps the two bytes of the text
+/// let matrix = (0..16).map(|i| 1u128 << ((i + 8) % 16)).rev().collect();
+/// Affine::with_options()
+///     .width(16)
+///     .len(2)
+///     .matrix(matrix)
+///     .build()
+///     .is_ok();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct AffineBuilder {
+    width: Option<usize>,
+    len: Option<usize>,
+    init: Option<u128>,
+    matrix: Option<Vec<u128>>,
+    name: Option<String>,
+}
+
+impl AffineBuilder {
+    /// The width, in bits, of the checksum. Mandatory, at most 128.
+    pub fn width(&mut self, w: usize) -> &mut Self {
+        self.width = Some(w);
+        self
+    }
+    /// The length, in bytes, of the texts. Mandatory.
+    pub fn len(&mut self, l: usize) -> &mut Self {
+        self.len = Some(l);
+        self
+    }
+    /// The checksum of the all-zero text, optional, defaults to 0.
+    pub fn init(&mut self, i: u128) -> &mut Self {
+        self.init = Some(i);
+        self
+    }
+    /// The columns of the matrix, one for each bit of the text, starting with the most significant bit of the first byte. Mandatory.
+    pub fn matrix(&mut self, m: Vec<u128>) -> &mut Self {
+        self.matrix = Some(m);
+        self
+    }
+    /// An optional name that gets used for display purposes.
+    pub fn name(&mut self, n: &str) -> &mut Self {
+        self.name = Some(String::from(n));
+        self
+    }
+    /// Builds the algorithm, after validating the parameters.
+    pub fn build(&self) -> Result<Affine, CheckBuilderErr> {
+        let width = self
+            .width
+            .ok_or(CheckBuilderErr::MissingParameter("width"))?;
+        if width == 0 || width > 128 {
+            return Err(CheckBuilderErr::ValueOutOfRange("width"));
+        }
+        let len = self.len.ok_or(CheckBuilderErr::MissingParameter("len"))?;
+        let mask = u128::MAX >> (128 - width);
+        let init = self.init.unwrap_or(0);
+        if init & !mask != 0 {
+            return Err(CheckBuilderErr::ValueOutOfRange("init"));
+        }
+        let matrix = self
+            .matrix
+            .clone()
+            .ok_or(CheckBuilderErr::MissingParameter("matrix"))?;
+        if matrix.len() != 8 * len || matrix.iter().any(|col| col & !mask != 0) {
+            return Err(CheckBuilderErr::ValueOutOfRange("matrix"));
+        }
+        Ok(Affine {
+            width,
+            init,
+            matrix,
+            name: self.name.clone(),
+        })
+    }
+}
+
+/// An affine map from the bits of a text to the checksum.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Affine {
+    width: usize,
+    init: u128,
+    matrix: Vec<u128>,
+    name: Option<String>,
+}
+
+impl Affine {
+    /// Creates an `AffineBuilder`, for more information see its documentation.
+    pub fn with_options() -> AffineBuilder {
+        AffineBuilder::default()
+    }
+    /// The length of the texts the map is defined for.
+    pub fn text_len(&self) -> usize {
+        self.matrix.len() / 8
+    }
+    /// Calculates the checksum of a text, or `None` if the text does not have the right length.
+    pub fn checksum(&self, bytes: &[u8]) -> Option<u128> {
+        if bytes.len() != self.text_len() {
+            return None;
+        }
+        let mut sum = self.init;
+        for (byte, cols) in bytes.iter().zip(self.matrix.chunks(8)) {
+            for (i, col) in cols.iter().enumerate() {
+                if byte & (0x80 >> i) != 0 {
+                    sum ^= col;
+                }
+            }
+        }
+        Some(sum)
+    }
+}
+
+impl Display for Affine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.name {
+            Some(n) => write!(f, "{}", n),
+            None => {
+                write!(
+                    f,
+                    "affine width={} len={} init={:#x} matrix=",
+                    self.width,
+                    self.text_len(),
+                    self.init
+                )?;
+                let digits = (self.width - 1) / 4 + 1;
+                for col in &self.matrix {
+                    write!(f, "{:0digits$x}", col, digits = digits)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Parses the matrix, where each column has the number of hexadecimal digits of `width` bits.
+fn parse_matrix(s: &str, width: usize) -> Option<Vec<u128>> {
+    if width == 0 || !s.is_ascii() {
+        return None;
+    }
+    let digits = (width - 1) / 4 + 1;
+    s.as_bytes()
+        .chunks(digits)
+        .map(|col| match col.len() {
+            l if l == digits => u128::from_str_radix(std::str::from_utf8(col).ok()?, 16).ok(),
+            _ => None,
+        })
+        .collect()
+}
+
+impl FromStr for AffineBuilder {
+    /// See FromStr for Affine
+    fn from_str(s: &str) -> Result<AffineBuilder, CheckBuilderErr> {
+        let mut affine = Affine::with_options();
+        // the matrix can only be parsed when the width is known
+        let mut matrix = None;
+        for x in KeyValIter::new(s) {
+            let (current_key, current_val) = match x {
+                Err(key) => return Err(CheckBuilderErr::MalformedString(key)),
+                Ok(s) => s,
+            };
+            let affine_op = match current_key.as_str() {
+                "width" => usize::from_str(&current_val).ok().map(|x| affine.width(x)),
+                "len" => usize::from_str(&current_val).ok().map(|x| affine.len(x)),
+                "init" => u128::from_hex(&current_val).ok().map(|x| affine.init(x)),
+                "matrix" => {
+                    matrix = Some(current_val);
+                    Some(&mut affine)
+                }
+                "name" => Some(affine.name(&current_val)),
+                _ => return Err(CheckBuilderErr::UnknownKey(current_key)),
+            };
+            match affine_op {
+                Some(a) => affine = a.clone(),
+                None => return Err(CheckBuilderErr::MalformedString(current_key)),
+            }
+        }
+        if let Some(m) = matrix {
+            let width = affine
+                .width
+                .ok_or(CheckBuilderErr::MissingParameter("width"))?;
+            let m = parse_matrix(&m, width)
+                .ok_or_else(|| CheckBuilderErr::MalformedString("matrix".to_owned()))?;
+            affine.matrix(m);
+        }
+        Ok(affine)
+    }
+    type Err = CheckBuilderErr;
+}
+
+impl FromStr for Affine {
+    /// Construct a new affine map from a string specification.
+    ///
+    /// Example:
+    ///
+    /// width=4 len=1 init=3 matrix=8421f000
+    fn from_str(s: &str) -> Result<Affine, CheckBuilderErr> {
+        AffineBuilder::from_str(s)?.build()
+    }
+    type Err = CheckBuilderErr;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::crc::CRC;
+    use crate::checksum::Digest;
+    #[test]
+    fn crc_columns() {
+        let crc = CRC::<u16>::from_str("width=16 poly=0x1021 init=0xffff").unwrap();
+        let zero = crc.digest(&[0u8; 3][..]).unwrap();
+        let matrix = (0..24)
+            .map(|i| {
+                let mut text = [0u8; 3];
+                text[i / 8] = 0x80 >> (i % 8);
+                u128::from(crc.digest(&text[..]).unwrap() ^ zero)
+            })
+            .collect();
+        let affine = Affine::with_options()
+            .width(16)
+            .len(3)
+            .init(u128::from(zero))
+            .matrix(matrix)
+            .build()
+            .unwrap();
+        for text in [[1u8, 2, 3], [0xff, 0, 0x5a], [0x31, 0x32, 0x33]] {
+            let sum = crc.digest(&text[..]).unwrap();
+            assert_eq!(affine.checksum(&text), Some(u128::from(sum)));
+        }
+        assert_eq!(affine.checksum(&[0u8; 4]), None);
+        let model = affine.to_string();
+        assert_eq!(Affine::from_str(&model["affine".len()..]), Ok(affine));
+    }
+    #[test]
+    fn parse() {
+        let affine = Affine::from_str("width=4 len=1 init=3 matrix=8421f000").unwrap();
+        assert_eq!(affine.checksum(&[0x80]), Some(0xb));
+        assert_eq!(affine.checksum(&[0x0f]), Some(0xc));
+        assert_eq!(
+            affine.to_string(),
+            "affine width=4 len=1 init=0x3 matrix=8421f000"
+        );
+        assert!(Affine::from_str("width=4 len=2 matrix=8421f000").is_err());
+        assert!(Affine::from_str("width=8 len=1 matrix=8421f000").is_err());
+    }
+}
@@ -0,0 +1,427 @@
+//! This module contains the functions for fitting an affine map to files and their checksums.
+//!
+//! Each file with its checksum is an equation in the columns of the matrix and `init`, which is the same
+//! for every bit of the checksum. So the equations are solved for all bits at once with gaussian elimination,
+//! where a row consists of the bits of a file together with a 1 for `init`, and the right side is the checksum.
+//!
+//! To determine the map completely, there have to be `8*len + 1` affinely independent files of length `len`,
+//! which is usually the case for a few more random files than that.
+//! With less files, the map is still checked for consistency, but the checksum of a text can only be calculated
+//! if the text is an affine combination of the files (see `AffineFit::checksum`).
+//!
+//! Since a lot of affine maps are just CRCs, xorsums or rotxors, `reverse_affine` also tries those on the files,
+//! returning them instead of the bare matrix if they fit.
+use super::{Affine, AffineBuilder};
+use crate::checksum::{
+    crc::{rev::reverse_crc, CRC},
+    rotxor::{rev::reverse_rotxor, RotXor},
+    xorsum::{rev::reverse_xorsum, XorSum},
+    CheckReverserError, Digest,
+};
+use std::fmt::Display;
+
+/// A solution found by `reverse_affine`.
+pub enum AffineSolution {
+    Affine(Affine),
+    CRC(CRC<u128>),
+    XorSum(XorSum<u64>),
+    RotXor(RotXor<u64>),
+}
+
+impl AffineSolution {
+    fn checksum(&self, bytes: &[u8]) -> Option<u128> {
+        match self {
+            AffineSolution::Affine(a) => a.checksum(bytes),
+            AffineSolution::CRC(c) => c.digest(bytes).ok(),
+            AffineSolution::XorSum(x) => x.digest(bytes).ok().map(u128::from),
+            AffineSolution::RotXor(r) => r.digest(bytes).ok().map(u128::from),
+        }
+    }
+}
+
+impl Display for AffineSolution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AffineSolution::Affine(a) => a.fmt(f),
+            AffineSolution::CRC(c) => c.fmt(f),
+            AffineSolution::XorSum(x) => x.fmt(f),
+            AffineSolution::RotXor(r) => r.fmt(f),
+        }
+    }
+}
+
+/// Find an affine map (or a CRC, xorsum or rotxor that is equal to it) fitting the files.
+///
+/// `spec` contains the known parameters of the map (only `width`, `len` and `init` can be given).
+/// `chk_bytes` are pairs of files and their checksums, which all need to have length `len`.
+/// `verbosity` makes the function output what it is doing.
+///
+/// If the files are inconsistent, there are no solutions, and if there are not enough files to determine
+/// the map, an error is returned unless one of the other algorithms fits.
+/// The `width` parameter of the builder has to be set.
+pub fn reverse_affine(
+    spec: &AffineBuilder,
+    chk_bytes: &[(&[u8], u128)],
+    verbosity: u64,
+) -> impl Iterator<Item = Result<AffineSolution, CheckReverserError>> {
+    let solutions = match fit_affine(spec, chk_bytes, verbosity) {
+        Ok(fit) => find_solutions(&fit, chk_bytes, verbosity),
+        Err(e) => vec![Err(e)],
+    };
+    solutions.into_iter()
+}
+
+fn find_solutions(
+    fit: &AffineFit,
+    chk_bytes: &[(&[u8], u128)],
+    verbosity: u64,
+) -> Vec<Result<AffineSolution, CheckReverserError>> {
+    let log = |s: &str| {
+        if verbosity > 0 {
+            eprintln!("<affine> {}", s);
+        }
+    };
+    if !fit.is_consistent() {
+        log("files are not consistent with an affine map");
+        return Vec::new();
+    }
+    log("trying crc, xorsum and rotxor");
+    let width = fit.width;
+    // all files have the same length, so init = 0 is enough for the crc
+    let mut named = Vec::new();
+    if chk_bytes.len() > 1 {
+        let mut crc = CRC::<u128>::with_options();
+        crc.width(width).init(0);
+        named.extend(
+            reverse_crc(&crc, chk_bytes, 0)
+                .filter_map(Result::ok)
+                .map(AffineSolution::CRC),
+        );
+    }
+    if width <= 64 && !chk_bytes.is_empty() {
+        named.extend(
+            reverse_xorsum(XorSum::with_options().width(width), chk_bytes, 0)
+                .filter_map(Result::ok)
+                .map(AffineSolution::XorSum),
+        );
+        named.extend(
+            reverse_rotxor(RotXor::with_options().width(width), chk_bytes, 0)
+                .filter_map(Result::ok)
+                .map(AffineSolution::RotXor),
+        );
+    }
+    // a given init is not known to the other reversers, so the solutions are checked again
+    let named: Vec<_> = named
+        .into_iter()
+        .filter(|sol| fit.agrees(|text| sol.checksum(text)))
+        .map(Ok)
+        .collect();
+    if !named.is_empty() {
+        return named;
+    }
+    match fit.to_affine() {
+        Some(affine) => vec![Ok(AffineSolution::Affine(affine))],
+        None => {
+            log(&format!(
+                "{} more independent files are needed",
+                fit.unknowns()
+            ));
+            vec![Err(CheckReverserError::UnsuitableFiles(
+                "not enough independent files to determine the affine map",
+            ))]
+        }
+    }
+}
+
+/// Fits an affine map to the files and their checksums, without trying other algorithms.
+///
+/// The parameters are the same as for `reverse_affine`.
+pub fn fit_affine(
+    spec: &AffineBuilder,
+    chk_bytes: &[(&[u8], u128)],
+    verbosity: u64,
+) -> Result<AffineFit, CheckReverserError> {
+    let log = |s: &str| {
+        if verbosity > 0 {
+            eprintln!("<affine> {}", s);
+        }
+    };
+    let width = spec
+        .width
+        .ok_or(CheckReverserError::MissingParameter("width"))?;
+    if width == 0 || width > 128 {
+        return Err(CheckReverserError::ValueOutOfRange("width"));
+    }
+    if spec.matrix.is_some() {
+        return Err(CheckReverserError::ValueOutOfRange("matrix"));
+    }
+    let len = match (spec.len, chk_bytes.first()) {
+        (Some(l), _) => l,
+        (None, Some((f, _))) => f.len(),
+        (None, None) => {
+            return Err(CheckReverserError::MissingParameter(
+                "need at least one file or len",
+            ))
+        }
+    };
+    if chk_bytes.iter().any(|(f, _)| f.len() != len) {
+        return Err(CheckReverserError::UnsuitableFiles(
+            "files need to have the same length",
+        ));
+    }
+    let mask = u128::MAX >> (128 - width);
+    let mut fit = AffineFit {
+        width,
+        len,
+        rows: Vec::new(),
+        consistent: true,
+    };
+    if let Some(init) = spec.init {
+        fit.add(fit.row(&vec![0; len]), init & mask);
+    }
+    log("eliminating files");
+    for (f, chk) in chk_bytes {
+        fit.add(fit.row(f), chk & mask);
+    }
+    Ok(fit)
+}
+
+/// A row of the system of equations.
+///
+/// It contains the bits of a text in the order of the columns of the matrix, followed by
+/// one bit for `init`, together with the checksum.
+#[derive(Clone, Debug)]
+struct Row {
+    bits: Vec<u64>,
+    sum: u128,
+}
+
+impl Row {
+    fn get(&self, i: usize) -> bool {
+        self.bits[i / 64] >> (i % 64) & 1 == 1
+    }
+    fn lowest_bit(&self) -> Option<usize> {
+        self.bits
+            .iter()
+            .enumerate()
+            .find(|(_, x)| **x != 0)
+            .map(|(i, x)| 64 * i + x.trailing_zeros() as usize)
+    }
+    fn add(&mut self, other: &Row) {
+        for (a, b) in self.bits.iter_mut().zip(other.bits.iter()) {
+            *a ^= b;
+        }
+        self.sum ^= other.sum;
+    }
+}
+
+/// An affine map fitted to files by `fit_affine`.
+///
+/// The map may not be determined completely by the files, in which case only the checksums of
+/// some texts are known.
+#[derive(Clone, Debug)]
+pub struct AffineFit {
+    width: usize,
+    len: usize,
+    // the rows are fully reduced, so the lowest set bit of each row is not set in any other row
+    rows: Vec<(usize, Row)>,
+    consistent: bool,
+}
+
+impl AffineFit {
+    /// The number of variables of the system: the bits of the text and `init`
+    fn vars(&self) -> usize {
+        8 * self.len + 1
+    }
+    fn row(&self, text: &[u8]) -> Row {
+        let mut bits = vec![0u64; self.vars() / 64 + 1];
+        for (i, byte) in text.iter().enumerate() {
+            for j in 0..8 {
+                if byte & (0x80 >> j) != 0 {
+                    bits[(8 * i + j) / 64] |= 1 << ((8 * i + j) % 64);
+                }
+            }
+        }
+        let init_bit = 8 * self.len;
+        bits[init_bit / 64] |= 1 << (init_bit % 64);
+        Row { bits, sum: 0 }
+    }
+    /// Eliminates the pivots of the other rows from a row
+    fn reduce(&self, row: &mut Row) {
+        for (pivot, other) in &self.rows {
+            if row.get(*pivot) {
+                row.add(other);
+            }
+        }
+    }
+    fn add(&mut self, mut row: Row, sum: u128) {
+        row.sum = sum;
+        self.reduce(&mut row);
+        let pivot = match row.lowest_bit() {
+            Some(p) => p,
+            None => {
+                // the text is an affine combination of the previous ones, so the sum has to match
+                if row.sum != 0 {
+                    self.consistent = false;
+                }
+                return;
+            }
+        };
+        for (_, other) in self.rows.iter_mut() {
+            if other.get(pivot) {
+                other.add(&row);
+            }
+        }
+        self.rows.push((pivot, row));
+    }
+    /// The text of a row and whether it contains `init`
+    fn text(&self, row: &Row) -> (Vec<u8>, bool) {
+        let text = (0..self.len)
+            .map(|i| (0..8).fold(0u8, |byte, j| byte << 1 | u8::from(row.get(8 * i + j))))
+            .collect();
+        (text, row.get(8 * self.len))
+    }
+    /// Whether there is an affine map fitting the files.
+    pub fn is_consistent(&self) -> bool {
+        self.consistent
+    }
+    /// The number of dimensions of the map that are not determined by the files.
+    ///
+    /// This is the number of additional files needed at least to determine the map.
+    pub fn unknowns(&self) -> usize {
+        self.vars() - self.rows.len()
+    }
+    /// Whether the map is consistent and determined completely by the files.
+    pub fn is_determined(&self) -> bool {
+        self.consistent && self.unknowns() == 0
+    }
+    /// The checksum of a text, if the map is consistent and the checksum is determined by the files.
+    ///
+    /// The text has to have the same length as the files.
+    pub fn checksum(&self, bytes: &[u8]) -> Option<u128> {
+        if !self.consistent || bytes.len() != self.len {
+            return None;
+        }
+        let mut row = self.row(bytes);
+        self.reduce(&mut row);
+        match row.lowest_bit() {
+            Some(_) => None,
+            None => Some(row.sum),
+        }
+    }
+    /// Gets the map if it is determined by the files.
+    pub fn to_affine(&self) -> Option<Affine> {
+        if !self.is_determined() {
+            return None;
+        }
+        let init = self.checksum(&vec![0; self.len])?;
+        let matrix = (0..8 * self.len)
+            .map(|i| {
+                let mut text = vec![0u8; self.len];
+                text[i / 8] = 0x80 >> (i % 8);
+                self.checksum(&text).map(|sum| sum ^ init)
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Affine::with_options()
+            .width(self.width)
+            .len(self.len)
+            .init(init)
+            .matrix(matrix)
+            .build()
+            .ok()
+    }
+    /// Whether a checksum function gives the same checksums as the fitted map, where they are determined.
+    fn agrees(&self, checksum: impl Fn(&[u8]) -> Option<u128>) -> bool {
+        let zero = checksum(&vec![0; self.len]);
+        self.rows.iter().all(|(_, row)| {
+            let (text, has_init) = self.text(row);
+            match (checksum(&text), zero) {
+                (Some(sum), _) if has_init => sum == row.sum,
+                // without init, the row is the difference of two texts
+                (Some(sum), Some(zero)) => sum ^ zero == row.sum,
+                _ => false,
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    // some pseudorandom files of the same length
+    fn files(n: usize, len: usize) -> Vec<Vec<u8>> {
+        let mut state = 0x12345678u32;
+        (0..n)
+            .map(|_| {
+                (0..len)
+                    .map(|_| {
+                        state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                        (state >> 16) as u8
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+    fn chk_files(files: &[Vec<u8>], checksum: impl Fn(&[u8]) -> u128) -> Vec<(&[u8], u128)> {
+        files
+            .iter()
+            .map(|f| (f.as_slice(), checksum(f.as_slice())))
+            .collect()
+    }
+    // xor of the 16-bit word with itself shifted by 3 bits, like a simple custom lfsr step
+    fn custom(text: &[u8]) -> u128 {
+        let word = u128::from(text[0]) << 8 | u128::from(text[1]);
+        (word ^ (word << 3) ^ 0x1234) & 0xffff
+    }
+    #[test]
+    fn custom_map() {
+        let f = files(24, 2);
+        let chk = chk_files(&f, custom);
+        let mut spec = Affine::with_options();
+        spec.width(16);
+        let fit = fit_affine(&spec, &chk, 0).unwrap();
+        assert!(fit.is_determined());
+        assert_eq!(fit.checksum(&[0xab, 0xcd]), Some(custom(&[0xab, 0xcd])));
+        let solutions: Vec<_> = reverse_affine(&spec, &chk, 0).collect();
+        assert_eq!(solutions.len(), 1);
+        let affine = match &solutions[0] {
+            Ok(AffineSolution::Affine(a)) => a,
+            _ => panic!("expected an affine map"),
+        };
+        assert_eq!(affine.checksum(&[0x5a, 0xa5]), Some(custom(&[0x5a, 0xa5])));
+        // only a few files do not determine the map
+        let fit = fit_affine(&spec, &chk[..5], 0).unwrap();
+        assert!(fit.is_consistent() && !fit.is_determined());
+        assert_eq!(fit.unknowns(), 12);
+        assert_eq!(fit.checksum(chk[3].0), Some(chk[3].1));
+        let solutions: Vec<_> = reverse_affine(&spec, &chk[..5], 0).collect();
+        assert_eq!(solutions.len(), 1);
+        assert!(solutions[0].is_err());
+    }
+    #[test]
+    fn inconsistent() {
+        let f = files(24, 2);
+        let mut chk = chk_files(&f, custom);
+        chk[20].1 ^= 1;
+        let mut spec = Affine::with_options();
+        spec.width(16);
+        assert!(!fit_affine(&spec, &chk, 0).unwrap().is_consistent());
+        assert_eq!(reverse_affine(&spec, &chk, 0).count(), 0);
+        let mut chk = chk_files(&f, custom);
+        chk[3].0 = &f[3][..1];
+        assert!(fit_affine(&spec, &chk, 0).is_err());
+    }
+    #[test]
+    fn finds_crc() {
+        let crc = CRC::<u128>::from_str("width=16 poly=0x8005 refin=true refout=true").unwrap();
+        let f = files(4, 3);
+        let chk = chk_files(&f, |x| crc.digest(x).unwrap());
+        let mut spec = Affine::with_options();
+        spec.width(16);
+        let solutions: Vec<_> = reverse_affine(&spec, &chk, 0)
+            .map(|x| x.unwrap().to_string())
+            .collect();
+        assert!(solutions.contains(&crc.to_string()));
+    }
+}
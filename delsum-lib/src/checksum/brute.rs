@@ -0,0 +1,297 @@
+//! A search for segments with a given checksum which works for any algorithm implementing `Digest`.
+//!
+//! Unlike `LinearCheck::find_segments`, this can not exploit any structure of the checksum, so every start is
+//! tried separately, which takes quadratic time in the length of the files.
+//! It is therefore only usable for moderately sized files, but it is the only option for algorithms
+//! which are not linear, like most hash functions.
+//!
+//! The search can be narrowed down with `BruteOptions`, which can limit the length of the segments
+//! and only consider starts and ends which are aligned to some number of bytes.
+use super::crc::LenAppend;
+use super::preprocess::Pad;
+use super::{Digest, RangePairs, RelativeIndex, Relativity};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::collections::hash_map::{Entry, HashMap};
+
+/// Options that restrict which segments the brute-force search tries.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BruteOptions {
+    /// The maximum length of a segment, or `None` for no limit
+    pub max_len: Option<usize>,
+    /// Segments only start at positions which are multiples of this
+    pub start_align: usize,
+    /// Segments only end at positions (after the last byte) which are multiples of this, in every file
+    pub end_align: usize,
+    /// Whether the starts are tried in parallel (only has an effect with the `parallel` feature)
+    pub parallel: bool,
+}
+
+impl Default for BruteOptions {
+    fn default() -> Self {
+        BruteOptions {
+            max_len: None,
+            start_align: 1,
+            end_align: 1,
+            parallel: true,
+        }
+    }
+}
+
+/// Like `LinearCheck::find_segments`, but by trying every segment, so it only needs a `Digest`.
+pub fn find_segments<D: Digest + Sync, B: AsRef<[u8]> + Sync>(
+    chk: &D,
+    bytes: &[B],
+    sum: &[D::Sum],
+    rel: Relativity,
+) -> RangePairs {
+    find_segments_with(chk, bytes, sum, rel, &BruteOptions::default())
+}
+
+/// Like `find_segments`, but only the segments allowed by `options` are tried.
+///
+/// Panics if one of the alignments is 0.
+pub fn find_segments_with<D: Digest + Sync, B: AsRef<[u8]> + Sync>(
+    chk: &D,
+    bytes: &[B],
+    sum: &[D::Sum],
+    rel: Relativity,
+    options: &BruteOptions,
+) -> RangePairs {
+    find_padded_segments(chk, bytes, sum, rel, None, None, options)
+}
+
+/// Like `find_segments_with`, but each segment gets padded with `pad` before being digested,
+/// followed by its (padded) length encoded with `lenappend`.
+pub(crate) fn find_padded_segments<D: Digest + Sync, B: AsRef<[u8]> + Sync>(
+    chk: &D,
+    bytes: &[B],
+    sum: &[D::Sum],
+    rel: Relativity,
+    pad: Option<Pad>,
+    lenappend: Option<LenAppend>,
+    options: &BruteOptions,
+) -> RangePairs {
+    assert!(options.start_align > 0 && options.end_align > 0);
+    let min_len = match bytes.iter().map(|x| x.as_ref().len()).min() {
+        Some(l) => l,
+        None => return Vec::new(),
+    };
+    let ends_from = |start: usize| {
+        // the ends are given in the same way as in RelativeIndex, which is the same for all files
+        let mut common: Option<Vec<usize>> = None;
+        for (b, s) in bytes.iter().zip(sum.iter()) {
+            let b = b.as_ref();
+            let mut lens = match rel {
+                Relativity::Start => 1..min_len - start + 1,
+                Relativity::End => {
+                    (b.len() - min_len + 1).saturating_sub(start).max(1)..b.len() - start + 1
+                }
+            };
+            if let Some(max_len) = options.max_len {
+                lens.end = lens.end.min(max_len + 1);
+            }
+            // skip to the first length for which the segment ends aligned
+            let align = options.end_align;
+            lens.start += (align - (start + lens.start) % align) % align;
+            let text = &b[start..];
+            let ends = prefix_matches(chk, pad, lenappend, text, s, lens.step_by(align))
+                .into_iter()
+                .map(|l| match rel {
+                    Relativity::Start => start + l,
+                    Relativity::End => b.len() - start - l,
+                });
+            common = Some(match common {
+                None => ends.collect(),
+                Some(c) => {
+                    let ends: Vec<_> = ends.collect();
+                    c.into_iter().filter(|e| ends.contains(e)).collect()
+                }
+            });
+        }
+        let ends: Vec<_> = common?
+            .into_iter()
+            .map(|e| match rel {
+                Relativity::Start => RelativeIndex::FromStart(e),
+                Relativity::End => RelativeIndex::FromEnd(e),
+            })
+            .collect();
+        if ends.is_empty() {
+            None
+        } else {
+            Some((start, ends))
+        }
+    };
+    #[cfg(feature = "parallel")]
+    {
+        if options.parallel {
+            let segs = (0..min_len)
+                .into_par_iter()
+                .step_by(options.start_align)
+                .filter_map(ends_from)
+                .collect();
+            return group_starts(segs);
+        }
+    }
+    let segs = (0..min_len)
+        .step_by(options.start_align)
+        .filter_map(ends_from)
+        .collect();
+    group_starts(segs)
+}
+
+/// Puts the starts with the same ends into one group, like `LinearCheck::find_segments` does.
+///
+/// The starts have to be increasing, and the groups are ordered by their first start.
+fn group_starts(segs: Vec<(usize, Vec<RelativeIndex>)>) -> RangePairs {
+    let mut groups: HashMap<_, usize> = HashMap::new();
+    let mut ret: RangePairs = Vec::new();
+    for (start, ends) in segs {
+        match groups.entry(ends) {
+            Entry::Occupied(group) => ret[*group.get()].0.push(start),
+            Entry::Vacant(group) => {
+                ret.push((vec![start], group.key().clone()));
+                group.insert(ret.len() - 1);
+            }
+        }
+    }
+    ret
+}
+
+/// Gets the lengths in `lens` for which the (padded) text prefix has the checksum `sum`.
+///
+/// The lengths have to be increasing and at most the length of `text`.
+fn prefix_matches<D: Digest>(
+    chk: &D,
+    pad: Option<Pad>,
+    lenappend: Option<LenAppend>,
+    text: &[u8],
+    sum: &D::Sum,
+    lens: impl Iterator<Item = usize>,
+) -> Vec<usize> {
+    let spec = chk.wordspec();
+    let wb = spec.word_bytes();
+    // the state after digesting the whole words of text[..digested]
+    let mut state = chk.init();
+    let mut digested = 0;
+    let mut matches = Vec::new();
+    for len in lens {
+        while digested + wb < len {
+            let word = &text[digested..digested + wb];
+            state = chk.dig_word(state, spec.bytes_to_word(word));
+            digested += wb;
+        }
+        // the part of the segment in the current word gets the padding appended
+        let mut tail = text[digested..len].to_vec();
+        if let Some(pad) = pad {
+            tail.resize(len - digested + pad.pad_len(len), pad.value);
+        }
+        let mut end_state = tail.chunks(wb).fold(state.clone(), |s, word| {
+            chk.dig_word(s, spec.bytes_to_word(word))
+        });
+        if let Some(l) = lenappend {
+            // segments too long for the encoding of the length can't match
+            end_state = match l.append(chk, end_state, digested + tail.len()) {
+                Some(state) => state,
+                None => break,
+            };
+        }
+        if chk.finalize(end_state) == *sum {
+            matches.push(len);
+        }
+    }
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::modsum::ModSum;
+    use crate::checksum::LinearCheck;
+    use std::str::FromStr;
+    // flattens the segments into sorted (start, end) pairs, since the grouping differs if starts come after ends
+    fn pairs(segs: RangePairs) -> Vec<(usize, usize)> {
+        let mut ret: Vec<_> = segs
+            .into_iter()
+            .flat_map(|(starts, ends)| {
+                starts.into_iter().flat_map(move |s| {
+                    ends.clone().into_iter().map(move |e| match e {
+                        RelativeIndex::FromStart(e) | RelativeIndex::FromEnd(e) => (s, e),
+                    })
+                })
+            })
+            .collect();
+        ret.sort_unstable();
+        ret
+    }
+    #[test]
+    fn same_as_linear() {
+        let chk = ModSum::<u16>::from_str("width=16 wordsize=16 endian=little").unwrap();
+        let files = [
+            Vec::from("the brute force search should agree"),
+            Vec::from("linear with the linear search, too!"),
+        ];
+        let sums = [
+            chk.digest(&b"force"[..]).unwrap(),
+            chk.digest(&b"h the"[..]).unwrap(),
+        ];
+        for rel in [Relativity::Start, Relativity::End] {
+            let linear = pairs(chk.find_segments(&files, &sums, rel));
+            assert!(!linear.is_empty());
+            assert_eq!(pairs(find_segments(&chk, &files, &sums, rel)), linear);
+        }
+    }
+    #[test]
+    fn grouped_like_linear() {
+        let chk = ModSum::<u8>::from_str("width=8").unwrap();
+        let files = [
+            Vec::from("\x00\x00\x05\x00\x00"),
+            Vec::from("\x07\x00\x00\x05\x00\x00"),
+        ];
+        for rel in [Relativity::Start, Relativity::End] {
+            let linear = chk.find_segments(&files[..1], &[5], rel);
+            assert_eq!(linear.len(), 1);
+            assert_eq!(find_segments(&chk, &files[..1], &[5], rel), linear);
+        }
+        let linear = chk.find_segments(&files, &[5, 5], Relativity::End);
+        assert_eq!(
+            linear,
+            vec![(
+                vec![1, 2],
+                (0..3).rev().map(RelativeIndex::FromEnd).collect()
+            )]
+        );
+        assert_eq!(
+            find_segments(&chk, &files, &[5, 5], Relativity::End),
+            linear
+        );
+    }
+    #[test]
+    fn options() {
+        let chk = ModSum::<u8>::from_str("width=8").unwrap();
+        let files = [Vec::from("\x01\x01\x01\x01\x01\x01")];
+        let all = pairs(find_segments(&chk, &files, &[2], Relativity::Start));
+        assert_eq!(all, vec![(0, 2), (1, 3), (2, 4), (3, 5), (4, 6)]);
+        let mut options = BruteOptions {
+            start_align: 2,
+            parallel: false,
+            ..BruteOptions::default()
+        };
+        let aligned = find_segments_with(&chk, &files, &[2], Relativity::Start, &options);
+        assert_eq!(pairs(aligned), vec![(0, 2), (2, 4), (4, 6)]);
+        options.end_align = 4;
+        let aligned = find_segments_with(&chk, &files, &[2], Relativity::Start, &options);
+        assert_eq!(pairs(aligned), vec![(2, 4)]);
+        let options = BruteOptions {
+            max_len: Some(3),
+            ..BruteOptions::default()
+        };
+        let short = find_segments_with(&chk, &files, &[3], Relativity::End, &options);
+        assert_eq!(pairs(short), vec![(0, 3), (1, 2), (2, 1), (3, 0)]);
+        let options = BruteOptions {
+            max_len: Some(2),
+            ..BruteOptions::default()
+        };
+        assert!(find_segments_with(&chk, &files, &[3], Relativity::End, &options).is_empty());
+    }
+}
@@ -0,0 +1,167 @@
+//! The 16-bit checksum of the BSD `sum` utility (also `sum -r`), which rotates the sum right by one bit before adding each byte
+//!
+//! Since it mixes rotations and additions, it is neither linear over the integers nor over GF(2),
+//! so it only implements `Digest` and segments are found by trying all of them.
+//!
+//! There are a number of parameters:
+//! * width: The number of bits in the sum type, has to be 16
+//! * init: The initial number (default 0)
+//! * check: The checksum of "123456789" (optional, gets checked at construction)
+//! * name: An optional name that gets used for display purposes
+use crate::bitnum::BitNum;
+use crate::checksum::{CheckBuilderErr, Digest};
+use crate::keyval::KeyValIter;
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// A builder to set the various parameters for the BSD sum.
+///
+/// Example:
+/// ```
+/// # use delsum_lib::checksum::bsdsum::BsdSum;
+/// BsdSum::with_options()
+///     .width(16)
+///     .check(0xd16f)
+///     .build()
+///     .is_ok();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct BsdSumBuilder {
+    width: Option<usize>,
+    init: Option<u16>,
+    check: Option<u16>,
+    name: Option<String>,
+}
+
+impl BsdSumBuilder {
+    /// The width, in bits, of the sum. Mandatory, and has to be 16.
+    pub fn width(&mut self, w: usize) -> &mut Self {
+        self.width = Some(w);
+        self
+    }
+    /// The initial value, optional, defaults to 0.
+    pub fn init(&mut self, i: u16) -> &mut Self {
+        self.init = Some(i);
+        self
+    }
+    /// The checksum of "123456789", gets checked on creation.
+    pub fn check(&mut self, c: u16) -> &mut Self {
+        self.check = Some(c);
+        self
+    }
+    /// An optional name that gets used for display purposes.
+    pub fn name(&mut self, n: &str) -> &mut Self {
+        self.name = Some(String::from(n));
+        self
+    }
+    /// Builds the algorithm, after validating the parameters.
+    pub fn build(&self) -> Result<BsdSum, CheckBuilderErr> {
+        let width = self
+            .width
+            .ok_or(CheckBuilderErr::MissingParameter("width"))?;
+        if width != 16 {
+            return Err(CheckBuilderErr::ValueOutOfRange("width"));
+        }
+        let s = BsdSum {
+            init: self.init.unwrap_or(0),
+            name: self.name.clone(),
+        };
+        match self.check {
+            Some(c) => {
+                if s.digest(&b"123456789"[..]).unwrap() == c {
+                    Ok(s)
+                } else {
+                    Err(CheckBuilderErr::CheckFail)
+                }
+            }
+            None => Ok(s),
+        }
+    }
+}
+
+/// The BSD sum algorithm.
+#[derive(Debug, PartialEq, Eq)]
+pub struct BsdSum {
+    init: u16,
+    name: Option<String>,
+}
+
+impl BsdSum {
+    /// Creates a `BsdSumBuilder`, for more information see its documentation.
+    pub fn with_options() -> BsdSumBuilder {
+        BsdSumBuilder::default()
+    }
+}
+
+impl Display for BsdSum {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.name {
+            Some(n) => write!(f, "{}", n),
+            None => write!(f, "bsdsum width=16 init={:#x}", self.init),
+        }
+    }
+}
+
+impl FromStr for BsdSumBuilder {
+    /// See FromStr for BsdSum
+    fn from_str(s: &str) -> Result<BsdSumBuilder, CheckBuilderErr> {
+        let mut sum = BsdSum::with_options();
+        for x in KeyValIter::new(s) {
+            let (current_key, current_val) = match x {
+                Err(key) => return Err(CheckBuilderErr::MalformedString(key)),
+                Ok(s) => s,
+            };
+            let sum_op = match current_key.as_str() {
+                "width" => usize::from_str(&current_val).ok().map(|x| sum.width(x)),
+                "init" => u16::from_hex(&current_val).ok().map(|x| sum.init(x)),
+                "check" => u16::from_hex(&current_val).ok().map(|x| sum.check(x)),
+                "name" => Some(sum.name(&current_val)),
+                _ => return Err(CheckBuilderErr::UnknownKey(current_key)),
+            };
+            match sum_op {
+                Some(s) => sum = s.clone(),
+                None => return Err(CheckBuilderErr::MalformedString(current_key)),
+            }
+        }
+        Ok(sum)
+    }
+    type Err = CheckBuilderErr;
+}
+
+impl FromStr for BsdSum {
+    /// Construct a new BSD sum from a string specification.
+    ///
+    /// Example:
+    ///
+    /// width=16 init=0
+    fn from_str(s: &str) -> Result<BsdSum, CheckBuilderErr> {
+        BsdSumBuilder::from_str(s)?.build()
+    }
+    type Err = CheckBuilderErr;
+}
+
+impl Digest for BsdSum {
+    type Sum = u16;
+    fn init(&self) -> Self::Sum {
+        self.init
+    }
+    fn dig_byte(&self, sum: Self::Sum, byte: u8) -> Self::Sum {
+        sum.rotate_right(1).wrapping_add(u16::from(byte))
+    }
+    fn finalize(&self, sum: Self::Sum) -> Self::Sum {
+        sum
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn sum_r() {
+        let chk = BsdSum::from_str("width=16").unwrap();
+        // `sum -r` gives the checksum in decimal
+        assert_eq!(chk.digest(&b"123456789"[..]).unwrap(), 53615);
+        assert_eq!(chk.to_string(), "bsdsum width=16 init=0x0");
+        assert!(BsdSum::from_str("width=32").is_err());
+    }
+}
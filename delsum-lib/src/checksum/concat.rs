@@ -0,0 +1,456 @@
+//! A combination of multiple checksums, whose sums are stored next to each other in a single value.
+//!
+//! Some formats store for example a CRC-16 in the upper half of a 32-bit field and a 16-bit sum
+//! in the lower half. In a model string, this is written as
+//! ```text
+//! concat(crc width=16 poly=0x1021, modsum width=16)
+//! ```
+//! with the sum of the first model in the most significant bits.
+//! Each component can have its own output transformation (except `outbits`), and
+//! the whole sum can get transformed too, by putting the parameters after the closing parenthesis.
+//!
+//! All components need to read the text with the same `wordsize` and `endian` and the total
+//! width is at most 128 bits.
+use super::{
+    crc::CRC, fletcher::Fletcher, modsum::ModSum, polyhash::PolyHash, rotxor::RotXor,
+    transform::Transformed, xorsum::XorSum, CheckBuilderErr, Digest, LinearCheck,
+};
+use crate::endian::WordSpec;
+use std::fmt::Display;
+use std::io::Read;
+
+/// A single checksum algorithm inside of a `Concat`.
+///
+/// The sums (and shifts) of the algorithms are converted to `u128`, so that different algorithms
+/// can be put together.
+pub enum Component {
+    CRC(CRC<u128>),
+    ModSum(ModSum<u64>),
+    Fletcher(Fletcher<u128>),
+    XorSum(XorSum<u64>),
+    RotXor(RotXor<u64>),
+    PolyHash(PolyHash<u64>),
+}
+
+/// Conversion from and to the `u128` that is used for the sums and shifts of a `Component`.
+trait Wide {
+    fn to_wide(self) -> u128;
+    fn from_wide(x: u128) -> Self;
+}
+
+impl Wide for u128 {
+    fn to_wide(self) -> u128 {
+        self
+    }
+    fn from_wide(x: u128) -> Self {
+        x
+    }
+}
+
+impl Wide for u64 {
+    fn to_wide(self) -> u128 {
+        u128::from(self)
+    }
+    fn from_wide(x: u128) -> Self {
+        x as u64
+    }
+}
+
+impl Wide for usize {
+    fn to_wide(self) -> u128 {
+        self as u128
+    }
+    fn from_wide(x: u128) -> Self {
+        x as usize
+    }
+}
+
+impl Wide for () {
+    fn to_wide(self) -> u128 {
+        0
+    }
+    fn from_wide(_: u128) -> Self {}
+}
+
+// calls the same expression for each kind of component
+macro_rules! on_component {
+    ($comp:expr, $c:ident => $e:expr) => {
+        match $comp {
+            Component::CRC($c) => $e,
+            Component::ModSum($c) => $e,
+            Component::Fletcher($c) => $e,
+            Component::XorSum($c) => $e,
+            Component::RotXor($c) => $e,
+            Component::PolyHash($c) => $e,
+        }
+    };
+}
+
+impl Display for Component {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        on_component!(self, c => c.fmt(f))
+    }
+}
+
+impl Digest for Component {
+    type Sum = u128;
+    fn init(&self) -> Self::Sum {
+        on_component!(self, c => c.init().to_wide())
+    }
+    fn dig_byte(&self, sum: Self::Sum, byte: u8) -> Self::Sum {
+        on_component!(self, c => c.dig_byte(Wide::from_wide(sum), byte).to_wide())
+    }
+    fn dig_word(&self, sum: Self::Sum, word: u64) -> Self::Sum {
+        on_component!(self, c => c.dig_word(Wide::from_wide(sum), word).to_wide())
+    }
+    fn wordspec(&self) -> WordSpec {
+        on_component!(self, c => c.wordspec())
+    }
+    fn finalize(&self, sum: Self::Sum) -> Self::Sum {
+        on_component!(self, c => c.finalize(Wide::from_wide(sum)).to_wide())
+    }
+    fn digest<R: Read>(&self, buf: R) -> Result<Self::Sum, std::io::Error> {
+        on_component!(self, c => c.digest(buf).map(Wide::to_wide))
+    }
+}
+
+// the shifts of modsum and xorsum are `()`, which also get converted
+#[allow(clippy::unit_arg)]
+impl LinearCheck for Component {
+    type Shift = u128;
+    fn init_shift(&self) -> Self::Shift {
+        on_component!(self, c => c.init_shift().to_wide())
+    }
+    fn inc_shift(&self, shift: Self::Shift) -> Self::Shift {
+        on_component!(self, c => c.inc_shift(Wide::from_wide(shift)).to_wide())
+    }
+    fn shift(&self, sum: Self::Sum, shift: &Self::Shift) -> Self::Sum {
+        on_component!(self, c => c
+            .shift(Wide::from_wide(sum), &Wide::from_wide(*shift))
+            .to_wide())
+    }
+    fn add(&self, sum_a: Self::Sum, sum_b: &Self::Sum) -> Self::Sum {
+        on_component!(self, c => c
+            .add(Wide::from_wide(sum_a), &Wide::from_wide(*sum_b))
+            .to_wide())
+    }
+    fn negate(&self, sum: Self::Sum) -> Self::Sum {
+        on_component!(self, c => c.negate(Wide::from_wide(sum)).to_wide())
+    }
+    fn shift_n(&self, n: usize) -> Self::Shift {
+        on_component!(self, c => c.shift_n(n).to_wide())
+    }
+    fn presums(
+        &self,
+        bytes: &[u8],
+        sum: &dyn Fn(usize) -> Option<Self::Sum>,
+        phase: usize,
+        start_range: std::ops::Range<usize>,
+        end_range: std::ops::Range<usize>,
+        cursor: &mut Option<(Self::Sum, usize)>,
+    ) -> (Vec<Self::Sum>, Vec<Self::Sum>) {
+        on_component!(self, c => {
+            let inner_sum = |end| sum(end).map(Wide::from_wide);
+            let mut inner_cursor = cursor.map(|(s, words)| (Wide::from_wide(s), words));
+            let (starts, ends) = c.presums(
+                bytes,
+                &inner_sum,
+                phase,
+                start_range,
+                end_range,
+                &mut inner_cursor,
+            );
+            *cursor = inner_cursor.map(|(s, words)| (s.to_wide(), words));
+            (
+                starts.into_iter().map(Wide::to_wide).collect(),
+                ends.into_iter().map(Wide::to_wide).collect(),
+            )
+        })
+    }
+}
+
+/// Multiple checksums whose sums are put next to each other, the first one in the most significant bits.
+///
+/// The operations of `Digest` and `LinearCheck` are just done on each component separately.
+pub struct Concat {
+    parts: Vec<(Transformed<Component>, usize)>,
+    wordspec: WordSpec,
+}
+
+impl Concat {
+    /// Creates a new `Concat` from the components together with their widths.
+    ///
+    /// The output transformation of each component must keep all `width` bits of the sum.
+    pub fn new(parts: Vec<(Transformed<Component>, usize)>) -> Result<Self, CheckBuilderErr> {
+        let wordspec = match parts.first() {
+            Some((p, _)) => p.wordspec(),
+            None => return Err(CheckBuilderErr::MissingParameter("concat")),
+        };
+        if parts.iter().any(|(p, _)| p.wordspec() != wordspec) {
+            return Err(CheckBuilderErr::ValueOutOfRange("wordsize"));
+        }
+        if parts.iter().map(|(_, w)| w).sum::<usize>() > 128 {
+            return Err(CheckBuilderErr::ValueOutOfRange("width"));
+        }
+        Ok(Concat { parts, wordspec })
+    }
+    /// The total width of the sum.
+    pub fn width(&self) -> usize {
+        self.parts.iter().map(|(_, w)| w).sum()
+    }
+    /// The widths of the components, in the order in which they were given.
+    pub fn widths(&self) -> Vec<usize> {
+        self.parts.iter().map(|(_, w)| *w).collect()
+    }
+    fn pack(&self, sums: impl Iterator<Item = u128>) -> u128 {
+        self.parts.iter().zip(sums).fold(0, |acc, ((_, w), s)| {
+            acc.checked_shl(*w as u32).unwrap_or(0) | s
+        })
+    }
+    fn unpack(&self, sum: u128) -> Vec<u128> {
+        split_sum(sum, &self.widths())
+    }
+    // applies an operation on each component, together with its part of the sum
+    fn each(&self, sum: u128, f: impl Fn(&Transformed<Component>, u128) -> u128) -> u128 {
+        let sums = self.unpack(sum);
+        self.pack(self.parts.iter().zip(sums).map(|((p, _), s)| f(p, s)))
+    }
+}
+
+/// Splits a sum into the parts with the given widths, with the first part in the most significant bits.
+pub fn split_sum(mut sum: u128, widths: &[usize]) -> Vec<u128> {
+    let mut sums: Vec<_> = widths
+        .iter()
+        .rev()
+        .map(|w| {
+            let mask = 1u128.checked_shl(*w as u32).unwrap_or(0).wrapping_sub(1);
+            let part = sum & mask;
+            sum = sum.checked_shr(*w as u32).unwrap_or(0);
+            part
+        })
+        .collect();
+    sums.reverse();
+    sums
+}
+
+impl Display for Concat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let parts: Vec<_> = self.parts.iter().map(|(p, _)| p.to_string()).collect();
+        write!(f, "concat({})", parts.join(", "))
+    }
+}
+
+impl Digest for Concat {
+    type Sum = u128;
+    fn init(&self) -> Self::Sum {
+        self.pack(self.parts.iter().map(|(p, _)| p.init()))
+    }
+    fn dig_byte(&self, sum: Self::Sum, byte: u8) -> Self::Sum {
+        self.each(sum, |p, s| p.dig_byte(s, byte))
+    }
+    fn dig_word(&self, sum: Self::Sum, word: u64) -> Self::Sum {
+        self.each(sum, |p, s| p.dig_word(s, word))
+    }
+    fn wordspec(&self) -> WordSpec {
+        self.wordspec
+    }
+    fn finalize(&self, sum: Self::Sum) -> Self::Sum {
+        self.each(sum, |p, s| p.finalize(s))
+    }
+    fn digest<R: Read>(&self, mut buf: R) -> Result<Self::Sum, std::io::Error> {
+        // every component reads the whole text on its own
+        let mut bytes = Vec::new();
+        buf.read_to_end(&mut bytes)?;
+        let sums = self
+            .parts
+            .iter()
+            .map(|(p, _)| p.digest(bytes.as_slice()))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(self.pack(sums.into_iter()))
+    }
+}
+
+impl LinearCheck for Concat {
+    type Shift = Vec<u128>;
+    fn init_shift(&self) -> Self::Shift {
+        self.parts.iter().map(|(p, _)| p.init_shift()).collect()
+    }
+    fn inc_shift(&self, shift: Self::Shift) -> Self::Shift {
+        self.parts
+            .iter()
+            .zip(shift)
+            .map(|((p, _), s)| p.inc_shift(s))
+            .collect()
+    }
+    fn shift(&self, sum: Self::Sum, shift: &Self::Shift) -> Self::Sum {
+        let sums = self.unpack(sum);
+        self.pack(
+            self.parts
+                .iter()
+                .zip(sums)
+                .zip(shift)
+                .map(|(((p, _), s), sh)| p.shift(s, sh)),
+        )
+    }
+    fn add(&self, sum_a: Self::Sum, sum_b: &Self::Sum) -> Self::Sum {
+        let sums_b = self.unpack(*sum_b);
+        let sums_a = self.unpack(sum_a);
+        self.pack(
+            self.parts
+                .iter()
+                .zip(sums_a.into_iter().zip(sums_b))
+                .map(|((p, _), (a, b))| p.add(a, &b)),
+        )
+    }
+    fn negate(&self, sum: Self::Sum) -> Self::Sum {
+        self.each(sum, |p, s| p.negate(s))
+    }
+    fn shift_n(&self, n: usize) -> Self::Shift {
+        self.parts.iter().map(|(p, _)| p.shift_n(n)).collect()
+    }
+    fn presums(
+        &self,
+        bytes: &[u8],
+        sum: &dyn Fn(usize) -> Option<Self::Sum>,
+        phase: usize,
+        start_range: std::ops::Range<usize>,
+        end_range: std::ops::Range<usize>,
+        cursor: &mut Option<(Self::Sum, usize)>,
+    ) -> (Vec<Self::Sum>, Vec<Self::Sum>) {
+        // the presums of the components are calculated separately, because they might have a
+        // transformation which only gets undone in their presums
+        let mut part_cursors = Vec::new();
+        let presums: Vec<_> = self
+            .parts
+            .iter()
+            .enumerate()
+            .map(|(i, (p, _))| {
+                let part_sum = |end| sum(end).map(|s| self.unpack(s)[i]);
+                let mut part_cursor = cursor.map(|(s, words)| (self.unpack(s)[i], words));
+                let presums = p.presums(
+                    bytes,
+                    &part_sum,
+                    phase,
+                    start_range.clone(),
+                    end_range.clone(),
+                    &mut part_cursor,
+                );
+                part_cursors.extend(part_cursor);
+                presums
+            })
+            .collect();
+        // every component has gone over the same words
+        *cursor = part_cursors
+            .first()
+            .map(|(_, words)| (self.pack(part_cursors.iter().map(|(s, _)| *s)), *words));
+        let pack_nth = |n: usize, ends: bool| {
+            self.pack(presums.iter().map(|(s, e)| if ends { e[n] } else { s[n] }))
+        };
+        let (start_len, end_len) = (presums[0].0.len(), presums[0].1.len());
+        (
+            (0..start_len).map(|n| pack_nth(n, false)).collect(),
+            (0..end_len).map(|n| pack_nth(n, true)).collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::tests::{test_find, test_prop};
+    use crate::checksum::transform::OutTransform;
+    use std::str::FromStr;
+    fn concat(parts: Vec<(Component, &str, usize)>) -> Concat {
+        Concat::new(
+            parts
+                .into_iter()
+                .map(|(c, t, w)| {
+                    let (mut transform, rest) =
+                        crate::checksum::transform::OutTransformBuilder::extract(t).unwrap();
+                    assert!(rest.is_empty());
+                    let transform: OutTransform = transform.width(w).build().unwrap();
+                    (Transformed::new(c, transform), w)
+                })
+                .collect(),
+        )
+        .unwrap()
+    }
+    #[test]
+    fn crc_and_sum() {
+        let crc = CRC::<u128>::from_str("width=16 poly=0x1021 init=0xffff").unwrap();
+        let sum = ModSum::<u64>::from_str("width=16").unwrap();
+        let crc_check = crc.digest(&b"123456789"[..]).unwrap();
+        let sum_check = sum.digest(&b"123456789"[..]).unwrap();
+        let chk = concat(vec![
+            (Component::CRC(crc), "", 16),
+            (Component::ModSum(sum), "", 16),
+        ]);
+        assert_eq!(chk.width(), 32);
+        assert_eq!(
+            chk.digest(&b"123456789"[..]).unwrap(),
+            crc_check << 16 | u128::from(sum_check)
+        );
+        assert_eq!(
+            chk.to_string(),
+            "concat(crc width=16 poly=0x1021 init=0xffff xorout=0x0 refin=false refout=false, \
+            modsum width=16 module=0x10000 init=0x0)"
+        );
+        test_prop(&chk);
+        test_find(&chk);
+    }
+    #[test]
+    fn transformed_parts() {
+        let crc = CRC::<u128>::from_str(
+            "width=32 poly=0x04c11db7 init=0xffffffff xorout=0xffffffff refin=true refout=true",
+        )
+        .unwrap();
+        let sum = ModSum::<u64>::from_str("width=8").unwrap();
+        let chk = concat(vec![
+            (Component::CRC(crc), "outendian=little", 32),
+            (Component::ModSum(sum), "negate=true", 8),
+        ]);
+        // 0xcbf43926 in little endian, followed by -0xdd
+        assert_eq!(chk.digest(&b"123456789"[..]).unwrap(), 0x2639f4cb23);
+        test_find(&chk);
+    }
+    #[test]
+    fn split() {
+        assert_eq!(
+            split_sum(0x12_3456_789a, &[16, 8, 16]),
+            vec![0x1234, 0x56, 0x789a]
+        );
+        assert_eq!(split_sum(u128::MAX, &[128]), vec![u128::MAX]);
+    }
+    #[test]
+    fn reverse_parts() {
+        let files: [&[u8]; 4] = [
+            &[0x12u8, 0x34u8, 0x56u8],
+            &[0x67u8, 0x41u8, 0xffu8],
+            &[0x15u8, 0x56u8, 0x76u8, 0x1fu8],
+            &[0x14u8, 0x62u8, 0x51u8, 0xa4u8, 0xd3u8],
+        ];
+        let model = "concat(crc width=16 poly=0x8005 refin=true refout=true, modsum width=8)";
+        let sums: Vec<_> = files
+            .iter()
+            .map(|f| crate::find_checksum(model, &[f.to_vec()]).unwrap().join(""))
+            .collect();
+        let finder = crate::find_algorithm(
+            "concat(crc width=16 refin=true refout=true, modsum width=8)",
+            &files,
+            &sums.join(","),
+            0,
+        )
+        .unwrap();
+        let found: Vec<_> = finder.find_all().map(Result::unwrap).collect();
+        assert!(found.contains(
+            &"concat(crc width=16 poly=0x8005 init=0x0 xorout=0x0 refin=true refout=true, \
+            modsum width=8 module=0x100 init=0x0)"
+                .to_owned()
+        ));
+        for model in found {
+            for (f, s) in files.iter().zip(sums.iter()) {
+                assert_eq!(&crate::find_checksum(&model, &[f.to_vec()]).unwrap()[0], s);
+            }
+        }
+    }
+}
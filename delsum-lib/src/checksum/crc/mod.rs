@@ -1,12 +1,15 @@
 pub mod rev;
-use super::{CheckBuilderErr, Digest, LinearCheck};
+use super::{digest_words, CheckBuilderErr, Digest, LinearCheck};
 use crate::bitnum::BitNum;
+use crate::endian::{Endian, WordSpec};
 use crate::keyval::KeyValIter;
+use std::convert::TryFrom;
 use std::fmt::Display;
+use std::io::Read;
 use std::str::FromStr;
 /// A builder for a CRC algorithm.
 ///
-/// The Sum type is one of u8, u16, u32, u64 or u128 and must be able to hold `width` bits.
+/// The Sum type is one of u8, u16, u32, u64, u128 or U512 and must be able to hold `width` bits.
 ///
 /// The parameters are the same as of the Rocksoft^TM Model CRC Algorithm:
 /// * `width`: the width in bits of the sum values
@@ -15,9 +18,19 @@ use std::str::FromStr;
 /// * `refin`: whether to reflect the input bytes
 /// * `refout`: whether to reflect the sum
 /// * `xorout`: what to XOR the output with
+/// * `wordsize`: the number of bits in a word, a multiple of 8 (default 8)
+/// * `endian`: the byte order of the words, either `little` or `big` (default big), also accepted as `wordorder`
+/// * `lenappend`: how the length of the text gets appended to it (see `LenAppend`, default is to not append it)
 /// * `check`: the checksum of the ASCII string "123456789" (is checked on `build()`, optional)
 /// * `name`: an optional name for the algorithm
 ///
+/// The words are fed to the CRC with their most significant byte first, like hardware CRC units
+/// (for example on STM32) do when given a 32-bit word from memory, with a partial word at the end being
+/// padded with zero bytes.
+///
+/// If `lenappend` is set, the length of the text in bytes is fed to the CRC after the text, like POSIX `cksum`
+/// does it. Since this is not linear anymore, such a CRC has to be built with `build_lenappend` into a `LenAppendCRC`.
+///
 /// For more information on the parameters (and CRCs in general), see "A PAINLESS GUIDE CRC ERROR DETECTION ALGORITHMS"
 /// or https://reveng.sourceforge.io/crc-catalogue/legend.htm (which is also a source of parameters for various common algorithms)
 #[derive(Clone, Debug)]
@@ -28,6 +41,9 @@ pub struct CRCBuilder<Sum: BitNum> {
     xorout: Option<Sum>,
     refin: Option<bool>,
     refout: Option<bool>,
+    wordsize: Option<usize>,
+    endian: Option<Endian>,
+    lenappend: Option<LenAppend>,
     check: Option<Sum>,
     name: Option<String>,
 }
@@ -63,6 +79,21 @@ impl<Sum: BitNum> CRCBuilder<Sum> {
         self.refout = Some(o);
         self
     }
+    /// Sets the number of bits in a word, a multiple of 8, default is 8.
+    pub fn wordsize(&mut self, w: usize) -> &mut Self {
+        self.wordsize = Some(w);
+        self
+    }
+    /// Sets the byte order of the words, default is big endian.
+    pub fn endian(&mut self, e: Endian) -> &mut Self {
+        self.endian = Some(e);
+        self
+    }
+    /// Sets how the length gets appended to the text, by default it is not appended.
+    pub fn lenappend(&mut self, l: LenAppend) -> &mut Self {
+        self.lenappend = Some(l);
+        self
+    }
     /// Sets the `check` parameter, no check is done if this is left out.
     pub fn check(&mut self, c: Sum) -> &mut Self {
         self.check = Some(c);
@@ -73,9 +104,49 @@ impl<Sum: BitNum> CRCBuilder<Sum> {
         self.name = Some(s.to_owned());
         self
     }
+    /// Gets how the length gets appended to the text, if at all.
+    pub fn get_lenappend(&self) -> Option<LenAppend> {
+        self.lenappend
+    }
     /// Build the object for the algorithm, generating the lookup table and verifying that
     /// the parameters are valid.
+    ///
+    /// Fails if `lenappend` is set, use `build_lenappend` for that.
     pub fn build(&self) -> Result<CRC<Sum>, CheckBuilderErr> {
+        if self.lenappend.is_some() {
+            return Err(CheckBuilderErr::ValueOutOfRange("lenappend"));
+        }
+        let crc = self.build_linear()?;
+        let mask = crc.mask;
+        self.verify_check(crc, mask)
+    }
+    /// Like `build`, but for a CRC with `lenappend` set, which is mandatory here.
+    pub fn build_lenappend(&self) -> Result<LenAppendCRC<Sum>, CheckBuilderErr> {
+        let lenappend = match self.lenappend {
+            None => return Err(CheckBuilderErr::MissingParameter("lenappend")),
+            Some(l) => l,
+        };
+        let crc = self.build_linear()?;
+        let mask = crc.mask;
+        self.verify_check(LenAppendCRC::new(crc, lenappend), mask)
+    }
+    /// Verifies that the `check` parameter (if given) matches the checksum of "123456789".
+    fn verify_check<D: Digest<Sum = Sum>>(&self, chk: D, mask: Sum) -> Result<D, CheckBuilderErr> {
+        match self.check {
+            Some(c) => {
+                if c & !mask != Sum::zero() {
+                    Err(CheckBuilderErr::ValueOutOfRange("check"))
+                } else if chk.digest(&b"123456789"[..]).unwrap() != c {
+                    Err(CheckBuilderErr::CheckFail)
+                } else {
+                    Ok(chk)
+                }
+            }
+            None => Ok(chk),
+        }
+    }
+    /// Builds the CRC without the appended length and without verifying `check`.
+    fn build_linear(&self) -> Result<CRC<Sum>, CheckBuilderErr> {
         let width = match self.width {
             None => return Err(CheckBuilderErr::MissingParameter("width")),
             Some(w) => w,
@@ -108,7 +179,11 @@ impl<Sum: BitNum> CRCBuilder<Sum> {
         if xorout & !mask != Sum::zero() {
             return Err(CheckBuilderErr::ValueOutOfRange("xorout"));
         }
-        let crc = CRC {
+        let wordspec = WordSpec::new(
+            self.wordsize.unwrap_or(8),
+            self.endian.unwrap_or(Endian::Big),
+        )?;
+        Ok(CRC {
             width,
             poly,
             init,
@@ -116,21 +191,10 @@ impl<Sum: BitNum> CRCBuilder<Sum> {
             refin,
             refout,
             mask,
+            wordspec,
             name: self.name.clone(),
             table: CRC::<Sum>::generate_crc_table(poly, width),
-        };
-        match self.check {
-            Some(chk) => {
-                if chk & !mask != Sum::zero() {
-                    Err(CheckBuilderErr::ValueOutOfRange("check"))
-                } else if crc.digest(&b"123456789"[..]).unwrap() != chk {
-                    Err(CheckBuilderErr::CheckFail)
-                } else {
-                    Ok(crc)
-                }
-            }
-            None => Ok(crc),
-        }
+        })
     }
 }
 
@@ -143,19 +207,93 @@ pub struct CRC<Sum: BitNum> {
     poly: Sum,
     mask: Sum,
     width: usize,
+    wordspec: WordSpec,
     name: Option<String>,
     table: Box<[Sum; 256]>,
 }
 
+/// The encoding of the length that gets appended to the text with `lenappend`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LenAppend {
+    /// As few bytes as needed, least significant byte first, like POSIX `cksum` (`minle`)
+    Minimal,
+    /// A 32-bit integer in the given byte order (`u32le` or `u32be`)
+    U32(Endian),
+}
+
+impl LenAppend {
+    /// The bytes that get appended to a text of `len` bytes, or `None` if `len` does not fit into the encoding.
+    ///
+    /// Like with POSIX `cksum`, the minimal encoding can hold any length, while the 32-bit ones
+    /// can't be used for texts of 4GiB or more.
+    pub fn encode(&self, len: usize) -> Option<Vec<u8>> {
+        match self {
+            LenAppend::Minimal => {
+                let mut bytes = Vec::new();
+                let mut rest = len;
+                while rest != 0 {
+                    bytes.push(rest as u8);
+                    rest >>= 8;
+                }
+                Some(bytes)
+            }
+            LenAppend::U32(Endian::Little) => Some(u32::try_from(len).ok()?.to_le_bytes().to_vec()),
+            LenAppend::U32(Endian::Big) => Some(u32::try_from(len).ok()?.to_be_bytes().to_vec()),
+        }
+    }
+    /// Feeds the encoded `len` to an unfinalized sum of `chk`, or returns `None` if it can't be encoded.
+    pub(crate) fn append<D: Digest + ?Sized>(
+        &self,
+        chk: &D,
+        sum: D::Sum,
+        len: usize,
+    ) -> Option<D::Sum> {
+        let bytes = self.encode(len)?;
+        Some(bytes.into_iter().fold(sum, |s, b| chk.dig_byte(s, b)))
+    }
+}
+
+impl FromStr for LenAppend {
+    type Err = CheckBuilderErr;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "minle" => Ok(LenAppend::Minimal),
+            "u32le" => Ok(LenAppend::U32(Endian::Little)),
+            "u32be" => Ok(LenAppend::U32(Endian::Big)),
+            _ => Err(CheckBuilderErr::MalformedString(String::from("lenappend"))),
+        }
+    }
+}
+
+impl Display for LenAppend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LenAppend::Minimal => write!(f, "minle"),
+            LenAppend::U32(Endian::Little) => write!(f, "u32le"),
+            LenAppend::U32(Endian::Big) => write!(f, "u32be"),
+        }
+    }
+}
+
 impl<Sum: BitNum> Display for CRC<Sum> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self.name {
             Some(n) => write!(f, "{}", n),
-            None => write!(
-                f,
-                "crc width={} poly={:#x} init={:#x} xorout={:#x} refin={} refout={}",
-                self.width, self.poly, self.init, self.xorout, self.refin, self.refout
-            ),
+            None => {
+                write!(
+                    f,
+                    "crc width={} poly={:#x} init={:#x} xorout={:#x} refin={} refout={}",
+                    self.width, self.poly, self.init, self.xorout, self.refin, self.refout
+                )?;
+                if self.wordspec.wordsize != 8 {
+                    write!(
+                        f,
+                        " wordsize={} endian={}",
+                        self.wordspec.wordsize, self.wordspec.endian
+                    )?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -170,6 +308,9 @@ impl<Sum: BitNum> CRC<Sum> {
             width: None,
             refin: None,
             refout: None,
+            wordsize: None,
+            endian: None,
+            lenappend: None,
             check: None,
             name: None,
         }
@@ -211,6 +352,68 @@ impl<Sum: BitNum> CRC<Sum> {
             sum
         }
     }
+    /// Multiplies an unreflected sum by X, with `bit` being added to the coefficient that gets reduced.
+    fn shift_bit(&self, unrefsum: Sum, bit: bool) -> Sum {
+        let overhang = unrefsum >> (self.width - 1) & Sum::one() != Sum::zero();
+        let shifted = (unrefsum << 1) & self.mask;
+        if overhang != bit {
+            shifted ^ self.poly
+        } else {
+            shifted
+        }
+    }
+    /// Raises an unreflected shift to the `n`-th power, by square and multiply.
+    fn shift_pow(&self, mut power: Sum, mut n: usize) -> Sum {
+        // `shift` takes a regular sum, so the unreflected shifts get regularized before and after
+        let mul = |a: Sum, b: &Sum| self.regularize(self.shift(self.regularize(a), b));
+        let mut shift = Sum::one();
+        while n > 0 {
+            if n & 1 == 1 {
+                shift = mul(shift, &power);
+            }
+            power = mul(power, &power);
+            n >>= 1;
+        }
+        shift
+    }
+    /// Multiplies an unreflected sum by X^8.
+    fn shift_byte(&self, unrefsum: Sum) -> Sum {
+        if self.width <= 8 {
+            let overhang = unrefsum << (8 - self.width);
+            self.get_table_entry(overhang)
+        } else {
+            let overhang = unrefsum >> (self.width - 8);
+            let l_remain = (unrefsum << 8) & self.mask;
+            self.get_table_entry(overhang) ^ l_remain
+        }
+    }
+    /// Processes a single bit of the text, for texts which are not a whole number of bytes.
+    pub fn dig_bit(&self, sum: Sum, bit: bool) -> Sum {
+        self.regularize(self.shift_bit(self.regularize(sum), bit))
+    }
+    /// Calculates the checksum of the `len` bits of `bytes` starting at bit `start`.
+    ///
+    /// The bits of a byte are ordered like the CRC reads them, i.e. from the most significant
+    /// bit to the least significant one, or the other way around if `refin` is set.
+    /// Note that the `wordsize` is ignored here, the bytes are read in order.
+    pub fn digest_bits(&self, bytes: &[u8], start: usize, len: usize) -> Sum {
+        let sum = (start..start + len).fold(self.init(), |sum, i| {
+            self.dig_bit(sum, text_bit(bytes, i, self.refin))
+        });
+        self.finalize(sum)
+    }
+    /// Expands the text into one byte per bit (in the order of `digest_bits`), for use with `BitCRC`.
+    pub fn text_bits(&self, bytes: &[u8]) -> Vec<u8> {
+        (0..8 * bytes.len())
+            .map(|i| text_bit(bytes, i, self.refin) as u8)
+            .collect()
+    }
+}
+
+/// Gets the bit at index `i` of the text, with the bits in a byte being least significant first if `refin` is set.
+pub(crate) fn text_bit(bytes: &[u8], i: usize, refin: bool) -> bool {
+    let shift = if refin { i & 7 } else { 7 - (i & 7) };
+    bytes[i / 8] >> shift & 1 == 1
 }
 impl<Sum: BitNum> FromStr for CRCBuilder<Sum> {
     /// See documentation of Fromstr on CRC<Sum>
@@ -230,6 +433,14 @@ impl<Sum: BitNum> FromStr for CRCBuilder<Sum> {
                 "xorout" => Sum::from_hex(&current_val).ok().map(|x| crc.xorout(x)),
                 "refin" => bool::from_str(&current_val).ok().map(|x| crc.refin(x)),
                 "refout" => bool::from_str(&current_val).ok().map(|x| crc.refout(x)),
+                "wordsize" => usize::from_str(&current_val).ok().map(|x| crc.wordsize(x)),
+                // hardware CRC units usually call it word order, so this is accepted too
+                "endian" | "wordorder" => {
+                    Endian::from_str(&current_val).ok().map(|x| crc.endian(x))
+                }
+                "lenappend" => LenAppend::from_str(&current_val)
+                    .ok()
+                    .map(|x| crc.lenappend(x)),
                 "residue" => Some(&mut crc),
                 "check" => Sum::from_hex(&current_val).ok().map(|x| crc.check(x)),
                 "name" => Some(crc.name(&current_val)),
@@ -286,6 +497,15 @@ impl<S: BitNum> Digest for CRC<S> {
             self.get_table_entry(overhang) ^ l_remain
         })
     }
+    fn dig_word(&self, sum: Self::Sum, word: u64) -> Self::Sum {
+        // the most significant byte of the word comes first
+        (0..self.wordspec.word_bytes())
+            .rev()
+            .fold(sum, |sum, i| self.dig_byte(sum, (word >> (8 * i)) as u8))
+    }
+    fn wordspec(&self) -> WordSpec {
+        self.wordspec
+    }
     fn finalize(&self, sum: Self::Sum) -> Self::Sum {
         sum ^ self.xorout
     }
@@ -337,14 +557,137 @@ impl<S: BitNum> LinearCheck for CRC<S> {
         Self::Shift::one()
     }
     fn inc_shift(&self, shift: Self::Shift) -> Self::Shift {
-        // note: shifts are always unreflected
-        if self.width <= 8 {
-            let overhang = shift << (8 - self.width);
-            self.get_table_entry(overhang)
-        } else {
-            let overhang = shift >> (self.width - 8);
-            let l_remain = (shift << 8) & self.mask;
-            self.get_table_entry(overhang) ^ l_remain
+        // note: shifts are always unreflected and go over a whole word
+        (0..self.wordspec.word_bytes()).fold(shift, |shift, _| self.shift_byte(shift))
+    }
+    fn shift_n(&self, n: usize) -> Self::Shift {
+        self.shift_pow(self.inc_shift(self.init_shift()), n)
+    }
+}
+
+/// A CRC which only reads the lowest bit of each byte of the text.
+///
+/// With texts expanded by `CRC::text_bits`, this can be used to find segments where
+/// the start and end are at bit positions instead of byte positions, at the cost of
+/// needing 8 times the memory.
+#[derive(PartialEq, Eq)]
+pub struct BitCRC<S: BitNum>(pub CRC<S>);
+
+impl<S: BitNum> Display for BitCRC<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<S: BitNum> Digest for BitCRC<S> {
+    type Sum = S;
+    fn init(&self) -> Self::Sum {
+        self.0.init()
+    }
+    fn dig_byte(&self, sum: Self::Sum, byte: u8) -> Self::Sum {
+        self.0.dig_bit(sum, byte & 1 == 1)
+    }
+    fn finalize(&self, sum: Self::Sum) -> Self::Sum {
+        self.0.finalize(sum)
+    }
+}
+
+impl<S: BitNum> LinearCheck for BitCRC<S> {
+    type Shift = S;
+    fn init_shift(&self) -> Self::Shift {
+        self.0.init_shift()
+    }
+    fn inc_shift(&self, shift: Self::Shift) -> Self::Shift {
+        // like in CRC, shifts are unreflected, but only advance by a single bit
+        self.0.shift_bit(shift, false)
+    }
+    fn shift_n(&self, n: usize) -> Self::Shift {
+        self.0.shift_pow(self.inc_shift(self.init_shift()), n)
+    }
+    fn shift(&self, sum: Self::Sum, shift: &Self::Shift) -> Self::Sum {
+        self.0.shift(sum, shift)
+    }
+    fn add(&self, sum_a: Self::Sum, sum_b: &Self::Sum) -> Self::Sum {
+        self.0.add(sum_a, sum_b)
+    }
+    fn negate(&self, sum: Self::Sum) -> Self::Sum {
+        self.0.negate(sum)
+    }
+}
+
+/// A CRC which gets the length of the text appended to it (see `LenAppend`), like POSIX `cksum`.
+///
+/// This is not linear anymore, so unlike `CRC`, it only implements `Digest`.
+/// Note that the length is only known at the end of the text, so only `digest` appends it,
+/// while the other methods are the ones of the CRC; the brute-force search in `brute` is
+/// given the `LenAppend` separately.
+#[derive(PartialEq, Eq)]
+pub struct LenAppendCRC<S: BitNum> {
+    crc: CRC<S>,
+    lenappend: LenAppend,
+}
+
+impl<S: BitNum> LenAppendCRC<S> {
+    /// Appends the length to the texts of `crc` with the encoding `lenappend`.
+    pub fn new(crc: CRC<S>, lenappend: LenAppend) -> Self {
+        LenAppendCRC { crc, lenappend }
+    }
+    /// The CRC without the appended length.
+    pub fn crc(&self) -> &CRC<S> {
+        &self.crc
+    }
+    /// Gets how the length is appended to the text.
+    pub fn lenappend(&self) -> LenAppend {
+        self.lenappend
+    }
+}
+
+impl<S: BitNum> Display for LenAppendCRC<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.crc.fmt(f)?;
+        match self.crc.name {
+            Some(_) => Ok(()),
+            None => write!(f, " lenappend={}", self.lenappend),
+        }
+    }
+}
+
+impl<S: BitNum> FromStr for LenAppendCRC<S> {
+    /// Like `CRC::from_str`, but `lenappend` has to be given.
+    fn from_str(s: &str) -> Result<LenAppendCRC<S>, CheckBuilderErr> {
+        CRCBuilder::<S>::from_str(s)?.build_lenappend()
+    }
+    type Err = CheckBuilderErr;
+}
+
+impl<S: BitNum> Digest for LenAppendCRC<S> {
+    type Sum = S;
+    fn init(&self) -> Self::Sum {
+        self.crc.init()
+    }
+    fn dig_byte(&self, sum: Self::Sum, byte: u8) -> Self::Sum {
+        self.crc.dig_byte(sum, byte)
+    }
+    fn dig_word(&self, sum: Self::Sum, word: u64) -> Self::Sum {
+        self.crc.dig_word(sum, word)
+    }
+    fn wordspec(&self) -> WordSpec {
+        self.crc.wordspec()
+    }
+    fn finalize(&self, sum: Self::Sum) -> Self::Sum {
+        self.crc.finalize(sum)
+    }
+    fn digest<R: Read>(&self, buf: R) -> Result<Self::Sum, std::io::Error> {
+        let (sum, len) = digest_words(&self.crc, buf)?;
+        match self.lenappend.append(&self.crc, sum, len) {
+            Some(sum) => Ok(self.finalize(sum)),
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "Length {} is too large for lenappend={}",
+                    len, self.lenappend
+                ),
+            )),
         }
     }
 }
@@ -352,7 +695,10 @@ impl<S: BitNum> LinearCheck for CRC<S> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bitnum::U512;
+    use crate::checksum::brute::{self, BruteOptions};
     use crate::checksum::tests::{check_example, test_find, test_prop, test_shifts};
+    use crate::checksum::{RelativeIndex, Relativity};
     #[test]
     fn cms_16() {
         assert!(CRC::<u32>::with_options()
@@ -574,6 +920,150 @@ mod tests {
         check_example(&crc, 0x030c57c0142280dfd62847)
     }
     #[test]
+    fn words() {
+        // the CRC unit of STM32 microcontrollers, fed with little endian 32-bit words
+        let crc = CRC::<u32>::from_str(
+            "width=32 poly=0x04c11db7 init=0xffffffff wordsize=32 endian=little",
+        )
+        .unwrap();
+        assert_eq!(crc.digest(&b"12345678"[..]).unwrap(), 0xfefc54f9);
+        assert_eq!(crc.digest(&b"123456789"[..]).unwrap(), 0xaff19057);
+        assert_eq!(
+            crc.to_string(),
+            "crc width=32 poly=0x4c11db7 init=0xffffffff xorout=0x0 refin=false refout=false wordsize=32 endian=little"
+        );
+        let shown = crc.to_string();
+        assert!(CRC::<u32>::from_str(shown.trim_start_matches("crc ")).unwrap() == crc);
+        let alias = "width=32 poly=0x04c11db7 init=0xffffffff wordsize=32 wordorder=little";
+        assert!(CRC::<u32>::from_str(alias).unwrap() == crc);
+        test_prop(&crc);
+        let x = Vec::from("implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR");
+        let y = Vec::from("This program comes with ABSOLUTELY NO WARRANTY; for details typ");
+        let merchantibility = crc.digest(b"MERCHANTABILITY".as_ref()).unwrap();
+        let ith_absolutely_ = crc.digest(b"ith ABSOLUTELY ".as_ref()).unwrap();
+        assert_eq!(
+            crc.find_segments(
+                &[x, y],
+                &[merchantibility, ith_absolutely_],
+                Relativity::Start
+            ),
+            vec![(vec![20], vec![RelativeIndex::FromStart(35)])]
+        );
+    }
+    #[test]
+    fn lenappend() {
+        // POSIX cksum
+        let cksum = "width=32 poly=0x04c11db7 xorout=0xffffffff lenappend=minle check=0x377a6011";
+        assert!(CRC::<u32>::from_str(cksum).is_err());
+        let crc = LenAppendCRC::<u32>::from_str(cksum).unwrap();
+        assert_eq!(crc.digest(&b""[..]).unwrap(), 0xffffffff);
+        assert_eq!(
+            crc.to_string(),
+            "crc width=32 poly=0x4c11db7 init=0x0 xorout=0xffffffff refin=false refout=false lenappend=minle"
+        );
+        assert_eq!(LenAppend::Minimal.encode(0x1234), Some(vec![0x34, 0x12]));
+        assert_eq!(
+            LenAppend::U32(Endian::Big).encode(0x1234),
+            Some(vec![0, 0, 0x12, 0x34])
+        );
+        #[cfg(target_pointer_width = "64")]
+        {
+            let len = 0x1_0000_0001;
+            assert_eq!(
+                LenAppend::Minimal.encode(len),
+                Some(vec![0x01, 0, 0, 0, 0x01])
+            );
+            assert_eq!(LenAppend::U32(Endian::Little).encode(len), None);
+        }
+        let x = Vec::from("implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR");
+        let y = Vec::from("This program comes with ABSOLUTELY NO WARRANTY; for details typ");
+        let merchantibility = crc.digest(b"MERCHANTABILITY".as_ref()).unwrap();
+        let ith_absolutely_ = crc.digest(b"ith ABSOLUTELY ".as_ref()).unwrap();
+        let sums = [merchantibility, ith_absolutely_];
+        let files = [x, y];
+        let find = |rel| {
+            let opts = BruteOptions::default();
+            let l = Some(crc.lenappend());
+            brute::find_padded_segments(crc.crc(), &files, &sums, rel, None, l, &opts)
+        };
+        assert_eq!(
+            find(Relativity::Start),
+            vec![(vec![20], vec![RelativeIndex::FromStart(35)])]
+        );
+        assert_eq!(
+            find(Relativity::End),
+            vec![(vec![20], vec![RelativeIndex::FromEnd(28)])]
+        );
+        let crc = LenAppendCRC::<u16>::from_str("width=16 poly=0x1021 init=0xffff lenappend=u32be")
+            .unwrap();
+        let mut text = Vec::from(&b"123456789"[..]);
+        text.extend([0, 0, 0, 9].iter());
+        let without = CRC::<u16>::from_str("width=16 poly=0x1021 init=0xffff").unwrap();
+        assert_eq!(
+            crc.digest(&b"123456789"[..]).unwrap(),
+            without.digest(text.as_slice()).unwrap()
+        );
+    }
+    #[test]
+    fn wide_256() {
+        let crc = CRC::<U512>::from_str(
+            "width=256 poly=0x04c11db704c11db704c11db704c11db704c11db704c11db704c11db704c11db7 \
+            init=0xffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff \
+            xorout=0xffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff \
+            refin=true refout=true \
+            check=0xcb76d35778adf619a4c084d1dbc084d1dbc084d1dbc084d1dbc084d1dbc084d1",
+        )
+        .unwrap();
+        test_shifts(&crc);
+        test_find(&crc);
+        test_prop(&crc);
+        check_example(
+            &crc,
+            U512::from_hex("6e918f109eba25c0d52bfb9233c6acb730e6c7d7e2304bc4e49f394fa2c86399")
+                .unwrap(),
+        );
+        let crc = CRC::<U512>::from_str(
+            "width=200 poly=0xb704c11db704c11db704c11db704c11db704c11db704c11db7 \
+            check=0x6931a9ad6931a9ad6931a9ad6931a9ad69bd376ef23a0ac6b5",
+        )
+        .unwrap();
+        test_shifts(&crc);
+        test_prop(&crc);
+        check_example(
+            &crc,
+            U512::from_hex("35af5fa5be9e5e3b7ca284857bab66a0dd641ec533855673f3").unwrap(),
+        );
+        // narrower widths give the same results as with the primitive types
+        let crc = CRC::<U512>::from_str(
+            "width=82 poly=0x0308c0111011401440411 refin=true refout=true check=0x09ea83f625023801fd612",
+        )
+        .unwrap();
+        check_example(&crc, U512::from(0x030c57c0142280dfd62847u128));
+    }
+    #[test]
+    fn bits() {
+        let crc = CRC::<u16>::from_str("width=15 poly=0x4599").unwrap();
+        let msg = [0x4au8, 0x5b, 0xe0];
+        assert_eq!(crc.digest_bits(&msg, 0, 19), 0x19bd);
+        // bits after the end are ignored
+        assert_eq!(crc.digest_bits(&[0x4a, 0x5b, 0xff], 0, 19), 0x19bd);
+        assert_eq!(crc.digest_bits(&msg, 0, 24), crc.digest(&msg[..]).unwrap());
+        let crc = CRC::<u16>::from_str("width=15 poly=0x4599 refin=true refout=true").unwrap();
+        assert_eq!(crc.digest_bits(&msg, 0, 19), 0x6f11);
+        assert_eq!(crc.digest_bits(&msg, 0, 24), crc.digest(&msg[..]).unwrap());
+        let bitcrc = BitCRC(crc);
+        test_shifts(&bitcrc);
+        test_prop(&bitcrc);
+        let text = [0x12u8, 0x34, 0x56, 0x78, 0x9a];
+        let sum = bitcrc.0.digest_bits(&text, 3, 19);
+        let segs = bitcrc.find_segments(&[bitcrc.0.text_bits(&text)], &[sum], Relativity::Start);
+        // bits 2 and 3 are zero, which makes no difference at the start with init = 0
+        assert_eq!(
+            segs,
+            vec![(vec![2, 3, 4], vec![RelativeIndex::FromStart(22)])]
+        );
+    }
+    #[test]
     fn parity_1() {
         let crc = CRC::<u8>::with_options()
             .poly(1)
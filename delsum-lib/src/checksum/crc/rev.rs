@@ -10,13 +10,23 @@
 //!
 //! If `init` is not known, it is neccessary to know two checksums of files with different lengths.
 //! In case only checksums of files with a set length are required, setting `init = 0` is sufficient.
+//!
+//! If the `wordsize` is bigger than 8 and no `endian` is given, both byte orders are tried.
+//! A given `lenappend` is simply appended to the files, so it has to be known beforehand.
+//! The returned CRCs are then the ones without it, which get the length appended with `LenAppendCRC::new`.
+//!
+//! When reversing CRCs of multiple widths on the same files, the conversion of the files to polynomials
+//! can be shared between them with `CrcTexts` and `reverse_crc_shared`.
 use super::{CRCBuilder, CRC};
+use crate::bitnum::BitNum;
 use crate::checksum::{unresult_iter, CheckReverserError};
+use crate::endian::{Endian, WordSpec};
 use delsum_poly::*;
 #[cfg(feature = "parallel")]
 use rayon::prelude::*;
-use std::convert::TryInto;
+use std::borrow::Cow;
 use std::pin::Pin;
+use std::sync::{Arc, Mutex};
 
 /// Find the parameters of a CRC algorithm.
 ///
@@ -25,17 +35,59 @@ use std::pin::Pin;
 /// `verbosity` makes the function output what it is doing.
 ///
 /// The `width` parameter of the builder has to be set.
-pub fn reverse_crc<'a>(
-    spec: &CRCBuilder<u128>,
-    chk_bytes: &'a [(&[u8], u128)],
+pub fn reverse_crc<'a, S: BitNum>(
+    spec: &CRCBuilder<S>,
+    chk_bytes: &'a [(&[u8], S)],
+    verbosity: u64,
+) -> impl Iterator<Item = Result<CRC<S>, CheckReverserError>> + 'a {
+    reverse_crc_bits(spec, whole_bytes(chk_bytes), verbosity)
+}
+
+/// Find the parameters of a CRC algorithm, for texts which are not a whole number of bytes.
+///
+/// Like `reverse_crc`, but each of the `chk_bits` also contains the number of bits of the text
+/// (starting from the first bit), with the bits ordered like in `CRC::digest_bits`.
+pub fn reverse_crc_bits<'a, S: BitNum + 'a>(
+    spec: &CRCBuilder<S>,
+    chk_bits: Vec<(&'a [u8], usize, S)>,
+    verbosity: u64,
+) -> impl Iterator<Item = Result<CRC<S>, CheckReverserError>> + 'a {
+    reverse_crc_texts(spec, chk_bits, None, verbosity)
+}
+
+/// Find the parameters of a CRC algorithm, reusing the polynomials of the files in `texts`.
+///
+/// Like `reverse_crc`, but the files are the ones of `texts` and `sums` contains their checksums.
+/// Calls with the same `texts` only convert the files once, even for different widths.
+pub fn reverse_crc_shared<'a, S: BitNum + 'a>(
+    spec: &CRCBuilder<S>,
+    texts: &'a CrcTexts<'a>,
+    sums: &[S],
     verbosity: u64,
-) -> impl Iterator<Item = Result<CRC<u128>, CheckReverserError>> + 'a {
+) -> impl Iterator<Item = Result<CRC<S>, CheckReverserError>> + 'a {
+    let chk_bits = texts
+        .files
+        .iter()
+        .zip(sums.iter())
+        .map(|(f, s)| (*f, 8 * f.len(), *s))
+        .collect();
+    reverse_crc_texts(spec, chk_bits, Some(texts), verbosity)
+}
+
+fn reverse_crc_texts<'a, S: BitNum + 'a>(
+    spec: &CRCBuilder<S>,
+    chk_bits: Vec<(&'a [u8], usize, S)>,
+    texts: Option<&'a CrcTexts<'a>>,
+    verbosity: u64,
+) -> impl Iterator<Item = Result<CRC<S>, CheckReverserError>> + 'a {
     let spec = spec.clone();
-    let ref_combinations: Vec<_> = ref_comb(spec.refin, spec.refout);
-    ref_combinations
+    let combinations = combinations(&spec);
+    combinations
         .into_iter()
-        .map(move |(refin, refout)| {
-            unresult_iter(reverse(&spec, chk_bytes, verbosity, refin, refout))
+        .map(move |(refin, refout, endian)| {
+            unresult_iter(reverse(
+                &spec, &chk_bits, texts, verbosity, refin, refout, endian,
+            ))
         })
         .flatten()
 }
@@ -43,23 +95,112 @@ pub fn reverse_crc<'a>(
 /// Parallel version of reverse_crc.
 ///
 /// Note that this is parallel only because it tries the 4 combinations of refin, refout
-/// at once when not given, giving at most a 4x speedup.
+/// (and the word orders) at once when not given, giving at most a 4x (or 8x) speedup.
+#[cfg(feature = "parallel")]
+pub fn reverse_crc_para<'a, S: BitNum>(
+    spec: &CRCBuilder<S>,
+    chk_bytes: &'a [(&[u8], S)],
+    verbosity: u64,
+) -> impl ParallelIterator<Item = Result<CRC<S>, CheckReverserError>> + 'a {
+    reverse_crc_bits_para(spec, whole_bytes(chk_bytes), verbosity)
+}
+
+/// Parallel version of reverse_crc_bits.
 #[cfg(feature = "parallel")]
-pub fn reverse_crc_para<'a>(
-    spec: &CRCBuilder<u128>,
-    chk_bytes: &'a [(&[u8], u128)],
+pub fn reverse_crc_bits_para<'a, S: BitNum + 'a>(
+    spec: &CRCBuilder<S>,
+    chk_bits: Vec<(&'a [u8], usize, S)>,
     verbosity: u64,
-) -> impl ParallelIterator<Item = Result<CRC<u128>, CheckReverserError>> + 'a {
+) -> impl ParallelIterator<Item = Result<CRC<S>, CheckReverserError>> + 'a {
+    reverse_crc_texts_para(spec, chk_bits, None, verbosity)
+}
+
+/// Parallel version of reverse_crc_shared.
+#[cfg(feature = "parallel")]
+pub fn reverse_crc_shared_para<'a, S: BitNum + 'a>(
+    spec: &CRCBuilder<S>,
+    texts: &'a CrcTexts<'a>,
+    sums: &[S],
+    verbosity: u64,
+) -> impl ParallelIterator<Item = Result<CRC<S>, CheckReverserError>> + 'a {
+    let chk_bits = texts
+        .files
+        .iter()
+        .zip(sums.iter())
+        .map(|(f, s)| (*f, 8 * f.len(), *s))
+        .collect();
+    reverse_crc_texts_para(spec, chk_bits, Some(texts), verbosity)
+}
+
+#[cfg(feature = "parallel")]
+fn reverse_crc_texts_para<'a, S: BitNum + 'a>(
+    spec: &CRCBuilder<S>,
+    chk_bits: Vec<(&'a [u8], usize, S)>,
+    texts: Option<&'a CrcTexts<'a>>,
+    verbosity: u64,
+) -> impl ParallelIterator<Item = Result<CRC<S>, CheckReverserError>> + 'a {
     let spec = spec.clone();
-    let ref_combinations: Vec<_> = ref_comb(spec.refin, spec.refout);
-    ref_combinations
+    let combinations = combinations(&spec);
+    combinations
         .into_par_iter()
-        .map(move |(refin, refout)| {
-            unresult_iter(reverse(&spec, chk_bytes, verbosity, refin, refout)).par_bridge()
+        .map(move |(refin, refout, endian)| {
+            unresult_iter(reverse(
+                &spec, &chk_bits, texts, verbosity, refin, refout, endian,
+            ))
+            .par_bridge()
         })
         .flatten()
 }
 
+// the polynomials of the files with their length in bits
+type TextPolys = Arc<Vec<(PolyPtr, usize)>>;
+
+/// The files for reversing CRCs of possibly multiple widths, together with their polynomials.
+///
+/// The polynomial of a file only depends on `refin` and the word order, so it is calculated
+/// the first time it is needed and then shared by all CRCs reversed with `reverse_crc_shared`.
+pub struct CrcTexts<'a> {
+    files: Vec<&'a [u8]>,
+    // the polynomials for each combination of refin and word order that was needed so far
+    polys: Mutex<Vec<((bool, WordSpec), TextPolys)>>,
+}
+
+impl<'a> CrcTexts<'a> {
+    pub fn new(files: &[&'a [u8]]) -> Self {
+        CrcTexts {
+            files: files.to_vec(),
+            polys: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn polys(&self, refin: bool, wordspec: WordSpec) -> TextPolys {
+        // the lock is held during the conversion, so that other threads wait for it instead of doing it again
+        let mut cache = self.polys.lock().unwrap();
+        if let Some((_, polys)) = cache.iter().find(|(key, _)| *key == (refin, wordspec)) {
+            return polys.clone();
+        }
+        let polys = Arc::new(
+            self.files
+                .iter()
+                .map(|f| {
+                    let text = reorder_words(f, wordspec);
+                    (text_to_poly(&text, 8 * text.len(), refin), 8 * text.len())
+                })
+                .collect(),
+        );
+        cache.push(((refin, wordspec), Arc::clone(&polys)));
+        polys
+    }
+}
+
+// all bits of the files are used
+fn whole_bytes<'a, S: BitNum>(chk_bytes: &'a [(&[u8], S)]) -> Vec<(&'a [u8], usize, S)> {
+    chk_bytes
+        .iter()
+        .map(|(b, c)| (*b, 8 * b.len(), *c))
+        .collect()
+}
+
 // find all combinations of refin, refout using both bool values when a parameter is not given
 fn ref_comb(maybe_refin: Option<bool>, maybe_refout: Option<bool>) -> Vec<(bool, bool)> {
     let refins = maybe_refin
@@ -75,20 +216,64 @@ fn ref_comb(maybe_refin: Option<bool>, maybe_refout: Option<bool>) -> Vec<(bool,
         .collect()
 }
 
+// all combinations of refin, refout and the word order that need to be tried
+fn combinations<S: BitNum>(spec: &CRCBuilder<S>) -> Vec<(bool, bool, Endian)> {
+    let endians = Endian::to_try(spec.endian, spec.wordsize);
+    ref_comb(spec.refin, spec.refout)
+        .into_iter()
+        .flat_map(|(i, o)| endians.iter().map(move |e| (i, o, *e)))
+        .collect()
+}
+
+// the CRC reads the bytes of a word in big endian order, so we reorder the
+// bytes beforehand, resulting in a text which can be read byte by byte
+fn reorder_words(bytes: &[u8], wordspec: WordSpec) -> Cow<'_, [u8]> {
+    if wordspec.wordsize == 8 {
+        return Cow::from(bytes);
+    }
+    let wb = wordspec.word_bytes();
+    Cow::from(
+        wordspec
+            .iter_words(bytes)
+            .flat_map(|word| (0..wb).rev().map(move |i| (word >> (8 * i)) as u8))
+            .collect::<Vec<_>>(),
+    )
+}
+
 // wrapper to call rev_from_polys with polynomial arguments
-fn reverse<'a>(
-    spec: &CRCBuilder<u128>,
-    chk_bytes: &'a [(&[u8], u128)],
+fn reverse<S: BitNum>(
+    spec: &CRCBuilder<S>,
+    chk_bits: &[(&[u8], usize, S)],
+    texts: Option<&CrcTexts>,
     verbosity: u64,
     refin: bool,
     refout: bool,
-) -> Result<impl Iterator<Item = CRC<u128>> + 'a, CheckReverserError> {
+    endian: Endian,
+) -> Result<impl Iterator<Item = CRC<S>>, CheckReverserError> {
     let width = match spec.width {
         Some(x) => x,
         None => return Err(CheckReverserError::MissingParameter("width")),
     };
+    let wordspec = WordSpec::new(spec.wordsize.unwrap_or(8), endian)
+        .map_err(|_| CheckReverserError::ValueOutOfRange("wordsize"))?;
+    let whole_bytes = wordspec.wordsize == 8 && spec.lenappend.is_none();
+    if !whole_bytes && chk_bits.iter().any(|(b, l, _)| *l != 8 * b.len()) {
+        return Err(CheckReverserError::UnsuitableFiles(
+            "texts with a bit length need a word size of 8 and no appended length",
+        ));
+    }
+    if let Some(lenappend) = spec.lenappend {
+        if chk_bits
+            .iter()
+            .any(|(b, _, _)| lenappend.encode(b.len()).is_none())
+        {
+            return Err(CheckReverserError::UnsuitableFiles(
+                "files too long for the appended length",
+            ));
+        }
+    }
     // check for errors in the parameters
-    if 3 > chk_bytes.len()
+    if 3 > chk_bits.len()
         + spec.init.is_some() as usize
         + spec.xorout.is_some() as usize
         + spec.poly.is_some() as usize
@@ -97,23 +282,42 @@ fn reverse<'a>(
             "at least 3 parameters/files",
         ));
     }
-    if spec.init.is_some()
-        || chk_bytes.iter().map(|x| x.0.len()).max() == chk_bytes.iter().map(|x| x.0.len()).min()
+    if spec.init.is_none()
+        && chk_bits.iter().map(|x| x.1).max() == chk_bits.iter().map(|x| x.1).min()
     {
         return Err(CheckReverserError::UnsuitableFiles(
             "need at least one file with different length",
         ));
     }
-    // convert the files to polynomials
-    let mut polys: Vec<_> = chk_bytes
+    // convert the files to polynomials, or take the shared ones if they can be used
+    let shared = match texts {
+        Some(texts) if spec.lenappend.is_none() => Some(texts.polys(refin, wordspec)),
+        _ => None,
+    };
+    let mut polys: Vec<_> = chk_bits
         .iter()
-        .map(|(b, c)| (bytes_to_poly(b, *c, width as u8, refin, refout), b.len()))
+        .enumerate()
+        .map(|(i, (b, l, c))| {
+            if let Some(shared) = &shared {
+                let (text, len) = &shared[i];
+                return (with_checksum(text, *c, width, refout), *len);
+            }
+            let mut text = reorder_words(b, wordspec);
+            if let Some(lenappend) = spec.lenappend {
+                // the lengths were checked to fit above
+                text.to_mut()
+                    .extend(lenappend.encode(b.len()).into_iter().flatten());
+            }
+            // a partial word at the end gets padded
+            let len = l + 8 * (text.len() - b.len());
+            (bytes_to_poly(&text, len, *c, width, refin, refout), len)
+        })
         .collect();
     // sort by reverse file length
     polys.sort_by(|(fa, la), (fb, lb)| la.cmp(&lb).then(deg(fa).cmp(&deg(fb)).reverse()));
     // convert parameters to polynomials
     let revinfo = RevInfo::from_builder(spec, refin, refout);
-    rev_from_polys(&revinfo, &polys, verbosity).map(|x| x.iter())
+    rev_from_polys(&revinfo, &polys, verbosity).map(move |x| x.iter(wordspec))
 }
 
 struct RevInfo {
@@ -126,11 +330,11 @@ struct RevInfo {
 }
 
 impl RevInfo {
-    fn from_builder(spec: &CRCBuilder<u128>, refin: bool, refout: bool) -> Self {
+    fn from_builder<S: BitNum>(spec: &CRCBuilder<S>, refin: bool, refout: bool) -> Self {
         let width = spec.width.unwrap();
-        let init = spec.init.map(|i| new_poly(&i.to_be_bytes()));
+        let init = spec.init.map(num_to_poly);
         let poly = spec.poly.map(|p| {
-            let mut p = new_poly(&p.to_be_bytes());
+            let mut p = num_to_poly(p);
             // add leading coefficient, which is omitted in binary form
             p.pin_mut()
                 .add_to(&new_poly_shifted(&[1], width as i64, true));
@@ -139,7 +343,7 @@ impl RevInfo {
         // while init and poly are unaffected by refout, xorout is not
         let xorout = spec
             .xorout
-            .map(|x| new_poly(&cond_reverse(width as u8, x, refout).to_be_bytes()));
+            .map(|x| num_to_poly(cond_reverse(width, x, refout)));
         RevInfo {
             width,
             init,
@@ -162,7 +366,7 @@ struct RevResult {
 
 impl RevResult {
     // iterate over all possible parameters
-    fn iter(self) -> impl Iterator<Item = CRC<u128>> {
+    fn iter<S: BitNum>(self, wordspec: WordSpec) -> impl Iterator<Item = CRC<S>> {
         let RevResult {
             polys,
             inits,
@@ -178,22 +382,21 @@ impl RevResult {
                 inits
                     .iter_inits(&pol, &xorout)
                     .map(move |(poly_p, init_p, xorout_p)| {
-                        // convert polynomial parameters to a CRC<u128>
-                        let poly = poly_to_u128(&add(
-                            &poly_p,
-                            &new_poly_shifted(&[1], width as i64, true),
-                        ));
-                        let init = poly_to_u128(&init_p);
-                        let xorout = cond_reverse(width as u8, poly_to_u128(&xorout_p), refout);
-                        CRC::<u128>::with_options()
-                            .width(width)
+                        // convert polynomial parameters to a CRC<S>
+                        let poly =
+                            poly_to_num(&add(&poly_p, &new_poly_shifted(&[1], width as i64, true)));
+                        let init = poly_to_num(&init_p);
+                        let xorout = cond_reverse(width, poly_to_num(&xorout_p), refout);
+                        let mut crc = CRC::<S>::with_options();
+                        crc.width(width)
                             .poly(poly)
                             .init(init)
                             .xorout(xorout)
                             .refin(refin)
                             .refout(refout)
-                            .build()
-                            .unwrap()
+                            .wordsize(wordspec.wordsize)
+                            .endian(wordspec.endian);
+                        crc.build().unwrap()
                     })
             })
             .flatten()
@@ -266,7 +469,7 @@ fn rev_from_polys(
         }
     };
     // InitPlace is essentially a sparse polynomial with at most 2 coefficients being 1
-    // note that it has an implied factor of 8, because it uses the byte position instead of bit position
+    // note that it uses the bit position, so texts do not need to be a whole number of bytes
     let mut polys: Vec<_> = arg_polys
         .iter()
         .rev()
@@ -310,7 +513,7 @@ fn remove_inits(init: &Poly, polys: &mut [InitPoly]) {
     for (p, l) in polys {
         match l {
             InitPlace::Single(d) => {
-                p.pin_mut().add_to(&shift(init, 8 * *d as i64));
+                p.pin_mut().add_to(&shift(init, *d as i64));
                 *l = InitPlace::None;
             }
             // note: this branch shouldn't happen, but it is also no problem if it happens
@@ -405,7 +608,7 @@ fn find_polyhull(spec: &RevInfo, polys: Vec<InitPoly>, verbosity: u64) -> (Vec<I
 
     log("gcd'ing different length files together");
     for ((p, l), (q, m)) in contain_init_vec.iter().zip(contain_init_vec.iter().skip(1)) {
-        let power_8n = |n: usize| new_poly_shifted(&[1], 8 * n as i64, true);
+        let power_n = |n: usize| new_poly_shifted(&[1], n as i64, true);
         // this essentially tries to cancel out the init in the checksums
         // if you have a*init and b*init, you can get 0 by calculating b*a*init - a*b*init
         // this is almost done here, except for cancelling unneccessary common X^k between a and b
@@ -413,28 +616,28 @@ fn find_polyhull(spec: &RevInfo, polys: Vec<InitPoly>, verbosity: u64) -> (Vec<I
             (InitPlace::None, _) | (_, InitPlace::None) => unreachable!(),
             (InitPlace::Single(d), InitPlace::Single(e)) => {
                 let min = d.min(e);
-                (power_8n(d - min), power_8n(e - min))
+                (power_n(d - min), power_n(e - min))
             }
             (InitPlace::Single(d), InitPlace::Pair(e1, e2)) => {
                 let min = d.min(e1).min(e2);
-                let p_fac = power_8n(d - min);
-                let mut q_fac = power_8n(e2 - min);
-                q_fac += &power_8n(e1 - min);
+                let p_fac = power_n(d - min);
+                let mut q_fac = power_n(e2 - min);
+                q_fac += &power_n(e1 - min);
                 (p_fac, q_fac)
             }
             (InitPlace::Pair(d1, d2), InitPlace::Single(e)) => {
                 let min = d1.min(d1).min(e);
-                let mut p_fac = power_8n(d2 - min);
-                p_fac += &power_8n(d1 - min);
-                let q_fac = power_8n(e - min);
+                let mut p_fac = power_n(d2 - min);
+                p_fac += &power_n(d1 - min);
+                let q_fac = power_n(e - min);
                 (p_fac, q_fac)
             }
             (InitPlace::Pair(d1, d2), InitPlace::Pair(e1, e2)) => {
                 let min = d1.min(d2).min(e1).min(e2);
-                let mut p_fac = power_8n(d2 - min);
-                p_fac += &power_8n(d1 - min);
-                let mut q_fac = power_8n(e2 - min);
-                q_fac += &power_8n(e1 - min);
+                let mut p_fac = power_n(d2 - min);
+                p_fac += &power_n(d1 - min);
+                let mut q_fac = power_n(e2 - min);
+                q_fac += &power_n(e1 - min);
                 (p_fac, q_fac)
             }
         };
@@ -521,7 +724,7 @@ impl MemoPower {
                 panic!("Internal Error: Polynomials non-ascending");
             }
             let x = new_polyrem(&new_poly(&[1 << 1]), &self.hull);
-            let power_diff = powermod(&x, (new_level - self.prev_power) as i64 * 8);
+            let power_diff = powermod(&x, (new_level - self.prev_power) as i64);
             self.prev_power = new_level;
             self.prev_ppoly *= &power_diff;
             self.prev_ppoly.rep()
@@ -684,7 +887,7 @@ impl PrefactorMod {
         let x = new_polyrem(&new_poly(&[&1 << 1]), red_poly);
         let mod_power = match xorout.1 {
             InitPlace::None => new_polyrem(&new_zero(), red_poly),
-            InitPlace::Single(l) => powermod(&x, 8 * l as i64),
+            InitPlace::Single(l) => powermod(&x, l as i64),
             _ => panic!("Internal Error: Double"),
         };
         let poly_copy = copy_poly(red_poly);
@@ -790,42 +993,89 @@ fn find_prod_comb(
     ret.pop().unwrap()
 }
 
-fn bytes_to_poly(bytes: &[u8], checksum: u128, width: u8, refin: bool, refout: bool) -> PolyPtr {
-    let mut poly = new_poly_shifted(bytes, width as i64, !refin);
-    let check_mask = 1u128.checked_shl(width as u32).unwrap().wrapping_sub(1);
+// only the first `bits` bits of the bytes are used
+fn bytes_to_poly<S: BitNum>(
+    bytes: &[u8],
+    bits: usize,
+    checksum: S,
+    width: usize,
+    refin: bool,
+    refout: bool,
+) -> PolyPtr {
+    with_checksum(&text_to_poly(bytes, bits, refin), checksum, width, refout)
+}
+
+// the polynomial of the first `bits` bits of the text, without the checksum
+fn text_to_poly(bytes: &[u8], bits: usize, refin: bool) -> PolyPtr {
+    let (full, rest) = (bits / 8, bits % 8);
+    let mut text = Cow::from(&bytes[..full]);
+    if rest != 0 {
+        // the unused bits of the last byte get cleared and shifted out afterwards
+        let mask = if refin {
+            (1u8 << rest) - 1
+        } else {
+            !(0xffu8 >> rest)
+        };
+        text.to_mut().push(bytes[full] & mask);
+    }
+    let mut poly = new_poly_shifted(&text, 0, !refin);
+    if rest != 0 {
+        poly = shift(&poly, -((8 - rest) as i64));
+    }
+    poly
+}
+
+// makes space for the checksum after the text polynomial and adds it
+fn with_checksum<S: BitNum>(text: &Poly, checksum: S, width: usize, refout: bool) -> PolyPtr {
+    let mut poly = shift(text, width as i64);
+    let check_mask = if width >= checksum.bits() {
+        !S::zero()
+    } else {
+        (S::one() << width) - S::one()
+    };
     let check = check_mask & cond_reverse(width, checksum, refout);
-    poly += &new_poly(&check.to_be_bytes());
+    poly += &num_to_poly(check);
     poly
 }
 
-fn cond_reverse(width: u8, value: u128, refout: bool) -> u128 {
+fn cond_reverse<S: BitNum>(width: usize, value: S, refout: bool) -> S {
     if refout {
-        value.reverse_bits() >> (128 - width)
+        value.revbits() >> (value.bits() - width)
     } else {
         value
     }
 }
 
-fn poly_to_u128(poly: &Poly) -> u128 {
-    u128::from_be_bytes(
-        poly.to_bytes(16)
-            .as_ref()
-            .unwrap()
-            .as_slice()
-            .try_into()
-            .unwrap(),
-    )
+// the polynomial library works on big endian bytes
+fn num_to_poly<S: BitNum>(num: S) -> PolyPtr {
+    let bytes: Vec<u8> = (0..num.bits() / 8)
+        .rev()
+        .map(|i| ((num >> (8 * i)) & S::from(0xff)).to_u8().unwrap())
+        .collect();
+    new_poly(&bytes)
+}
+
+fn poly_to_num<S: BitNum>(poly: &Poly) -> S {
+    let bytes = poly.to_bytes((S::zero().bits() / 8) as i64);
+    bytes
+        .as_ref()
+        .unwrap()
+        .as_slice()
+        .iter()
+        .fold(S::zero(), |acc, &b| (acc << 8) ^ S::from(b))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bitnum::U512;
     use crate::checksum::{
-        crc::{CRCBuilder, CRC},
+        crc::{CRCBuilder, LenAppend, LenAppendCRC, CRC},
         Digest,
     };
     use quickcheck::{Arbitrary, TestResult};
     use std::convert::TryInto;
+    use std::str::FromStr;
     impl Arbitrary for CRCBuilder<u128> {
         fn arbitrary<G: quickcheck::Gen>(g: &mut G) -> Self {
             let width = (u8::arbitrary(g) % 128) + 1;
@@ -852,7 +1102,7 @@ mod tests {
                 &new_poly_shifted(&[1], crc.width as i64, true),
             ),
             new_poly(&crc.init.to_be_bytes()),
-            new_poly(&cond_reverse(crc.width as u8, crc.xorout, crc.refout).to_be_bytes()),
+            num_to_poly(cond_reverse(crc.width, crc.xorout, crc.refout)),
         )
     }
     fn prepare_xoroutless(
@@ -870,8 +1120,15 @@ mod tests {
         let mut polys = Vec::new();
         for file in files {
             let check = crc.digest(file.as_slice()).unwrap();
-            let file_poly = bytes_to_poly(&file, check, crc.width as u8, crc.refin, crc.refout);
-            polys.push((file_poly, InitPlace::Single(file.len())));
+            let file_poly = bytes_to_poly(
+                &file,
+                8 * file.len(),
+                check,
+                crc.width,
+                crc.refin,
+                crc.refout,
+            );
+            polys.push((file_poly, InitPlace::Single(8 * file.len())));
         }
         let (polys, _) = remove_xorouts(&maybe_xorout, polys);
         Some(polys)
@@ -886,8 +1143,15 @@ mod tests {
         let mut polys = Vec::new();
         for file in files {
             let check = crc.digest(file.as_slice()).unwrap() ^ crc.xorout;
-            let file_poly = bytes_to_poly(&file, check, crc.width as u8, crc.refin, crc.refout);
-            polys.push((file_poly, InitPlace::Single(file.len())));
+            let file_poly = bytes_to_poly(
+                &file,
+                8 * file.len(),
+                check,
+                crc.width,
+                crc.refin,
+                crc.refout,
+            );
+            polys.push((file_poly, InitPlace::Single(8 * file.len())));
         }
         remove_inits(&init_p, &mut polys);
         TestResult::from_bool(polys.iter().all(|p| rem(&p.0, &poly_p).is_zero()))
@@ -906,8 +1170,15 @@ mod tests {
             let mut polys = Vec::new();
             for file in &files {
                 let check = crc.digest(file.as_slice()).unwrap();
-                let file_poly = bytes_to_poly(file, check, crc.width as u8, crc.refin, crc.refout);
-                polys.push((file_poly, InitPlace::Single(file.len())));
+                let file_poly = bytes_to_poly(
+                    file,
+                    8 * file.len(),
+                    check,
+                    crc.width,
+                    crc.refin,
+                    crc.refout,
+                );
+                polys.push((file_poly, InitPlace::Single(8 * file.len())));
             }
             let maybe_xorout = if known {
                 Some(get_polys_from_crc(&crc).2)
@@ -1132,4 +1403,201 @@ mod tests {
             }
         }
     }
+    #[test]
+    fn test_crc_given_init() {
+        let crc = CRC::<u128>::with_options()
+            .poly(0x1021)
+            .width(16)
+            .init(0xffff)
+            .build()
+            .unwrap();
+        // with init given, files of the same length are enough and files of different lengths also work
+        let files = [
+            vec![0x12u8, 0x34u8, 0x56u8],
+            vec![0x67u8, 0x41u8, 0xffu8],
+            vec![0x15u8, 0x56u8, 0x76u8, 0x1fu8],
+        ];
+        let chk_files: Vec<_> = files
+            .iter()
+            .map(|f| (f.as_slice(), crc.digest(f.as_slice()).unwrap()))
+            .collect();
+        let mut crc_init = CRC::<u128>::with_options();
+        crc_init.width(16).init(0xffff);
+        for chk_files in &[&chk_files[..2], &chk_files[..]] {
+            let found: Vec<_> = reverse_crc(&crc_init, chk_files, 0)
+                .map(|c| c.unwrap().to_string())
+                .collect();
+            assert!(found.contains(&crc.to_string()));
+        }
+    }
+    #[test]
+    fn test_crc256() {
+        let crc = CRC::<U512>::from_str(
+            "width=256 poly=0x04c11db704c11db704c11db704c11db704c11db704c11db704c11db704c11db7 \
+            init=0x123456789abcdef0fedcba9876543210 xorout=0xffffffffffffffffffffffffffffffffffffffff \
+            refin=true refout=true",
+        )
+        .unwrap();
+        let files: [&[u8]; 4] = [
+            &[0x12u8, 0x34u8, 0x56u8],
+            &[0x67u8, 0x41u8, 0xffu8],
+            &[0x15u8, 0x56u8, 0x76u8, 0x1fu8],
+            &[0x14u8, 0x62u8, 0x51u8, 0xa4u8, 0xd3u8],
+        ];
+        let chk_files: Vec<_> = files
+            .iter()
+            .map(|f| (*f, crc.digest(*f).unwrap()))
+            .collect();
+        let mut crc_naive = CRC::<U512>::with_options();
+        crc_naive.width(256).poly(crc.poly).refin(true).refout(true);
+        let mut has_appeared = false;
+        for c in reverse_crc(&crc_naive, &chk_files, 0) {
+            let n = c.unwrap();
+            has_appeared |= n == crc;
+            for (file, original_check) in &chk_files {
+                assert_eq!(n.digest(*file).unwrap(), *original_check);
+            }
+        }
+        assert!(has_appeared);
+    }
+    #[test]
+    fn test_crc_bits() {
+        let texts: [&[u8]; 4] = [
+            &[0x4a, 0x5b, 0xe0],
+            &[0x12, 0x34, 0x56, 0x78],
+            &[0xde, 0xad, 0xbe, 0xef, 0x01],
+            &[0x9f, 0x00, 0x13, 0x37, 0x42, 0x11],
+        ];
+        let bit_lens = [19, 27, 37, 46];
+        for refin in [false, true].iter() {
+            let crc = CRC::<u16>::with_options()
+                .width(15)
+                .poly(0x4599)
+                .init(0x1234)
+                .xorout(0x0ff)
+                .refin(*refin)
+                .refout(*refin)
+                .build()
+                .unwrap();
+            let chk_bits: Vec<_> = texts
+                .iter()
+                .zip(bit_lens.iter())
+                .map(|(t, l)| (*t, *l, crc.digest_bits(t, 0, *l)))
+                .collect();
+            let mut crc_naive = CRC::<u16>::with_options();
+            crc_naive
+                .width(15)
+                .poly(0x4599)
+                .refin(*refin)
+                .refout(*refin);
+            let mut has_appeared = false;
+            for c in reverse_crc_bits(&crc_naive, chk_bits.clone(), 0) {
+                let n = c.unwrap();
+                has_appeared |= n == crc;
+                for (text, len, original_check) in &chk_bits {
+                    assert_eq!(n.digest_bits(text, 0, *len), *original_check);
+                }
+            }
+            assert!(has_appeared);
+        }
+    }
+    #[test]
+    fn test_crc_words() {
+        let crc = CRC::<u32>::from_str(
+            "width=32 poly=0x04c11db7 init=0xffffffff wordsize=32 endian=little",
+        )
+        .unwrap();
+        let files: [&[u8]; 4] = [
+            b"12345678",
+            &[0x67u8, 0x41u8, 0xffu8, 0x00u8, 0x12u8],
+            &[
+                0x15u8, 0x56u8, 0x76u8, 0x1fu8, 0x3cu8, 0x21u8, 0xaau8, 0x97u8,
+            ],
+            &[
+                0x14u8, 0x62u8, 0x51u8, 0xa4u8, 0xd3u8, 0x01u8, 0x02u8, 0x03u8, 0x04u8,
+            ],
+        ];
+        let chk_files: Vec<_> = files
+            .iter()
+            .map(|f| (*f, crc.digest(*f).unwrap()))
+            .collect();
+        let mut crc_naive = CRC::<u32>::with_options();
+        crc_naive.width(32).wordsize(32).refin(false).refout(false);
+        let mut has_appeared = false;
+        for c in reverse_crc(&crc_naive, &chk_files, 0) {
+            let n = c.unwrap();
+            has_appeared |= n == crc;
+            for (file, original_check) in &chk_files {
+                assert_eq!(n.digest(*file).unwrap(), *original_check);
+            }
+        }
+        assert!(has_appeared);
+    }
+    #[test]
+    fn test_crc_lenappend() {
+        let crc = LenAppendCRC::<u32>::from_str(
+            "width=32 poly=0x04c11db7 xorout=0xffffffff lenappend=minle",
+        )
+        .unwrap();
+        let files: [&[u8]; 4] = [
+            &[0x12u8, 0x34u8, 0x56u8],
+            &[0x67u8, 0x41u8, 0xffu8],
+            &[0x15u8, 0x56u8, 0x76u8, 0x1fu8],
+            &[0x14u8, 0x62u8, 0x51u8, 0xa4u8, 0xd3u8],
+        ];
+        let chk_files: Vec<_> = files
+            .iter()
+            .map(|f| (*f, crc.digest(*f).unwrap()))
+            .collect();
+        let mut crc_naive = CRC::<u32>::with_options();
+        crc_naive
+            .width(32)
+            .refin(false)
+            .refout(false)
+            .lenappend(LenAppend::Minimal);
+        let mut has_appeared = false;
+        for c in reverse_crc(&crc_naive, &chk_files, 0) {
+            let n = LenAppendCRC::new(c.unwrap(), LenAppend::Minimal);
+            has_appeared |= n == crc;
+            for (file, original_check) in &chk_files {
+                assert_eq!(n.digest(*file).unwrap(), *original_check);
+            }
+        }
+        assert!(has_appeared);
+    }
+    #[test]
+    fn test_crc_shared() {
+        let files: [&[u8]; 4] = [
+            b"123456789",
+            &[0x67u8, 0x41u8, 0xffu8, 0x00u8, 0x12u8],
+            &[0x15u8, 0x56u8, 0x76u8, 0x1fu8, 0x3cu8, 0x21u8],
+            &[0x14u8, 0x62u8, 0x51u8, 0xa4u8],
+        ];
+        let texts = CrcTexts::new(&files);
+        for model in [
+            "width=16 poly=0x1021 init=0xffff refin=true refout=true",
+            "width=12 poly=0x80f init=0x123 xorout=0xfff",
+            "width=16 poly=0x8005 init=0x800 wordsize=16 endian=little",
+        ] {
+            let crc = CRC::<u128>::from_str(model).unwrap();
+            let chk_files: Vec<_> = files
+                .iter()
+                .map(|f| (*f, crc.digest(*f).unwrap()))
+                .collect();
+            let sums: Vec<_> = chk_files.iter().map(|(_, s)| *s).collect();
+            let mut spec = CRC::<u128>::with_options();
+            spec.width(crc.width);
+            if model.contains("wordsize") {
+                spec.wordsize(16);
+            }
+            let shared: Vec<_> = reverse_crc_shared(&spec, &texts, &sums, 0)
+                .map(Result::unwrap)
+                .collect();
+            let unshared: Vec<_> = reverse_crc(&spec, &chk_files, 0)
+                .map(Result::unwrap)
+                .collect();
+            assert!(shared.contains(&crc));
+            assert!(shared == unshared);
+        }
+    }
 }
@@ -4,17 +4,20 @@
 //! * there is a sum which is just the bytes summed modulo some number
 //! * there is also a second sum which the sum of all of the normal sums (modulo the same number)
 //!
-//! Note that text word sizes are currently only `u8`.
+//! By default, the text is summed byte by byte, but it can also be read as words of 16 or 32 bits
+//! (as in the usual definitions of Fletcher-32 and Fletcher-64), in which case a trailing partial
+//! word is padded with zero bytes.
 //!
 //! It works roughly like this:
 //! ```
 //! # fn check(file: &[u8]) -> u32 {
 //! # let module = 0xfff1u32;
 //! # let init = 1;
+//! # let init2 = 0;
 //! # let (addout1, addout2) = (0, 0);
 //! # let hwidth = 16;
 //! let mut sum1 = init;
-//! let mut sum2 = 0;
+//! let mut sum2 = init2;
 //! for byte in file {
 //!     sum1 = (sum1 + *byte as u32) % module;
 //!     sum2 = (sum2 + sum1) % module;
@@ -29,27 +32,31 @@
 //! * width: Total number of bits of the checksum (twice the amount of bits of the individual sums)
 //! * module: The number by which both sums get reduced
 //! * init: The initial value of the regular sum
+//! * init2: The initial value of the cumulative sum (default 0)
 //! * addout: The value that gets added at the end, compact
 //! * swap: Whether to swap the values in the compact representation, i.e. put the regular sum above the cumulative sum
+//! * wordsize: The number of bits of a word that gets summed (a multiple of 8, default 8)
+//! * endian: The byte order of the words, either `little` or `big` (default big, irrelevant for a wordsize of 8)
+//! * signed: Whether the words are two's complement numbers, like a `char` in C on x86 (default false)
 //! * check: The checksum of the bytes "123456789", checked to be correct on build
 //! * name: The name to be used when displaying the algorithm (optional)
 //!
-//! Note that the `init` parameter, unlike the `addout` parameter, is not compact and is only added to the regular sum,
-//! as for the cumulative sum, it is equivalent to the addout (so you can just add the cumulative `init` to the cumulative `addout`).
-
+//! Note that the `init` and `init2` parameters, unlike the `addout` parameter, are not compact.
+//! The `init2` parameter is equivalent to adding it to the cumulative part of `addout`, but it allows
+//! giving published parameters as they are.
 
 pub mod rev;
 use crate::bitnum::BitNum;
 use crate::checksum::{CheckBuilderErr, Digest, LinearCheck};
+use crate::endian::{Endian, WordSpec};
 use crate::keyval::KeyValIter;
 use std::fmt::Display;
 use std::str::FromStr;
 
-
 /// A builder for a fletcher.
 ///
 /// One can use it for specifying a fletcher algorithm, which can be used for checksumming.
-/// 
+///
 /// Example:
 /// ```
 /// # use delsum_lib::checksum::fletcher::Fletcher;
@@ -67,8 +74,12 @@ pub struct FletcherBuilder<Sum: BitNum> {
     width: Option<usize>,
     module: Option<Sum>,
     init: Option<Sum>,
+    init2: Option<Sum>,
     addout: Option<Sum>,
     swap: Option<bool>,
+    wordsize: Option<usize>,
+    endian: Option<Endian>,
+    signed: Option<bool>,
     check: Option<Sum>,
     name: Option<String>,
 }
@@ -91,6 +102,11 @@ impl<S: BitNum> FletcherBuilder<S> {
         self.init = Some(i);
         self
     }
+    /// Sets the initial value of the cumulative sum (default 0)
+    pub fn init2(&mut self, i: S) -> &mut Self {
+        self.init2 = Some(i);
+        self
+    }
     /// Sets a value that gets added after the checksum is finished
     ///
     /// Contains separate values for both sums, the cumulative one is bitshifted
@@ -104,6 +120,21 @@ impl<S: BitNum> FletcherBuilder<S> {
         self.swap = Some(s);
         self
     }
+    /// The number of bits in a word of the text, a multiple of 8 (default 8)
+    pub fn wordsize(&mut self, w: usize) -> &mut Self {
+        self.wordsize = Some(w);
+        self
+    }
+    /// The byte order of the words of the text (default big endian)
+    pub fn endian(&mut self, e: Endian) -> &mut Self {
+        self.endian = Some(e);
+        self
+    }
+    /// Whether the words are signed numbers, which get subtracted if negative (default false)
+    pub fn signed(&mut self, s: bool) -> &mut Self {
+        self.signed = Some(s);
+        self
+    }
     /// Checks whether c is the same as the checksum of "123456789" on creation
     pub fn check(&mut self, c: S) -> &mut Self {
         self.check = Some(c);
@@ -117,6 +148,7 @@ impl<S: BitNum> FletcherBuilder<S> {
     /// Returns the Fletcher object after verifying correctness
     pub fn build(&self) -> Result<Fletcher<S>, CheckBuilderErr> {
         let init = self.init.unwrap_or_else(S::zero);
+        let init2 = self.init2.unwrap_or_else(S::zero);
         let addout = self.addout.unwrap_or_else(S::zero);
         // note: we only store the half width because it is more useful to us
         let hwidth = match self.width {
@@ -136,16 +168,24 @@ impl<S: BitNum> FletcherBuilder<S> {
         } else {
             self.module.unwrap_or_else(S::zero)
         };
+        let wordspec = WordSpec::new(
+            self.wordsize.unwrap_or(8),
+            self.endian.unwrap_or(Endian::Big),
+        )?;
         let mut fletch = Fletcher {
             hwidth,
             module,
             init,
+            init2,
             addout,
             swap: self.swap.unwrap_or(false),
+            wordspec,
+            signed: self.signed.unwrap_or(false),
             mask,
             name: self.name.clone(),
         };
         fletch.init = init % module;
+        fletch.init2 = init2 % module;
         let (mut s, mut c) = fletch.from_compact(addout);
         s = s % module;
         c = c % module;
@@ -169,8 +209,11 @@ pub struct Fletcher<Sum: BitNum> {
     hwidth: usize,
     module: Sum,
     init: Sum,
+    init2: Sum,
     addout: Sum,
     swap: bool,
+    wordspec: WordSpec,
+    signed: bool,
     mask: Sum,
     name: Option<String>,
 }
@@ -179,15 +222,30 @@ impl<Sum: BitNum> Display for Fletcher<Sum> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self.name {
             Some(n) => write!(f, "{}", n),
-            None => write!(
-                f,
-                "fletcher width={} module={:#x} init={:#x} addout={:#x} swap={}",
-                2 * self.hwidth,
-                self.module,
-                self.init,
-                self.addout,
-                self.swap
-            ),
+            None => {
+                write!(
+                    f,
+                    "fletcher width={} module={:#x} init={:#x}",
+                    2 * self.hwidth,
+                    self.module,
+                    self.init
+                )?;
+                if self.init2 != Sum::zero() {
+                    write!(f, " init2={:#x}", self.init2)?;
+                }
+                write!(f, " addout={:#x} swap={}", self.addout, self.swap)?;
+                if self.wordspec.wordsize != 8 {
+                    write!(
+                        f,
+                        " wordsize={} endian={}",
+                        self.wordspec.wordsize, self.wordspec.endian
+                    )?;
+                }
+                if self.signed {
+                    write!(f, " signed=true")?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -199,8 +257,12 @@ impl<Sum: BitNum> Fletcher<Sum> {
             width: None,
             module: None,
             init: None,
+            init2: None,
             addout: None,
             swap: None,
+            wordsize: None,
+            endian: None,
+            signed: None,
             check: None,
             name: None,
         }
@@ -218,6 +280,27 @@ impl<Sum: BitNum> Fletcher<Sum> {
         let (l, h) = if self.swap { (c, s) } else { (s, c) };
         (l & self.mask) ^ (h & self.mask) << self.hwidth
     }
+    /// Reduces a word of the text modulo the module
+    fn reduce_word(&self, word: u64) -> Sum {
+        match Sum::from_u64(word) {
+            Some(w) => w % self.module,
+            // if the word does not fit into Sum, the module certainly fits into an u64
+            None => Sum::from_u64(word % self.module.to_u64().unwrap()).unwrap(),
+        }
+    }
+    /// Gets the value of a word modulo the module, taking the sign into account
+    fn word_value(&self, word: u64) -> Sum {
+        if !self.signed {
+            return self.reduce_word(word);
+        }
+        let value = self.wordspec.to_signed(word);
+        let reduced = self.reduce_word(value.unsigned_abs());
+        if value < 0 && reduced != Sum::zero() {
+            self.module - reduced
+        } else {
+            reduced
+        }
+    }
 }
 
 impl<Sum: BitNum> FromStr for FletcherBuilder<Sum> {
@@ -233,8 +316,16 @@ impl<Sum: BitNum> FromStr for FletcherBuilder<Sum> {
                 "width" => usize::from_str(&current_val).ok().map(|x| fletch.width(x)),
                 "module" => Sum::from_hex(&current_val).ok().map(|x| fletch.module(x)),
                 "init" => Sum::from_hex(&current_val).ok().map(|x| fletch.init(x)),
+                "init2" => Sum::from_hex(&current_val).ok().map(|x| fletch.init2(x)),
                 "addout" => Sum::from_hex(&current_val).ok().map(|x| fletch.addout(x)),
                 "swap" => bool::from_str(&current_val).ok().map(|x| fletch.swap(x)),
+                "wordsize" => usize::from_str(&current_val)
+                    .ok()
+                    .map(|x| fletch.wordsize(x)),
+                "endian" => Endian::from_str(&current_val)
+                    .ok()
+                    .map(|x| fletch.endian(x)),
+                "signed" => bool::from_str(&current_val).ok().map(|x| fletch.signed(x)),
                 "check" => Sum::from_hex(&current_val).ok().map(|x| fletch.check(x)),
                 "name" => Some(fletch.name(&current_val)),
                 _ => return Err(CheckBuilderErr::UnknownKey(current_key)),
@@ -269,14 +360,20 @@ impl<Sum: BitNum> FromStr for Fletcher<Sum> {
 impl<S: BitNum> Digest for Fletcher<S> {
     type Sum = S;
     fn init(&self) -> Self::Sum {
-        self.to_compact((self.init, S::zero()))
+        self.to_compact((self.init, self.init2))
     }
     fn dig_byte(&self, sum: Self::Sum, byte: u8) -> Self::Sum {
+        self.dig_word(sum, u64::from(byte))
+    }
+    fn dig_word(&self, sum: Self::Sum, word: u64) -> Self::Sum {
         let (mut s, mut c) = self.from_compact(sum);
-        s = (s + S::from(byte) % self.module) % self.module;
+        s = (s + self.word_value(word)) % self.module;
         c = (c + s) % self.module;
         self.to_compact((s, c))
     }
+    fn wordspec(&self) -> WordSpec {
+        self.wordspec
+    }
     fn finalize(&self, sum: Self::Sum) -> Self::Sum {
         self.add(sum, &self.addout)
     }
@@ -290,6 +387,11 @@ impl<S: BitNum> LinearCheck for Fletcher<S> {
     fn inc_shift(&self, shift: Self::Shift) -> Self::Shift {
         (shift + S::one()) % self.module
     }
+    fn shift_n(&self, n: usize) -> Self::Shift {
+        // the shift is just the number of words modulo the module
+        let module = self.module.to_u128().unwrap();
+        S::from_u128(n as u128 % module).unwrap()
+    }
     fn shift(&self, sum: Self::Sum, shift: &Self::Shift) -> Self::Sum {
         let (s, mut c) = self.from_compact(sum);
         c = (c + s * *shift) % self.module;
@@ -355,6 +457,75 @@ mod tests {
         check_example(&f16, 0x7815);
     }
     #[test]
+    fn fletcher32_words() {
+        let f32 =
+            Fletcher::<u32>::from_str("width=32 module=ffff wordsize=16 endian=little").unwrap();
+        assert_eq!(f32.digest(&b"abcde"[..]).unwrap(), 0xf04fc729);
+        assert_eq!(f32.digest(&b"abcdef"[..]).unwrap(), 0x56502d2a);
+        assert_eq!(f32.digest(&b"abcdefgh"[..]).unwrap(), 0xebe19591);
+        test_find(&f32);
+        test_prop(&f32);
+        let model = f32.to_string();
+        assert_eq!(
+            Fletcher::<u32>::from_str(model.strip_prefix("fletcher").unwrap()).unwrap(),
+            f32
+        );
+    }
+    #[test]
+    fn fletcher64_words() {
+        let f64 = Fletcher::<u64>::with_options()
+            .width(64)
+            .module(0xffffffff)
+            .wordsize(32)
+            .endian(Endian::Little)
+            .build()
+            .unwrap();
+        assert_eq!(f64.digest(&b"abcde"[..]).unwrap(), 0xc8c6c527646362c6);
+        assert_eq!(f64.digest(&b"abcdef"[..]).unwrap(), 0xc8c72b276463c8c6);
+        assert_eq!(f64.digest(&b"abcdefgh"[..]).unwrap(), 0x312e2b28cccac8c6);
+        test_find(&f64);
+        test_prop(&f64);
+    }
+    #[test]
+    fn signed() {
+        let chk = Fletcher::<u32>::from_str("width=32 signed=true").unwrap();
+        // 0x80 is -128 and 0x01 is 1
+        assert_eq!(chk.digest(&[0x80u8, 0x01][..]).unwrap(), 0xff01ff81);
+        test_shifts(&chk);
+        test_find(&chk);
+        test_prop(&chk);
+        let model = chk.to_string();
+        assert_eq!(
+            Fletcher::<u32>::from_str(model.strip_prefix("fletcher").unwrap()).unwrap(),
+            chk
+        );
+    }
+    #[test]
+    fn init2() {
+        let chk =
+            Fletcher::<u32>::from_str("width=32 module=ffff init=1 init2=1 swap=true").unwrap();
+        // init2 is the same as an addout on the cumulative sum
+        let same =
+            Fletcher::<u32>::from_str("width=32 module=ffff init=1 addout=1 swap=true").unwrap();
+        assert_eq!(chk.digest(&b"123456789"[..]).unwrap(), 0x01de091f);
+        assert_eq!(
+            chk.digest(&b"123456789"[..]).unwrap(),
+            same.digest(&b"123456789"[..]).unwrap()
+        );
+        test_shifts(&chk);
+        test_find(&chk);
+        test_prop(&chk);
+        let model = chk.to_string();
+        assert_eq!(
+            model,
+            "fletcher width=32 module=0xffff init=0x1 init2=0x1 addout=0x0 swap=true"
+        );
+        assert_eq!(
+            Fletcher::<u32>::from_str(model.strip_prefix("fletcher").unwrap()).unwrap(),
+            chk
+        );
+    }
+    #[test]
     fn fletcher8() {
         let f8 = Fletcher::<u8>::from_str("width=8 module=f init=0 addout=0 swap=false check=0xc")
             .unwrap();
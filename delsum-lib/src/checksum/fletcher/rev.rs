@@ -11,13 +11,21 @@
 //! If `init` is not known, it is neccessary to know two checksums of files with different lengths.
 //! In case only checksums of files with a set length are required, setting `init = 0` is sufficient.
 //!
+//! The initial value of the cumulative sum, `init2`, can not be distinguished from the cumulative part of `addout`,
+//! so the solutions are given in a canonical form where `init2` is 0, unless it is given.
+//!
 //! It is probable that giving just two files + checksum might already be enough, but there will
 //! probably also be many some false positives.
+//!
+//! If the `wordsize` is bigger than 8 and no `endian` is given, both byte orders are tried.
+//! Likewise, signed words are tried too if `signed` is not given and some word of the files is negative.
 use super::{Fletcher, FletcherBuilder};
+use crate::checksum::modsum::rev::signedness;
 use crate::checksum::{unresult_iter, CheckReverserError};
-use crate::factor::divisors_range;
+use crate::endian::{Endian, WordSpec};
+use crate::factor::{divisors_range, mod_red, xgcd};
 use num_bigint::BigInt;
-use num_traits::{one, zero, One, Signed, Zero};
+use num_traits::{one, zero, One, Zero};
 #[cfg(feature = "parallel")]
 use rayon::prelude::*;
 use std::convert::TryInto;
@@ -35,19 +43,17 @@ pub fn reverse_fletcher<'a>(
     verbosity: u64,
 ) -> impl Iterator<Item = Result<Fletcher<u128>, CheckReverserError>> + 'a {
     let spec = spec.clone();
-    let swap = spec
-        .swap
-        .map(|x| vec![x])
-        .unwrap_or_else(|| vec![false, true]);
-    swap.into_iter()
-        .map(move |s| unresult_iter(reverse(&spec, chk_bytes, s, verbosity).map(|x| x.iter())))
-        .flatten()
+    combinations(&spec, chk_bytes)
+        .into_iter()
+        .flat_map(move |(s, e, sg)| {
+            unresult_iter(reverse(&spec, chk_bytes, s, e, sg, verbosity).map(|x| x.iter()))
+        })
 }
 
 /// Parallel version of reverse_fletcher.
 ///
-/// It is parallel in the sense that there are two threads, for swap=false and swap=true, if it is not given,
-/// so don't expect too much speedup.
+/// It is parallel in the sense that there are two threads, for swap=false and swap=true, if it is not given
+/// (and also for both byte orders and signednesses, if they are not given), so don't expect too much speedup.
 #[cfg(feature = "parallel")]
 pub fn reverse_fletcher_para<'a>(
     spec: &FletcherBuilder<u128>,
@@ -55,15 +61,36 @@ pub fn reverse_fletcher_para<'a>(
     verbosity: u64,
 ) -> impl ParallelIterator<Item = Result<Fletcher<u128>, CheckReverserError>> + 'a {
     let spec = spec.clone();
+    combinations(&spec, chk_bytes)
+        .into_par_iter()
+        .map(move |(s, e, sg)| {
+            unresult_iter(reverse(&spec, chk_bytes, s, e, sg, verbosity).map(|x| x.iter()))
+                .par_bridge()
+        })
+        .flatten()
+}
+
+// all combinations of swap, endian and signedness that need to be tried
+fn combinations(
+    spec: &FletcherBuilder<u128>,
+    chk_bytes: &[(&[u8], u128)],
+) -> Vec<(bool, Endian, bool)> {
     let swap = spec
         .swap
         .map(|x| vec![x])
         .unwrap_or_else(|| vec![false, true]);
-    swap.into_par_iter()
-        .map(move |s| {
-            unresult_iter(reverse(&spec, chk_bytes, s, verbosity).map(|x| x.iter())).par_bridge()
+    let endian = Endian::to_try(spec.endian, spec.wordsize);
+    let endian_signed: Vec<_> = endian
+        .into_iter()
+        .flat_map(|e| {
+            signedness(spec.signed, spec.wordsize, e, chk_bytes)
+                .into_iter()
+                .map(move |sg| (e, sg))
         })
-        .flatten()
+        .collect();
+    swap.into_iter()
+        .flat_map(|s| endian_signed.iter().map(move |(e, sg)| (s, *e, *sg)))
+        .collect()
 }
 
 // contains the information needed for iterating over the possible algorithms
@@ -72,22 +99,28 @@ pub fn reverse_fletcher_para<'a>(
 #[derive(Debug, Clone)]
 struct ReversingResult {
     inits: PrefactorMod,
+    init2: BigInt,
     addout1: BigInt,
     addout2: (BigInt, usize),
     modules: Vec<BigInt>,
     width: usize,
     swap: bool,
+    wordspec: WordSpec,
+    signed: bool,
 }
 
 impl ReversingResult {
     fn iter(self) -> impl Iterator<Item = Fletcher<u128>> {
         let ReversingResult {
             inits,
+            init2,
             addout1,
             addout2,
             modules,
             width,
             swap,
+            wordspec,
+            signed,
         } = self;
         modules
             .into_iter()
@@ -97,14 +130,21 @@ impl ReversingResult {
                 } else {
                     (&m).try_into().unwrap()
                 };
+                // init2 is part of the constant that was found for addout2, so we remove it again
+                let addout2 = (&addout2.0 - &init2, addout2.1);
+                let init2: u128 = mod_red(&init2, &m).try_into().unwrap();
                 inits.iter(&addout1, &addout2, &m).map(move |(i, s1, s2)| {
                     let addout = glue_sum(s1, s2, width, swap);
                     Fletcher::with_options()
                         .addout(addout)
                         .init(i as u128)
+                        .init2(init2)
                         .module(module)
                         .width(width)
                         .swap(swap)
+                        .wordsize(wordspec.wordsize)
+                        .endian(wordspec.endian)
+                        .signed(signed)
                         .build()
                         .unwrap()
                 })
@@ -121,6 +161,9 @@ impl ReversingResult {
 // Because we also know the file, we can subtract a + b + c + d + e or 5a + 4b + 3c + 2d + 1e and get
 //      (init + addout1) mod m
 //      (5*init + addout2) mod m
+// If the text is read as words, a, b, c, d, e are words instead of bytes and everything else stays the same
+// (this also holds if the words are signed and therefore possibly negative).
+//
 // Note that the notation `mod` here does not mean that the result is 0 <= x < m, just that the difference
 // to the unreduced form is a multiple of `m`.
 // So we can just subtract these values without knowing the value of m.
@@ -143,6 +186,8 @@ fn reverse(
     spec: &FletcherBuilder<u128>,
     chk_bytes: &[(&[u8], u128)],
     swap: bool,
+    endian: Endian,
+    signed: bool,
     verbosity: u64,
 ) -> Result<ReversingResult, CheckReverserError> {
     let log = |s| {
@@ -157,15 +202,17 @@ fn reverse(
     let width = spec
         .width
         .ok_or(CheckReverserError::MissingParameter("width"))?;
+    let wordspec = WordSpec::new(spec.wordsize.unwrap_or(8), endian)
+        .map_err(|_| CheckReverserError::ValueOutOfRange("wordsize"))?;
     log("finding parameters of lower sum");
     // finding the parameters of the lower sum is pretty much a separate problem already
     // handled in modsum, so we delegate to that
-    let (module, addout1) = find_regular_sum(&spec, &files, swap);
+    let (module, addout1) = find_regular_sum(&spec, &files, swap, wordspec, signed);
     let mut module = BigInt::from(module);
     let mut addout1 = BigInt::from(addout1);
     // here, we take the the checksums and remove the cumulative sum sums from them
     // the second value of each value is supposed to be the multiplicity of init in the sum
-    let mut cumusums = cumusum(width, &files, &module, swap);
+    let mut cumusums = cumusum(width, &files, &module, swap, wordspec, signed);
     if let Some(init) = spec.init {
         log("removing inits from upper sum");
         // if we have the parameter init already given, we can remove
@@ -200,11 +247,14 @@ fn reverse(
         .collect();
     Ok(ReversingResult {
         inits,
+        init2: BigInt::from(spec.init2.unwrap_or(0)),
         addout1,
         addout2,
         modules,
         width,
         swap,
+        wordspec,
+        signed,
     })
 }
 
@@ -240,12 +290,14 @@ fn find_regular_sum(
     spec: &FletcherBuilder<u128>,
     files: &[(&[u8], u128)],
     swap: bool,
+    wordspec: WordSpec,
+    signed: bool,
 ) -> (u128, i128) {
     let width = spec.width.unwrap();
     let mut sums = Vec::new();
     for (f, chk) in files.iter() {
         let (chk_lo, _) = split_sum(*chk, width, swap);
-        sums.push(f.iter().copied().map(i128::from).sum::<i128>() - chk_lo as i128);
+        sums.push(wordspec.iter_values(f, signed).sum::<i128>() - chk_lo as i128);
     }
     let mut module = 0;
     // init is here actually addout1 + init, which we can only know if we have both values
@@ -272,9 +324,10 @@ fn remove_addout2(
     swap: bool,
 ) -> (Vec<(BigInt, usize)>, (BigInt, usize)) {
     let width = spec.width.unwrap();
+    // the constant in the cumulative sum is addout2 + init2
     let maybe_addout = spec
         .addout
-        .map(|x| BigInt::from(split_sum(x, width, swap).1));
+        .map(|x| BigInt::from(split_sum(x, width, swap).1) + spec.init2.unwrap_or(0));
     let mut ret_vec = Vec::new();
     let mut prev = sums
         .pop()
@@ -338,36 +391,27 @@ fn cumusum(
     chk_bytes: &[(&[u8], u128)],
     module: &BigInt,
     swap: bool,
+    wordspec: WordSpec,
+    signed: bool,
 ) -> Vec<(BigInt, usize)> {
     let mut sums = Vec::new();
     for (bytes, chk) in chk_bytes {
         let mut current_sum: BigInt = zero();
         let mut cumusum: BigInt = zero();
-        for byte in *bytes {
-            current_sum += BigInt::from(*byte);
+        for word in wordspec.iter_values(bytes, signed) {
+            current_sum += BigInt::from(word);
             cumusum += &current_sum;
         }
         cumusum = mod_red(&cumusum, module);
         let (_, check) = split_sum(*chk, width, swap);
-        sums.push((BigInt::from(check) - cumusum, bytes.len()));
+        sums.push((
+            BigInt::from(check) - cumusum,
+            wordspec.word_count(bytes.len()),
+        ));
     }
     sums
 }
 
-// modular reduction, because % is just wrong
-fn mod_red(n: &BigInt, module: &BigInt) -> BigInt {
-    if module.is_zero() {
-        // yes, n modulo 0 is n and i will die on this hill
-        n.clone()
-    } else {
-        let k = n % module;
-        if k < zero() {
-            module + k
-        } else {
-            k
-        }
-    }
-}
 fn find_init(
     maybe_init: &Option<BigInt>,
     module: &mut BigInt,
@@ -549,31 +593,6 @@ fn gcd(a: &BigInt, b: &BigInt) -> BigInt {
     xgcd(a, b).0
 }
 
-fn xgcd(a: &BigInt, b: &BigInt) -> (BigInt, (BigInt, BigInt)) {
-    let mut a = a.abs();
-    let mut b = b.abs();
-    if a.is_zero() {
-        return (b, (zero(), one()));
-    }
-    if b.is_zero() {
-        return (a, (one(), zero()));
-    }
-    let mut a_fac = (one(), zero());
-    let mut b_fac = (zero(), one());
-    if a < b {
-        std::mem::swap(&mut a, &mut b);
-        std::mem::swap(&mut a_fac, &mut b_fac);
-    }
-    while !b.is_zero() {
-        std::mem::swap(&mut a, &mut b);
-        std::mem::swap(&mut a_fac, &mut b_fac);
-        let fac = &b / &a;
-        let rem = &b % &a;
-        b = rem;
-        b_fac = (b_fac.0 - &fac * &a_fac.0, b_fac.1 - &fac * &a_fac.1);
-    }
-    (a, a_fac)
-}
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -591,12 +610,21 @@ mod tests {
             new_fletcher.module(module);
             let init = u64::arbitrary(g) as u128 % module;
             new_fletcher.init(init);
+            let init2 = u64::arbitrary(g) as u128 % module;
+            new_fletcher.init2(init2);
             let swap = bool::arbitrary(g);
             new_fletcher.swap(swap);
             let addout1 = u64::arbitrary(g) as u128 % module;
             let addout2 = u64::arbitrary(g) as u128 % module;
             let addout = glue_sum(addout1 as u64, addout2 as u64, width, swap);
             new_fletcher.addout(addout);
+            new_fletcher.wordsize(8 << (u8::arbitrary(g) % 3));
+            new_fletcher.endian(if bool::arbitrary(g) {
+                Endian::Little
+            } else {
+                Endian::Big
+            });
+            new_fletcher.signed(bool::arbitrary(g));
             new_fletcher
         }
     }
@@ -628,35 +656,162 @@ mod tests {
             assert_eq!(vec![f16], x)
         }
     }
+    #[test]
+    fn fletcher32_words() {
+        let f32 = Fletcher::with_options()
+            .width(32)
+            .module(0xffffu128)
+            .addout(0x12345678)
+            .init(0x4321)
+            .wordsize(16)
+            .endian(Endian::Little)
+            .build()
+            .unwrap();
+        let f = [
+            &[145u8, 43, 41, 159, 51, 200, 25, 53, 53, 75, 100, 41, 99][..],
+            &[238, 92, 59, 96, 189, 61, 241, 51][..],
+            &[33, 241, 149, 112, 184][..],
+            &[1, 2, 3][..],
+        ];
+        let chk_files: Vec<_> = f
+            .iter()
+            .map(|f| {
+                let checksum: u128 = f32.digest(*f).unwrap();
+                (*f, checksum)
+            })
+            .collect();
+        let mut naive = Fletcher::<u128>::with_options();
+        naive.width(32).swap(false).wordsize(16).module(0xffff);
+        let m: Vec<_> = reverse_fletcher(&naive, &chk_files, 0)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(vec![f32], m);
+    }
+    #[test]
+    fn signed() {
+        let f16 = Fletcher::with_options()
+            .width(16)
+            .module(0xffu128)
+            .addout(0x1234)
+            .signed(true)
+            .build()
+            .unwrap();
+        let f = [
+            &[145u8, 43, 41, 159, 51, 200, 25, 53, 53, 75, 100, 41, 99][..],
+            &[238, 92, 59, 96, 189, 61, 241, 51][..],
+            &[33, 241, 149, 112, 184][..],
+            &[1, 2, 3][..],
+        ];
+        let chk_files: Vec<_> = f
+            .iter()
+            .map(|f| {
+                let checksum: u128 = f16.digest(*f).unwrap();
+                (*f, checksum)
+            })
+            .collect();
+        let mut naive = Fletcher::<u128>::with_options();
+        naive.width(16).swap(false).init(0);
+        let m: Vec<_> = reverse_fletcher(&naive, &chk_files, 0)
+            .filter_map(Result::ok)
+            .collect();
+        assert!(m.contains(&f16));
+    }
+    #[test]
+    fn init2() {
+        let f16 = Fletcher::with_options()
+            .width(16)
+            .module(0xffu128)
+            .init(1)
+            .init2(1)
+            .build()
+            .unwrap();
+        let f = [
+            &[145u8, 43, 41, 159, 51, 200, 25, 53, 53, 75, 100, 41, 99][..],
+            &[238, 92, 59, 96, 189, 61, 241, 51][..],
+            &[33, 241, 149, 112, 184][..],
+            &[1, 2, 3][..],
+        ];
+        let chk_files: Vec<_> = f
+            .iter()
+            .map(|f| {
+                let checksum: u128 = f16.digest(*f).unwrap();
+                (*f, checksum)
+            })
+            .collect();
+        let mut naive = Fletcher::<u128>::with_options();
+        naive.width(16).swap(false).signed(false).module(0xff);
+        let canonical = Fletcher::with_options()
+            .width(16)
+            .module(0xffu128)
+            .init(1)
+            .addout(0x100)
+            .build()
+            .unwrap();
+        let m: Vec<_> = reverse_fletcher(&naive, &chk_files, 0)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(vec![canonical], m);
+        naive.init2(1);
+        let m: Vec<_> = reverse_fletcher(&naive, &chk_files, 0)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(vec![f16], m);
+    }
     #[quickcheck]
     fn qc_fletch_rev(
         mut files: Vec<Vec<u8>>,
-        fletch_build: FletcherBuilder<u128>,
+        mut fletch_build: FletcherBuilder<u128>,
         known: (bool, bool, bool, bool),
     ) -> TestResult {
         files.sort_by(|a, b| a.len().cmp(&b.len()).then(a.cmp(&b)).reverse());
         if files.iter().zip(files.iter().skip(1)).any(|(a, b)| a == b) || files.len() <= 3 {
             return TestResult::discard();
         }
+        // without negative words, the unsigned variant is the one that gets reported
+        let file_refs: Vec<_> = files.iter().map(|f| (f.as_slice(), 0)).collect();
+        let signs = signedness(
+            None,
+            fletch_build.wordsize,
+            fletch_build.endian.unwrap(),
+            &file_refs,
+        );
+        if signs.len() == 1 {
+            fletch_build.signed(false);
+        }
+        let chk_fletcher = fletch_build.build().unwrap();
+        if !known.1 {
+            // if init2 is not given, it gets moved into addout
+            let width = fletch_build.width.unwrap();
+            let swap = fletch_build.swap.unwrap();
+            let module = fletch_build.module.unwrap();
+            let (addout1, addout2) = split_sum(fletch_build.addout.unwrap(), width, swap);
+            let addout2 = (addout2 as u128 + fletch_build.init2.unwrap()) % module;
+            fletch_build
+                .init2(0)
+                .addout(glue_sum(addout1, addout2 as u64, width, swap));
+        }
         let fletcher = fletch_build.build().unwrap();
         let mut naive = Fletcher::<u128>::with_options();
         naive.width(fletch_build.width.unwrap());
+        naive.wordsize(fletch_build.wordsize.unwrap());
         if known.0 {
             naive.module(fletch_build.module.unwrap());
         }
         if known.1 {
             naive.init(fletch_build.init.unwrap());
+            naive.init2(fletch_build.init2.unwrap());
         }
         if known.2 {
             naive.addout(fletch_build.addout.unwrap());
         }
         if known.3 {
             naive.swap(fletch_build.swap.unwrap());
+            naive.endian(fletch_build.endian.unwrap());
         }
         let chk_files: Vec<_> = files
             .iter()
             .map(|f| {
-                let checksum = fletcher.digest(f.as_slice()).unwrap();
+                let checksum = chk_fletcher.digest(f.as_slice()).unwrap();
                 (f.as_slice(), checksum)
             })
             .collect();
@@ -0,0 +1,280 @@
+//! The Fowler–Noll–Vo hash, which multiplies by a prime and XORs each byte
+//!
+//! There are two variants: FNV-1 multiplies before XORing the byte, while FNV-1a XORs the byte first.
+//! Since it mixes multiplication and XOR, it is neither linear over the integers nor over GF(2),
+//! so it only implements `Digest` and segments are found by trying all of them.
+//!
+//! There are a number of parameters:
+//! * width: The number of bits in the sum type, at most 128
+//! * prime: The number by which the hash gets multiplied (defaults to the FNV prime for widths 32, 64 and 128)
+//! * init: The initial number, called offset basis (defaults to the FNV offset basis for widths 32, 64 and 128)
+//! * variant: Either `1` or `1a` (default 1a)
+//! * check: The checksum of "123456789" (optional, gets checked at construction)
+//! * name: An optional name that gets used for display purposes
+use crate::bitnum::BitNum;
+use crate::checksum::{CheckBuilderErr, Digest};
+use crate::keyval::KeyValIter;
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// The variant of the FNV hash, which is about the order of multiplication and XOR.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FnvVariant {
+    /// Multiplies the hash by the prime before XORing the byte
+    Fnv1,
+    /// XORs the byte before multiplying the hash by the prime
+    Fnv1a,
+}
+
+impl FromStr for FnvVariant {
+    type Err = CheckBuilderErr;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1" => Ok(FnvVariant::Fnv1),
+            "1a" => Ok(FnvVariant::Fnv1a),
+            _ => Err(CheckBuilderErr::MalformedString(String::from("variant"))),
+        }
+    }
+}
+
+impl Display for FnvVariant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FnvVariant::Fnv1 => write!(f, "1"),
+            FnvVariant::Fnv1a => write!(f, "1a"),
+        }
+    }
+}
+
+/// A builder to set the various parameters for the FNV hash.
+///
+/// Example:
+/// ```
+/// # use delsum_lib::checksum::fnv::Fnv;
+/// Fnv::<u32>::with_options()
+///     .width(32)
+///     .check(0xbb86b11c)
+///     .build()
+///     .is_ok();
+/// ```
+#[derive(Debug, Clone)]
+pub struct FnvBuilder<S: BitNum> {
+    width: Option<usize>,
+    prime: Option<S>,
+    init: Option<S>,
+    variant: Option<FnvVariant>,
+    check: Option<S>,
+    name: Option<String>,
+}
+
+impl<S: BitNum> FnvBuilder<S> {
+    /// The width, in bits, of the hash. Mandatory.
+    pub fn width(&mut self, w: usize) -> &mut Self {
+        self.width = Some(w);
+        self
+    }
+    /// The factor by which the hash gets multiplied, mandatory unless the width is 32, 64 or 128.
+    pub fn prime(&mut self, p: S) -> &mut Self {
+        self.prime = Some(p);
+        self
+    }
+    /// The initial value (offset basis), mandatory unless the width is 32, 64 or 128.
+    pub fn init(&mut self, i: S) -> &mut Self {
+        self.init = Some(i);
+        self
+    }
+    /// The order of multiplication and XOR, optional, defaults to FNV-1a.
+    pub fn variant(&mut self, v: FnvVariant) -> &mut Self {
+        self.variant = Some(v);
+        self
+    }
+    /// The checksum of "123456789", gets checked on creation.
+    pub fn check(&mut self, c: S) -> &mut Self {
+        self.check = Some(c);
+        self
+    }
+    /// An optional name that gets used for display purposes.
+    pub fn name(&mut self, n: &str) -> &mut Self {
+        self.name = Some(String::from(n));
+        self
+    }
+    /// Builds the algorithm, after validating the parameters.
+    pub fn build(&self) -> Result<Fnv<S>, CheckBuilderErr> {
+        let width = self
+            .width
+            .ok_or(CheckBuilderErr::MissingParameter("width"))?;
+        if width == 0 || width > S::zero().bits() {
+            return Err(CheckBuilderErr::ValueOutOfRange("width"));
+        }
+        let mask = if width < S::zero().bits() {
+            (S::one() << width) - S::one()
+        } else {
+            !S::zero()
+        };
+        // the published parameters, as (prime, offset basis)
+        let (default_prime, default_init) = match width {
+            32 => (Some(0x0100_0193), Some(0x811c_9dc5)),
+            64 => (Some(0x0000_0100_0000_01b3), Some(0xcbf2_9ce4_8422_2325)),
+            128 => (
+                Some(0x0000_0000_0100_0000_0000_0000_0000_013b),
+                Some(0x6c62_272e_07bb_0142_62b8_2175_6295_c58d),
+            ),
+            _ => (None, None),
+        };
+        let prime = match self.prime {
+            Some(p) => p,
+            None => default_prime
+                .and_then(S::from_u128)
+                .ok_or(CheckBuilderErr::MissingParameter("prime"))?,
+        };
+        if prime & !mask != S::zero() {
+            return Err(CheckBuilderErr::ValueOutOfRange("prime"));
+        }
+        let init = match self.init {
+            Some(i) => i,
+            None => default_init
+                .and_then(S::from_u128)
+                .ok_or(CheckBuilderErr::MissingParameter("init"))?,
+        };
+        if init & !mask != S::zero() {
+            return Err(CheckBuilderErr::ValueOutOfRange("init"));
+        }
+        let s = Fnv {
+            width,
+            mask,
+            prime,
+            init,
+            variant: self.variant.unwrap_or(FnvVariant::Fnv1a),
+            name: self.name.clone(),
+        };
+        match self.check {
+            Some(c) => {
+                if s.digest(&b"123456789"[..]).unwrap() == c {
+                    Ok(s)
+                } else {
+                    Err(CheckBuilderErr::CheckFail)
+                }
+            }
+            None => Ok(s),
+        }
+    }
+}
+
+/// The FNV hash algorithm.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Fnv<S: BitNum> {
+    width: usize,
+    mask: S,
+    prime: S,
+    init: S,
+    variant: FnvVariant,
+    name: Option<String>,
+}
+
+impl<S: BitNum> Fnv<S> {
+    /// Creates a `FnvBuilder`, for more information see its documentation.
+    pub fn with_options() -> FnvBuilder<S> {
+        FnvBuilder {
+            width: None,
+            prime: None,
+            init: None,
+            variant: None,
+            check: None,
+            name: None,
+        }
+    }
+}
+
+impl<S: BitNum> Display for Fnv<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.name {
+            Some(n) => write!(f, "{}", n),
+            None => write!(
+                f,
+                "fnv width={} prime={:#x} init={:#x} variant={}",
+                self.width, self.prime, self.init, self.variant
+            ),
+        }
+    }
+}
+
+impl<S: BitNum> FromStr for FnvBuilder<S> {
+    /// See FromStr for Fnv<S>
+    fn from_str(s: &str) -> Result<FnvBuilder<S>, CheckBuilderErr> {
+        let mut fnv = Fnv::<S>::with_options();
+        for x in KeyValIter::new(s) {
+            let (current_key, current_val) = match x {
+                Err(key) => return Err(CheckBuilderErr::MalformedString(key)),
+                Ok(s) => s,
+            };
+            let fnv_op = match current_key.as_str() {
+                "width" => usize::from_str(&current_val).ok().map(|x| fnv.width(x)),
+                "prime" => S::from_hex(&current_val).ok().map(|x| fnv.prime(x)),
+                "init" => S::from_hex(&current_val).ok().map(|x| fnv.init(x)),
+                "variant" => FnvVariant::from_str(&current_val)
+                    .ok()
+                    .map(|x| fnv.variant(x)),
+                "check" => S::from_hex(&current_val).ok().map(|x| fnv.check(x)),
+                "name" => Some(fnv.name(&current_val)),
+                _ => return Err(CheckBuilderErr::UnknownKey(current_key)),
+            };
+            match fnv_op {
+                Some(f) => fnv = f.clone(),
+                None => return Err(CheckBuilderErr::MalformedString(current_key)),
+            }
+        }
+        Ok(fnv)
+    }
+    type Err = CheckBuilderErr;
+}
+
+impl<S: BitNum> FromStr for Fnv<S> {
+    /// Construct a new FNV hash from a string specification.
+    ///
+    /// Example:
+    ///
+    /// width=32 prime=1000193 init=811c9dc5 variant=1a
+    fn from_str(s: &str) -> Result<Fnv<S>, CheckBuilderErr> {
+        FnvBuilder::from_str(s)?.build()
+    }
+    type Err = CheckBuilderErr;
+}
+
+impl<S: BitNum> Digest for Fnv<S> {
+    type Sum = S;
+    fn init(&self) -> Self::Sum {
+        self.init
+    }
+    fn dig_byte(&self, sum: Self::Sum, byte: u8) -> Self::Sum {
+        let byte = S::from(byte);
+        match self.variant {
+            FnvVariant::Fnv1 => (sum.wrapping_mul(&self.prime) & self.mask) ^ byte,
+            FnvVariant::Fnv1a => (sum ^ byte).wrapping_mul(&self.prime) & self.mask,
+        }
+    }
+    fn finalize(&self, sum: Self::Sum) -> Self::Sum {
+        sum
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn published() {
+        let fnv1a = Fnv::<u32>::from_str("width=32").unwrap();
+        assert_eq!(fnv1a.digest(&b"a"[..]).unwrap(), 0xe40c292c);
+        assert_eq!(fnv1a.digest(&b"foobar"[..]).unwrap(), 0xbf9cf968);
+        assert!(Fnv::<u32>::from_str("width=32 variant=1 check=24148816").is_ok());
+        assert!(Fnv::<u64>::from_str("width=64 check=06d5573923c6cdfc").is_ok());
+        assert!(Fnv::<u128>::from_str("width=128 check=da2d42a08d04e4585dd325117f71d504").is_ok());
+        assert_eq!(
+            Fnv::<u32>::from_str("width=24"),
+            Err(CheckBuilderErr::MissingParameter("prime"))
+        );
+        assert_eq!(
+            fnv1a.to_string(),
+            "fnv width=32 prime=0x1000193 init=0x811c9dc5 variant=1a"
+        );
+    }
+}
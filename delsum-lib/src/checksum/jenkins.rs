@@ -0,0 +1,171 @@
+//! Bob Jenkins' one-at-a-time hash, a 32-bit hash which mixes each byte with shifts and additions
+//!
+//! Since it mixes additions and XOR, it is neither linear over the integers nor over GF(2),
+//! so it only implements `Digest` and segments are found by trying all of them.
+//!
+//! There are a number of parameters:
+//! * width: The number of bits in the sum type, has to be 32
+//! * init: The initial number (default 0)
+//! * check: The checksum of "123456789" (optional, gets checked at construction)
+//! * name: An optional name that gets used for display purposes
+use crate::bitnum::BitNum;
+use crate::checksum::{CheckBuilderErr, Digest};
+use crate::keyval::KeyValIter;
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// A builder to set the various parameters for the one-at-a-time hash.
+///
+/// Example:
+/// ```
+/// # use delsum_lib::checksum::jenkins::Jenkins;
+/// Jenkins::with_options()
+///     .width(32)
+///     .check(0xc66b58c5)
+///     .build()
+///     .is_ok();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct JenkinsBuilder {
+    width: Option<usize>,
+    init: Option<u32>,
+    check: Option<u32>,
+    name: Option<String>,
+}
+
+impl JenkinsBuilder {
+    /// The width, in bits, of the hash. Mandatory, and has to be 32.
+    pub fn width(&mut self, w: usize) -> &mut Self {
+        self.width = Some(w);
+        self
+    }
+    /// The initial value, optional, defaults to 0.
+    pub fn init(&mut self, i: u32) -> &mut Self {
+        self.init = Some(i);
+        self
+    }
+    /// The checksum of "123456789", gets checked on creation.
+    pub fn check(&mut self, c: u32) -> &mut Self {
+        self.check = Some(c);
+        self
+    }
+    /// An optional name that gets used for display purposes.
+    pub fn name(&mut self, n: &str) -> &mut Self {
+        self.name = Some(String::from(n));
+        self
+    }
+    /// Builds the algorithm, after validating the parameters.
+    pub fn build(&self) -> Result<Jenkins, CheckBuilderErr> {
+        let width = self
+            .width
+            .ok_or(CheckBuilderErr::MissingParameter("width"))?;
+        if width != 32 {
+            return Err(CheckBuilderErr::ValueOutOfRange("width"));
+        }
+        let s = Jenkins {
+            init: self.init.unwrap_or(0),
+            name: self.name.clone(),
+        };
+        match self.check {
+            Some(c) => {
+                if s.digest(&b"123456789"[..]).unwrap() == c {
+                    Ok(s)
+                } else {
+                    Err(CheckBuilderErr::CheckFail)
+                }
+            }
+            None => Ok(s),
+        }
+    }
+}
+
+/// The one-at-a-time hash algorithm.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Jenkins {
+    init: u32,
+    name: Option<String>,
+}
+
+impl Jenkins {
+    /// Creates a `JenkinsBuilder`, for more information see its documentation.
+    pub fn with_options() -> JenkinsBuilder {
+        JenkinsBuilder::default()
+    }
+}
+
+impl Display for Jenkins {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.name {
+            Some(n) => write!(f, "{}", n),
+            None => write!(f, "jenkins width=32 init={:#x}", self.init),
+        }
+    }
+}
+
+impl FromStr for JenkinsBuilder {
+    /// See FromStr for Jenkins
+    fn from_str(s: &str) -> Result<JenkinsBuilder, CheckBuilderErr> {
+        let mut jenkins = Jenkins::with_options();
+        for x in KeyValIter::new(s) {
+            let (current_key, current_val) = match x {
+                Err(key) => return Err(CheckBuilderErr::MalformedString(key)),
+                Ok(s) => s,
+            };
+            let jenkins_op = match current_key.as_str() {
+                "width" => usize::from_str(&current_val).ok().map(|x| jenkins.width(x)),
+                "init" => u32::from_hex(&current_val).ok().map(|x| jenkins.init(x)),
+                "check" => u32::from_hex(&current_val).ok().map(|x| jenkins.check(x)),
+                "name" => Some(jenkins.name(&current_val)),
+                _ => return Err(CheckBuilderErr::UnknownKey(current_key)),
+            };
+            match jenkins_op {
+                Some(j) => jenkins = j.clone(),
+                None => return Err(CheckBuilderErr::MalformedString(current_key)),
+            }
+        }
+        Ok(jenkins)
+    }
+    type Err = CheckBuilderErr;
+}
+
+impl FromStr for Jenkins {
+    /// Construct a new one-at-a-time hash from a string specification.
+    ///
+    /// Example:
+    ///
+    /// width=32 init=0
+    fn from_str(s: &str) -> Result<Jenkins, CheckBuilderErr> {
+        JenkinsBuilder::from_str(s)?.build()
+    }
+    type Err = CheckBuilderErr;
+}
+
+impl Digest for Jenkins {
+    type Sum = u32;
+    fn init(&self) -> Self::Sum {
+        self.init
+    }
+    fn dig_byte(&self, sum: Self::Sum, byte: u8) -> Self::Sum {
+        let mut h = sum.wrapping_add(u32::from(byte));
+        h = h.wrapping_add(h << 10);
+        h ^ (h >> 6)
+    }
+    fn finalize(&self, sum: Self::Sum) -> Self::Sum {
+        let mut h = sum.wrapping_add(sum << 3);
+        h ^= h >> 11;
+        h.wrapping_add(h << 15)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn one_at_a_time() {
+        let chk = Jenkins::from_str("width=32").unwrap();
+        assert_eq!(chk.digest(&b"a"[..]).unwrap(), 0xca2e9442);
+        let fox = b"The quick brown fox jumps over the lazy dog";
+        assert_eq!(chk.digest(&fox[..]).unwrap(), 0x519e91f5);
+        assert!(Jenkins::from_str("width=16").is_err());
+    }
+}
@@ -0,0 +1,203 @@
+//! A selection of every `stride`-th byte of the text, starting at byte `lane`, which can be applied to any algorithm.
+//!
+//! Some old boards split their firmware into multiple ROMs with 8-bit data buses, which are accessed in parallel,
+//! so that each ROM holds every second (or fourth) byte of the firmware and carries its own checksum.
+//! The text of such a checksum can be described by these parameters:
+//! * stride: The distance between two consecutive bytes of the text, in decimal (default 1)
+//! * lane: The position of the first byte of the text, in decimal and smaller than `stride` (default 0)
+//!
+//! The checksum is then calculated over the selected bytes, as if they were next to each other.
+use super::{CheckBuilderErr, RangePairs, RelativeIndex};
+use crate::keyval::KeyValIter;
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// A builder for the lane selection.
+///
+/// Example:
+/// ```
+/// # use delsum_lib::checksum::lanes::Lanes;
+/// Lanes::with_options()
+///     .stride(2)
+///     .lane(1)
+///     .build()
+///     .is_ok();
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct LanesBuilder {
+    stride: Option<usize>,
+    lane: Option<usize>,
+}
+
+impl LanesBuilder {
+    /// The distance between two consecutive bytes, optional, defaults to 1.
+    pub fn stride(&mut self, s: usize) -> &mut Self {
+        self.stride = Some(s);
+        self
+    }
+    /// The position of the first byte, optional, defaults to 0.
+    pub fn lane(&mut self, l: usize) -> &mut Self {
+        self.lane = Some(l);
+        self
+    }
+    /// Builds the lane selection, after validating the parameters.
+    pub fn build(&self) -> Result<Lanes, CheckBuilderErr> {
+        let stride = self.stride.unwrap_or(1);
+        if stride == 0 {
+            return Err(CheckBuilderErr::ValueOutOfRange("stride"));
+        }
+        let lane = self.lane.unwrap_or(0);
+        if lane >= stride {
+            return Err(CheckBuilderErr::ValueOutOfRange("lane"));
+        }
+        Ok(Lanes { stride, lane })
+    }
+    /// Extracts the keys of the lane selection from a model string, returning the
+    /// builder and the rest of the model string.
+    pub(crate) fn extract(s: &str) -> Result<(LanesBuilder, String), CheckBuilderErr> {
+        let mut lanes = Lanes::with_options();
+        let mut rest = Vec::new();
+        for x in KeyValIter::new(s) {
+            let (current_key, current_val) = match x {
+                Err(key) => return Err(CheckBuilderErr::MalformedString(key)),
+                Ok(s) => s,
+            };
+            let lanes_op = match current_key.as_str() {
+                "stride" => usize::from_str(&current_val)
+                    .ok()
+                    .map(|x| lanes.stride(x)),
+                "lane" => usize::from_str(&current_val).ok().map(|x| lanes.lane(x)),
+                _ => {
+                    rest.push(format!("{}=\"{}\"", current_key, current_val));
+                    continue;
+                }
+            };
+            if lanes_op.is_none() {
+                return Err(CheckBuilderErr::MalformedString(current_key));
+            }
+        }
+        Ok((lanes, rest.join(" ")))
+    }
+}
+
+/// A selection of the bytes `lane`, `lane + stride`, `lane + 2*stride`, ... of a text.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Lanes {
+    stride: usize,
+    lane: usize,
+}
+
+impl Default for Lanes {
+    fn default() -> Self {
+        Lanes { stride: 1, lane: 0 }
+    }
+}
+
+impl Lanes {
+    /// Creates a `LanesBuilder`, for more information see its documentation.
+    pub fn with_options() -> LanesBuilder {
+        LanesBuilder::default()
+    }
+    /// Whether all bytes of the text are selected.
+    pub fn is_identity(&self) -> bool {
+        self.stride == 1
+    }
+    /// Gets the selected bytes of a text.
+    pub fn select(&self, bytes: &[u8]) -> Vec<u8> {
+        bytes
+            .iter()
+            .skip(self.lane)
+            .step_by(self.stride)
+            .copied()
+            .collect()
+    }
+    /// Converts the segments found in the selected bytes of the files back to positions in the
+    /// original files, which have the lengths `lens`.
+    ///
+    /// A start is the position of the first selected byte and an end is the position after
+    /// the last selected byte of the segment.
+    /// For ends relative to the end of the files, the position after the last selected byte depends on how
+    /// many bytes of other lanes follow it, so the end of the file where this is the smallest is used.
+    pub fn file_segments(&self, segments: RangePairs, lens: &[usize]) -> RangePairs {
+        // the number of bytes after the last selected byte of the files
+        let trailing = lens
+            .iter()
+            .filter(|&&n| n > self.lane)
+            .map(|n| (n - self.lane - 1) % self.stride)
+            .min()
+            .unwrap_or(0);
+        segments
+            .into_iter()
+            .map(|(starts, ends)| {
+                let starts = starts
+                    .into_iter()
+                    .map(|s| self.lane + s * self.stride)
+                    .collect();
+                let ends = ends
+                    .into_iter()
+                    .map(|e| match e {
+                        RelativeIndex::FromStart(n) => {
+                            RelativeIndex::FromStart(self.lane + (n - 1) * self.stride + 1)
+                        }
+                        RelativeIndex::FromEnd(n) => {
+                            RelativeIndex::FromEnd(n * self.stride + trailing)
+                        }
+                    })
+                    .collect();
+                (starts, ends)
+            })
+            .collect()
+    }
+}
+
+impl Display for Lanes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_identity() {
+            return Ok(());
+        }
+        write!(f, "stride={} lane={}", self.stride, self.lane)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::modsum::ModSum;
+    use crate::checksum::{Digest, LinearCheck, Relativity};
+    fn lanes(s: &str) -> Lanes {
+        let (builder, rest) = LanesBuilder::extract(s).unwrap();
+        assert!(rest.is_empty());
+        builder.build().unwrap()
+    }
+    #[test]
+    fn select() {
+        let text = b"0123456789";
+        assert_eq!(lanes("").select(text), text.to_vec());
+        assert_eq!(lanes("stride=2").select(text), b"02468".to_vec());
+        assert_eq!(lanes("stride=4 lane=3").select(text), b"37".to_vec());
+        assert!(Lanes::with_options().stride(2).lane(2).build().is_err());
+        assert!(Lanes::with_options().stride(0).build().is_err());
+    }
+    #[test]
+    fn even_odd_segments() {
+        let chk = ModSum::<u16>::from_str("width=16").unwrap();
+        let odd = lanes("stride=2 lane=1");
+        let files = [
+            Vec::from("aXb1c2d3e4f5g6h"),
+            Vec::from("jXk1l2m3n4o5p6"),
+        ];
+        let sum = chk.digest(&b"123456"[..]).unwrap();
+        let selected: Vec<_> = files.iter().map(|f| odd.select(f)).collect();
+        let lens: Vec<_> = files.iter().map(Vec::len).collect();
+        let segs = chk.find_segments(&selected, &[sum, sum], Relativity::Start);
+        assert_eq!(
+            odd.file_segments(segs, &lens),
+            vec![(vec![3], vec![RelativeIndex::FromStart(14)])]
+        );
+        let segs = chk.find_segments(&selected, &[sum, sum], Relativity::End);
+        assert_eq!(
+            odd.file_segments(segs, &lens),
+            vec![(vec![3], vec![RelativeIndex::FromEnd(0)])]
+        );
+    }
+}
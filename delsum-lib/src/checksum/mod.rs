@@ -1,11 +1,27 @@
+pub mod affine;
+pub mod brute;
+pub mod bsdsum;
+pub mod concat;
 pub mod crc;
 pub mod fletcher;
+pub mod fnv;
+pub mod jenkins;
+pub mod lanes;
 pub mod modsum;
+pub mod pearson;
+pub mod polyhash;
+pub mod preprocess;
+pub mod presum;
+pub mod rotxor;
+pub mod sysvsum;
+pub mod transform;
+pub mod weightsum;
+pub mod xorsum;
 
+use crate::endian::WordSpec;
+use presum::{PresumStrategy, SumBytes};
 #[cfg(feature = "parallel")]
 use rayon::prelude::*;
-use std::cmp::Ordering;
-use std::convert::TryFrom;
 use std::io::Read;
 
 /// A basic trait for a checksum where
@@ -25,7 +41,7 @@ pub trait Digest {
     /// and gets converted to a Sum by finalize
     /// is not really feasable because of the operations LinearCheck would need to do
     /// both on Sums and interal States, so a single Sum type must be enough.
-    type Sum: Clone + Eq + Ord + std::fmt::Debug + Send + Sync + SumStr;
+    type Sum: Clone + Eq + Ord + std::fmt::Debug + Send + Sync + SumStr + SumBytes;
     /// Gets an initial sum before the bytes are processed through the sum.
     ///
     /// For instance in the case of crc, the sum type is some integer and the returned value from
@@ -35,18 +51,52 @@ pub trait Digest {
     ///
     /// For a crc, this corresponds to shifting, adding the byte and reducing.
     fn dig_byte(&self, sum: Self::Sum, byte: u8) -> Self::Sum;
+    /// Processes a single word from the text, with the word size given by `wordspec`.
+    ///
+    /// By default, words are just bytes, so this just calls `dig_byte`.
+    fn dig_word(&self, sum: Self::Sum, word: u64) -> Self::Sum {
+        self.dig_byte(sum, word as u8)
+    }
+    /// Describes how the text is split into words before given to `dig_word`.
+    fn wordspec(&self) -> WordSpec {
+        WordSpec::default()
+    }
     /// After all bytes are read, this function is called to do some finalization.
     ///
     /// In the case of crc, this corresponds to adding a constant at the end
     /// (and maybe also adding some 0s to the end of the text).
     fn finalize(&self, sum: Self::Sum) -> Self::Sum;
     /// Takes a reader and calculates the checksums of all bytes therein.
+    ///
+    /// If the number of bytes is not a multiple of the word size, the last word is padded with zeros.
     fn digest<R: Read>(&self, buf: R) -> Result<Self::Sum, std::io::Error> {
-        let sum = buf.bytes().try_fold(self.init(), |partsum, newbyte| {
-            newbyte.map(|x| self.dig_byte(partsum, x))
-        })?;
-        Ok(self.finalize(sum))
+        digest_words(self, buf).map(|(sum, _)| self.finalize(sum))
+    }
+}
+
+/// Reads all words of the reader into the sum, without finalizing it.
+///
+/// Also returns the number of bytes read, for checksums which need the length of the text.
+pub(crate) fn digest_words<D: Digest + ?Sized, R: Read>(
+    chk: &D,
+    buf: R,
+) -> Result<(D::Sum, usize), std::io::Error> {
+    let spec = chk.wordspec();
+    let mut word = Vec::with_capacity(spec.word_bytes());
+    let mut sum = chk.init();
+    let mut len = 0;
+    for byte in buf.bytes() {
+        word.push(byte?);
+        len += 1;
+        if word.len() == spec.word_bytes() {
+            sum = chk.dig_word(sum, spec.bytes_to_word(&word));
+            word.clear();
+        }
+    }
+    if !word.is_empty() {
+        sum = chk.dig_word(sum, spec.bytes_to_word(&word));
     }
+    Ok((sum, len))
 }
 
 #[derive(Copy, Clone)]
@@ -54,7 +104,7 @@ pub enum Relativity {
     Start,
     End,
 }
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
 pub enum RelativeIndex {
     FromStart(usize),
     FromEnd(usize),
@@ -78,7 +128,7 @@ pub enum RelativeIndex {
 ///
 /// The assumptions are here (the `self`s are omitted for clarity):
 /// * `add(a,b)` forms an abeliean group with `negate(a)` as inverse (hereafter, the sum value 0 will be equal to `add(init(), negate(init()))`)
-/// * `shift(s, shift_n(1)) == dig(s, 0u8)`
+/// * `shift(s, shift_n(1)) == dig_word(s, 0u64)` (where, if there is no special word size, this is just `dig_byte(s, 0u8)`)
 /// * `shift(s, shift_n(1))` is bijective in the set of all valid `Sum` values
 /// * `shift(shift(s, shift_n(a)), shift_n(b)) == shift(s, shift_n(a+b))`
 /// * `add(dig_byte(s, 0u8), dig_byte(r, 0u8)) == dig_byte(add(s, r), 0u8)`
@@ -110,43 +160,117 @@ pub trait LinearCheck: Digest + Send + Sync {
         }
         shift
     }
+    /// The same algorithm with a width of `bits`, whose sums are the lowest `bits` bits of the sums of `self`,
+    /// or `None` if the higher bits influence the lower ones (see `transform::truncated_module`).
+    ///
+    /// This is used to search for segments if the `outbits` of an `OutTransform` cut off the higher bits.
+    fn truncated(&self, _bits: usize) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
     #[doc(hidden)]
     fn presums(
         &self,
         bytes: &[u8],
-        sum: &Self::Sum,
+        sum: &dyn Fn(usize) -> Option<Self::Sum>,
+        phase: usize,
         start_range: std::ops::Range<usize>,
         end_range: std::ops::Range<usize>,
+        cursor: &mut Option<(Self::Sum, usize)>,
     ) -> (Vec<Self::Sum>, Vec<Self::Sum>) {
         // we calculate two presum arrays, one for the starting values and one for the end values
-        let mut state = self.init();
-        let mut start_presums = Vec::with_capacity(start_range.len());
+        //
+        // The text is read as words, beginning at byte `phase`, so only starts at `phase + k*wordsize` are
+        // considered, but ends can be at any byte, with a partial last word being zero-padded.
+        // Each presum is stored together with the number of words before it, which is used for shifting.
+        //
+        // The target sum is given for each end, and ends without a target get an arbitrary one,
+        // so they have to be filtered out of the results by the caller.
+        //
+        // The presums of a text can also be calculated for consecutive ranges, so that only the presums of one range
+        // have to be kept at a time. Then `cursor` contains the state after the words before the current ranges
+        // (or is `None` at the start of the text) and gets advanced to the word where the next ranges start,
+        // so that the text is only read once.
+        let spec = self.wordspec();
+        let wb = spec.word_bytes();
+        let (mut state, first) = cursor.take().unwrap_or_else(|| (self.init(), 0));
+        // empty ranges do not need any words, and the next ranges start at the ends of the current ones
+        let range_ends = [&start_range, &end_range]
+            .iter()
+            .filter(|r| !r.is_empty())
+            .map(|r| r.end)
+            .collect::<Vec<_>>();
+        let stop = range_ends.iter().copied().max().unwrap_or(0);
+        let resume = range_ends
+            .iter()
+            .min()
+            .map_or(0, |e| e.saturating_sub(phase) / wb);
+        let mut start_presums = Vec::with_capacity(start_range.len() / wb + 1);
         let mut end_presums = Vec::with_capacity(end_range.len());
         let neg_init = self.negate(self.init());
-        for (i, c) in bytes.iter().enumerate() {
-            if start_range.contains(&i) {
+        let end_presum = |state: Self::Sum, end: usize| {
+            let neg_sum = self.negate(sum(end).unwrap_or_else(|| self.init()));
+            self.add(self.finalize(state), &neg_sum)
+        };
+        for e in end_range.start..phase.min(end_range.end) {
+            // ends before the phase start do not contain any words
+            end_presums.push((end_presum(self.init(), e), 0));
+        }
+        let text = bytes.get(phase..).unwrap_or(&[]);
+        let mut words = first;
+        for (j, chunk) in text.chunks(wb).enumerate().skip(first) {
+            let pos = phase + j * wb;
+            if j == resume {
+                *cursor = Some((state.clone(), j));
+            }
+            if pos >= stop {
+                break;
+            }
+            if start_range.contains(&pos) {
                 // from the startsums, we substract the init value of the checksum
-                start_presums.push(self.add(state.clone(), &neg_init));
+                start_presums.push((self.add(state.clone(), &neg_init), j));
+            }
+            // if any end is in the middle of the current word, we digest a padded word
+            for (k, e) in (pos..pos + chunk.len()).enumerate() {
+                if end_range.contains(&e) && k + 1 < wb {
+                    let partial = self.dig_word(state.clone(), spec.bytes_to_word(&chunk[..=k]));
+                    // from the endsums, we finalize them and subtract the given final sum
+                    end_presums.push((end_presum(partial, e), j + 1));
+                }
             }
-            state = self.dig_byte(state, *c);
-            if end_range.contains(&i) {
-                // from the endsums, we finalize them and subtract the given final sum
-                let endstate = self.add(self.finalize(state.clone()), &self.negate(sum.clone()));
-                end_presums.push(endstate);
+            state = self.dig_word(state, spec.bytes_to_word(chunk));
+            words = j + 1;
+            if chunk.len() == wb && end_range.contains(&(pos + wb - 1)) {
+                end_presums.push((end_presum(state.clone(), pos + wb - 1), j + 1));
             }
         }
-        // we then shift checksums to length of file
-        let mut shift = self.init_shift();
-        for i in (0..bytes.len()).rev() {
-            if end_range.contains(&i) {
-                end_presums[i - end_range.start] =
-                    self.shift(end_presums[i - end_range.start].clone(), &shift)
+        if cursor.is_none() {
+            // the text ended before the next ranges
+            *cursor = Some((state, words));
+        }
+        if start_presums.is_empty() && end_presums.is_empty() {
+            return (Vec::new(), Vec::new());
+        }
+        // we then shift checksums to length of file (in words), beginning with the highest word count in the ranges
+        let total = spec.word_count(text.len());
+        let counts = start_presums
+            .iter()
+            .chain(end_presums.iter())
+            .map(|(_, n)| *n);
+        let (low, high) = counts.fold((total, 0), |(l, h), n| (l.min(n), h.max(n)));
+        let mut shift = self.shift_n(total - high);
+        let mut start_iter = start_presums.iter_mut().rev().peekable();
+        let mut end_iter = end_presums.iter_mut().rev().peekable();
+        for i in (low..=high).rev() {
+            while let Some((s, _)) = end_iter.next_if(|(_, n)| *n == i) {
+                *s = self.shift(s.clone(), &shift);
             }
-            shift = self.inc_shift(shift);
-            if start_range.contains(&i) {
-                start_presums[i - start_range.start] =
-                    self.shift(start_presums[i - start_range.start].clone(), &shift)
+            while let Some((s, _)) = start_iter.next_if(|(_, n)| *n == i) {
+                *s = self.shift(s.clone(), &shift);
             }
+            shift = self.inc_shift(shift);
         }
         // This has the effect that, when substracting the n'th startsum from the m'th endsum, we get the checksum
         // from n to m, minus the final sum (all shifted by (len-m)), which is 0 exactly when the checksum from n to m is equal to
@@ -165,7 +289,8 @@ pub trait LinearCheck: Digest + Send + Sync {
         //                  (finalize(i*[n..m]) - s)*x^(k-m) == 0
         // (2) (3) (6)  <=> finalize(i*[n..m]) - s           == 0
         // (1)          <=> finalize(i*[n..m])               == s
-        (start_presums, end_presums)
+        let strip = |v: Vec<(Self::Sum, usize)>| v.into_iter().map(|(s, _)| s).collect();
+        (strip(start_presums), strip(end_presums))
     }
 
     /// Given some bytes and a target sum, determines all segments in the bytes that have that
@@ -176,152 +301,163 @@ pub trait LinearCheck: Digest + Send + Sync {
     /// has the target checksum.
     ///
     /// This function has a high space usage per byte: for `n` bytes, it uses a total space of `n*(8 + 2*sizeof(Sum))` bytes.
+    /// If that is too much, `find_segments_with` with `PresumStrategy::External` can be used instead.
     /// The time is bounded by the runtime of the sort algorithm, which is around `n*log(n)`.
     /// If Hashtables were used, it could be done in linear time, but they take too much space.
-    fn find_segments(&self, bytes: &[Vec<u8>], sum: &[Self::Sum], rel: Relativity) -> RangePairs {
-        if bytes.is_empty() {
-            return Vec::new();
+    fn find_segments<B: AsRef<[u8]> + Sync>(
+        &self,
+        bytes: &[B],
+        sum: &[Self::Sum],
+        rel: Relativity,
+    ) -> RangePairs {
+        self.find_segments_by(bytes, &|i, _| sum.get(i).cloned(), rel)
+    }
+
+    /// Like `find_segments`, but the target sum of a segment depends on where it ends.
+    ///
+    /// `target(i, e)` gives the target sum of the segments in the `i`-th file whose last byte is at `e`,
+    /// or `None` if no segment can end there.
+    /// This allows searching for segments whose checksum is stored in the file right after them.
+    ///
+    /// Note that the target can't depend on the start of the segment in the same way, since the
+    /// presums of the starts and ends would not be independent anymore.
+    fn find_segments_by<B: AsRef<[u8]> + Sync>(
+        &self,
+        bytes: &[B],
+        target: &(dyn Fn(usize, usize) -> Option<Self::Sum> + Sync),
+        rel: Relativity,
+    ) -> RangePairs {
+        match self.find_segments_with(bytes, target, rel, PresumStrategy::InMemory) {
+            Ok(segs) => segs,
+            Err(_) => unreachable!("only presums in temporary files can fail"),
         }
-        if u32::try_from(bytes[0].len()).is_err() {
-            // only support 32-bit length files for now, since a usize for every byte would take a lot of space
-            panic!("File must be under 4GiB!");
+    }
+
+    /// Like `find_segments_by`, but the `strategy` determines whether the presums are sorted in memory or in temporary files.
+    ///
+    /// Returns an error if the temporary files could not be written or read.
+    fn find_segments_with<B: AsRef<[u8]> + Sync>(
+        &self,
+        bytes: &[B],
+        target: &(dyn Fn(usize, usize) -> Option<Self::Sum> + Sync),
+        rel: Relativity,
+        strategy: PresumStrategy,
+    ) -> std::io::Result<RangePairs> {
+        if bytes.is_empty() {
+            return Ok(Vec::new());
         }
-        let min_len = bytes.iter().map(|x| x.len()).min().unwrap();
+        let min_len = bytes.iter().map(|x| x.as_ref().len()).min().unwrap();
         let end_range = |b: &[u8]| match rel {
             Relativity::Start => 0..min_len,
             Relativity::End => (b.len() - min_len)..b.len(),
         };
-        #[cfg(feature = "parallel")]
-        let (start_presums, end_presums) = bytes
-            .par_iter()
-            .zip(sum.par_iter())
-            .map(|(b, s)| self.presums(b, &s, 0..min_len, end_range(&b)))
-            .unzip();
-        #[cfg(not(feature = "parallel"))]
-        let (start_presums, end_presums) = bytes
-            .iter()
-            .zip(sum.iter())
-            .map(|(b, s)| self.presums(b, &s, 0..min_len, end_range(&b)))
-            .unzip();
-
-        let start_preset = PresumSet::new(start_presums);
-        let end_preset = PresumSet::new(end_presums);
-
+        let wb = self.wordspec().word_bytes();
         let mut ret_vec = Vec::new();
-        for (a, b) in start_preset.equal_pairs(&end_preset) {
-            let starts: Vec<_> = a.iter().map(|x| usize::try_from(*x).unwrap()).collect();
-            let ends: Vec<_> = b.iter().map(|x| usize::try_from(*x).unwrap() + 1).collect();
-            let min_start = *starts.iter().min().unwrap_or(&min_len);
-            let max_end = *ends.iter().max().unwrap_or(&0);
-            let rel_ends: Vec<_> = ends
-                .into_iter()
-                .filter(|x| x > &min_start)
-                .map(|x| match rel {
-                    Relativity::Start => RelativeIndex::FromStart(x),
-                    Relativity::End => RelativeIndex::FromEnd(min_len - x),
-                })
-                .collect();
-            let rel_starts = starts.into_iter().filter(|x| x < &max_end).collect();
-            if !rel_ends.is_empty() {
-                ret_vec.push((rel_starts, rel_ends));
-            }
-        }
-        ret_vec
-    }
-}
-
-pub type RangePairs = Vec<(Vec<usize>, Vec<RelativeIndex>)>;
-
-/// A struct for helping to sort and get duplicates of arrays of arrays.
-#[derive(Debug)]
-struct PresumSet<Sum: Clone + Eq + Ord + std::fmt::Debug> {
-    idx: Vec<u32>,
-    presum: Vec<Vec<Sum>>,
-}
-
-impl<Sum: Clone + Eq + Ord + std::fmt::Debug + Send + Sync> PresumSet<Sum> {
-    /// Gets a new PresumSet. Gets sorted on construction.
-    fn new(presum: Vec<Vec<Sum>>) -> Self {
-        let firstlen = presum[0].len();
-        // check that all sum arrays are of the same length
-        for x in presum.iter() {
-            assert_eq!(firstlen, x.len());
-        }
-        // vector of all indices
-        let mut idxvec: Vec<_> = (0..=(firstlen - 1) as u32).collect();
-        // get a permutation vector representing the sort of the presum arrays first by value and then by index
-
-        #[cfg(feature = "parallel")]
-        idxvec.par_sort_unstable_by(|a, b| Self::cmp_idx(&presum, *a, &presum, *b).then(a.cmp(&b)));
-        #[cfg(not(feature = "parallel"))]
-        idxvec.sort_unstable_by(|a, b| Self::cmp_idx(&presum, *a, &presum, *b).then(a.cmp(&b)));
-        Self {
-            idx: idxvec,
-            presum,
-        }
-    }
-    /// Compares all elements of the first vector at an index to the ones of the second vector lexicographically (assuming same length).
-    fn cmp_idx(presum_a: &[Vec<Sum>], a: u32, presum_b: &[Vec<Sum>], b: u32) -> Ordering {
-        for (x, y) in presum_a.iter().zip(presum_b.iter()) {
-            let cmp = x[a as usize].cmp(&y[b as usize]);
-            if cmp != Ordering::Equal {
-                return cmp;
-            }
-        }
-        Ordering::Equal
-    }
-    /// Finds groups of indices equal elements in the first set and the second set and
-    /// returns them for each equal array.
-    fn equal_pairs(&self, other: &Self) -> Vec<(Vec<u32>, Vec<u32>)> {
-        let mut ret = Vec::new();
-        let mut a_idx = 0;
-        let mut b_idx = 0;
-        while a_idx < self.idx.len() && b_idx < self.idx.len() {
-            let apos = self.idx[a_idx];
-            let bpos = other.idx[b_idx];
-            match Self::cmp_idx(&self.presum, apos, &other.presum, bpos) {
-                Ordering::Less => {
-                    a_idx += 1;
+        // for word sizes larger than a byte, segments can start at any byte, so we
+        // search separately for each possible position of the start inside a word
+        for phase in 0..wb.min(min_len) {
+            // the presums of the starts and ends in the ranges, with the ends relative to the start of the ends,
+            // continuing from the `cursors` of the files
+            let presums_in =
+                |starts: std::ops::Range<usize>,
+                 ends: std::ops::Range<usize>,
+                 cursors: &mut [Option<(Self::Sum, usize)>]| {
+                    let file_ends = |b: &[u8]| {
+                        let start = end_range(b).start;
+                        start + ends.start..start + ends.end
+                    };
+                    let file_presums = |(i, (b, cursor)): (usize, (&B, _))| {
+                        let b = b.as_ref();
+                        let target = |e| target(i, e);
+                        self.presums(b, &target, phase, starts.clone(), file_ends(b), cursor)
+                    };
+                    #[cfg(feature = "parallel")]
+                    let presums = bytes
+                        .par_iter()
+                        .zip(cursors.par_iter_mut())
+                        .enumerate()
+                        .map(file_presums)
+                        .unzip();
+                    #[cfg(not(feature = "parallel"))]
+                    let presums = bytes
+                        .iter()
+                        .zip(cursors.iter_mut())
+                        .enumerate()
+                        .map(file_presums)
+                        .unzip();
+                    presums
+                };
+            let pairs = match strategy.resolve::<Self::Sum>(bytes.len(), min_len) {
+                PresumStrategy::InMemory => {
+                    let (start_presums, end_presums) =
+                        presums_in(0..min_len, 0..min_len, &mut vec![None; bytes.len()]);
+                    presum::equal_presums(start_presums, end_presums)
                 }
-                Ordering::Greater => {
-                    b_idx += 1;
-                }
-                Ordering::Equal => {
-                    let mut n_a = 0;
-                    // gets all runs of equal elements in a and b array
-                    for x in &self.idx[a_idx..] {
-                        if Self::cmp_idx(&self.presum, apos, &self.presum, *x) == Ordering::Equal {
-                            n_a += 1;
-                        } else {
-                            break;
-                        }
-                    }
-                    let mut n_b = 0;
-                    for x in &other.idx[b_idx..] {
-                        if Self::cmp_idx(&other.presum, bpos, &other.presum, *x) == Ordering::Equal
-                        {
-                            n_b += 1;
-                        } else {
-                            break;
-                        }
+                PresumStrategy::External { run_len } => {
+                    // runs start at word boundaries, so the indices of the starts can be offset by whole words
+                    let run_len = run_len.max(wb) / wb * wb;
+                    let mut start_runs = presum::Runs::new(bytes.len());
+                    let mut end_runs = presum::Runs::new(bytes.len());
+                    // the ends of a file can be far behind its starts, so both are calculated separately,
+                    // each in a single pass over the files
+                    let mut start_cursors = vec![None; bytes.len()];
+                    let mut end_cursors = vec![None; bytes.len()];
+                    for a in (0..min_len).step_by(run_len) {
+                        let range = a..(a + run_len).min(min_len);
+                        let (start_presums, _) =
+                            presums_in(range.clone(), a..a, &mut start_cursors);
+                        start_runs.push(start_presums, a / wb)?;
+                        let (_, end_presums) = presums_in(a..a, range, &mut end_cursors);
+                        end_runs.push(end_presums, a)?;
                     }
-                    let mut a_vec = Vec::new();
-                    a_vec.extend_from_slice(&self.idx[a_idx..a_idx + n_a]);
-                    let mut b_vec = Vec::new();
-                    b_vec.extend_from_slice(&other.idx[b_idx..b_idx + n_b]);
-                    ret.push((a_vec, b_vec));
-                    // puts indexes beyond equal elements
-                    a_idx += n_a;
-                    b_idx += n_b;
+                    presum::equal_runs(&mut start_runs, &mut end_runs)?
+                }
+                PresumStrategy::Hashed => {
+                    let file_presums = |i: usize| {
+                        let b = bytes[i].as_ref();
+                        let target = |e| target(i, e);
+                        self.presums(b, &target, phase, 0..min_len, end_range(b), &mut None)
+                    };
+                    presum::equal_hashed(bytes.len(), file_presums)
+                }
+                PresumStrategy::Auto { .. } => unreachable!("resolve never returns Auto"),
+            };
+
+            for (a, b) in pairs {
+                let starts: Vec<_> = a.iter().map(|x| phase + x * wb).collect();
+                // the presums of ends without a target are meaningless, so they are removed here
+                let has_target = |x: &usize| {
+                    bytes
+                        .iter()
+                        .enumerate()
+                        .all(|(i, f)| target(i, end_range(f.as_ref()).start + x).is_some())
+                };
+                let ends: Vec<_> = b.into_iter().filter(has_target).map(|x| x + 1).collect();
+                let min_start = *starts.iter().min().unwrap_or(&min_len);
+                let max_end = *ends.iter().max().unwrap_or(&0);
+                let rel_ends: Vec<_> = ends
+                    .into_iter()
+                    .filter(|x| x > &min_start)
+                    .map(|x| match rel {
+                        Relativity::Start => RelativeIndex::FromStart(x),
+                        Relativity::End => RelativeIndex::FromEnd(min_len - x),
+                    })
+                    .collect();
+                let rel_starts = starts.into_iter().filter(|x| x < &max_end).collect();
+                if !rel_ends.is_empty() {
+                    ret_vec.push((rel_starts, rel_ends));
                 }
             }
         }
-        // sort it, for good measure
-        ret.sort_unstable();
-        ret
+        if wb > 1 {
+            ret_vec.sort_by_key(|(starts, _): &(Vec<usize>, _)| starts[0]);
+        }
+        Ok(ret_vec)
     }
 }
 
+pub type RangePairs = Vec<(Vec<usize>, Vec<RelativeIndex>)>;
+
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub enum CheckBuilderErr {
     /// The checksum given on construction does not match
@@ -364,6 +500,7 @@ impl std::error::Error for CheckBuilderErr {}
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub enum CheckReverserError {
     MissingParameter(&'static str),
+    ValueOutOfRange(&'static str),
     UnsuitableFiles(&'static str),
     ChecksumFileMismatch,
 }
@@ -373,6 +510,7 @@ impl std::fmt::Display for CheckReverserError {
         use CheckReverserError::*;
         match self {
             MissingParameter(s) => write!(f, "Missing Parameters: {}", s),
+            ValueOutOfRange(s) => write!(f, "Value for parameter '{}' invalid", s),
             UnsuitableFiles(s) => write!(
                 f,
                 "Could not reverse because\
@@ -469,6 +607,11 @@ nie gefühlten, leichten, dumpfen Schmerz zu fühlen begann.
         new_sum = chk.shift(new_sum, &shift4);
         new_sum = chk.dig_byte(new_sum, b'T');
         assert_eq!(test_sum, chk.finalize(new_sum));
+        let shift100 = (0..100).fold(chk.init_shift(), |shift, _| chk.inc_shift(shift));
+        assert_eq!(
+            chk.shift(test_sum.clone(), &shift100),
+            chk.shift(test_sum, &chk.shift_n(100))
+        );
     }
     pub fn test_find<L: LinearCheck>(chk: &L) {
         let sum_1_9 = chk.digest(&b"123456789"[..]).unwrap();
@@ -496,18 +639,28 @@ nie gefühlten, leichten, dumpfen Schmerz zu fühlen begann.
             ),
             vec![(vec![10], vec![RelativeIndex::FromStart(19)])]
         );
+        let files = [
+            Vec::from("XXX12345678987654321AndSoOn"),
+            Vec::from("ABC123456789.super."),
+            Vec::from("Za!987654321ergrfrf"),
+        ];
+        let sums = [sum_1_9_1, sum_1_9, sum_9_1];
         assert_eq!(
-            chk.find_segments(
-                &[
-                    Vec::from("XXX12345678987654321AndSoOn"),
-                    Vec::from("ABC123456789.super."),
-                    Vec::from("Za!987654321ergrfrf")
-                ],
-                &[sum_1_9_1, sum_1_9, sum_9_1],
-                Relativity::End
-            ),
+            chk.find_segments(&files, &sums, Relativity::End),
             vec![(vec![3], vec![RelativeIndex::FromEnd(7)])]
-        )
+        );
+        // the files have different lengths, so the ends of a run are at different positions than its starts
+        let target = |i: usize, _| sums.get(i).cloned();
+        for strategy in &[
+            PresumStrategy::External { run_len: 3 },
+            PresumStrategy::Hashed,
+        ] {
+            assert_eq!(
+                chk.find_segments_with(&files, &target, Relativity::End, *strategy)
+                    .unwrap(),
+                vec![(vec![3], vec![RelativeIndex::FromEnd(7)])]
+            );
+        }
     }
     pub fn check_example<D: Digest>(chk: &D, sum: D::Sum) {
         assert_eq!(chk.digest(EXAMPLE_TEXT.as_bytes()).unwrap(), sum)
@@ -589,8 +742,8 @@ nie gefühlten, leichten, dumpfen Schmerz zu fühlen begann.
     fn check_shift1<L: LinearCheck>(chk: &L, a: &L::Sum) {
         assert_eq!(
             chk.shift(a.clone(), &chk.shift_n(1)),
-            chk.dig_byte(a.clone(), 0u8),
-            "Shift1 Fail: shift({:x?}, shift_n1(1)) != dig_byte({:x?}, 0u8)",
+            chk.dig_word(a.clone(), 0u64),
+            "Shift1 Fail: shift({:x?}, shift_n1(1)) != dig_word({:x?}, 0u64)",
             a,
             a
         );
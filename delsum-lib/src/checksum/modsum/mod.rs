@@ -1,16 +1,24 @@
 //! A simple modular sum over bytes (i.e. `bytes.sum() % module`)
 //!
+//! The text can also be summed as words of multiple bytes (e.g. `u16` in little endian),
+//! in which case a trailing partial word is padded with zero bytes.
+//!
 //! There are a number of parameters:
 //! * width: The number of bits in the sum type, at most 64
 //! * module: The sum is taken modulo this number
 //! * init: The initial number
+//! * wordsize: The number of bits in a word that gets summed, a multiple of 8 (default 8)
+//! * endian: The byte order of the words, either `little` or `big` (default big)
+//! * signed: Whether the words are two's complement numbers, like a `char` in C on x86 (default false)
 //! * check: The checksum of "123456789" (optional, gets checked at construction)
 //! * name: An optional name that gets used for display purposes
 //!
 //! Note that a parameter to add at the end is not needed, since it is equivalent to `init`.
 pub mod rev;
 use crate::bitnum::Modnum;
+use crate::checksum::transform::truncated_module;
 use crate::checksum::{CheckBuilderErr, Digest, LinearCheck};
+use crate::endian::{Endian, WordSpec};
 use crate::keyval::KeyValIter;
 use std::fmt::Display;
 use std::str::FromStr;
@@ -32,6 +40,9 @@ pub struct ModSumBuilder<S: Modnum> {
     width: Option<usize>,
     module: Option<S>,
     init: Option<S>,
+    wordsize: Option<usize>,
+    endian: Option<Endian>,
+    signed: Option<bool>,
     check: Option<S>,
     name: Option<String>,
 }
@@ -56,6 +67,23 @@ impl<S: Modnum> ModSumBuilder<S> {
         self.init = Some(i);
         self
     }
+    /// The number of bits of a word of the text, a multiple of 8, optional, defaults to 8.
+    pub fn wordsize(&mut self, w: usize) -> &mut Self {
+        self.wordsize = Some(w);
+        self
+    }
+    /// The byte order of the words of the text, optional, defaults to big endian.
+    pub fn endian(&mut self, e: Endian) -> &mut Self {
+        self.endian = Some(e);
+        self
+    }
+    /// Whether the words are interpreted as signed numbers, optional, defaults to false.
+    ///
+    /// Negative words get subtracted from the sum.
+    pub fn signed(&mut self, s: bool) -> &mut Self {
+        self.signed = Some(s);
+        self
+    }
     /// The checksum of "123456789", gets checked on creation.
     pub fn check(&mut self, c: S) -> &mut Self {
         self.check = Some(c);
@@ -79,10 +107,16 @@ impl<S: Modnum> ModSumBuilder<S> {
         if module != S::zero() {
             init = init % module
         };
+        let wordspec = WordSpec::new(
+            self.wordsize.unwrap_or(8),
+            self.endian.unwrap_or(Endian::Big),
+        )?;
         let s = ModSum {
             width,
             module,
             init,
+            wordspec,
+            signed: self.signed.unwrap_or(false),
             name: self.name.clone(),
         };
         match self.check {
@@ -98,6 +132,33 @@ impl<S: Modnum> ModSumBuilder<S> {
             None => Ok(s),
         }
     }
+    /// The same partially known algorithm for the lowest `bits` bits of the sums (see `LinearCheck::truncated`),
+    /// or `None` if the given module lets the higher bits influence the lower ones.
+    ///
+    /// If the module is not given, only modules up to `2^bits` can be found with it.
+    pub(crate) fn truncated(&self, bits: usize) -> Option<Self> {
+        let mask = if bits < S::zero().bits() {
+            (S::one() << bits) - S::one()
+        } else {
+            !S::zero()
+        };
+        let module = match self.module {
+            Some(m) => Some(truncated_module(m, bits)?),
+            None => None,
+        };
+        let reduce = |x: S| match module {
+            Some(m) if m != S::zero() => x % m,
+            _ => x & mask,
+        };
+        Some(ModSumBuilder {
+            width: Some(bits),
+            module,
+            init: self.init.map(reduce),
+            check: self.check.map(|c| c & mask),
+            name: None,
+            ..self.clone()
+        })
+    }
 }
 
 /// A Modsum checksum algorithm.
@@ -108,6 +169,8 @@ pub struct ModSum<S: Modnum> {
     width: usize,
     module: S,
     init: S,
+    wordspec: WordSpec,
+    signed: bool,
     name: Option<String>,
 }
 
@@ -118,21 +181,62 @@ impl<S: Modnum> ModSum<S> {
             width: None,
             module: None,
             init: None,
+            wordsize: None,
+            endian: None,
+            signed: None,
             check: None,
             name: None,
         }
     }
+    /// Reduces a word of the text modulo the module.
+    fn reduce_word(&self, word: u64) -> S {
+        match (S::from_u64(word), self.module == S::zero()) {
+            (Some(w), true) => w,
+            (Some(w), false) => w % self.module,
+            (None, false) => S::from_u64(word % self.module.to_u64().unwrap()).unwrap(),
+            // a module of 0 means 2^(bits of S), so we can just cut off the top bits
+            (None, true) => S::from_u64(word & (u64::MAX >> (64 - self.module.bits()))).unwrap(),
+        }
+    }
+    /// Gets the value of a word modulo the module, taking the sign into account.
+    fn word_value(&self, word: u64) -> S {
+        match self.signed {
+            true => {
+                let value = self.wordspec.to_signed(word);
+                let reduced = self.reduce_word(value.unsigned_abs());
+                if value < 0 {
+                    self.negate(reduced)
+                } else {
+                    reduced
+                }
+            }
+            false => self.reduce_word(word),
+        }
+    }
 }
 
 impl<Sum: Modnum> Display for ModSum<Sum> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self.name {
             Some(n) => write!(f, "{}", n),
-            None => write!(
-                f,
-                "modsum width={} module={:#x} init={:#x}",
-                self.width, self.module, self.init
-            ),
+            None => {
+                write!(
+                    f,
+                    "modsum width={} module={:#x} init={:#x}",
+                    self.width, self.module, self.init
+                )?;
+                if self.wordspec.wordsize != 8 {
+                    write!(
+                        f,
+                        " wordsize={} endian={}",
+                        self.wordspec.wordsize, self.wordspec.endian
+                    )?;
+                }
+                if self.signed {
+                    write!(f, " signed=true")?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -150,6 +254,9 @@ impl<Sum: Modnum> FromStr for ModSumBuilder<Sum> {
                 "width" => usize::from_str(&current_val).ok().map(|x| sum.width(x)),
                 "module" => Sum::from_hex(&current_val).ok().map(|x| sum.module(x)),
                 "init" => Sum::from_hex(&current_val).ok().map(|x| sum.init(x)),
+                "wordsize" => usize::from_str(&current_val).ok().map(|x| sum.wordsize(x)),
+                "endian" => Endian::from_str(&current_val).ok().map(|x| sum.endian(x)),
+                "signed" => bool::from_str(&current_val).ok().map(|x| sum.signed(x)),
                 "name" => Some(sum.name(&current_val)),
                 _ => return Err(CheckBuilderErr::UnknownKey(current_key)),
             };
@@ -181,7 +288,13 @@ impl<S: Modnum> Digest for ModSum<S> {
         self.init
     }
     fn dig_byte(&self, sum: Self::Sum, byte: u8) -> Self::Sum {
-        sum.add_mod(&S::from(byte), &self.module)
+        self.dig_word(sum, u64::from(byte))
+    }
+    fn dig_word(&self, sum: Self::Sum, word: u64) -> Self::Sum {
+        sum.add_mod(&self.word_value(word), &self.module)
+    }
+    fn wordspec(&self) -> WordSpec {
+        self.wordspec
     }
     fn finalize(&self, sum: Self::Sum) -> Self::Sum {
         sum
@@ -211,12 +324,28 @@ impl<S: Modnum> LinearCheck for ModSum<S> {
             self.module - sum
         }
     }
+    fn truncated(&self, bits: usize) -> Option<Self> {
+        let module = truncated_module(self.module, bits)?;
+        let init = if module == S::zero() {
+            self.init
+        } else {
+            self.init % module
+        };
+        Some(ModSum {
+            width: bits,
+            module,
+            init,
+            wordspec: self.wordspec,
+            signed: self.signed,
+            name: None,
+        })
+    }
 }
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::checksum::tests::{test_prop, test_shifts};
-    use crate::checksum::{RelativeIndex, Relativity};
+    use crate::checksum::{presum::PresumStrategy, RelativeIndex, Relativity};
     #[test]
     fn screw() {
         let s = ModSum::<u8>::with_options()
@@ -246,6 +375,50 @@ mod tests {
         test_prop(&s);
     }
     #[test]
+    fn words() {
+        let chk = ModSum::<u16>::from_str("width=16 wordsize=16 endian=little").unwrap();
+        assert_eq!(chk.digest(&[1u8, 2, 3][..]).unwrap(), 0x0204);
+        test_prop(&chk);
+        let x = Vec::from("implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR");
+        let y = Vec::from("This program comes with ABSOLUTELY NO WARRANTY; for details typ");
+        let merchantibility = chk.digest(b"MERCHANTABILITY".as_ref()).unwrap();
+        let ith_absolutely_ = chk.digest(b"ith ABSOLUTELY ".as_ref()).unwrap();
+        assert_eq!(
+            chk.find_segments(
+                &[x, y],
+                &[merchantibility, ith_absolutely_],
+                Relativity::Start
+            ),
+            vec![(vec![20], vec![RelativeIndex::FromStart(35)])]
+        );
+        let chk = ModSum::<u8>::from_str("width=8 module=fb wordsize=32 endian=big").unwrap();
+        // (0xffffffff + 0x01000000) % 0xfb
+        assert_eq!(
+            chk.digest(&[0xffu8, 0xff, 0xff, 0xff, 1][..]).unwrap(),
+            0xf7
+        );
+    }
+    #[test]
+    fn signed() {
+        let chk = ModSum::<u16>::from_str("width=16 signed=true").unwrap();
+        // 0x80 + 0x01 as i8 is -127
+        assert_eq!(chk.digest(&[0x80u8, 0x01][..]).unwrap(), 0xff81);
+        assert_eq!(
+            chk.to_string(),
+            "modsum width=16 module=0x0 init=0x0 signed=true"
+        );
+        test_shifts(&chk);
+        test_prop(&chk);
+        let chk = ModSum::<u16>::from_str("width=16 module=fff1 signed=true").unwrap();
+        assert_eq!(chk.digest(&[0x80u8, 0x01][..]).unwrap(), 0xff72);
+        test_shifts(&chk);
+        test_prop(&chk);
+        let chk =
+            ModSum::<u32>::from_str("width=32 wordsize=16 endian=little signed=true").unwrap();
+        assert_eq!(chk.digest(&[0x00u8, 0x80, 0x02][..]).unwrap(), 0xffff8002);
+        test_prop(&chk);
+    }
+    #[test]
     fn checksum_type() {
         let chk = ModSum::<u16>::with_options()
             .width(16)
@@ -272,4 +445,36 @@ mod tests {
             vec![(vec![20], vec![RelativeIndex::FromStart(35)])]
         );
     }
+    #[test]
+    fn segments_by() {
+        let chk = ModSum::<u16>::from_str("width=16 module=fff1").unwrap();
+        // each file has a record with its sum stored right after it, big endian
+        let files: Vec<Vec<u8>> = ["a short record", "a somewhat longer record"]
+            .iter()
+            .map(|record| {
+                let sum = chk.digest(record.as_bytes()).unwrap();
+                let mut file = b"header |".to_vec();
+                file.extend_from_slice(record.as_bytes());
+                file.extend_from_slice(&sum.to_be_bytes());
+                file.extend_from_slice(b"| trailer");
+                file
+            })
+            .collect();
+        let target = |i: usize, e: usize| {
+            let field = files[i].get(e + 1..e + 3)?;
+            Some(u16::from_be_bytes([field[0], field[1]]))
+        };
+        let segs = chk.find_segments_by(&files, &target, Relativity::End);
+        assert!(segs.contains(&(vec![8], vec![RelativeIndex::FromEnd(11)])));
+        for strategy in &[
+            PresumStrategy::External { run_len: 7 },
+            PresumStrategy::Hashed,
+        ] {
+            assert_eq!(
+                chk.find_segments_with(&files, &target, Relativity::End, *strategy)
+                    .unwrap(),
+                segs
+            );
+        }
+    }
 }
@@ -7,8 +7,14 @@
 //! * a different file with checksum
 //!
 //! Of course, giving more files will result in fewer false positives.
+//!
+//! For sums over words bigger than a byte, both byte orders are tried if `endian` is not given,
+//! and each result contains the byte order for which it fits.
+//! Similarly, if `signed` is not given, the words are also tried as signed numbers, as long as
+//! that makes a difference for the given files.
 use super::{ModSum, ModSumBuilder};
 use crate::checksum::{unresult_iter, CheckReverserError};
+use crate::endian::{Endian, WordSpec};
 use crate::factor::{divisors_range, gcd};
 use std::iter::Iterator;
 /// Find the parameters of a modsum algorithm.
@@ -26,13 +32,52 @@ pub fn reverse_modsum(
     chk_bytes: &[(&[u8], u128)],
     verbosity: u64,
 ) -> impl Iterator<Item = Result<ModSum<u64>, CheckReverserError>> {
-    let res = reverse(spec, chk_bytes, verbosity).map(|x| x.iter());
-    unresult_iter(res)
+    let endians = Endian::to_try(spec.endian, spec.wordsize);
+    let res: Vec<_> = endians
+        .into_iter()
+        .flat_map(|e| {
+            signedness(spec.signed, spec.wordsize, e, chk_bytes)
+                .into_iter()
+                .map(move |s| (e, s))
+        })
+        .map(|(e, s)| reverse(spec, chk_bytes, e, s, verbosity).map(|x| x.iter()))
+        .collect();
+    res.into_iter().flat_map(unresult_iter)
+}
+
+/// The signedness values to try for the given files.
+///
+/// If no word of the files is negative when read as a signed number, both
+/// variants give the same results, so only the unsigned one is tried.
+pub(crate) fn signedness(
+    signed: Option<bool>,
+    wordsize: Option<usize>,
+    endian: Endian,
+    chk_bytes: &[(&[u8], u128)],
+) -> Vec<bool> {
+    if let Some(s) = signed {
+        return vec![s];
+    }
+    let wordspec = match WordSpec::new(wordsize.unwrap_or(8), endian) {
+        Ok(w) => w,
+        // the error gets reported later on
+        Err(_) => return vec![false],
+    };
+    let has_negative = chk_bytes
+        .iter()
+        .any(|(f, _)| wordspec.iter_values(f, true).any(|x| x < 0));
+    if has_negative {
+        vec![false, true]
+    } else {
+        vec![false]
+    }
 }
 struct RevResult {
     modlist: Vec<u128>,
     init: i128,
     width: usize,
+    wordspec: WordSpec,
+    signed: bool,
 }
 
 impl RevResult {
@@ -42,6 +87,8 @@ impl RevResult {
             modlist,
             init,
             width,
+            wordspec,
+            signed,
         } = self;
         modlist.into_iter().map(move |module| {
             let init_negative = init < 0;
@@ -53,6 +100,9 @@ impl RevResult {
                 .width(width)
                 .module(module as u64)
                 .init(init as u64)
+                .wordsize(wordspec.wordsize)
+                .endian(wordspec.endian)
+                .signed(signed)
                 .build()
                 .unwrap()
         })
@@ -64,9 +114,12 @@ impl RevResult {
 // init mod m.
 // If we have two files, we can take their difference and have a number that is 0 mod m, which means m divides this number.
 // The solutions are then the divisors m in the appropiate range.
+// For sums over words, the same thing works with a, b, c, d being words, which may also be negative.
 fn reverse(
     spec: &ModSumBuilder<u64>,
     chk_bytes: &[(&[u8], u128)],
+    endian: Endian,
+    signed: bool,
     verbosity: u64,
 ) -> Result<RevResult, CheckReverserError> {
     let log = |s| {
@@ -78,14 +131,26 @@ fn reverse(
     let width = spec
         .width
         .ok_or(CheckReverserError::MissingParameter("width"))?;
+    let wordspec = WordSpec::new(spec.wordsize.unwrap_or(8), endian)
+        .map_err(|_| CheckReverserError::ValueOutOfRange("wordsize"))?;
     let mut sums = Vec::<i128>::new();
     let max_sum = 1u128 << width;
     let mut min_sum = 0;
-    log("summing files up");
+    if wordspec.wordsize > 8 {
+        log(match endian {
+            Endian::Little => "summing files up as little endian words",
+            Endian::Big => "summing files up as big endian words",
+        });
+    } else {
+        log("summing files up");
+    }
+    if signed {
+        log("interpreting words as signed");
+    }
     for (f, chk) in chk_bytes {
         min_sum = min_sum.max(*chk as u128);
         // here we calculate (init mod m)
-        sums.push(f.iter().copied().map(i128::from).sum::<i128>() - *chk as i128);
+        sums.push(wordspec.iter_values(f, signed).sum::<i128>() - *chk as i128);
     }
     let mut module = 0;
     log("removing inits");
@@ -103,6 +168,8 @@ fn reverse(
         modlist,
         init,
         width,
+        wordspec,
+        signed,
     })
 }
 
@@ -149,6 +216,13 @@ mod tests {
                 u64::arbitrary(g)
             };
             new_modsum.init(init);
+            new_modsum.wordsize(8 << (u8::arbitrary(g) % 3));
+            new_modsum.endian(if bool::arbitrary(g) {
+                Endian::Little
+            } else {
+                Endian::Big
+            });
+            new_modsum.signed(bool::arbitrary(g));
             new_modsum
         }
     }
@@ -156,16 +230,28 @@ mod tests {
     #[quickcheck]
     fn qc_modsum_rev(
         mut files: Vec<Vec<u8>>,
-        modsum_build: ModSumBuilder<u64>,
+        mut modsum_build: ModSumBuilder<u64>,
         known: (bool, bool),
     ) -> TestResult {
         files.sort_by(|a, b| a.len().cmp(&b.len()).then(a.cmp(&b)).reverse());
         if files.iter().zip(files.iter().skip(1)).any(|(a, b)| a == b) || files.len() <= 2 {
             return TestResult::discard();
         }
+        // without negative words, the unsigned variant is the one that gets reported
+        let file_refs: Vec<_> = files.iter().map(|f| (f.as_slice(), 0)).collect();
+        let signs = signedness(
+            None,
+            modsum_build.wordsize,
+            modsum_build.endian.unwrap(),
+            &file_refs,
+        );
+        if signs.len() == 1 {
+            modsum_build.signed(false);
+        }
         let modsum = modsum_build.build().unwrap();
         let mut naive = ModSum::<u64>::with_options();
         naive.width(modsum_build.width.unwrap());
+        naive.wordsize(modsum_build.wordsize.unwrap());
         if known.0 {
             naive.module(modsum_build.module.unwrap());
         }
@@ -202,6 +288,63 @@ mod tests {
         TestResult::from_bool(has_appeared)
     }
     #[test]
+    fn words() {
+        let modsum = ModSum::with_options()
+            .width(16)
+            .module(0xfff1)
+            .init(0x1234)
+            .wordsize(16)
+            .endian(Endian::Little)
+            .build()
+            .unwrap();
+        let f: Vec<Vec<u8>> = [41usize, 57, 100]
+            .iter()
+            .map(|&l| (0..l).map(|i| (i * 97 + l * 13) as u8).collect())
+            .collect();
+        let chk_files: Vec<_> = f
+            .iter()
+            .map(|f| {
+                let checksum = modsum.digest(f.as_slice()).unwrap() as u128;
+                (f.as_slice(), checksum)
+            })
+            .collect();
+        let mut naive = ModSum::<u64>::with_options();
+        naive.width(16).wordsize(16).module(0xfff1);
+        let m: Vec<_> = reverse_modsum(&naive, &chk_files, 0)
+            .filter_map(Result::ok)
+            .collect();
+        assert_eq!(m, vec![modsum]);
+    }
+    #[test]
+    fn signed() {
+        let modsum = ModSum::with_options()
+            .width(16)
+            .module(0xfff1)
+            .init(0x1234)
+            .wordsize(16)
+            .endian(Endian::Little)
+            .signed(true)
+            .build()
+            .unwrap();
+        let f: Vec<Vec<u8>> = [80usize, 181, 300]
+            .iter()
+            .map(|&l| (0..l).map(|i| (i * 101 + l * 7) as u8).collect())
+            .collect();
+        let chk_files: Vec<_> = f
+            .iter()
+            .map(|f| {
+                let checksum = modsum.digest(f.as_slice()).unwrap() as u128;
+                (f.as_slice(), checksum)
+            })
+            .collect();
+        let mut naive = ModSum::<u64>::with_options();
+        naive.width(16).wordsize(16);
+        let m: Vec<_> = reverse_modsum(&naive, &chk_files, 0)
+            .filter_map(Result::ok)
+            .collect();
+        assert!(m.contains(&modsum));
+    }
+    #[test]
     fn error1() {
         let modsum = ModSum::with_options()
             .width(38)
@@ -0,0 +1,226 @@
+//! Pearson hashing, an 8-bit hash which looks up the XOR of the hash and each byte in a table
+//!
+//! Since the table is an arbitrary permutation, the hash is not linear in any sense,
+//! so it only implements `Digest` and segments are found by trying all of them.
+//!
+//! There are a number of parameters:
+//! * width: The number of bits in the sum type, has to be 8
+//! * init: The initial number (default 0)
+//! * table: The 256 bytes of the table, as 512 hexadecimal digits (defaults to the table from RFC 3074)
+//! * check: The checksum of "123456789" (optional, gets checked at construction)
+//! * name: An optional name that gets used for display purposes
+use crate::bitnum::BitNum;
+use crate::checksum::{CheckBuilderErr, Digest};
+use crate::keyval::KeyValIter;
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// The table from RFC 3074, which is also the one from the example in Pearson's paper.
+const RFC3074_TABLE: [u8; 256] = [
+    251, 175, 119, 215, 81, 14, 79, 191, 103, 49, 181, 143, 186, 157, 0, 232, 31, 32, 55, 60, 152,
+    58, 17, 237, 174, 70, 160, 144, 220, 90, 57, 223, 59, 3, 18, 140, 111, 166, 203, 196, 134, 243,
+    124, 95, 222, 179, 197, 65, 180, 48, 36, 15, 107, 46, 233, 130, 165, 30, 123, 161, 209, 23, 97,
+    16, 40, 91, 219, 61, 100, 10, 210, 109, 250, 127, 22, 138, 29, 108, 244, 67, 207, 9, 178, 204,
+    74, 98, 126, 249, 167, 116, 34, 77, 193, 200, 121, 5, 20, 113, 71, 35, 128, 13, 182, 94, 25,
+    226, 227, 199, 75, 27, 41, 245, 230, 224, 43, 225, 177, 26, 155, 150, 212, 142, 218, 115, 241,
+    73, 88, 105, 39, 114, 62, 255, 192, 201, 145, 214, 168, 158, 221, 148, 154, 122, 12, 84, 82,
+    163, 44, 139, 228, 236, 205, 242, 217, 11, 187, 146, 159, 64, 86, 239, 195, 42, 106, 198, 118,
+    112, 184, 172, 87, 2, 173, 117, 176, 229, 247, 253, 137, 185, 99, 164, 102, 147, 45, 66, 231,
+    52, 141, 211, 194, 206, 246, 238, 56, 110, 78, 248, 63, 240, 189, 93, 92, 51, 53, 183, 19, 171,
+    72, 50, 33, 104, 101, 69, 8, 252, 83, 120, 76, 135, 85, 54, 202, 125, 188, 213, 96, 235, 136,
+    208, 162, 129, 190, 132, 156, 38, 47, 1, 7, 254, 24, 4, 216, 131, 89, 21, 28, 133, 37, 153,
+    149, 80, 170, 68, 6, 169, 234, 151,
+];
+
+/// Parses a table given as 512 hexadecimal digits.
+fn parse_table(s: &str) -> Option<Box<[u8; 256]>> {
+    let s = s.strip_prefix("0x").unwrap_or(s);
+    if s.len() != 512 || !s.is_ascii() {
+        return None;
+    }
+    let mut table = Box::new([0u8; 256]);
+    for (entry, digits) in table.iter_mut().zip(s.as_bytes().chunks(2)) {
+        *entry = u8::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()?;
+    }
+    Some(table)
+}
+
+/// A builder to set the various parameters for Pearson hashing.
+///
+/// Example:
+/// ```
+/// # use delsum_lib::checksum::pearson::Pearson;
+/// Pearson::with_options()
+///     .width(8)
+///     .check(0x13)
+///     .build()
+///     .is_ok();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PearsonBuilder {
+    width: Option<usize>,
+    init: Option<u8>,
+    table: Option<Box<[u8; 256]>>,
+    check: Option<u8>,
+    name: Option<String>,
+}
+
+impl PearsonBuilder {
+    /// The width, in bits, of the hash. Mandatory, and has to be 8.
+    pub fn width(&mut self, w: usize) -> &mut Self {
+        self.width = Some(w);
+        self
+    }
+    /// The initial value, optional, defaults to 0.
+    pub fn init(&mut self, i: u8) -> &mut Self {
+        self.init = Some(i);
+        self
+    }
+    /// The lookup table, optional, defaults to the table from RFC 3074.
+    pub fn table(&mut self, t: [u8; 256]) -> &mut Self {
+        self.table = Some(Box::new(t));
+        self
+    }
+    /// The checksum of "123456789", gets checked on creation.
+    pub fn check(&mut self, c: u8) -> &mut Self {
+        self.check = Some(c);
+        self
+    }
+    /// An optional name that gets used for display purposes.
+    pub fn name(&mut self, n: &str) -> &mut Self {
+        self.name = Some(String::from(n));
+        self
+    }
+    /// Builds the algorithm, after validating the parameters.
+    pub fn build(&self) -> Result<Pearson, CheckBuilderErr> {
+        let width = self
+            .width
+            .ok_or(CheckBuilderErr::MissingParameter("width"))?;
+        if width != 8 {
+            return Err(CheckBuilderErr::ValueOutOfRange("width"));
+        }
+        let s = Pearson {
+            init: self.init.unwrap_or(0),
+            table: self
+                .table
+                .clone()
+                .unwrap_or_else(|| Box::new(RFC3074_TABLE)),
+            name: self.name.clone(),
+        };
+        match self.check {
+            Some(c) => {
+                if s.digest(&b"123456789"[..]).unwrap() == c {
+                    Ok(s)
+                } else {
+                    Err(CheckBuilderErr::CheckFail)
+                }
+            }
+            None => Ok(s),
+        }
+    }
+}
+
+/// The Pearson hash algorithm.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Pearson {
+    init: u8,
+    table: Box<[u8; 256]>,
+    name: Option<String>,
+}
+
+impl Pearson {
+    /// Creates a `PearsonBuilder`, for more information see its documentation.
+    pub fn with_options() -> PearsonBuilder {
+        PearsonBuilder::default()
+    }
+}
+
+impl Display for Pearson {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.name {
+            Some(n) => write!(f, "{}", n),
+            None => {
+                write!(f, "pearson width=8 init={:#x}", self.init)?;
+                if *self.table != RFC3074_TABLE {
+                    write!(f, " table=")?;
+                    for entry in self.table.iter() {
+                        write!(f, "{:02x}", entry)?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl FromStr for PearsonBuilder {
+    /// See FromStr for Pearson
+    fn from_str(s: &str) -> Result<PearsonBuilder, CheckBuilderErr> {
+        let mut pearson = Pearson::with_options();
+        for x in KeyValIter::new(s) {
+            let (current_key, current_val) = match x {
+                Err(key) => return Err(CheckBuilderErr::MalformedString(key)),
+                Ok(s) => s,
+            };
+            let pearson_op = match current_key.as_str() {
+                "width" => usize::from_str(&current_val).ok().map(|x| pearson.width(x)),
+                "init" => u8::from_hex(&current_val).ok().map(|x| pearson.init(x)),
+                "table" => parse_table(&current_val).map(|x| pearson.table(*x)),
+                "check" => u8::from_hex(&current_val).ok().map(|x| pearson.check(x)),
+                "name" => Some(pearson.name(&current_val)),
+                _ => return Err(CheckBuilderErr::UnknownKey(current_key)),
+            };
+            match pearson_op {
+                Some(p) => pearson = p.clone(),
+                None => return Err(CheckBuilderErr::MalformedString(current_key)),
+            }
+        }
+        Ok(pearson)
+    }
+    type Err = CheckBuilderErr;
+}
+
+impl FromStr for Pearson {
+    /// Construct a new Pearson hash from a string specification.
+    ///
+    /// Example:
+    ///
+    /// width=8 init=0
+    fn from_str(s: &str) -> Result<Pearson, CheckBuilderErr> {
+        PearsonBuilder::from_str(s)?.build()
+    }
+    type Err = CheckBuilderErr;
+}
+
+impl Digest for Pearson {
+    type Sum = u8;
+    fn init(&self) -> Self::Sum {
+        self.init
+    }
+    fn dig_byte(&self, sum: Self::Sum, byte: u8) -> Self::Sum {
+        self.table[usize::from(sum ^ byte)]
+    }
+    fn finalize(&self, sum: Self::Sum) -> Self::Sum {
+        sum
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn tables() {
+        let chk = Pearson::from_str("width=8").unwrap();
+        assert_eq!(chk.digest(&b"abc"[..]).unwrap(), 0x05);
+        // the identity table makes the hash a xor of the bytes
+        let identity: String = (0..=255u8).map(|x| format!("{:02x}", x)).collect();
+        let spec = format!("width=8 init=5a table={}", identity);
+        let chk = Pearson::from_str(&spec).unwrap();
+        assert_eq!(chk.digest(&[0x12u8, 0x34][..]).unwrap(), 0x5a ^ 0x12 ^ 0x34);
+        assert_eq!(
+            chk.to_string(),
+            format!("pearson width=8 init=0x5a table={}", identity)
+        );
+        assert!(Pearson::from_str("width=8 table=00").is_err());
+    }
+}
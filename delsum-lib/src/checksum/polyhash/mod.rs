@@ -0,0 +1,348 @@
+//! A multiplicative hash over bytes (i.e. `hash = hash * mult + byte` for every byte, modulo some number)
+//!
+//! This covers, for example, the Java `String.hashCode` (with `mult=1f`), the additive DJB2 hash
+//! (with `mult=21 init=1505`) and the sdbm hash (with `mult=1003f`).
+//!
+//! There are a number of parameters:
+//! * width: The number of bits in the sum type, at most 64
+//! * mult: The number by which the hash gets multiplied before each byte
+//! * module: The hash is taken modulo this number (0 meaning `2^width`, the default)
+//! * init: The initial number
+//! * addout: The number that gets added at the end
+//! * check: The checksum of "123456789" (optional, gets checked at construction)
+//! * name: An optional name that gets used for display purposes
+//!
+//! Note that finding segments with a given checksum only works correctly if `mult` and `module` are coprime.
+pub mod rev;
+use crate::bitnum::Modnum;
+use crate::checksum::transform::truncated_module;
+use crate::checksum::{CheckBuilderErr, Digest, LinearCheck};
+use crate::keyval::KeyValIter;
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// A builder to set the various parameters for the polyhash algorithm.
+///
+/// Example:
+/// ```
+/// # use delsum_lib::checksum::polyhash::PolyHash;
+/// PolyHash::<u32>::with_options()
+///     .width(32)
+///     .mult(31)
+///     .check(0x90b21035)
+///     .build()
+///     .is_ok();
+/// ```
+/// Note that module = 0 is assumed to be 2^width
+#[derive(Debug, Clone)]
+pub struct PolyHashBuilder<S: Modnum> {
+    width: Option<usize>,
+    mult: Option<S>,
+    module: Option<S>,
+    init: Option<S>,
+    addout: Option<S>,
+    check: Option<S>,
+    name: Option<String>,
+}
+
+impl<S: Modnum> PolyHashBuilder<S> {
+    /// The total width, in bits, of the hash. Mandatory.
+    pub fn width(&mut self, w: usize) -> &mut Self {
+        self.width = Some(w);
+        self
+    }
+    /// The factor by which the hash gets multiplied before each byte. Mandatory.
+    pub fn mult(&mut self, m: S) -> &mut Self {
+        self.mult = Some(m);
+        self
+    }
+    /// The number by which the remainder is taken, optional, defaults to 0.
+    ///
+    /// If this is 0, it is equivalent to be `2^width`.
+    pub fn module(&mut self, m: S) -> &mut Self {
+        self.module = Some(m);
+        self
+    }
+    /// The initial value, optional, defaults to 0.
+    pub fn init(&mut self, i: S) -> &mut Self {
+        self.init = Some(i);
+        self
+    }
+    /// The value that gets added at the end, optional, defaults to 0.
+    pub fn addout(&mut self, a: S) -> &mut Self {
+        self.addout = Some(a);
+        self
+    }
+    /// The checksum of "123456789", gets checked on creation.
+    pub fn check(&mut self, c: S) -> &mut Self {
+        self.check = Some(c);
+        self
+    }
+    /// An optional name that gets used for display purposes.
+    pub fn name(&mut self, n: &str) -> &mut Self {
+        self.name = Some(String::from(n));
+        self
+    }
+    /// Builds the algorithm, after validating the parameters.
+    pub fn build(&self) -> Result<PolyHash<S>, CheckBuilderErr> {
+        let width = self
+            .width
+            .ok_or(CheckBuilderErr::MissingParameter("width"))?;
+        if width == 0 || width > S::zero().bits() {
+            return Err(CheckBuilderErr::ValueOutOfRange("width"));
+        }
+        let mut module = self.module.unwrap_or_else(S::zero);
+        if module == S::zero() && width < module.bits() {
+            module = S::one() << width
+        };
+        let reduce = |x: S| {
+            if module == S::zero() {
+                x
+            } else {
+                x % module
+            }
+        };
+        let mult = reduce(self.mult.ok_or(CheckBuilderErr::MissingParameter("mult"))?);
+        let init = reduce(self.init.unwrap_or_else(S::zero));
+        let addout = reduce(self.addout.unwrap_or_else(S::zero));
+        let s = PolyHash {
+            width,
+            mult,
+            module,
+            init,
+            addout,
+            name: self.name.clone(),
+        };
+        match self.check {
+            Some(c) => {
+                if s.digest(&b"123456789"[..]).unwrap() == c {
+                    Ok(s)
+                } else {
+                    Err(CheckBuilderErr::CheckFail)
+                }
+            }
+            None => Ok(s),
+        }
+    }
+    /// The same partially known algorithm for the lowest `bits` bits of the hashes (see `LinearCheck::truncated`),
+    /// or `None` if the given module lets the higher bits influence the lower ones.
+    ///
+    /// If the module is not given, only modules up to `2^bits` can be found with it.
+    pub(crate) fn truncated(&self, bits: usize) -> Option<Self> {
+        let mask = if bits < S::zero().bits() {
+            (S::one() << bits) - S::one()
+        } else {
+            !S::zero()
+        };
+        let module = match self.module {
+            Some(m) => Some(truncated_module(m, bits)?),
+            None => None,
+        };
+        let reduce = |x: S| match module {
+            Some(m) if m != S::zero() => x % m,
+            _ => x & mask,
+        };
+        Some(PolyHashBuilder {
+            width: Some(bits),
+            mult: self.mult.map(reduce),
+            module,
+            init: self.init.map(reduce),
+            addout: self.addout.map(reduce),
+            check: self.check.map(|c| c & mask),
+            name: None,
+        })
+    }
+}
+
+/// A PolyHash checksum algorithm.
+///
+/// Implements LinearCheck so that finding checksummed locations in a file is efficiently possible.
+#[derive(Debug, PartialEq, Eq)]
+pub struct PolyHash<S: Modnum> {
+    width: usize,
+    mult: S,
+    module: S,
+    init: S,
+    addout: S,
+    name: Option<String>,
+}
+
+impl<S: Modnum> PolyHash<S> {
+    /// Creates a `PolyHashBuilder`, for more information see its documentation.
+    pub fn with_options() -> PolyHashBuilder<S> {
+        PolyHashBuilder {
+            width: None,
+            mult: None,
+            module: None,
+            init: None,
+            addout: None,
+            check: None,
+            name: None,
+        }
+    }
+}
+
+impl<Sum: Modnum> Display for PolyHash<Sum> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.name {
+            Some(n) => write!(f, "{}", n),
+            None => write!(
+                f,
+                "polyhash width={} mult={:#x} module={:#x} init={:#x} addout={:#x}",
+                self.width, self.mult, self.module, self.init, self.addout
+            ),
+        }
+    }
+}
+
+impl<Sum: Modnum> FromStr for PolyHashBuilder<Sum> {
+    /// See FromStr for PolyHash<Sum>
+    fn from_str(s: &str) -> Result<PolyHashBuilder<Sum>, CheckBuilderErr> {
+        let mut sum = PolyHash::<Sum>::with_options();
+        for x in KeyValIter::new(s) {
+            let (current_key, current_val) = match x {
+                Err(key) => return Err(CheckBuilderErr::MalformedString(key)),
+                Ok(s) => s,
+            };
+            let hash_op = match current_key.as_str() {
+                "width" => usize::from_str(&current_val).ok().map(|x| sum.width(x)),
+                "mult" => Sum::from_hex(&current_val).ok().map(|x| sum.mult(x)),
+                "module" => Sum::from_hex(&current_val).ok().map(|x| sum.module(x)),
+                "init" => Sum::from_hex(&current_val).ok().map(|x| sum.init(x)),
+                "addout" => Sum::from_hex(&current_val).ok().map(|x| sum.addout(x)),
+                "check" => Sum::from_hex(&current_val).ok().map(|x| sum.check(x)),
+                "name" => Some(sum.name(&current_val)),
+                _ => return Err(CheckBuilderErr::UnknownKey(current_key)),
+            };
+            match hash_op {
+                Some(c) => sum = c.clone(),
+                None => return Err(CheckBuilderErr::MalformedString(current_key)),
+            }
+        }
+        Ok(sum)
+    }
+    type Err = CheckBuilderErr;
+}
+
+impl<Sum: Modnum> FromStr for PolyHash<Sum> {
+    /// Construct a new polynomial hash from a string specification.
+    ///
+    /// Example:
+    ///
+    /// width=32 mult=1f module=0 init=0 addout=0
+    fn from_str(s: &str) -> Result<PolyHash<Sum>, CheckBuilderErr> {
+        PolyHashBuilder::from_str(s)?.build()
+    }
+    type Err = CheckBuilderErr;
+}
+
+impl<S: Modnum> Digest for PolyHash<S> {
+    type Sum = S;
+    fn init(&self) -> Self::Sum {
+        self.init
+    }
+    fn dig_byte(&self, sum: Self::Sum, byte: u8) -> Self::Sum {
+        sum.mul_mod(&self.mult, &self.module)
+            .add_mod(&S::from(byte), &self.module)
+    }
+    fn finalize(&self, sum: Self::Sum) -> Self::Sum {
+        sum.add_mod(&self.addout, &self.module)
+    }
+}
+
+impl<S: Modnum> LinearCheck for PolyHash<S> {
+    // the shift is a power of mult
+    type Shift = S;
+    fn init_shift(&self) -> Self::Shift {
+        S::one()
+    }
+    fn inc_shift(&self, shift: Self::Shift) -> Self::Shift {
+        shift.mul_mod(&self.mult, &self.module)
+    }
+    fn shift(&self, sum: Self::Sum, shift: &Self::Shift) -> Self::Sum {
+        sum.mul_mod(shift, &self.module)
+    }
+    fn shift_n(&self, mut n: usize) -> Self::Shift {
+        // square and multiply
+        let mut power = self.mult;
+        let mut shift = self.init_shift();
+        while n > 0 {
+            if n & 1 == 1 {
+                shift = shift.mul_mod(&power, &self.module);
+            }
+            power = power.mul_mod(&power, &self.module);
+            n >>= 1;
+        }
+        shift
+    }
+    fn add(&self, sum_a: Self::Sum, sum_b: &Self::Sum) -> Self::Sum {
+        sum_a.add_mod(sum_b, &self.module)
+    }
+    fn negate(&self, sum: Self::Sum) -> Self::Sum {
+        if sum == S::zero() {
+            sum
+        } else if self.module == S::zero() {
+            !sum + S::one()
+        } else {
+            self.module - sum
+        }
+    }
+    fn truncated(&self, bits: usize) -> Option<Self> {
+        let module = truncated_module(self.module, bits)?;
+        let reduce = |x: S| {
+            if module == S::zero() {
+                x
+            } else {
+                x % module
+            }
+        };
+        Some(PolyHash {
+            width: bits,
+            mult: reduce(self.mult),
+            module,
+            init: reduce(self.init),
+            addout: reduce(self.addout),
+            name: None,
+        })
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::tests::{test_find, test_prop, test_shifts};
+    #[test]
+    fn java() {
+        let s = PolyHash::<u32>::with_options()
+            .width(32)
+            .mult(31)
+            .check(0x90b21035)
+            .build()
+            .unwrap();
+        assert_eq!(s.digest(&b"hello"[..]).unwrap(), 0x5e918d2);
+        test_shifts(&s);
+        test_find(&s);
+        test_prop(&s);
+    }
+    #[test]
+    fn djb2() {
+        let s = PolyHash::<u32>::from_str("width=32 mult=21 init=1505 check=35cdbb82").unwrap();
+        assert_eq!(s.digest(&b"hello"[..]).unwrap(), 0xf923099);
+        assert_eq!(
+            s.to_string(),
+            "polyhash width=32 mult=0x21 module=0x0 init=0x1505 addout=0x0"
+        );
+        test_shifts(&s);
+        test_prop(&s);
+    }
+    #[test]
+    fn prime_module() {
+        let s =
+            PolyHash::<u16>::from_str("width=16 mult=100 module=fff1 init=1 addout=20").unwrap();
+        test_shifts(&s);
+        test_find(&s);
+        test_prop(&s);
+        let s = PolyHash::<u8>::from_str("width=7 mult=3 init=5").unwrap();
+        test_shifts(&s);
+        test_prop(&s);
+    }
+}
@@ -0,0 +1,593 @@
+//! This module contains the function(s) for reversing the parameters for a polyhash algorithm.
+//!
+//! There are different methods depending on what is known:
+//! * If `module` is a power of two (including the default `0`), the parameters are found bit by bit,
+//!   since the lowest `k` bits of the hash only depend on the lowest `k` bits of the parameters.
+//!   Here, `mult` can be unknown.
+//! * If `module` is something else, `init` and `addout` are found by solving linear congruences.
+//!   If `mult` is not given, every value of it below the module is tried, with `init` and `addout`
+//!   eliminated so that most of them are ruled out quickly. This is only done for modules up to `2^24`,
+//!   above that `mult` has to be given.
+//! * If `module` is not given, but `mult` is, the hashes are calculated without reduction and the
+//!   possible modules are the divisors of their greatest common divisor, similar to `modsum`.
+//!   Since the numbers get very big, this only works with small files.
+//!   If neither is given, `module` is assumed to be `2^width`, so other modules have to be given.
+//!
+//! If `init` is unknown, it is neccessary to know checksums of files with different lengths.
+//! If all files have the same length, `init` is set to 0.
+//! Parameters which result in the same hash for every file (like `init=0 addout=0` and `init=80000000 addout=80000000`
+//! for `width=32` and odd `mult`) are only output once, with the smallest `init`.
+use super::{PolyHash, PolyHashBuilder};
+use crate::checksum::{unresult_iter, CheckReverserError};
+use crate::factor::{divisors_range, gcd, mod_red, xgcd};
+use num_bigint::BigInt;
+use num_traits::{one, zero, One, Zero};
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::iter::Iterator;
+
+// the number of solutions (or intermediate solutions) after which we give up
+const MAX_SOLUTIONS: usize = 0x1000;
+
+// the largest module (which is not a power of two) for which every mult is tried if it is not given
+const MAX_MULT_SEARCH: u64 = 1 << 24;
+
+/// Find the parameters of a polyhash algorithm.
+///
+/// `spec` contains the known parameters of the algorithm (by setting the corresponding values in the builder).
+/// `chk_bytes` are pairs of files and their checksums.
+/// `verbosity` makes the function output what it is doing
+///
+/// The `width` parameter of the builder has to be set.
+pub fn reverse_polyhash(
+    spec: &PolyHashBuilder<u64>,
+    chk_bytes: &[(&[u8], u128)],
+    verbosity: u64,
+) -> impl Iterator<Item = Result<PolyHash<u64>, CheckReverserError>> {
+    unresult_iter(reverse(spec, chk_bytes, verbosity).map(|x| x.into_iter()))
+}
+
+fn reverse(
+    spec: &PolyHashBuilder<u64>,
+    chk_bytes: &[(&[u8], u128)],
+    verbosity: u64,
+) -> Result<Vec<PolyHash<u64>>, CheckReverserError> {
+    let log = |s: &str| {
+        if verbosity > 0 {
+            eprintln!("<polyhash> {}", s);
+        }
+    };
+    let width = spec
+        .width
+        .ok_or(CheckReverserError::MissingParameter("width"))?;
+    if width == 0 || width > 64 {
+        return Err(CheckReverserError::ValueOutOfRange("width"));
+    }
+    let mut spec = spec.clone();
+    let same_len = chk_bytes.windows(2).all(|x| x[0].0.len() == x[1].0.len());
+    if spec.init.is_none() && spec.addout.is_none() && same_len {
+        log("all files have the same length, setting init to 0");
+        spec.init = Some(0);
+    }
+    let files: Vec<_> = chk_bytes.iter().map(|(f, c)| (*f, *c as u64)).collect();
+    let solutions = match (spec.module, spec.mult) {
+        (Some(0), _) | (None, None) => {
+            if spec.module.is_none() {
+                log("neither module nor mult given, assuming a module of 2^width");
+            }
+            log("finding parameters bit by bit");
+            lift(&spec, width, &files)?
+                .into_iter()
+                .map(|(m, i, a)| (0, m, i, a))
+                .collect()
+        }
+        (Some(module), _) if module.is_power_of_two() => {
+            log("finding parameters bit by bit");
+            let bits = module.trailing_zeros() as usize;
+            if bits > width {
+                return Err(CheckReverserError::ValueOutOfRange("module"));
+            }
+            lift(&spec, bits, &files)?
+                .into_iter()
+                .map(|(m, i, a)| (module, m, i, a))
+                .collect()
+        }
+        (Some(module), None) => {
+            if module > MAX_MULT_SEARCH {
+                return Err(CheckReverserError::MissingParameter(
+                    "mult (for modules above 2^24 which are not a power of two)",
+                ));
+            }
+            log("trying every mult");
+            let mults = mult_candidates(&spec, module, &files);
+            if mults.len() > MAX_SOLUTIONS {
+                return Err(too_many());
+            }
+            log("solving for init and addout");
+            let mut solutions = Vec::new();
+            for mult in mults {
+                solutions.extend(
+                    solve_linear(&spec, module, mult, &files)?
+                        .into_iter()
+                        .map(|(i, a)| (module, mult, i, a)),
+                );
+            }
+            solutions
+        }
+        (Some(module), Some(mult)) => {
+            log("solving for init and addout");
+            solve_linear(&spec, module, mult, &files)?
+                .into_iter()
+                .map(|(i, a)| (module, mult, i, a))
+                .collect()
+        }
+        (None, Some(mult)) => {
+            log("calculating unreduced hashes");
+            let module = find_module(&spec, mult, &files)?;
+            log("finding all possible factors");
+            let max_chk = files.iter().map(|(_, c)| *c as u128).max().unwrap_or(0);
+            let mut solutions = Vec::new();
+            for module in divisors_range(module, max_chk + 1, 1u128 << width) {
+                // the module 2^64 can't be represented, but is equivalent to 0
+                let module = u64::try_from(module).unwrap_or(0);
+                solutions.extend(
+                    solve_linear(&spec, module, mult, &files)?
+                        .into_iter()
+                        .map(|(i, a)| (module, mult, i, a)),
+                );
+            }
+            solutions
+        }
+    };
+    Ok(solutions
+        .into_iter()
+        .filter_map(|(module, mult, init, addout)| {
+            PolyHash::with_options()
+                .width(width)
+                .module(module)
+                .mult(mult)
+                .init(init)
+                .addout(addout)
+                .build()
+                .ok()
+        })
+        .collect())
+}
+
+// Since the lowest k bits of the hash modulo 2^n only depend on the lowest k bits of the parameters,
+// we can extend all solutions modulo 2^k to solutions modulo 2^(k+1) by trying both possible values
+// of the next bit of each unknown parameter.
+fn lift(
+    spec: &PolyHashBuilder<u64>,
+    bits: usize,
+    files: &[(&[u8], u64)],
+) -> Result<Vec<(u64, u64, u64)>, CheckReverserError> {
+    let mut candidates = vec![(0u64, 0u64, 0u64)];
+    for j in 0..bits {
+        let bit = 1u64 << j;
+        let mask = u64::MAX >> (63 - j);
+        let choices = |known: Option<u64>| match known {
+            Some(x) => vec![x & bit],
+            None => vec![0, bit],
+        };
+        // for each multiplier, the power of mult and the hash with init = addout = 0 are cached
+        let mut hashes = HashMap::new();
+        let mut new_candidates = Vec::new();
+        for (mult, init, addout) in candidates {
+            for mult_bit in choices(spec.mult) {
+                let mult = mult | mult_bit;
+                let file_hashes = hashes.entry(mult).or_insert_with(|| {
+                    files
+                        .iter()
+                        .map(|(f, _)| wrapping_hash(mult, f))
+                        .collect::<Vec<_>>()
+                });
+                for init_bit in choices(spec.init) {
+                    for addout_bit in choices(spec.addout) {
+                        let (init, addout) = (init | init_bit, addout | addout_bit);
+                        let fits = files.iter().zip(file_hashes.iter()).all(|((_, chk), h)| {
+                            let hash = init.wrapping_mul(h.0).wrapping_add(h.1);
+                            (hash.wrapping_add(addout) ^ chk) & mask == 0
+                        });
+                        if fits {
+                            new_candidates.push((mult, init, addout));
+                        }
+                    }
+                }
+            }
+        }
+        if new_candidates.len() > MAX_SOLUTIONS {
+            return Err(too_many());
+        }
+        candidates = new_candidates;
+    }
+    let module = if bits == 64 { 0 } else { 1 << bits };
+    Ok(remove_equivalent(candidates, module, !files.is_empty()))
+}
+
+// returns mult^len and the hash with init = addout = 0, modulo 2^64
+fn wrapping_hash(mult: u64, file: &[u8]) -> (u64, u64) {
+    file.iter().fold((1u64, 0u64), |(power, hash), byte| {
+        (
+            power.wrapping_mul(mult),
+            hash.wrapping_mul(mult).wrapping_add(u64::from(*byte)),
+        )
+    })
+}
+
+// With a known mult, each file gives an equation
+//      init * mult^len + hash + addout ≡ checksum (mod module)
+// where hash is the hash of the file with init = addout = 0.
+fn solve_linear(
+    spec: &PolyHashBuilder<u64>,
+    module: u64,
+    mult: u64,
+    files: &[(&[u8], u64)],
+) -> Result<Vec<(u64, u64)>, CheckReverserError> {
+    let bmodule = if module == 0 {
+        BigInt::one() << 64
+    } else {
+        BigInt::from(module)
+    };
+    let mult = BigInt::from(mult);
+    // pairs of (mult^len, checksum - hash)
+    let equations: Vec<_> = files
+        .iter()
+        .map(|(f, chk)| {
+            let (power, hash) = f
+                .iter()
+                .fold((one(), zero()), |(p, h): (BigInt, BigInt), b| {
+                    (
+                        mod_red(&(p * &mult), &bmodule),
+                        mod_red(&(h * &mult + BigInt::from(*b)), &bmodule),
+                    )
+                });
+            (power, mod_red(&(BigInt::from(*chk) - hash), &bmodule))
+        })
+        .collect();
+    // the set of possible inits, as (init mod step, step)
+    let mut inits = match spec.init {
+        Some(i) => (mod_red(&BigInt::from(i), &bmodule), bmodule.clone()),
+        None => (zero(), one()),
+    };
+    let congruences: Vec<_> = match (spec.addout, equations.first()) {
+        (Some(addout), _) => equations
+            .iter()
+            .map(|(p, d)| (p.clone(), d - BigInt::from(addout)))
+            .collect(),
+        // if addout is not known, it is eliminated by taking the difference to the first file
+        (None, Some((p0, d0))) => equations
+            .iter()
+            .skip(1)
+            .map(|(p, d)| (p - p0, d - d0))
+            .collect(),
+        (None, None) => Vec::new(),
+    };
+    for (a, b) in congruences {
+        let solution = solve_congruence(&a, &b, &bmodule).and_then(|x| intersect(&inits, &x));
+        inits = match solution {
+            Some(x) => x,
+            None => return Ok(Vec::new()),
+        };
+    }
+    let count = &bmodule / &inits.1;
+    if count > BigInt::from(MAX_SOLUTIONS) {
+        return Err(too_many());
+    }
+    let mut solutions = Vec::new();
+    let mut init = inits.0.clone();
+    while init < bmodule {
+        let addout = match (spec.addout, equations.first()) {
+            (Some(addout), _) => BigInt::from(addout),
+            (None, Some((p0, d0))) => mod_red(&(d0 - &init * p0), &bmodule),
+            (None, None) => zero(),
+        };
+        let to_u64 = |x: &BigInt| u64::try_from(mod_red(x, &bmodule)).unwrap();
+        solutions.push((to_u64(&init), to_u64(&addout)));
+        init += &inits.1;
+    }
+    let mult = u64::try_from(mult).unwrap();
+    Ok(remove_equivalent(
+        solutions.into_iter().map(|(i, a)| (mult, i, a)).collect(),
+        module,
+        !files.is_empty(),
+    )
+    .into_iter()
+    .map(|(_, i, a)| (i, a))
+    .collect())
+}
+
+// Without a given mult, every value below the module is tried. For each file, we have
+//      init * mult^len ≡ checksum - hash - addout (mod module)
+// and if addout is unknown, it is eliminated by taking the difference to the first file.
+// Each of these congruences has the form init * x ≡ y, so if init is unknown too, all of them
+// have to agree on it, which means that x_j * y_k ≡ x_k * y_j for every pair.
+// These conditions are only neccessary, so the remaining candidates still have to be solved.
+fn mult_candidates(spec: &PolyHashBuilder<u64>, module: u64, files: &[(&[u8], u64)]) -> Vec<u64> {
+    // since the module is at most 2^24, the products fit into a u64
+    let m = module;
+    // the shortest files first, since most candidates are ruled out after the first few
+    let mut files = files.to_vec();
+    files.sort_by_key(|(f, _)| f.len());
+    let addout = spec.addout.unwrap_or(0) % m;
+    let init = spec.init.map(|i| i % m);
+    // (mult^len, checksum - hash - addout) for a file
+    let equation = |mult: u64, (f, chk): &(&[u8], u64)| {
+        let (power, hash) = f.iter().fold((1 % m, 0), |(p, h), b| {
+            (p * mult % m, (h * mult + u64::from(*b)) % m)
+        });
+        (power, (chk % m + 2 * m - hash - addout) % m)
+    };
+    (0..m)
+        .filter(|&mult| {
+            let mut equations = files.iter().map(|file| equation(mult, file));
+            let first = match spec.addout {
+                Some(_) => None,
+                None => equations.next(),
+            };
+            let mut equations = equations.map(|(p, d)| match first {
+                Some((p0, d0)) => ((p + m - p0) % m, (d + m - d0) % m),
+                None => (p, d),
+            });
+            match init {
+                Some(init) => equations.all(|(x, y)| init * x % m == y),
+                None => match equations.next() {
+                    Some((px, py)) => equations.all(|(x, y)| px * y % m == x * py % m),
+                    None => true,
+                },
+            }
+        })
+        .collect()
+}
+
+// finds the solutions of a*x ≡ b (mod module) as x ≡ r (mod step)
+fn solve_congruence(a: &BigInt, b: &BigInt, module: &BigInt) -> Option<(BigInt, BigInt)> {
+    let a = mod_red(a, module);
+    let b = mod_red(b, module);
+    let (common, _) = xgcd(&a, module);
+    if !(&b % &common).is_zero() {
+        return None;
+    }
+    let step = module / &common;
+    let (_, (a_inv, _)) = xgcd(&(&a / &common), &step);
+    Some((mod_red(&(b / &common * a_inv), &step), step))
+}
+
+// intersects x ≡ r1 (mod s1) and x ≡ r2 (mod s2) using the chinese remainder theorem
+fn intersect(x: &(BigInt, BigInt), y: &(BigInt, BigInt)) -> Option<(BigInt, BigInt)> {
+    let ((r1, s1), (r2, s2)) = (x, y);
+    let (common, _) = xgcd(s1, s2);
+    let diff = r2 - r1;
+    if !(&diff % &common).is_zero() {
+        return None;
+    }
+    let s2_red = s2 / &common;
+    let (_, (s1_inv, _)) = xgcd(&(s1 / &common), &s2_red);
+    let t = mod_red(&(diff / &common * s1_inv), &s2_red);
+    let step = s1 * &s2_red;
+    Some((mod_red(&(r1 + s1 * t), &step), step))
+}
+
+// If the module is not known, we calculate the hashes without reducing, and from
+//      init * mult^len + hash + addout - checksum ≡ 0 (mod module)
+// we eliminate the unknowns of init and addout to get multiples of the module
+fn find_module(
+    spec: &PolyHashBuilder<u64>,
+    mult: u64,
+    files: &[(&[u8], u64)],
+) -> Result<u128, CheckReverserError> {
+    let mult = BigInt::from(mult);
+    // pairs of (mult^len, hash - checksum)
+    let sums: Vec<_> = files
+        .iter()
+        .map(|(f, chk)| {
+            let (power, hash) = f
+                .iter()
+                .fold((one(), zero()), |(p, h): (BigInt, BigInt), b| {
+                    (p * &mult, h * &mult + BigInt::from(*b))
+                });
+            (power, hash - BigInt::from(*chk))
+        })
+        .collect();
+    let big_gcd = |a: &BigInt, b: &BigInt| xgcd(a, b).0;
+    let mut module: BigInt = zero();
+    match (spec.init, spec.addout) {
+        (Some(init), Some(addout)) => {
+            let (init, addout) = (BigInt::from(init), BigInt::from(addout));
+            for (p, e) in &sums {
+                module = big_gcd(&module, &(&init * p + e + &addout));
+            }
+        }
+        (Some(init), None) => {
+            let init = BigInt::from(init);
+            let values: Vec<_> = sums.iter().map(|(p, e)| &init * p + e).collect();
+            for v in values.iter().skip(1) {
+                module = big_gcd(&module, &(v - &values[0]));
+            }
+        }
+        (None, Some(addout)) => {
+            let addout = BigInt::from(addout);
+            let values: Vec<_> = sums.iter().map(|(p, e)| (p, e + &addout)).collect();
+            let (p0, e0) = &values[0];
+            for (p, e) in values.iter().skip(1) {
+                let g = big_gcd(p0, p);
+                module = big_gcd(&module, &(e * (*p0 / &g) - e0 * (*p / &g)));
+            }
+        }
+        (None, None) => {
+            // differences to the first file, which eliminate addout
+            let diffs: Vec<_> = sums
+                .iter()
+                .skip(1)
+                .map(|(p, e)| (p - &sums[0].0, e - &sums[0].1))
+                .collect();
+            let pivot = diffs.iter().find(|(p, _)| !p.is_zero());
+            for (p, e) in &diffs {
+                module = match pivot {
+                    // eliminate init using the pivot file; the powers usually share
+                    // large factors, which can be divided out of both congruences
+                    Some((pp, pe)) => {
+                        let g = big_gcd(pp, p);
+                        big_gcd(&module, &(e * (pp / &g) - pe * (p / &g)))
+                    }
+                    None => big_gcd(&module, e),
+                };
+            }
+        }
+    }
+    if module.is_zero() {
+        return Err(CheckReverserError::UnsuitableFiles(
+            "too short or too similar",
+        ));
+    }
+    u128::try_from(module).map_err(|_| CheckReverserError::UnsuitableFiles("not enough files"))
+}
+
+// Removes parameters which give the same result for every file as another parameter set
+// with a smaller init. For the same mult, this is the case exactly if
+//      (init_a - init_b) * (mult - 1) ≡ 0 (mod module)
+// given that both parameter sets agree on at least one file, meaning that the
+// inits are equal modulo module / gcd(mult - 1, module).
+fn remove_equivalent(
+    mut solutions: Vec<(u64, u64, u64)>,
+    module: u64,
+    has_files: bool,
+) -> Vec<(u64, u64, u64)> {
+    if !has_files {
+        return solutions;
+    }
+    let module = if module == 0 {
+        1u128 << 64
+    } else {
+        module as u128
+    };
+    solutions.sort_unstable();
+    let mut seen = HashSet::new();
+    solutions
+        .into_iter()
+        .filter(|(mult, init, _)| {
+            let period = module / gcd((*mult as u128 + module - 1) % module, module);
+            seen.insert((*mult, *init as u128 % period))
+        })
+        .collect()
+}
+
+fn too_many() -> CheckReverserError {
+    CheckReverserError::UnsuitableFiles("too many solutions, try giving more files or parameters")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::Digest;
+    use quickcheck::{Arbitrary, TestResult};
+    use std::str::FromStr;
+    impl Arbitrary for PolyHashBuilder<u64> {
+        fn arbitrary<G: quickcheck::Gen>(g: &mut G) -> Self {
+            let mut new_hash = PolyHash::with_options();
+            let width = u8::arbitrary(g) % 64 + 1;
+            let mask = u64::MAX >> (64 - width);
+            new_hash.width(width as usize);
+            new_hash.mult(u64::arbitrary(g) & mask | 1);
+            new_hash.init(u64::arbitrary(g) & mask);
+            new_hash.addout(u64::arbitrary(g) & mask);
+            new_hash
+        }
+    }
+
+    #[quickcheck]
+    fn qc_polyhash_rev(
+        files: Vec<Vec<u8>>,
+        hash_build: PolyHashBuilder<u64>,
+        known: (bool, bool, bool),
+    ) -> TestResult {
+        if files.is_empty() {
+            return TestResult::discard();
+        }
+        let hash = hash_build.build().unwrap();
+        let mut naive = PolyHash::<u64>::with_options();
+        naive.width(hash_build.width.unwrap());
+        if known.0 {
+            naive.mult(hash_build.mult.unwrap());
+        }
+        if known.1 {
+            naive.init(hash_build.init.unwrap());
+        }
+        if known.2 {
+            naive.addout(hash_build.addout.unwrap());
+        }
+        let chk_files: Vec<_> = files
+            .iter()
+            .map(|f| (f.as_slice(), hash.digest(f.as_slice()).unwrap() as u128))
+            .collect();
+        let mut has_appeared = false;
+        for hash_loop in reverse_polyhash(&naive, &chk_files, 0) {
+            let hash_loop = match hash_loop {
+                Err(_) => return TestResult::discard(),
+                Ok(x) => x,
+            };
+            if hash_loop.mult == hash.mult {
+                has_appeared = true;
+            }
+            for (file, original_check) in &chk_files {
+                let checksum = hash_loop.digest(*file).unwrap();
+                if checksum as u128 != *original_check {
+                    eprintln!("expected checksum: {:x}", original_check);
+                    eprintln!("actual checksum: {:x}", checksum);
+                    eprintln!("polyhash: {}", hash_loop);
+                    return TestResult::failed();
+                }
+            }
+        }
+        TestResult::from_bool(has_appeared)
+    }
+    fn test_files() -> Vec<Vec<u8>> {
+        [13usize, 27, 100]
+            .iter()
+            .map(|&l| (0..l).map(|i| (i * 97 + l * 13) as u8).collect())
+            .collect()
+    }
+    fn reverse_str(original: &str, naive: &str) -> Vec<PolyHash<u64>> {
+        let hash = PolyHash::<u64>::from_str(original).unwrap();
+        let files = test_files();
+        let chk_files: Vec<_> = files
+            .iter()
+            .map(|f| (f.as_slice(), hash.digest(f.as_slice()).unwrap() as u128))
+            .collect();
+        let naive = PolyHashBuilder::<u64>::from_str(naive).unwrap();
+        reverse_polyhash(&naive, &chk_files, 0)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    }
+    #[test]
+    fn djb2() {
+        let djb2 = "width=32 mult=21 init=1505";
+        let found = reverse_str(djb2, "width=32");
+        // the highest bit of mult only has a small effect, so there is a false positive here
+        assert!(found.contains(&PolyHash::from_str(djb2).unwrap()));
+        assert!(found.len() <= 2);
+        let found = reverse_str(djb2, "width=32 mult=21 module=0");
+        assert_eq!(found, vec![PolyHash::from_str(djb2).unwrap()]);
+    }
+    #[test]
+    fn prime_module() {
+        let hash = "width=16 mult=100 module=fff1 init=1234 addout=20";
+        let found = reverse_str(hash, "width=16 mult=100 module=fff1");
+        assert_eq!(found, vec![PolyHash::from_str(hash).unwrap()]);
+        let found = reverse_str(hash, "width=16 mult=100 init=1234");
+        assert!(found.contains(&PolyHash::from_str(hash).unwrap()));
+        let found = reverse_str(hash, "width=16 module=fff1");
+        assert!(found.contains(&PolyHash::from_str(hash).unwrap()));
+        assert!(found.len() <= 2);
+        let found = reverse_str(hash, "width=16 module=fff1 addout=20");
+        assert_eq!(found, vec![PolyHash::from_str(hash).unwrap()]);
+        // too large to try every mult
+        let files = test_files();
+        let chk_files: Vec<_> = files.iter().map(|f| (f.as_slice(), 0)).collect();
+        let naive = PolyHashBuilder::<u64>::from_str("width=32 module=fffffffb").unwrap();
+        assert!(reverse_polyhash(&naive, &chk_files, 0)
+            .next()
+            .unwrap()
+            .is_err());
+    }
+}
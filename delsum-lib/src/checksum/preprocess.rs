@@ -0,0 +1,320 @@
+//! A generic transformation of the text before it gets checksummed, which can be applied to any algorithm.
+//!
+//! Sometimes the checksum is not calculated over the stored bytes directly, but over a trivially transformed version of them,
+//! like bytes that were XORed with a constant or a text padded up to a block size.
+//!
+//! There are a number of parameters, which are applied in this order:
+//! * nibbleswap: Whether to swap the high and low nibble of each byte (default false)
+//! * xorin: A byte that is XORed to each byte, in hexadecimal (default 0)
+//! * invertin: Whether to invert all bits of each byte (default false)
+//! * pad: Pads the text with a byte up to a multiple of a block size, written as `blocksize:byte` with the block size in decimal
+//!   and the byte in hexadecimal, like `pad=4:ff` (default is no padding)
+//!
+//! When reversing, `xorin=?` can be given for the GF(2)-linear algorithms (`crc`, `xorsum` and `rotxor`), in which case
+//! every value of `xorin` is tried.
+use super::brute::{self, BruteOptions};
+use super::{CheckBuilderErr, Digest, RangePairs, Relativity};
+use crate::keyval::KeyValIter;
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// The padding of a text up to a multiple of `block` bytes with the byte `value`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pad {
+    pub block: usize,
+    pub value: u8,
+}
+
+impl Pad {
+    /// The number of bytes to be appended to a text of length `len`.
+    pub fn pad_len(&self, len: usize) -> usize {
+        (self.block - len % self.block) % self.block
+    }
+}
+
+impl FromStr for Pad {
+    type Err = CheckBuilderErr;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let malformed = || CheckBuilderErr::MalformedString(String::from("pad"));
+        let (block, value) = match s.find(':') {
+            Some(i) => (&s[..i], &s[i + 1..]),
+            None => (s, "0"),
+        };
+        let block = usize::from_str(block).map_err(|_| malformed())?;
+        let value = u8::from_str_radix(value, 16).map_err(|_| malformed())?;
+        if block == 0 {
+            return Err(CheckBuilderErr::ValueOutOfRange("pad"));
+        }
+        Ok(Pad { block, value })
+    }
+}
+
+impl Display for Pad {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{:02x}", self.block, self.value)
+    }
+}
+
+/// A builder for the input transformation.
+///
+/// Example:
+/// ```
+/// # use delsum_lib::checksum::preprocess::InTransform;
+/// InTransform::with_options()
+///     .xorin(0x5a)
+///     .pad(4, 0xff)
+///     .build()
+///     .is_ok();
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct InTransformBuilder {
+    nibbleswap: Option<bool>,
+    xorin: Option<Option<u8>>,
+    invertin: Option<bool>,
+    pad: Option<Pad>,
+}
+
+impl InTransformBuilder {
+    /// Whether to swap the nibbles of each byte, optional, defaults to false.
+    pub fn nibbleswap(&mut self, n: bool) -> &mut Self {
+        self.nibbleswap = Some(n);
+        self
+    }
+    /// The byte that is XORed to each byte, optional, defaults to 0.
+    pub fn xorin(&mut self, x: u8) -> &mut Self {
+        self.xorin = Some(Some(x));
+        self
+    }
+    /// Marks `xorin` as unknown, which is only useful for reversing.
+    pub fn unknown_xorin(&mut self) -> &mut Self {
+        self.xorin = Some(None);
+        self
+    }
+    /// Whether to invert the bits of each byte, optional, defaults to false.
+    pub fn invertin(&mut self, i: bool) -> &mut Self {
+        self.invertin = Some(i);
+        self
+    }
+    /// Pads the text with `value` bytes up to a multiple of `block` bytes, optional, defaults to no padding.
+    pub fn pad(&mut self, block: usize, value: u8) -> &mut Self {
+        self.pad = Some(Pad { block, value });
+        self
+    }
+    /// Builds the transformation, after validating the parameters.
+    pub fn build(&self) -> Result<InTransform, CheckBuilderErr> {
+        if let Some(Pad { block: 0, .. }) = self.pad {
+            return Err(CheckBuilderErr::ValueOutOfRange("pad"));
+        }
+        Ok(InTransform {
+            nibbleswap: self.nibbleswap.unwrap_or(false),
+            xorin: self.xorin.unwrap_or(Some(0)),
+            invertin: self.invertin.unwrap_or(false),
+            pad: self.pad,
+        })
+    }
+    /// Extracts the keys of the transformation from a model string, returning the
+    /// builder and the rest of the model string.
+    pub(crate) fn extract(s: &str) -> Result<(InTransformBuilder, String), CheckBuilderErr> {
+        let mut transform = InTransform::with_options();
+        let mut rest = Vec::new();
+        for x in KeyValIter::new(s) {
+            let (current_key, current_val) = match x {
+                Err(key) => return Err(CheckBuilderErr::MalformedString(key)),
+                Ok(s) => s,
+            };
+            let transform_op = match current_key.as_str() {
+                "nibbleswap" => bool::from_str(&current_val)
+                    .ok()
+                    .map(|x| transform.nibbleswap(x)),
+                "xorin" if current_val == "?" => Some(transform.unknown_xorin()),
+                "xorin" => u8::from_str_radix(current_val.trim_start_matches("0x"), 16)
+                    .ok()
+                    .map(|x| transform.xorin(x)),
+                "invertin" => bool::from_str(&current_val)
+                    .ok()
+                    .map(|x| transform.invertin(x)),
+                "pad" => Pad::from_str(&current_val)
+                    .ok()
+                    .map(|p| transform.pad(p.block, p.value)),
+                _ => {
+                    rest.push(format!("{}=\"{}\"", current_key, current_val));
+                    continue;
+                }
+            };
+            if transform_op.is_none() {
+                return Err(CheckBuilderErr::MalformedString(current_key));
+            }
+        }
+        Ok((transform, rest.join(" ")))
+    }
+}
+
+/// A transformation of the text before it gets checksummed.
+///
+/// `xorin` is `None` if it is unknown, in which case the transformation can not be applied,
+/// but `candidates` gives the transformations for every possible value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InTransform {
+    nibbleswap: bool,
+    xorin: Option<u8>,
+    invertin: bool,
+    pad: Option<Pad>,
+}
+
+impl Default for InTransform {
+    fn default() -> Self {
+        InTransform {
+            nibbleswap: false,
+            xorin: Some(0),
+            invertin: false,
+            pad: None,
+        }
+    }
+}
+
+impl InTransform {
+    /// Creates a `InTransformBuilder`, for more information see its documentation.
+    pub fn with_options() -> InTransformBuilder {
+        InTransformBuilder::default()
+    }
+    /// Whether the transformation does not change anything.
+    pub fn is_identity(&self) -> bool {
+        *self == InTransform::default()
+    }
+    /// Whether the value of `xorin` is known, so that the transformation can be applied.
+    pub fn is_known(&self) -> bool {
+        self.xorin.is_some()
+    }
+    /// The padding of the text, if any.
+    pub fn pad(&self) -> Option<Pad> {
+        self.pad
+    }
+    /// The same transformation, but without padding the text.
+    pub fn without_pad(&self) -> InTransform {
+        InTransform { pad: None, ..*self }
+    }
+    /// Gets all transformations that are possible if `xorin` is unknown, or only itself otherwise.
+    pub fn candidates(&self) -> Vec<InTransform> {
+        match self.xorin {
+            Some(_) => vec![*self],
+            None => (0..=255u8)
+                .map(|x| InTransform {
+                    xorin: Some(x),
+                    ..*self
+                })
+                .collect(),
+        }
+    }
+    /// Applies the transformation to a single byte.
+    ///
+    /// Panics if `xorin` is unknown.
+    pub fn map_byte(&self, mut byte: u8) -> u8 {
+        if self.nibbleswap {
+            byte = byte.rotate_left(4);
+        }
+        byte ^= self.xorin.expect("Can not apply an unknown xorin");
+        if self.invertin {
+            byte = !byte;
+        }
+        byte
+    }
+    /// Applies the transformation to a text, including the padding.
+    ///
+    /// Panics if `xorin` is unknown.
+    pub fn apply(&self, bytes: &[u8]) -> Vec<u8> {
+        let mut ret: Vec<_> = bytes.iter().map(|b| self.map_byte(*b)).collect();
+        if let Some(pad) = self.pad {
+            ret.resize(bytes.len() + pad.pad_len(bytes.len()), pad.value);
+        }
+        ret
+    }
+    /// Like `LinearCheck::find_segments`, but each segment gets padded with `pad`, which has to be set.
+    ///
+    /// The bytes should already be transformed by `map_byte`.
+    /// Since the padding depends on the length of the segment, the linearity of the checksum can't be exploited,
+    /// so every start is tried separately, taking quadratic time.
+    pub fn find_padded_segments<D: Digest + Sync, B: AsRef<[u8]> + Sync>(
+        &self,
+        chk: &D,
+        bytes: &[B],
+        sum: &[D::Sum],
+        rel: Relativity,
+        opts: &BruteOptions,
+    ) -> RangePairs {
+        let pad = self
+            .pad
+            .expect("Can not search padded segments without padding");
+        brute::find_padded_segments(chk, bytes, sum, rel, Some(pad), None, opts)
+    }
+}
+
+impl Display for InTransform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        if self.nibbleswap {
+            parts.push("nibbleswap=true".to_owned());
+        }
+        match self.xorin {
+            Some(0) => (),
+            Some(x) => parts.push(format!("xorin={:#x}", x)),
+            None => parts.push("xorin=?".to_owned()),
+        }
+        if self.invertin {
+            parts.push("invertin=true".to_owned());
+        }
+        if let Some(pad) = self.pad {
+            parts.push(format!("pad={}", pad));
+        }
+        write!(f, "{}", parts.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::modsum::ModSum;
+    use crate::checksum::RelativeIndex;
+    fn transform(s: &str) -> InTransform {
+        let (builder, rest) = InTransformBuilder::extract(s).unwrap();
+        assert!(rest.is_empty());
+        builder.build().unwrap()
+    }
+    #[test]
+    fn apply() {
+        let text = [0x12u8, 0x34, 0xa5];
+        assert_eq!(transform("").apply(&text), text.to_vec());
+        assert_eq!(transform("xorin=ff").apply(&text), vec![0xed, 0xcb, 0x5a]);
+        assert_eq!(
+            transform("nibbleswap=true xorin=1 invertin=true").apply(&text),
+            vec![0xdf, 0xbd, 0xa4]
+        );
+        assert_eq!(
+            transform("pad=4:ff").apply(&text),
+            vec![0x12, 0x34, 0xa5, 0xff]
+        );
+        assert_eq!(transform("pad=3").apply(&text), text.to_vec());
+        assert_eq!(transform("xorin=?").candidates().len(), 256);
+        assert!(InTransformBuilder::extract("pad=0:ff").is_err());
+        assert_eq!(
+            transform("xorin=5a pad=4:ff").to_string(),
+            "xorin=0x5a pad=4:ff"
+        );
+    }
+    #[test]
+    fn padded_segments() {
+        let chk = ModSum::<u16>::from_str("width=16").unwrap();
+        let pad = transform("pad=4:a5");
+        let sum = chk.digest(&pad.apply(b"123456")[..]).unwrap();
+        let files = [Vec::from("ab123456cde"), Vec::from("xy123456zw")];
+        assert_eq!(
+            pad.find_padded_segments(
+                &chk,
+                &files,
+                &[sum, sum],
+                Relativity::Start,
+                &BruteOptions::default()
+            ),
+            vec![(vec![2], vec![RelativeIndex::FromStart(8)])]
+        );
+    }
+}
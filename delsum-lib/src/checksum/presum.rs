@@ -0,0 +1,637 @@
+//! Finding equal presums for `LinearCheck::find_segments`.
+//!
+//! Each start and each end of a segment has an array of presums with one sum for every file, and a segment
+//! has the target checksum in all files exactly when the arrays of its start and its end are equal.
+//! These are found by sorting the arrays of the starts and the ends and going through both in order.
+//!
+//! By default, everything is sorted in memory, which uses `n*(8 + 2*sizeof(Sum))` bytes for `n` bytes
+//! (or `n*(16 + 2*sizeof(Sum))` bytes if a file is larger than 4GiB).
+//! With `PresumStrategy::External`, the presums are instead calculated in runs of limited length, which
+//! are sorted and written to temporary files, and then merged from there, so that only a single run
+//! has to fit into memory.
+//! At most `MAX_FAN_IN` runs are merged at once, with more runs being merged into bigger runs first,
+//! so that not too many files are open at the same time.
+//! With `PresumStrategy::Hashed`, the presums are calculated one file at a time and only a hash of the
+//! arrays is kept, so that the full arrays are only needed for the starts and ends with colliding hashes.
+use crate::bitnum::BitNum;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::convert::{TryFrom, TryInto};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::marker::PhantomData;
+use std::path::PathBuf;
+use std::sync::atomic::{self, AtomicUsize};
+
+/// The maximal number of runs that are read at the same time when merging them.
+const MAX_FAN_IN: usize = 64;
+
+/// How the presums are sorted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PresumStrategy {
+    /// Sort all presums in memory
+    InMemory,
+    /// Calculate and sort the presums of at most `run_len` positions at a time, write each sorted run to
+    /// a temporary file (in `std::env::temp_dir()`), and merge the runs afterwards.
+    ///
+    /// Each run continues where the previous one stopped in the files, so they are still only read once,
+    /// and the memory only depends on `run_len`.
+    External { run_len: usize },
+    /// Calculate the presums one file at a time and sort 64-bit hashes of the presum arrays first, so that the
+    /// full arrays only have to be kept for starts and ends whose hashes are equal.
+    ///
+    /// This needs two passes over the files, but uses a lot less memory than `InMemory` if there are many files.
+    Hashed,
+    /// Choose one of the other strategies, so that the estimated memory usage stays below `max_memory` bytes.
+    Auto { max_memory: usize },
+}
+
+impl PresumStrategy {
+    /// Chooses the strategy for `files` files with `len` presums each if this is `Auto`, otherwise returns it unchanged.
+    ///
+    /// The estimates only include the presums and their indices, not the files themselves.
+    pub fn resolve<Sum>(self, files: usize, len: usize) -> PresumStrategy {
+        let max_memory = match self {
+            PresumStrategy::Auto { max_memory } => max_memory,
+            strategy => return strategy,
+        };
+        let sum = std::mem::size_of::<Sum>();
+        let idx = match u32::try_from(len) {
+            Ok(_) => 4,
+            Err(_) => 8,
+        };
+        // the starts and ends each have the presums of every file and their sorted indices
+        let in_memory = 2 * len * (files * sum + idx);
+        // a hash and an index for every start and end, and the presums of a single file along with their word counts
+        let hashed = 2 * len * (8 + idx + sum + 8);
+        if in_memory <= max_memory {
+            PresumStrategy::InMemory
+        } else if hashed <= max_memory {
+            PresumStrategy::Hashed
+        } else {
+            // a run is like `InMemory`, but with 64-bit indices
+            let run_len = max_memory / (2 * (files * sum + 8));
+            PresumStrategy::External {
+                run_len: run_len.max(1),
+            }
+        }
+    }
+}
+
+/// Converting sums to a fixed number of bytes and back, for writing them to temporary files.
+pub trait SumBytes: Sized {
+    /// The number of bytes of every sum.
+    fn byte_len() -> usize;
+    /// Appends the bytes of the sum to `out`, in little endian.
+    fn push_bytes(&self, out: &mut Vec<u8>);
+    /// Reads a sum from the `byte_len()` bytes written by `push_bytes`.
+    fn from_bytes(bytes: &[u8]) -> Self;
+}
+
+impl<T: BitNum> SumBytes for T {
+    fn byte_len() -> usize {
+        T::zero().bits() / 8
+    }
+    fn push_bytes(&self, out: &mut Vec<u8>) {
+        for k in 0..Self::byte_len() {
+            let byte = (*self >> (8 * k)) & T::from(0xff);
+            out.push(byte.try_into().unwrap_or(0));
+        }
+    }
+    fn from_bytes(bytes: &[u8]) -> Self {
+        bytes
+            .iter()
+            .enumerate()
+            .fold(T::zero(), |sum, (k, b)| sum ^ (T::from(*b) << (8 * k)))
+    }
+}
+
+/// The index type of a `PresumSet`, which is `u32` if possible to save memory.
+trait PresumIdx: Copy + Ord + Send + Sync {
+    fn from_usize(x: usize) -> Self;
+    fn to_usize(self) -> usize;
+}
+
+impl PresumIdx for u32 {
+    fn from_usize(x: usize) -> Self {
+        u32::try_from(x).unwrap()
+    }
+    fn to_usize(self) -> usize {
+        self as usize
+    }
+}
+
+impl PresumIdx for u64 {
+    fn from_usize(x: usize) -> Self {
+        x as u64
+    }
+    fn to_usize(self) -> usize {
+        usize::try_from(self).unwrap()
+    }
+}
+
+/// Groups of start and end indices whose presum arrays are equal.
+pub(crate) type EqualPairs = Vec<(Vec<usize>, Vec<usize>)>;
+
+/// Finds the groups of starts and ends with equal presums, sorting them in memory.
+///
+/// The indices only take 4 bytes each, unless there are more than `u32::MAX` presums.
+pub(crate) fn equal_presums<Sum>(starts: Vec<Vec<Sum>>, ends: Vec<Vec<Sum>>) -> EqualPairs
+where
+    Sum: Clone + Eq + Ord + std::fmt::Debug + Send + Sync,
+{
+    let len = starts[0].len().max(ends[0].len());
+    if u32::try_from(len).is_ok() {
+        equal_presums_idx::<Sum, u32>(starts, ends)
+    } else {
+        equal_presums_idx::<Sum, u64>(starts, ends)
+    }
+}
+
+fn equal_presums_idx<Sum, Idx>(starts: Vec<Vec<Sum>>, ends: Vec<Vec<Sum>>) -> EqualPairs
+where
+    Sum: Clone + Eq + Ord + std::fmt::Debug + Send + Sync,
+    Idx: PresumIdx,
+{
+    let start_preset = PresumSet::<Sum, Idx>::new(starts);
+    let end_preset = PresumSet::<Sum, Idx>::new(ends);
+    let to_usize = |v: Vec<Idx>| v.into_iter().map(Idx::to_usize).collect();
+    start_preset
+        .equal_pairs(&end_preset)
+        .into_iter()
+        .map(|(a, b)| (to_usize(a), to_usize(b)))
+        .collect()
+}
+
+/// A struct for helping to sort and get duplicates of arrays of arrays.
+#[derive(Debug)]
+struct PresumSet<Sum: Clone + Eq + Ord + std::fmt::Debug, Idx> {
+    idx: Vec<Idx>,
+    presum: Vec<Vec<Sum>>,
+}
+
+impl<Sum: Clone + Eq + Ord + std::fmt::Debug + Send + Sync, Idx: PresumIdx> PresumSet<Sum, Idx> {
+    /// Gets a new PresumSet. Gets sorted on construction.
+    fn new(presum: Vec<Vec<Sum>>) -> Self {
+        let firstlen = presum[0].len();
+        // check that all sum arrays are of the same length
+        for x in presum.iter() {
+            assert_eq!(firstlen, x.len());
+        }
+        // vector of all indices
+        let mut idxvec: Vec<_> = (0..firstlen).map(Idx::from_usize).collect();
+        // get a permutation vector representing the sort of the presum arrays first by value and then by index
+
+        #[cfg(feature = "parallel")]
+        idxvec.par_sort_unstable_by(|a, b| Self::cmp_idx(&presum, *a, &presum, *b).then(a.cmp(&b)));
+        #[cfg(not(feature = "parallel"))]
+        idxvec.sort_unstable_by(|a, b| Self::cmp_idx(&presum, *a, &presum, *b).then(a.cmp(&b)));
+        Self {
+            idx: idxvec,
+            presum,
+        }
+    }
+    /// Compares all elements of the first vector at an index to the ones of the second vector lexicographically (assuming same length).
+    fn cmp_idx(presum_a: &[Vec<Sum>], a: Idx, presum_b: &[Vec<Sum>], b: Idx) -> Ordering {
+        for (x, y) in presum_a.iter().zip(presum_b.iter()) {
+            let cmp = x[a.to_usize()].cmp(&y[b.to_usize()]);
+            if cmp != Ordering::Equal {
+                return cmp;
+            }
+        }
+        Ordering::Equal
+    }
+    /// Finds groups of indices equal elements in the first set and the second set and
+    /// returns them for each equal array.
+    fn equal_pairs(&self, other: &Self) -> Vec<(Vec<Idx>, Vec<Idx>)> {
+        let mut ret = Vec::new();
+        let mut a_idx = 0;
+        let mut b_idx = 0;
+        while a_idx < self.idx.len() && b_idx < other.idx.len() {
+            let apos = self.idx[a_idx];
+            let bpos = other.idx[b_idx];
+            match Self::cmp_idx(&self.presum, apos, &other.presum, bpos) {
+                Ordering::Less => {
+                    a_idx += 1;
+                }
+                Ordering::Greater => {
+                    b_idx += 1;
+                }
+                Ordering::Equal => {
+                    let mut n_a = 0;
+                    // gets all runs of equal elements in a and b array
+                    for x in &self.idx[a_idx..] {
+                        if Self::cmp_idx(&self.presum, apos, &self.presum, *x) == Ordering::Equal {
+                            n_a += 1;
+                        } else {
+                            break;
+                        }
+                    }
+                    let mut n_b = 0;
+                    for x in &other.idx[b_idx..] {
+                        if Self::cmp_idx(&other.presum, bpos, &other.presum, *x) == Ordering::Equal
+                        {
+                            n_b += 1;
+                        } else {
+                            break;
+                        }
+                    }
+                    let mut a_vec = Vec::new();
+                    a_vec.extend_from_slice(&self.idx[a_idx..a_idx + n_a]);
+                    let mut b_vec = Vec::new();
+                    b_vec.extend_from_slice(&other.idx[b_idx..b_idx + n_b]);
+                    ret.push((a_vec, b_vec));
+                    // puts indexes beyond equal elements
+                    a_idx += n_a;
+                    b_idx += n_b;
+                }
+            }
+        }
+        // sort it, for good measure
+        ret.sort_unstable();
+        ret
+    }
+}
+
+/// Like `equal_presums`, but the presums of the `i`-th file are given by `presums(i)`, which gets called twice
+/// for every file, and only the presums with colliding hashes are kept for the comparison.
+pub(crate) fn equal_hashed<Sum, F>(files: usize, presums: F) -> EqualPairs
+where
+    Sum: Clone + Eq + Ord + std::fmt::Debug + Send + Sync + SumBytes,
+    F: Fn(usize) -> (Vec<Sum>, Vec<Sum>),
+{
+    let mut start_hashes = Vec::new();
+    let mut end_hashes = Vec::new();
+    for i in 0..files {
+        let (starts, ends) = presums(i);
+        hash_into(&mut start_hashes, &starts);
+        hash_into(&mut end_hashes, &ends);
+    }
+    let (start_cand, end_cand) = candidates(&start_hashes, &end_hashes);
+    drop((start_hashes, end_hashes));
+    let pick = |sums: Vec<Sum>, cand: &[usize]| cand.iter().map(|x| sums[*x].clone()).collect();
+    let (starts, ends): (Vec<Vec<Sum>>, Vec<Vec<Sum>>) = (0..files)
+        .map(|i| {
+            let (starts, ends) = presums(i);
+            (pick(starts, &start_cand), pick(ends, &end_cand))
+        })
+        .unzip();
+    // the indices of the candidates are mapped back to the indices of the presums
+    equal_presums(starts, ends)
+        .into_iter()
+        .map(|(a, b)| {
+            let a = a.into_iter().map(|x| start_cand[x]).collect();
+            let b = b.into_iter().map(|x| end_cand[x]).collect();
+            (a, b)
+        })
+        .collect()
+}
+
+/// Mixes the sums of another file into the hashes of the presum arrays, using FNV-1a on their bytes.
+fn hash_into<Sum: SumBytes>(hashes: &mut Vec<u64>, sums: &[Sum]) {
+    const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x100_0000_01b3;
+    hashes.resize(sums.len(), FNV_OFFSET);
+    let mut bytes = Vec::new();
+    for (hash, sum) in hashes.iter_mut().zip(sums) {
+        bytes.clear();
+        sum.push_bytes(&mut bytes);
+        for b in &bytes {
+            *hash = (*hash ^ u64::from(*b)).wrapping_mul(FNV_PRIME);
+        }
+    }
+}
+
+/// Gets the sorted indices of the starts and the ends whose hash is also the hash of some end or start respectively.
+fn candidates(start_hashes: &[u64], end_hashes: &[u64]) -> (Vec<usize>, Vec<usize>) {
+    let len = start_hashes.len().max(end_hashes.len());
+    if u32::try_from(len).is_ok() {
+        candidates_idx::<u32>(start_hashes, end_hashes)
+    } else {
+        candidates_idx::<u64>(start_hashes, end_hashes)
+    }
+}
+
+fn candidates_idx<Idx: PresumIdx>(a: &[u64], b: &[u64]) -> (Vec<usize>, Vec<usize>) {
+    let sorted = |hashes: &[u64]| {
+        let mut idx: Vec<_> = (0..hashes.len()).map(Idx::from_usize).collect();
+        #[cfg(feature = "parallel")]
+        idx.par_sort_unstable_by_key(|x| hashes[x.to_usize()]);
+        #[cfg(not(feature = "parallel"))]
+        idx.sort_unstable_by_key(|x| hashes[x.to_usize()]);
+        idx
+    };
+    let (a_sorted, b_sorted) = (sorted(a), sorted(b));
+    let (mut a_cand, mut b_cand) = (Vec::new(), Vec::new());
+    let (mut a_idx, mut b_idx) = (0, 0);
+    while a_idx < a_sorted.len() && b_idx < b_sorted.len() {
+        let a_hash = a[a_sorted[a_idx].to_usize()];
+        let b_hash = b[b_sorted[b_idx].to_usize()];
+        match a_hash.cmp(&b_hash) {
+            Ordering::Less => a_idx += 1,
+            Ordering::Greater => b_idx += 1,
+            Ordering::Equal => {
+                // takes all elements with this hash on both sides
+                while a_idx < a_sorted.len() && a[a_sorted[a_idx].to_usize()] == a_hash {
+                    a_cand.push(a_sorted[a_idx].to_usize());
+                    a_idx += 1;
+                }
+                while b_idx < b_sorted.len() && b[b_sorted[b_idx].to_usize()] == b_hash {
+                    b_cand.push(b_sorted[b_idx].to_usize());
+                    b_idx += 1;
+                }
+            }
+        }
+    }
+    a_cand.sort_unstable();
+    b_cand.sort_unstable();
+    (a_cand, b_cand)
+}
+
+/// A temporary file which gets deleted when dropped.
+struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    fn create() -> io::Result<(Self, File)> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let n = COUNTER.fetch_add(1, atomic::Ordering::Relaxed);
+        let path =
+            std::env::temp_dir().join(format!("delsum-{}-{}.presums", std::process::id(), n));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        Ok((TempFile { path }, file))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Sorted runs of presums with their indices, each one in a temporary file.
+///
+/// The entries are written as the sums of each file followed by the index as a little endian `u64`.
+pub(crate) struct Runs<Sum> {
+    runs: Vec<TempFile>,
+    files: usize,
+    sum: PhantomData<Sum>,
+}
+
+impl<Sum> Runs<Sum>
+where
+    Sum: Clone + Eq + Ord + std::fmt::Debug + Send + Sync + SumBytes,
+{
+    /// Creates an empty set of runs for presum arrays of `files` sums.
+    pub(crate) fn new(files: usize) -> Self {
+        Runs {
+            runs: Vec::new(),
+            files,
+            sum: PhantomData,
+        }
+    }
+    /// Sorts the presums and writes them to a new run, with `offset` added to their indices.
+    pub(crate) fn push(&mut self, presum: Vec<Vec<Sum>>, offset: usize) -> io::Result<()> {
+        if presum[0].is_empty() {
+            return Ok(());
+        }
+        let set = PresumSet::<Sum, u64>::new(presum);
+        let (temp, file) = TempFile::create()?;
+        // the file gets deleted when something fails
+        self.runs.push(temp);
+        let mut out = BufWriter::new(file);
+        let mut entry = Vec::new();
+        for i in set.idx {
+            entry.clear();
+            for p in &set.presum {
+                p[i as usize].push_bytes(&mut entry);
+            }
+            entry.extend_from_slice(&(offset as u64 + i).to_le_bytes());
+            out.write_all(&entry)?;
+        }
+        out.flush()
+    }
+    /// Reads the runs back, merged into one sorted sequence.
+    ///
+    /// If there are more than `MAX_FAN_IN` runs, groups of them get merged into bigger runs first.
+    fn merge(&mut self) -> io::Result<Merge<Sum>> {
+        while self.runs.len() > MAX_FAN_IN {
+            let runs = std::mem::take(&mut self.runs);
+            for group in runs.chunks(MAX_FAN_IN) {
+                let merged = self.merge_into_run(group)?;
+                self.runs.push(merged);
+            }
+        }
+        self.open_merge(&self.runs)
+    }
+    /// Merges the given runs and writes the result to a new run.
+    fn merge_into_run(&self, runs: &[TempFile]) -> io::Result<TempFile> {
+        let mut merge = self.open_merge(runs)?;
+        let (temp, file) = TempFile::create()?;
+        let mut out = BufWriter::new(file);
+        let mut entry = Vec::new();
+        while let Some((sums, idx)) = merge.next_entry()? {
+            entry.clear();
+            for sum in &sums {
+                sum.push_bytes(&mut entry);
+            }
+            entry.extend_from_slice(&idx.to_le_bytes());
+            out.write_all(&entry)?;
+        }
+        out.flush()?;
+        Ok(temp)
+    }
+    /// Opens the given runs for merging them.
+    fn open_merge(&self, runs: &[TempFile]) -> io::Result<Merge<Sum>> {
+        let mut readers = Vec::new();
+        for run in runs {
+            readers.push(RunReader {
+                reader: BufReader::new(File::open(&run.path)?),
+                files: self.files,
+                entry: vec![0; self.files * Sum::byte_len() + 8],
+                sum: PhantomData,
+            });
+        }
+        let mut heap = BinaryHeap::new();
+        for (n, reader) in readers.iter_mut().enumerate() {
+            if let Some((sums, idx)) = reader.next_entry()? {
+                heap.push(Reverse((sums, idx, n)));
+            }
+        }
+        Ok(Merge { readers, heap })
+    }
+}
+
+/// Reads the entries of a single run.
+struct RunReader<Sum> {
+    reader: BufReader<File>,
+    files: usize,
+    entry: Vec<u8>,
+    sum: PhantomData<Sum>,
+}
+
+impl<Sum: SumBytes> RunReader<Sum> {
+    fn next_entry(&mut self) -> io::Result<Option<(Vec<Sum>, u64)>> {
+        match self.reader.read_exact(&mut self.entry) {
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            res => res?,
+        }
+        let (sums, idx) = self.entry.split_at(self.files * Sum::byte_len());
+        let sums = sums.chunks(Sum::byte_len()).map(Sum::from_bytes).collect();
+        Ok(Some((sums, u64::from_le_bytes(idx.try_into().unwrap()))))
+    }
+}
+
+/// Merges the entries of multiple runs in sorted order.
+struct Merge<Sum> {
+    readers: Vec<RunReader<Sum>>,
+    heap: BinaryHeap<Reverse<(Vec<Sum>, u64, usize)>>,
+}
+
+impl<Sum: Ord + SumBytes> Merge<Sum> {
+    fn next_entry(&mut self) -> io::Result<Option<(Vec<Sum>, u64)>> {
+        let Reverse((sums, idx, n)) = match self.heap.pop() {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        if let Some((next_sums, next_idx)) = self.readers[n].next_entry()? {
+            self.heap.push(Reverse((next_sums, next_idx, n)));
+        }
+        Ok(Some((sums, idx)))
+    }
+}
+
+/// Like `equal_presums`, but for presums which were written to runs.
+pub(crate) fn equal_runs<Sum>(
+    starts: &mut Runs<Sum>,
+    ends: &mut Runs<Sum>,
+) -> io::Result<EqualPairs>
+where
+    Sum: Clone + Eq + Ord + std::fmt::Debug + Send + Sync + SumBytes,
+{
+    let mut start_merge = starts.merge()?;
+    let mut end_merge = ends.merge()?;
+    let mut a = start_merge.next_entry()?;
+    let mut b = end_merge.next_entry()?;
+    let mut ret = Vec::new();
+    while let (Some((a_sums, _)), Some((b_sums, _))) = (&a, &b) {
+        match a_sums.cmp(b_sums) {
+            Ordering::Less => a = start_merge.next_entry()?,
+            Ordering::Greater => b = end_merge.next_entry()?,
+            Ordering::Equal => {
+                // gets all runs of equal elements in both sequences
+                let sums = a_sums.clone();
+                let mut a_vec = Vec::new();
+                while let Some((s, idx)) = &a {
+                    if *s != sums {
+                        break;
+                    }
+                    a_vec.push(usize::try_from(*idx).unwrap());
+                    a = start_merge.next_entry()?;
+                }
+                let mut b_vec = Vec::new();
+                while let Some((s, idx)) = &b {
+                    if *s != sums {
+                        break;
+                    }
+                    b_vec.push(usize::try_from(*idx).unwrap());
+                    b = end_merge.next_entry()?;
+                }
+                ret.push((a_vec, b_vec));
+            }
+        }
+    }
+    ret.sort_unstable();
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::prelude::*;
+    fn random_presums(rng: &mut StdRng, files: usize, len: usize) -> Vec<Vec<u16>> {
+        // few different values, so that there are a lot of equal arrays
+        (0..files)
+            .map(|_| (0..len).map(|_| rng.gen_range(0, 4)).collect())
+            .collect()
+    }
+    #[test]
+    fn sum_bytes() {
+        let mut bytes = Vec::new();
+        0x1234u16.push_bytes(&mut bytes);
+        0xabu8.push_bytes(&mut bytes);
+        assert_eq!(bytes, vec![0x34, 0x12, 0xab]);
+        assert_eq!(u16::from_bytes(&bytes[..2]), 0x1234);
+        assert_eq!(u8::from_bytes(&bytes[2..]), 0xab);
+    }
+    fn runs(presums: &[Vec<u16>], run_len: usize) -> Runs<u16> {
+        let len = presums[0].len();
+        let mut runs = Runs::new(presums.len());
+        for offset in (0..len).step_by(run_len) {
+            let chunk = presums
+                .iter()
+                .map(|x| x[offset..(offset + run_len).min(len)].to_vec())
+                .collect();
+            runs.push(chunk, offset).unwrap();
+        }
+        runs
+    }
+    #[test]
+    fn runs_like_in_memory() {
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+        let starts = random_presums(&mut rng, 2, 1000);
+        let ends = random_presums(&mut rng, 2, 1000);
+        let expected = equal_presums(starts.clone(), ends.clone());
+        let (mut start_runs, mut end_runs) = (runs(&starts, 300), runs(&ends, 300));
+        assert_eq!(
+            equal_runs(&mut start_runs, &mut end_runs).unwrap(),
+            expected
+        );
+        let paths: Vec<_> = start_runs.runs.iter().map(|r| r.path.clone()).collect();
+        drop(start_runs);
+        assert!(paths.iter().all(|p| !p.exists()));
+    }
+    #[test]
+    fn many_runs() {
+        let mut rng = StdRng::seed_from_u64(0xface);
+        let starts = random_presums(&mut rng, 2, 1000);
+        let ends = random_presums(&mut rng, 2, 1000);
+        let expected = equal_presums(starts.clone(), ends.clone());
+        // more runs than can be merged at once, so they get merged in two steps
+        let (mut start_runs, mut end_runs) = (runs(&starts, 7), runs(&ends, 7));
+        assert!(start_runs.runs.len() > MAX_FAN_IN);
+        let paths: Vec<_> = start_runs.runs.iter().map(|r| r.path.clone()).collect();
+        assert_eq!(
+            equal_runs(&mut start_runs, &mut end_runs).unwrap(),
+            expected
+        );
+        assert!(start_runs.runs.len() <= MAX_FAN_IN);
+        assert!(paths.iter().all(|p| !p.exists()));
+    }
+    #[test]
+    fn hashed_like_in_memory() {
+        let mut rng = StdRng::seed_from_u64(0xfeed);
+        let starts = random_presums(&mut rng, 3, 1000);
+        let ends = random_presums(&mut rng, 3, 900);
+        let expected = equal_presums(starts.clone(), ends.clone());
+        assert!(!expected.is_empty());
+        let hashed = equal_hashed(3, |i| (starts[i].clone(), ends[i].clone()));
+        assert_eq!(hashed, expected);
+    }
+    #[test]
+    fn resolve() {
+        let auto = |max_memory| PresumStrategy::Auto { max_memory }.resolve::<u32>(16, 1000);
+        assert_eq!(auto(1 << 20), PresumStrategy::InMemory);
+        assert_eq!(auto(100_000), PresumStrategy::Hashed);
+        assert_eq!(auto(20_000), PresumStrategy::External { run_len: 138 });
+        let external = PresumStrategy::External { run_len: 10 };
+        assert_eq!(external.resolve::<u32>(16, 1000), external);
+    }
+}
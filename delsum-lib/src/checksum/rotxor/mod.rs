@@ -0,0 +1,275 @@
+//! A checksum that rotates the sum before xoring each byte into it (i.e. `sum = rotl(sum, rot) ^ byte`)
+//!
+//! There are a number of parameters:
+//! * width: The number of bits in the sum type, at most 64
+//! * rot: The number of bits by which the sum is rotated to the left, smaller than `width`
+//! * init: The initial number
+//! * xorout: The number that gets xored to the sum at the end
+//! * check: The checksum of "123456789" (optional, gets checked at construction)
+//! * name: An optional name that gets used for display purposes
+//!
+//! If `width` is smaller than 8, the bytes are cut off to their lowest `width` bits.
+pub mod rev;
+use crate::bitnum::BitNum;
+use crate::checksum::{CheckBuilderErr, Digest, LinearCheck};
+use crate::keyval::KeyValIter;
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// A builder to set the various parameters for the rotxor algorithm.
+///
+/// Example:
+/// ```
+/// # use delsum_lib::checksum::rotxor::RotXor;
+/// RotXor::<u16>::with_options()
+///     .width(16)
+///     .rot(1)
+///     .init(0xffff)
+///     .build()
+///     .is_ok();
+/// ```
+#[derive(Debug, Clone)]
+pub struct RotXorBuilder<S: BitNum> {
+    width: Option<usize>,
+    rot: Option<usize>,
+    init: Option<S>,
+    xorout: Option<S>,
+    check: Option<S>,
+    name: Option<String>,
+}
+
+impl<S: BitNum> RotXorBuilder<S> {
+    /// The width, in bits, of the sum. Mandatory.
+    pub fn width(&mut self, w: usize) -> &mut Self {
+        self.width = Some(w);
+        self
+    }
+    /// The number of bits to rotate the sum to the left before each byte. Mandatory.
+    pub fn rot(&mut self, r: usize) -> &mut Self {
+        self.rot = Some(r);
+        self
+    }
+    /// The initial value, optional, defaults to 0.
+    pub fn init(&mut self, i: S) -> &mut Self {
+        self.init = Some(i);
+        self
+    }
+    /// The value to xor to the final sum, optional, defaults to 0.
+    pub fn xorout(&mut self, x: S) -> &mut Self {
+        self.xorout = Some(x);
+        self
+    }
+    /// The checksum of "123456789", gets checked on creation.
+    pub fn check(&mut self, c: S) -> &mut Self {
+        self.check = Some(c);
+        self
+    }
+    /// An optional name that gets used for display purposes.
+    pub fn name(&mut self, n: &str) -> &mut Self {
+        self.name = Some(String::from(n));
+        self
+    }
+    /// Builds the algorithm, after validating the parameters.
+    pub fn build(&self) -> Result<RotXor<S>, CheckBuilderErr> {
+        let width = self
+            .width
+            .ok_or(CheckBuilderErr::MissingParameter("width"))?;
+        if width == 0 || width > S::zero().bits() {
+            return Err(CheckBuilderErr::ValueOutOfRange("width"));
+        }
+        let rot = self.rot.ok_or(CheckBuilderErr::MissingParameter("rot"))?;
+        if rot >= width {
+            return Err(CheckBuilderErr::ValueOutOfRange("rot"));
+        }
+        let mask = if width < S::zero().bits() {
+            (S::one() << width) - S::one()
+        } else {
+            !S::zero()
+        };
+        let init = self.init.unwrap_or_else(S::zero);
+        if init & !mask != S::zero() {
+            return Err(CheckBuilderErr::ValueOutOfRange("init"));
+        }
+        let xorout = self.xorout.unwrap_or_else(S::zero);
+        if xorout & !mask != S::zero() {
+            return Err(CheckBuilderErr::ValueOutOfRange("xorout"));
+        }
+        let s = RotXor {
+            width,
+            mask,
+            rot,
+            init,
+            xorout,
+            name: self.name.clone(),
+        };
+        match self.check {
+            Some(c) => {
+                if s.digest(&b"123456789"[..]).unwrap() == c {
+                    Ok(s)
+                } else {
+                    Err(CheckBuilderErr::CheckFail)
+                }
+            }
+            None => Ok(s),
+        }
+    }
+}
+
+/// A RotXor checksum algorithm.
+///
+/// Implements LinearCheck so that finding checksummed locations in a file is efficiently possible.
+#[derive(Debug, PartialEq, Eq)]
+pub struct RotXor<S: BitNum> {
+    width: usize,
+    mask: S,
+    rot: usize,
+    init: S,
+    xorout: S,
+    name: Option<String>,
+}
+
+impl<S: BitNum> RotXor<S> {
+    /// Creates a `RotXorBuilder`, for more information see its documentation.
+    pub fn with_options() -> RotXorBuilder<S> {
+        RotXorBuilder {
+            width: None,
+            rot: None,
+            init: None,
+            xorout: None,
+            check: None,
+            name: None,
+        }
+    }
+    /// Rotates the sum to the left by `n` bits, where `n < width`.
+    fn rotl(&self, sum: S, n: usize) -> S {
+        if n == 0 {
+            return sum;
+        }
+        ((sum << n) & self.mask) ^ (sum >> (self.width - n))
+    }
+}
+
+impl<Sum: BitNum> Display for RotXor<Sum> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.name {
+            Some(n) => write!(f, "{}", n),
+            None => write!(
+                f,
+                "rotxor width={} rot={} init={:#x} xorout={:#x}",
+                self.width, self.rot, self.init, self.xorout
+            ),
+        }
+    }
+}
+
+impl<Sum: BitNum> FromStr for RotXorBuilder<Sum> {
+    /// See FromStr for RotXor<Sum>
+    fn from_str(s: &str) -> Result<RotXorBuilder<Sum>, CheckBuilderErr> {
+        let mut sum = RotXor::<Sum>::with_options();
+        for x in KeyValIter::new(s) {
+            let (current_key, current_val) = match x {
+                Err(key) => return Err(CheckBuilderErr::MalformedString(key)),
+                Ok(s) => s,
+            };
+            let rotxor_op = match current_key.as_str() {
+                "width" => usize::from_str(&current_val).ok().map(|x| sum.width(x)),
+                "rot" => usize::from_str(&current_val).ok().map(|x| sum.rot(x)),
+                "init" => Sum::from_hex(&current_val).ok().map(|x| sum.init(x)),
+                "xorout" => Sum::from_hex(&current_val).ok().map(|x| sum.xorout(x)),
+                "check" => Sum::from_hex(&current_val).ok().map(|x| sum.check(x)),
+                "name" => Some(sum.name(&current_val)),
+                _ => return Err(CheckBuilderErr::UnknownKey(current_key)),
+            };
+            match rotxor_op {
+                Some(c) => sum = c.clone(),
+                None => return Err(CheckBuilderErr::MalformedString(current_key)),
+            }
+        }
+        Ok(sum)
+    }
+    type Err = CheckBuilderErr;
+}
+
+impl<Sum: BitNum> FromStr for RotXor<Sum> {
+    /// Construct a new rotxor algorithm from a string specification.
+    ///
+    /// Example:
+    ///
+    /// width=16 rot=1 init=ffff xorout=0
+    fn from_str(s: &str) -> Result<RotXor<Sum>, CheckBuilderErr> {
+        RotXorBuilder::from_str(s)?.build()
+    }
+    type Err = CheckBuilderErr;
+}
+
+impl<S: BitNum> Digest for RotXor<S> {
+    type Sum = S;
+    fn init(&self) -> Self::Sum {
+        self.init
+    }
+    fn dig_byte(&self, sum: Self::Sum, byte: u8) -> Self::Sum {
+        self.rotl(sum, self.rot) ^ (S::from(byte) & self.mask)
+    }
+    fn finalize(&self, sum: Self::Sum) -> Self::Sum {
+        sum ^ self.xorout
+    }
+}
+
+impl<S: BitNum> LinearCheck for RotXor<S> {
+    // the shift is the number of bits to rotate
+    type Shift = usize;
+    fn init_shift(&self) -> Self::Shift {
+        0
+    }
+    fn inc_shift(&self, shift: Self::Shift) -> Self::Shift {
+        (shift + self.rot) % self.width
+    }
+    fn shift(&self, sum: Self::Sum, shift: &Self::Shift) -> Self::Sum {
+        self.rotl(sum, *shift)
+    }
+    fn shift_n(&self, n: usize) -> Self::Shift {
+        (n % self.width) * self.rot % self.width
+    }
+    fn add(&self, sum_a: Self::Sum, sum_b: &Self::Sum) -> Self::Sum {
+        sum_a ^ *sum_b
+    }
+    fn negate(&self, sum: Self::Sum) -> Self::Sum {
+        sum
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::tests::{test_find, test_prop, test_shifts};
+    #[test]
+    fn rot1() {
+        let s = RotXor::<u16>::with_options()
+            .width(16)
+            .rot(1)
+            .init(0xffff)
+            .xorout(0x1234)
+            .build()
+            .unwrap();
+        test_shifts(&s);
+        test_find(&s);
+        test_prop(&s);
+        // ((0xffff <<< 1) ^ 0x01) <<< 1 ^ 0x80
+        assert_eq!(
+            s.digest(&[0x01u8, 0x80][..]).unwrap(),
+            0xfffd ^ 0x80 ^ 0x1234
+        );
+    }
+    #[test]
+    fn odd_widths() {
+        let s = RotXor::<u32>::from_str("width=29 rot=7 init=1234567").unwrap();
+        test_shifts(&s);
+        test_find(&s);
+        test_prop(&s);
+        let s = RotXor::<u8>::from_str("width=5 rot=3").unwrap();
+        test_shifts(&s);
+        test_prop(&s);
+        assert_eq!(s.to_string(), "rotxor width=5 rot=3 init=0x0 xorout=0x0");
+        // 0x31 is cut off to 0x11, which gets rotated to 0x0c and xored with 0x12
+        assert_eq!(s.digest(&b"12"[..]).unwrap(), 0x1e);
+    }
+}
@@ -0,0 +1,254 @@
+//! This module contains the function(s) for reversing the parameters for a rotxor algorithm.
+//!
+//! If `rot` is not given, all rotation amounts smaller than `width` are tried.
+//! For a given rotation, the checksum is linear over GF(2) in `init` and `xorout`, so they
+//! can be found by solving a system of linear equations.
+//!
+//! Generally, files with lengths that lead to different total rotations of `init` are needed
+//! to tell `init` and `xorout` apart. If they can't be told apart with the given files,
+//! the solution where the undeterminable bits of `init` are 0 is returned.
+use super::{RotXor, RotXorBuilder};
+use crate::checksum::{unresult_iter, CheckReverserError, Digest, LinearCheck};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::iter::Iterator;
+/// Find the parameters of a rotxor algorithm.
+///
+/// `spec` contains the known parameters of the algorithm (by setting the corresponding values in the builder).
+/// `chk_bytes` are pairs of files and their checksums.
+/// `verbosity` makes the function output what it is doing.
+///
+/// The `width` parameter of the builder has to be set.
+pub fn reverse_rotxor<'a>(
+    spec: &RotXorBuilder<u64>,
+    chk_bytes: &'a [(&[u8], u128)],
+    verbosity: u64,
+) -> impl Iterator<Item = Result<RotXor<u64>, CheckReverserError>> + 'a {
+    let spec = spec.clone();
+    rotations(&spec).into_iter().flat_map(move |r| {
+        unresult_iter(reverse(&spec, chk_bytes, r, verbosity).map(|x| x.into_iter()))
+    })
+}
+
+/// Parallel version of reverse_rotxor.
+///
+/// It is parallel in the sense that each rotation amount is tried in its own thread.
+#[cfg(feature = "parallel")]
+pub fn reverse_rotxor_para<'a>(
+    spec: &RotXorBuilder<u64>,
+    chk_bytes: &'a [(&[u8], u128)],
+    verbosity: u64,
+) -> impl ParallelIterator<Item = Result<RotXor<u64>, CheckReverserError>> + 'a {
+    let spec = spec.clone();
+    rotations(&spec)
+        .into_par_iter()
+        .map(move |r| {
+            unresult_iter(reverse(&spec, chk_bytes, r, verbosity).map(|x| x.into_iter()))
+                .par_bridge()
+        })
+        .flatten()
+}
+
+// all rotation amounts that need to be tried
+fn rotations(spec: &RotXorBuilder<u64>) -> Vec<usize> {
+    match (spec.rot, spec.width) {
+        (Some(r), _) => vec![r],
+        (None, Some(w)) => (0..w).collect(),
+        // missing width gets reported in reverse
+        (None, None) => vec![0],
+    }
+}
+
+// For a file of length n, the checksum is
+//      rotl(init, n*rot) ^ xorout ^ sum
+// where sum is the checksum of the file with init = xorout = 0.
+// Each bit of this is a linear equation in the bits of init and xorout.
+fn reverse(
+    spec: &RotXorBuilder<u64>,
+    chk_bytes: &[(&[u8], u128)],
+    rot: usize,
+    verbosity: u64,
+) -> Result<Option<RotXor<u64>>, CheckReverserError> {
+    let log = |s: &str| {
+        if verbosity > 0 {
+            eprintln!("<rotxor rot={}> {}", rot, s);
+        }
+    };
+    let width = spec
+        .width
+        .ok_or(CheckReverserError::MissingParameter("width"))?;
+    if width == 0 || width > 64 {
+        return Err(CheckReverserError::ValueOutOfRange("width"));
+    }
+    let plain = RotXor::<u64>::with_options()
+        .width(width)
+        .rot(rot)
+        .build()
+        .map_err(|_| CheckReverserError::ValueOutOfRange("rot"))?;
+    // the variables are the bits of init, followed by the bits of xorout
+    let mut rows = Vec::new();
+    let known = |value: Option<u64>, offset: usize, rows: &mut Vec<(u128, bool)>| {
+        if let Some(v) = value {
+            for i in 0..width {
+                rows.push((1u128 << (offset + i), v >> i & 1 == 1));
+            }
+        }
+    };
+    known(spec.init, 0, &mut rows);
+    known(spec.xorout, width, &mut rows);
+    log("calculating sums of files");
+    for (f, chk) in chk_bytes {
+        let rhs = (*chk as u64) ^ plain.digest(*f).unwrap();
+        let k = plain.shift_n(f.len());
+        for i in 0..width {
+            let init_bit = (i + width - k) % width;
+            rows.push((
+                (1u128 << init_bit) | (1u128 << (width + i)),
+                rhs >> i & 1 == 1,
+            ));
+        }
+    }
+    log("solving equations");
+    let solution = match solve_gf2(rows, 2 * width) {
+        Some(s) => s,
+        None => {
+            log("no solution found");
+            return Ok(None);
+        }
+    };
+    let mask = u64::MAX >> (64 - width);
+    Ok(RotXor::with_options()
+        .width(width)
+        .rot(rot)
+        .init(solution as u64 & mask)
+        .xorout((solution >> width) as u64 & mask)
+        .build()
+        .ok())
+}
+
+// Solves a linear system over GF(2), where each row is a bitmask of the variables
+// and the right hand side. Variables that are not determined by the system are set to 0.
+fn solve_gf2(mut rows: Vec<(u128, bool)>, vars: usize) -> Option<u128> {
+    let mut pivots = Vec::new();
+    for col in 0..vars {
+        let bit = 1u128 << col;
+        let r = pivots.len();
+        let p = match (r..rows.len()).find(|&i| rows[i].0 & bit != 0) {
+            Some(p) => p,
+            None => continue,
+        };
+        rows.swap(r, p);
+        let (pivot_mask, pivot_rhs) = rows[r];
+        for (i, row) in rows.iter_mut().enumerate() {
+            if i != r && row.0 & bit != 0 {
+                row.0 ^= pivot_mask;
+                row.1 ^= pivot_rhs;
+            }
+        }
+        pivots.push(col);
+    }
+    // the remaining rows are all zero, so they have to have a zero right side
+    if rows[pivots.len()..].iter().any(|(_, rhs)| *rhs) {
+        return None;
+    }
+    let mut solution = 0;
+    for (row, col) in pivots.iter().enumerate() {
+        if rows[row].1 {
+            solution |= 1 << col;
+        }
+    }
+    Some(solution)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck::{Arbitrary, TestResult};
+    use std::str::FromStr;
+    impl Arbitrary for RotXorBuilder<u64> {
+        fn arbitrary<G: quickcheck::Gen>(g: &mut G) -> Self {
+            let mut new_rotxor = RotXor::with_options();
+            let width = u8::arbitrary(g) % 64 + 1;
+            let mask = u64::MAX >> (64 - width);
+            new_rotxor.width(width as usize);
+            new_rotxor.rot(usize::from(u8::arbitrary(g) % width));
+            new_rotxor.init(u64::arbitrary(g) & mask);
+            new_rotxor.xorout(u64::arbitrary(g) & mask);
+            new_rotxor
+        }
+    }
+
+    #[quickcheck]
+    fn qc_rotxor_rev(
+        files: Vec<Vec<u8>>,
+        rotxor_build: RotXorBuilder<u64>,
+        known: (bool, bool, bool),
+    ) -> TestResult {
+        if files.is_empty() {
+            return TestResult::discard();
+        }
+        let rotxor = rotxor_build.build().unwrap();
+        let mut naive = RotXor::<u64>::with_options();
+        naive.width(rotxor_build.width.unwrap());
+        if known.0 {
+            naive.rot(rotxor_build.rot.unwrap());
+        }
+        if known.1 {
+            naive.init(rotxor_build.init.unwrap());
+        }
+        if known.2 {
+            naive.xorout(rotxor_build.xorout.unwrap());
+        }
+        let chk_files: Vec<_> = files
+            .iter()
+            .map(|f| {
+                let checksum = rotxor.digest(f.as_slice()).unwrap();
+                (f.as_slice(), checksum as u128)
+            })
+            .collect();
+        let mut has_appeared = false;
+        for rotxor_loop in reverse_rotxor(&naive, &chk_files, 0) {
+            let rotxor_loop = match rotxor_loop {
+                Err(_) => return TestResult::failed(),
+                Ok(x) => x,
+            };
+            if rotxor_loop.rot == rotxor.rot {
+                has_appeared = true;
+            }
+            for (file, original_check) in &chk_files {
+                let checksum = rotxor_loop.digest(*file).unwrap();
+                if checksum as u128 != *original_check {
+                    eprintln!("expected checksum: {:x}", original_check);
+                    eprintln!("actual checksum: {:x}", checksum);
+                    eprintln!("rotxor: {}", rotxor_loop);
+                    return TestResult::failed();
+                }
+            }
+        }
+        TestResult::from_bool(has_appeared)
+    }
+    #[test]
+    fn rot5() {
+        let rotxor = RotXor::<u64>::from_str("width=16 rot=5 init=1d0f xorout=ffff").unwrap();
+        let f: Vec<Vec<u8>> = [13usize, 27, 100]
+            .iter()
+            .map(|&l| (0..l).map(|i| (i * 97 + l * 13) as u8).collect())
+            .collect();
+        let chk_files: Vec<_> = f
+            .iter()
+            .map(|f| (f.as_slice(), rotxor.digest(f.as_slice()).unwrap() as u128))
+            .collect();
+        let mut naive = RotXor::<u64>::with_options();
+        naive.width(16);
+        let r: Vec<_> = reverse_rotxor(&naive, &chk_files, 0)
+            .filter_map(Result::ok)
+            .collect();
+        // init = ffff is indistinguishable from init = 0 with an inverted xorout,
+        // because it is invariant under rotation
+        assert_eq!(r.len(), 1);
+        assert_eq!(r[0].rot, 5);
+        for (file, chk) in &chk_files {
+            assert_eq!(r[0].digest(*file).unwrap() as u128, *chk);
+        }
+    }
+}
@@ -0,0 +1,171 @@
+//! The 16-bit checksum of the System V `sum` utility (also `sum -s`), which adds all bytes and then folds the sum to 16 bits
+//!
+//! Because of the folding at the end, it is not linear modulo `2^16` or `2^32`,
+//! so it only implements `Digest` and segments are found by trying all of them.
+//! Note that the sum of the bytes itself is available as `modsum`.
+//!
+//! There are a number of parameters:
+//! * width: The number of bits in the sum type, has to be 16
+//! * init: The initial 32-bit number of the sum before folding (default 0)
+//! * check: The checksum of "123456789" (optional, gets checked at construction)
+//! * name: An optional name that gets used for display purposes
+use crate::bitnum::BitNum;
+use crate::checksum::{CheckBuilderErr, Digest};
+use crate::keyval::KeyValIter;
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// A builder to set the various parameters for the System V sum.
+///
+/// Example:
+/// ```
+/// # use delsum_lib::checksum::sysvsum::SysvSum;
+/// SysvSum::with_options()
+///     .width(16)
+///     .check(0x1dd)
+///     .build()
+///     .is_ok();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SysvSumBuilder {
+    width: Option<usize>,
+    init: Option<u32>,
+    check: Option<u32>,
+    name: Option<String>,
+}
+
+impl SysvSumBuilder {
+    /// The width, in bits, of the sum. Mandatory, and has to be 16.
+    pub fn width(&mut self, w: usize) -> &mut Self {
+        self.width = Some(w);
+        self
+    }
+    /// The initial value of the unfolded sum, optional, defaults to 0.
+    pub fn init(&mut self, i: u32) -> &mut Self {
+        self.init = Some(i);
+        self
+    }
+    /// The checksum of "123456789", gets checked on creation.
+    pub fn check(&mut self, c: u32) -> &mut Self {
+        self.check = Some(c);
+        self
+    }
+    /// An optional name that gets used for display purposes.
+    pub fn name(&mut self, n: &str) -> &mut Self {
+        self.name = Some(String::from(n));
+        self
+    }
+    /// Builds the algorithm, after validating the parameters.
+    pub fn build(&self) -> Result<SysvSum, CheckBuilderErr> {
+        let width = self
+            .width
+            .ok_or(CheckBuilderErr::MissingParameter("width"))?;
+        if width != 16 {
+            return Err(CheckBuilderErr::ValueOutOfRange("width"));
+        }
+        let s = SysvSum {
+            init: self.init.unwrap_or(0),
+            name: self.name.clone(),
+        };
+        match self.check {
+            Some(c) => {
+                if s.digest(&b"123456789"[..]).unwrap() == c {
+                    Ok(s)
+                } else {
+                    Err(CheckBuilderErr::CheckFail)
+                }
+            }
+            None => Ok(s),
+        }
+    }
+}
+
+/// The System V sum algorithm.
+///
+/// The sum type is 32 bits wide to hold the unfolded sum, but the checksum is only 16 bits wide.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SysvSum {
+    init: u32,
+    name: Option<String>,
+}
+
+impl SysvSum {
+    /// Creates a `SysvSumBuilder`, for more information see its documentation.
+    pub fn with_options() -> SysvSumBuilder {
+        SysvSumBuilder::default()
+    }
+}
+
+impl Display for SysvSum {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.name {
+            Some(n) => write!(f, "{}", n),
+            None => write!(f, "sysvsum width=16 init={:#x}", self.init),
+        }
+    }
+}
+
+impl FromStr for SysvSumBuilder {
+    /// See FromStr for SysvSum
+    fn from_str(s: &str) -> Result<SysvSumBuilder, CheckBuilderErr> {
+        let mut sum = SysvSum::with_options();
+        for x in KeyValIter::new(s) {
+            let (current_key, current_val) = match x {
+                Err(key) => return Err(CheckBuilderErr::MalformedString(key)),
+                Ok(s) => s,
+            };
+            let sum_op = match current_key.as_str() {
+                "width" => usize::from_str(&current_val).ok().map(|x| sum.width(x)),
+                "init" => u32::from_hex(&current_val).ok().map(|x| sum.init(x)),
+                "check" => u32::from_hex(&current_val).ok().map(|x| sum.check(x)),
+                "name" => Some(sum.name(&current_val)),
+                _ => return Err(CheckBuilderErr::UnknownKey(current_key)),
+            };
+            match sum_op {
+                Some(s) => sum = s.clone(),
+                None => return Err(CheckBuilderErr::MalformedString(current_key)),
+            }
+        }
+        Ok(sum)
+    }
+    type Err = CheckBuilderErr;
+}
+
+impl FromStr for SysvSum {
+    /// Construct a new System V sum from a string specification.
+    ///
+    /// Example:
+    ///
+    /// width=16 init=0
+    fn from_str(s: &str) -> Result<SysvSum, CheckBuilderErr> {
+        SysvSumBuilder::from_str(s)?.build()
+    }
+    type Err = CheckBuilderErr;
+}
+
+impl Digest for SysvSum {
+    type Sum = u32;
+    fn init(&self) -> Self::Sum {
+        self.init
+    }
+    fn dig_byte(&self, sum: Self::Sum, byte: u8) -> Self::Sum {
+        sum.wrapping_add(u32::from(byte))
+    }
+    fn finalize(&self, sum: Self::Sum) -> Self::Sum {
+        let folded = (sum & 0xffff) + (sum >> 16);
+        (folded & 0xffff) + (folded >> 16)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn sum_s() {
+        let chk = SysvSum::from_str("width=16").unwrap();
+        // `sum -s` gives the checksum in decimal
+        assert_eq!(chk.digest(&b"123456789"[..]).unwrap(), 477);
+        // the folding adds the carry back in
+        assert_eq!(chk.digest(&[0xffu8; 0x102][..]).unwrap(), 0xff);
+    }
+}
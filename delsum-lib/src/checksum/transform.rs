@@ -0,0 +1,542 @@
+//! A generic transformation of the final checksum value, which can be applied to any algorithm.
+//!
+//! Many stored checksums are not the direct output of a checksum algorithm, but have been post-processed a bit,
+//! like the negated sum of Intel HEX files or a CRC that is stored in little endian.
+//!
+//! There are a number of parameters, which are applied in this order:
+//! * negate: Whether to take the two's complement of the sum (default false)
+//! * complement: Whether to take the one's complement of the sum (default false)
+//! * outbits: Only keep the lowest `outbits` bits of the sum (default is the width of the sum)
+//! * outendian: Whether to store the sum in `little` or `big` endian (default big)
+//!
+//! Note that `outbits` is not invertible, so the sum of the algorithm can't be recovered from the stored checksum.
+//! For `modsum` and `polyhash` with a power of two as module (like the default) and `xorsum`, the lowest `outbits` bits
+//! of the sum only depend on the lowest `outbits` bits of the parameters, so they are the sum of the same algorithm with
+//! a width of `outbits` (see `LinearCheck::truncated`), for which segments and parameters can be searched for as usual.
+//! This also works for a module below `2^outbits`, but for other modules, the higher bits of the sum influence the lower ones.
+//! The same is true for all other algorithms (like `crc`, where the higher bits get shifted into the lower ones),
+//! so for them, segments can only be searched for by trying all of them, and parameters can't be searched for.
+use super::{CheckBuilderErr, Digest, LinearCheck};
+use crate::bitnum::BitNum;
+use crate::endian::{Endian, WordSpec};
+use crate::keyval::KeyValIter;
+use std::fmt::Display;
+use std::io::Read;
+use std::str::FromStr;
+
+/// A builder for the output transformation.
+///
+/// Example:
+/// ```
+/// # use delsum_lib::checksum::transform::OutTransform;
+/// OutTransform::with_options()
+///     .width(16)
+///     .negate(true)
+///     .outendian(delsum_lib::endian::Endian::Little)
+///     .build()
+///     .is_ok();
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct OutTransformBuilder {
+    width: Option<usize>,
+    negate: Option<bool>,
+    complement: Option<bool>,
+    outbits: Option<usize>,
+    outendian: Option<Endian>,
+}
+
+impl OutTransformBuilder {
+    /// The width of the sum that gets transformed. Mandatory.
+    pub fn width(&mut self, w: usize) -> &mut Self {
+        self.width = Some(w);
+        self
+    }
+    /// Whether to negate the sum modulo `2^width`, optional, defaults to false.
+    pub fn negate(&mut self, n: bool) -> &mut Self {
+        self.negate = Some(n);
+        self
+    }
+    /// Whether to invert all bits of the sum, optional, defaults to false.
+    pub fn complement(&mut self, c: bool) -> &mut Self {
+        self.complement = Some(c);
+        self
+    }
+    /// The number of low bits of the sum that are kept, optional, defaults to `width`.
+    pub fn outbits(&mut self, o: usize) -> &mut Self {
+        self.outbits = Some(o);
+        self
+    }
+    /// The byte order in which the sum is stored, optional, defaults to big endian.
+    pub fn outendian(&mut self, e: Endian) -> &mut Self {
+        self.outendian = Some(e);
+        self
+    }
+    /// Builds the transformation, after validating the parameters.
+    pub fn build(&self) -> Result<OutTransform, CheckBuilderErr> {
+        let width = self
+            .width
+            .ok_or(CheckBuilderErr::MissingParameter("width"))?;
+        let outbits = self.outbits.unwrap_or(width);
+        if outbits == 0 || outbits > width {
+            return Err(CheckBuilderErr::ValueOutOfRange("outbits"));
+        }
+        let outendian = self.outendian.unwrap_or(Endian::Big);
+        if outendian == Endian::Little && outbits & 7 != 0 {
+            return Err(CheckBuilderErr::ValueOutOfRange("outendian"));
+        }
+        Ok(OutTransform {
+            width,
+            negate: self.negate.unwrap_or(false),
+            complement: self.complement.unwrap_or(false),
+            outbits,
+            outendian,
+        })
+    }
+    /// Extracts the keys of the transformation from a model string, returning the
+    /// builder and the rest of the model string.
+    pub(crate) fn extract(s: &str) -> Result<(OutTransformBuilder, String), CheckBuilderErr> {
+        let mut transform = OutTransform::with_options();
+        let mut rest = Vec::new();
+        for x in KeyValIter::new(s) {
+            let (current_key, current_val) = match x {
+                Err(key) => return Err(CheckBuilderErr::MalformedString(key)),
+                Ok(s) => s,
+            };
+            let transform_op = match current_key.as_str() {
+                "negate" => bool::from_str(&current_val)
+                    .ok()
+                    .map(|x| transform.negate(x)),
+                "complement" => bool::from_str(&current_val)
+                    .ok()
+                    .map(|x| transform.complement(x)),
+                "outbits" => usize::from_str(&current_val)
+                    .ok()
+                    .map(|x| transform.outbits(x)),
+                "outendian" => Endian::from_str(&current_val)
+                    .ok()
+                    .map(|x| transform.outendian(x)),
+                _ => {
+                    rest.push(format!("{}=\"{}\"", current_key, current_val));
+                    continue;
+                }
+            };
+            if transform_op.is_none() {
+                return Err(CheckBuilderErr::MalformedString(current_key));
+            }
+        }
+        Ok((transform, rest.join(" ")))
+    }
+}
+
+/// A transformation of the final sum.
+///
+/// `apply` is used to get the stored checksum from the sum of an algorithm and
+/// `invert` gets the sum of the algorithm back, if possible.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OutTransform {
+    width: usize,
+    negate: bool,
+    complement: bool,
+    outbits: usize,
+    outendian: Endian,
+}
+
+impl OutTransform {
+    /// Creates a `OutTransformBuilder`, for more information see its documentation.
+    pub fn with_options() -> OutTransformBuilder {
+        OutTransformBuilder::default()
+    }
+    /// The width of the transformed sum.
+    pub fn outbits(&self) -> usize {
+        self.outbits
+    }
+    /// Whether the transformation does not change anything.
+    pub fn is_identity(&self) -> bool {
+        !self.negate && !self.complement && self.is_invertible() && self.outendian == Endian::Big
+    }
+    /// Whether the sum of the algorithm can be recovered from the transformed sum.
+    pub fn is_invertible(&self) -> bool {
+        self.outbits == self.width
+    }
+    /// The same transformation for a sum which is only `outbits` wide, which is always invertible.
+    ///
+    /// Since negating and complementing do not let the higher bits influence the lower ones, applying it to the
+    /// lowest `outbits` bits of a sum gives the same result as applying `self` to the whole sum.
+    pub fn truncated(&self) -> OutTransform {
+        OutTransform {
+            width: self.outbits,
+            ..*self
+        }
+    }
+    fn mask<S: BitNum>(bits: usize) -> S {
+        if bits >= S::zero().bits() {
+            !S::zero()
+        } else {
+            (S::one() << bits) - S::one()
+        }
+    }
+    fn swap_bytes<S: BitNum>(&self, sum: S) -> S {
+        if self.outendian == Endian::Big {
+            return sum;
+        }
+        let byte_mask = S::from(0xffu8);
+        (0..self.outbits / 8).fold(S::zero(), |acc, i| {
+            (acc << 8) ^ ((sum >> (8 * i)) & byte_mask)
+        })
+    }
+    /// Applies the transformation to a sum of the algorithm.
+    pub fn apply<S: BitNum>(&self, mut sum: S) -> S {
+        let mask = Self::mask::<S>(self.width);
+        if self.negate {
+            sum = S::zero().wrapping_sub(&sum) & mask;
+        }
+        if self.complement {
+            sum = sum ^ mask;
+        }
+        sum = sum & Self::mask::<S>(self.outbits);
+        self.swap_bytes(sum)
+    }
+    /// Gets the sum of the algorithm from a transformed sum, or `None` if
+    /// this is not possible because bits were cut off.
+    pub fn invert<S: BitNum>(&self, mut sum: S) -> Option<S> {
+        if !self.is_invertible() {
+            return None;
+        }
+        let mask = Self::mask::<S>(self.width);
+        sum = self.swap_bytes(sum) & mask;
+        if self.complement {
+            sum = sum ^ mask;
+        }
+        if self.negate {
+            sum = S::zero().wrapping_sub(&sum) & mask;
+        }
+        Some(sum)
+    }
+}
+
+impl Display for OutTransform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        if self.negate {
+            parts.push("negate=true".to_owned());
+        }
+        if self.complement {
+            parts.push("complement=true".to_owned());
+        }
+        if self.outbits != self.width {
+            parts.push(format!("outbits={}", self.outbits));
+        }
+        if self.outendian != Endian::Big {
+            parts.push(format!("outendian={}", self.outendian));
+        }
+        write!(f, "{}", parts.join(" "))
+    }
+}
+
+/// The module of a sum modulo `module` (where 0 stands for `2^bits` of `S`) after cutting it off to its lowest `bits` bits.
+///
+/// Taking a power of two which is at least `2^bits` as module does not change the lowest `bits` bits, so the module
+/// becomes `2^bits`, and a module of at most `2^bits` gives sums which are not cut off at all.
+/// For other modules, the cut off sum is not a sum modulo any number, so `None` is returned.
+pub(crate) fn truncated_module<S: BitNum>(module: S, bits: usize) -> Option<S> {
+    if bits >= module.bits() || (module != S::zero() && module >> bits == S::zero()) {
+        return Some(module);
+    }
+    if module == S::zero() || module & (module - S::one()) == S::zero() {
+        Some(S::one() << bits)
+    } else {
+        None
+    }
+}
+
+/// An algorithm whose output gets transformed by an `OutTransform`.
+///
+/// The `LinearCheck` operations are the ones of the underlying algorithm, only `finalize`
+/// applies the transformation, which gets undone on the target sum in `presums`.
+/// If the transformation cuts off bits, the presums are those of the truncated algorithm (see `LinearCheck::truncated`)
+/// instead, so `presums` and `find_segments` panic if there is none (which can be checked with `is_searchable`).
+#[derive(Debug)]
+pub struct Transformed<D> {
+    inner: D,
+    transform: OutTransform,
+}
+
+impl<D> Transformed<D> {
+    pub fn new(inner: D, transform: OutTransform) -> Self {
+        Transformed { inner, transform }
+    }
+}
+
+impl<L: LinearCheck> Transformed<L>
+where
+    L::Sum: BitNum,
+{
+    /// Whether the segments with a given checksum can be searched for with `find_segments`.
+    pub fn is_searchable(&self) -> bool {
+        self.transform.is_invertible() || self.inner.truncated(self.transform.outbits()).is_some()
+    }
+}
+
+impl<D: Display> Display for Transformed<D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.inner)?;
+        if !self.transform.is_identity() {
+            write!(f, " {}", self.transform)?;
+        }
+        Ok(())
+    }
+}
+
+impl<D: Digest> Digest for Transformed<D>
+where
+    D::Sum: BitNum,
+{
+    type Sum = D::Sum;
+    fn init(&self) -> Self::Sum {
+        self.inner.init()
+    }
+    fn dig_byte(&self, sum: Self::Sum, byte: u8) -> Self::Sum {
+        self.inner.dig_byte(sum, byte)
+    }
+    fn dig_word(&self, sum: Self::Sum, word: u64) -> Self::Sum {
+        self.inner.dig_word(sum, word)
+    }
+    fn wordspec(&self) -> WordSpec {
+        self.inner.wordspec()
+    }
+    fn finalize(&self, sum: Self::Sum) -> Self::Sum {
+        self.transform.apply(self.inner.finalize(sum))
+    }
+    fn digest<R: Read>(&self, buf: R) -> Result<Self::Sum, std::io::Error> {
+        // the inner checksum might do more than just finalizing (like appending the length of a CRC)
+        self.inner.digest(buf).map(|sum| self.transform.apply(sum))
+    }
+}
+
+impl<L: LinearCheck> LinearCheck for Transformed<L>
+where
+    L::Sum: BitNum,
+{
+    type Shift = L::Shift;
+    fn init_shift(&self) -> Self::Shift {
+        self.inner.init_shift()
+    }
+    fn inc_shift(&self, shift: Self::Shift) -> Self::Shift {
+        self.inner.inc_shift(shift)
+    }
+    fn shift(&self, sum: Self::Sum, shift: &Self::Shift) -> Self::Sum {
+        self.inner.shift(sum, shift)
+    }
+    fn add(&self, sum_a: Self::Sum, sum_b: &Self::Sum) -> Self::Sum {
+        self.inner.add(sum_a, sum_b)
+    }
+    fn negate(&self, sum: Self::Sum) -> Self::Sum {
+        self.inner.negate(sum)
+    }
+    fn shift_n(&self, n: usize) -> Self::Shift {
+        self.inner.shift_n(n)
+    }
+    fn presums(
+        &self,
+        bytes: &[u8],
+        sum: &dyn Fn(usize) -> Option<Self::Sum>,
+        phase: usize,
+        start_range: std::ops::Range<usize>,
+        end_range: std::ops::Range<usize>,
+        cursor: &mut Option<(Self::Sum, usize)>,
+    ) -> (Vec<Self::Sum>, Vec<Self::Sum>) {
+        if !self.transform.is_invertible() {
+            // the presums are only compared with each other, so they can be the ones of the truncated algorithm
+            let inner = self
+                .inner
+                .truncated(self.transform.outbits())
+                .expect("Can not search for segments of a non-invertible transformation");
+            return Transformed::new(inner, self.transform.truncated()).presums(
+                bytes,
+                sum,
+                phase,
+                start_range,
+                end_range,
+                cursor,
+            );
+        }
+        let inner_sum = |end| {
+            sum(end).map(|s| {
+                self.transform
+                    .invert(s)
+                    .expect("Can not search for segments of a non-invertible transformation")
+            })
+        };
+        self.inner
+            .presums(bytes, &inner_sum, phase, start_range, end_range, cursor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::crc::CRC;
+    use crate::checksum::modsum::ModSum;
+    use crate::checksum::tests::test_find;
+    use crate::checksum::{RelativeIndex, Relativity};
+    fn transform(width: usize, s: &str) -> OutTransform {
+        let (mut builder, rest) = OutTransformBuilder::extract(s).unwrap();
+        assert!(rest.is_empty());
+        builder.width(width).build().unwrap()
+    }
+    #[test]
+    fn intel_hex() {
+        let chk = Transformed::new(
+            ModSum::<u8>::from_str("width=8").unwrap(),
+            transform(8, "negate=true"),
+        );
+        let record = [0x03u8, 0x00, 0x30, 0x00, 0x02, 0x33, 0x7a];
+        assert_eq!(chk.digest(&record[..]).unwrap(), 0x1e);
+        assert_eq!(
+            chk.to_string(),
+            "modsum width=8 module=0x0 init=0x0 negate=true"
+        );
+    }
+    #[test]
+    fn crc_transforms() {
+        let crc =
+            "width=32 poly=0x04c11db7 init=0xffffffff xorout=0xffffffff refin=true refout=true";
+        let chk = Transformed::new(
+            CRC::<u32>::from_str(crc).unwrap(),
+            transform(32, "outendian=little"),
+        );
+        assert_eq!(chk.digest(&b"123456789"[..]).unwrap(), 0x2639f4cb);
+        test_find(&chk);
+        let chk = Transformed::new(
+            CRC::<u32>::from_str(crc).unwrap(),
+            transform(32, "complement=true outbits=16 outendian=little"),
+        );
+        assert_eq!(chk.digest(&b"123456789"[..]).unwrap(), 0xd9c6);
+    }
+    #[test]
+    fn segments_outbits() {
+        let model = "crc width=16 poly=0x1021 outbits=8";
+        let data: Vec<Vec<u8>> = (1..=4u8)
+            .map(|n| (0..20 * n).map(|i| i.wrapping_mul(n) ^ 0x5a).collect())
+            .collect();
+        let sums = crate::find_checksum(model, &data).unwrap().join(",");
+        let files: Vec<Vec<u8>> = data
+            .iter()
+            .map(|d| [&b"hdr"[..], d, &b"!!"[..]].concat())
+            .collect();
+        let segs = crate::find_checksum_segments(model, &files, &sums, Relativity::End).unwrap();
+        assert!(segs.contains(&(vec![3], vec![RelativeIndex::FromEnd(2)])));
+    }
+    #[test]
+    fn segments_truncated() {
+        let data: Vec<Vec<u8>> = (1..=4u8)
+            .map(|n| (0..20 * n).map(|i| i.wrapping_mul(n) ^ 0x5a).collect())
+            .collect();
+        let files: Vec<Vec<u8>> = data
+            .iter()
+            .map(|d| [&b"hdr"[..], d, &b"!!"[..]].concat())
+            .collect();
+        for model in &[
+            "modsum width=16 init=0x1234 negate=true outbits=8",
+            "xorsum width=32 wordsize=16 outbits=12",
+            "polyhash width=32 mult=0x1f addout=0xabcdef outbits=16",
+        ] {
+            let sums = crate::find_checksum(model, &data).unwrap().join(",");
+            let segs =
+                crate::find_checksum_segments(model, &files, &sums, Relativity::End).unwrap();
+            assert!(segs.contains(&(vec![3], vec![RelativeIndex::FromEnd(2)])));
+        }
+    }
+    #[test]
+    fn truncated() {
+        assert_eq!(truncated_module(0u16, 8), Some(0x100));
+        assert_eq!(truncated_module(0x1000u16, 8), Some(0x100));
+        assert_eq!(truncated_module(0xfbu16, 8), Some(0xfb));
+        assert_eq!(truncated_module(0x100u16, 8), Some(0x100));
+        assert_eq!(truncated_module(0xfff1u16, 8), None);
+        let chk = |model: &str, outbits| {
+            Transformed::new(
+                ModSum::<u16>::from_str(model).unwrap(),
+                transform(16, &format!("outbits={}", outbits)),
+            )
+        };
+        assert!(chk("width=16", 8).is_searchable());
+        assert!(chk("width=16 module=0xfb", 8).is_searchable());
+        assert!(!chk("width=16 module=0xfff1", 8).is_searchable());
+        let crc = Transformed::new(
+            CRC::<u16>::from_str("width=16 poly=0x1021").unwrap(),
+            transform(16, "outbits=8"),
+        );
+        assert!(!crc.is_searchable());
+    }
+    #[test]
+    fn reverse_truncated() {
+        let data: Vec<Vec<u8>> = (1..=3u8)
+            .map(|n| (0..50 * n).map(|i| i.wrapping_mul(n) ^ 0x5a).collect())
+            .collect();
+        let files: Vec<&[u8]> = data.iter().map(Vec::as_slice).collect();
+        let sums = |model: &str| {
+            files
+                .iter()
+                .map(|f| crate::find_checksum(model, &[f.to_vec()]).unwrap().join(""))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let model = "modsum width=16 init=0x1234 negate=true outbits=12";
+        let found: Vec<_> = crate::find_algorithm(
+            "modsum width=16 negate=true outbits=12",
+            &files,
+            &sums(model),
+            0,
+        )
+        .unwrap()
+        .find_all()
+        .map(Result::unwrap)
+        .collect();
+        assert!(found.contains(&"modsum width=12 module=0x1000 init=0x234 negate=true".to_owned()));
+        for f in found {
+            assert_eq!(sums(&f), sums(model));
+        }
+        let model = "polyhash width=32 mult=0x1f init=0x1234567 outbits=24";
+        let found: Vec<_> = crate::find_algorithm(
+            "polyhash width=32 module=0 mult=0x1f outbits=24",
+            &files,
+            &sums(model),
+            0,
+        )
+        .unwrap()
+        .find_all()
+        .map(Result::unwrap)
+        .collect();
+        assert!(!found.is_empty());
+        for f in found {
+            assert_eq!(sums(&f), sums(model));
+        }
+        for model in &[
+            "crc width=16 poly=0x1021 outbits=8",
+            "modsum width=16 module=0xfff1 outbits=8",
+        ] {
+            assert!(crate::find_algorithm(model, &files, &sums(model), 0).is_err());
+        }
+    }
+    #[test]
+    fn invert() {
+        for s in &[
+            "negate=true",
+            "complement=true",
+            "outendian=little",
+            "negate=true complement=true outendian=little",
+        ] {
+            let t = transform(24, s);
+            for x in &[0u32, 1, 0x123456, 0xffffff] {
+                assert_eq!(t.invert(t.apply(*x)), Some(*x));
+            }
+        }
+        assert_eq!(transform(24, "outbits=8").invert(0x12u32), None);
+        assert!(OutTransform::with_options()
+            .width(12)
+            .outendian(Endian::Little)
+            .build()
+            .is_err());
+    }
+}
@@ -0,0 +1,263 @@
+//! A sum of the bytes of a text with a fixed length, where each position has its own weight (i.e. `sum(w_i * byte_i) % module`)
+//!
+//! Lots of identification numbers and fixed-length records use a sum like this, for example
+//! ISBN-10 (weights 10 down to 1 modulo 11) or EAN (alternating weights 1 and 3 modulo 10).
+//! Since the weights are only defined for texts of one length, it does not implement `Digest`,
+//! and checksums are calculated with `WeightSum::checksum` instead.
+//!
+//! There are a number of parameters:
+//! * width: The number of bits in the sum type, at most 64
+//! * module: The sum is taken modulo this number (default 0, which means `2^width`)
+//! * init: The initial number (default 0)
+//! * weights: The weight of each byte, as hexadecimal numbers which are padded to the number of digits of `width` bits and written one after another
+//! * name: An optional name that gets used for display purposes
+//!
+//! There is no `check` parameter, since "123456789" usually has a different length than the texts.
+pub mod rev;
+use crate::bitnum::BitNum;
+use crate::checksum::CheckBuilderErr;
+use crate::keyval::KeyValIter;
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// A builder to set the various parameters for the weighted sum.
+///
+/// Example:
+/// ```
+/// # use delsum_lib::checksum::weightsum::WeightSum;
+/// // ISBN-10, where valid numbers have a sum of 0
+/// WeightSum::with_options()
+///     .width(8)
+///     .module(11)
+///     .weights(vec![10, 9, 8, 7, 6, 5, 4, 3, 2, 1])
+///     .build()
+///     .is_ok();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct WeightSumBuilder {
+    width: Option<usize>,
+    module: Option<u64>,
+    init: Option<u64>,
+    weights: Option<Vec<u64>>,
+    name: Option<String>,
+}
+
+impl WeightSumBuilder {
+    /// The width, in bits, of the sum. Mandatory, at most 64.
+    pub fn width(&mut self, w: usize) -> &mut Self {
+        self.width = Some(w);
+        self
+    }
+    /// The number by which the remainder is taken, optional, defaults to 0, which means `2^width`.
+    pub fn module(&mut self, m: u64) -> &mut Self {
+        self.module = Some(m);
+        self
+    }
+    /// The initial value, optional, defaults to 0.
+    pub fn init(&mut self, i: u64) -> &mut Self {
+        self.init = Some(i);
+        self
+    }
+    /// The weight of each byte of the text, which also determines the length of the texts. Mandatory.
+    pub fn weights(&mut self, w: Vec<u64>) -> &mut Self {
+        self.weights = Some(w);
+        self
+    }
+    /// An optional name that gets used for display purposes.
+    pub fn name(&mut self, n: &str) -> &mut Self {
+        self.name = Some(String::from(n));
+        self
+    }
+    /// Builds the algorithm, after validating the parameters.
+    pub fn build(&self) -> Result<WeightSum, CheckBuilderErr> {
+        let width = self
+            .width
+            .ok_or(CheckBuilderErr::MissingParameter("width"))?;
+        if width == 0 || width > 64 {
+            return Err(CheckBuilderErr::ValueOutOfRange("width"));
+        }
+        let module = match self.module.unwrap_or(0) {
+            0 => 1u128 << width,
+            m if u128::from(m) > 1u128 << width => {
+                return Err(CheckBuilderErr::ValueOutOfRange("module"))
+            }
+            m => u128::from(m),
+        };
+        let init = u128::from(self.init.unwrap_or(0));
+        if init >= module {
+            return Err(CheckBuilderErr::ValueOutOfRange("init"));
+        }
+        let weights = self
+            .weights
+            .clone()
+            .ok_or(CheckBuilderErr::MissingParameter("weights"))?;
+        if weights.iter().any(|w| u128::from(*w) >= module) {
+            return Err(CheckBuilderErr::ValueOutOfRange("weights"));
+        }
+        Ok(WeightSum {
+            width,
+            module,
+            init,
+            weights,
+            name: self.name.clone(),
+        })
+    }
+}
+
+/// A sum of the bytes of a text, each multiplied with the weight for its position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WeightSum {
+    width: usize,
+    // can be 2^64, so it does not fit into a u64
+    module: u128,
+    init: u128,
+    weights: Vec<u64>,
+    name: Option<String>,
+}
+
+impl WeightSum {
+    /// Creates a `WeightSumBuilder`, for more information see its documentation.
+    pub fn with_options() -> WeightSumBuilder {
+        WeightSumBuilder::default()
+    }
+    /// The length of the texts the weights are defined for.
+    pub fn text_len(&self) -> usize {
+        self.weights.len()
+    }
+    /// Calculates the checksum of a text, or `None` if the text does not have the right length.
+    pub fn checksum(&self, bytes: &[u8]) -> Option<u64> {
+        if bytes.len() != self.text_len() {
+            return None;
+        }
+        let sum = bytes
+            .iter()
+            .zip(self.weights.iter())
+            .fold(self.init, |sum, (byte, weight)| {
+                (sum + u128::from(*byte) * u128::from(*weight)) % self.module
+            });
+        Some(sum as u64)
+    }
+}
+
+impl Display for WeightSum {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.name {
+            Some(n) => write!(f, "{}", n),
+            None => {
+                let module = if self.module == 1u128 << self.width {
+                    0
+                } else {
+                    self.module
+                };
+                write!(
+                    f,
+                    "weightsum width={} module={:#x} init={:#x} weights=",
+                    self.width, module, self.init
+                )?;
+                let digits = (self.width - 1) / 4 + 1;
+                for weight in &self.weights {
+                    write!(f, "{:0digits$x}", weight, digits = digits)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Parses the weights, where each weight has the number of hexadecimal digits of `width` bits.
+fn parse_weights(s: &str, width: usize) -> Option<Vec<u64>> {
+    if width == 0 || !s.is_ascii() {
+        return None;
+    }
+    let digits = (width - 1) / 4 + 1;
+    s.as_bytes()
+        .chunks(digits)
+        .map(|w| match w.len() {
+            l if l == digits => u64::from_str_radix(std::str::from_utf8(w).ok()?, 16).ok(),
+            _ => None,
+        })
+        .collect()
+}
+
+impl FromStr for WeightSumBuilder {
+    /// See FromStr for WeightSum
+    fn from_str(s: &str) -> Result<WeightSumBuilder, CheckBuilderErr> {
+        let mut sum = WeightSum::with_options();
+        // the weights can only be parsed when the width is known
+        let mut weights = None;
+        for x in KeyValIter::new(s) {
+            let (current_key, current_val) = match x {
+                Err(key) => return Err(CheckBuilderErr::MalformedString(key)),
+                Ok(s) => s,
+            };
+            let sum_op = match current_key.as_str() {
+                "width" => usize::from_str(&current_val).ok().map(|x| sum.width(x)),
+                "module" => u64::from_hex(&current_val).ok().map(|x| sum.module(x)),
+                "init" => u64::from_hex(&current_val).ok().map(|x| sum.init(x)),
+                "weights" => {
+                    weights = Some(current_val);
+                    Some(&mut sum)
+                }
+                "name" => Some(sum.name(&current_val)),
+                _ => return Err(CheckBuilderErr::UnknownKey(current_key)),
+            };
+            match sum_op {
+                Some(s) => sum = s.clone(),
+                None => return Err(CheckBuilderErr::MalformedString(current_key)),
+            }
+        }
+        if let Some(w) = weights {
+            let width = sum
+                .width
+                .ok_or(CheckBuilderErr::MissingParameter("width"))?;
+            let w = parse_weights(&w, width)
+                .ok_or_else(|| CheckBuilderErr::MalformedString("weights".to_owned()))?;
+            sum.weights(w);
+        }
+        Ok(sum)
+    }
+    type Err = CheckBuilderErr;
+}
+
+impl FromStr for WeightSum {
+    /// Construct a new weighted sum from a string specification.
+    ///
+    /// Example:
+    ///
+    /// width=8 module=a init=0 weights=010301030103
+    fn from_str(s: &str) -> Result<WeightSum, CheckBuilderErr> {
+        WeightSumBuilder::from_str(s)?.build()
+    }
+    type Err = CheckBuilderErr;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn isbn() {
+        let isbn = WeightSum::from_str("width=8 module=b weights=0a090807060504030201").unwrap();
+        let text = b"0306406152";
+        let digits: Vec<_> = text.iter().map(|d| d - b'0').collect();
+        assert_eq!(isbn.checksum(&digits), Some(0));
+        // the ascii offset adds 0x30 * (10 + 9 + ... + 1) = 0x30 * 55, which is 0 mod 11
+        assert_eq!(isbn.checksum(text), Some(0));
+        assert_eq!(isbn.checksum(b"0306406153"), Some(1));
+        assert_eq!(isbn.checksum(&text[1..]), None);
+        assert_eq!(
+            isbn.to_string(),
+            "weightsum width=8 module=0xb init=0x0 weights=0a090807060504030201"
+        );
+        assert!(WeightSum::from_str("width=8 module=b weights=0b").is_err());
+        assert!(WeightSum::from_str("width=8 module=b weights=0").is_err());
+    }
+    #[test]
+    fn full_width() {
+        let sum = WeightSum::from_str("width=64 weights=ffffffffffffffff0000000000000002").unwrap();
+        assert_eq!(sum.checksum(&[2, 3]), Some(4));
+        assert_eq!(
+            sum.to_string(),
+            "weightsum width=64 module=0x0 init=0x0 weights=ffffffffffffffff0000000000000002"
+        );
+    }
+}
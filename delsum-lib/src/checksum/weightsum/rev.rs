@@ -0,0 +1,330 @@
+//! This module contains the function for finding the weights and module of a weighted sum.
+//!
+//! Each file with its checksum is a linear equation `init + sum(w_i * byte_i) = checksum (mod module)`,
+//! so with a known module, the weights and `init` can be found by solving the equations over the integers modulo `module`.
+//!
+//! If the module is not known, the equations are first brought into echelon form over the integers:
+//! the rows that end up with all coefficients zero then have a right side which is a multiple of the module,
+//! so the module has to be a divisor of the gcd of those, which is bigger than all checksums.
+//!
+//! To determine everything, at least `len + 2` files of length `len` are needed for an unknown module,
+//! but usually a few more than that, since for example ascii digits leave the lowest bits of the bytes without
+//! a lot of variation.
+use super::{WeightSum, WeightSumBuilder};
+use crate::checksum::CheckReverserError;
+use crate::factor::{divisors_range, mod_red, xgcd};
+use num_bigint::BigInt;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+
+/// Find the parameters of a weighted sum.
+///
+/// `spec` contains the known parameters of the algorithm (`width`, `module` and `init` can be given).
+/// `chk_bytes` are pairs of files and their checksums, which all need to have the same length.
+/// `verbosity` makes the function output what it is doing.
+///
+/// There is one result for each candidate module for which the weights are determined by the files.
+/// The `width` parameter of the builder has to be set.
+pub fn reverse_weightsum(
+    spec: &WeightSumBuilder,
+    chk_bytes: &[(&[u8], u128)],
+    verbosity: u64,
+) -> impl Iterator<Item = Result<WeightSum, CheckReverserError>> {
+    let res = match reverse(spec, chk_bytes, verbosity) {
+        Ok(sums) => sums,
+        Err(e) => vec![Err(e)],
+    };
+    res.into_iter()
+}
+
+fn reverse(
+    spec: &WeightSumBuilder,
+    chk_bytes: &[(&[u8], u128)],
+    verbosity: u64,
+) -> Result<Vec<Result<WeightSum, CheckReverserError>>, CheckReverserError> {
+    let log = |s: &str| {
+        if verbosity > 0 {
+            eprintln!("<weightsum> {}", s);
+        }
+    };
+    let width = spec
+        .width
+        .ok_or(CheckReverserError::MissingParameter("width"))?;
+    if width == 0 || width > 64 {
+        return Err(CheckReverserError::ValueOutOfRange("width"));
+    }
+    if spec.weights.is_some() {
+        return Err(CheckReverserError::ValueOutOfRange("weights"));
+    }
+    let len = match chk_bytes.first() {
+        Some((f, _)) => f.len(),
+        None => {
+            return Err(CheckReverserError::MissingParameter(
+                "need at least one file",
+            ))
+        }
+    };
+    if chk_bytes.iter().any(|(f, _)| f.len() != len) {
+        return Err(CheckReverserError::UnsuitableFiles(
+            "files need to have the same length",
+        ));
+    }
+    let max_module = 1u128 << width;
+    if chk_bytes.iter().any(|(_, s)| *s >= max_module) {
+        return Err(CheckReverserError::ChecksumFileMismatch);
+    }
+    // the unknowns are the weights followed by init, and the last column is the checksum
+    let mut rows: Vec<Vec<BigInt>> = chk_bytes
+        .iter()
+        .map(|(f, s)| {
+            f.iter()
+                .map(|b| BigInt::from(*b))
+                .chain([BigInt::one(), BigInt::from(*s)])
+                .collect()
+        })
+        .collect();
+    if let Some(init) = spec.init {
+        let mut row = vec![BigInt::zero(); len + 2];
+        row[len] = BigInt::one();
+        row[len + 1] = BigInt::from(init);
+        rows.push(row);
+    }
+    let modules = match spec.module {
+        Some(0) => vec![max_module],
+        Some(m) => vec![u128::from(m)],
+        None => {
+            log("finding the module");
+            let multiple = module_multiple(rows.clone(), len + 1);
+            let multiple = match multiple.to_u128() {
+                Some(m) if m != 0 => m,
+                _ => {
+                    return Err(CheckReverserError::UnsuitableFiles(
+                        "not enough files to determine the module",
+                    ))
+                }
+            };
+            log(&format!("module divides {:#x}", multiple));
+            let min_module = chk_bytes.iter().map(|(_, s)| *s).max().unwrap_or(0) + 1;
+            divisors_range(multiple, min_module, max_module)
+        }
+    };
+    let mut ret = Vec::new();
+    let mut ambiguous = false;
+    for module in modules {
+        log(&format!("solving for module {:#x}", module));
+        match solve_mod(&rows, len + 1, module) {
+            Solution::Unique(sol) => {
+                // a module of 2^width is given as 0
+                let module = (module % max_module) as u64;
+                let mut sum = WeightSum::with_options();
+                sum.width(width)
+                    .module(module)
+                    .init(sol[len] as u64)
+                    .weights(sol[..len].iter().map(|w| *w as u64).collect());
+                if let Some(n) = &spec.name {
+                    sum.name(n);
+                }
+                match sum.build() {
+                    Ok(s) => ret.push(Ok(s)),
+                    Err(_) => log("solution does not fit the width"),
+                }
+            }
+            Solution::Ambiguous => {
+                log("weights are not determined by the files");
+                ambiguous = true;
+            }
+            Solution::Inconsistent => log("files are not consistent"),
+        }
+    }
+    if ret.is_empty() && ambiguous {
+        return Err(CheckReverserError::UnsuitableFiles(
+            "not enough independent files to determine the weights",
+        ));
+    }
+    Ok(ret)
+}
+
+/// Brings the first `vars` columns into echelon form over the integers and returns
+/// the gcd of the right sides of the rows which are zero in those columns.
+///
+/// Only unimodular row operations are used, so every module for which the equations
+/// have a solution divides the result.
+fn module_multiple(mut rows: Vec<Vec<BigInt>>, vars: usize) -> BigInt {
+    let mut rank = 0;
+    for col in 0..vars {
+        // euclid's algorithm on the column, until only the pivot row is nonzero
+        loop {
+            let pivot = (rank..rows.len())
+                .filter(|&i| !rows[i][col].is_zero())
+                .min_by_key(|&i| rows[i][col].abs());
+            let pivot = match pivot {
+                Some(p) => p,
+                None => break,
+            };
+            rows.swap(rank, pivot);
+            let pivot_row = rows[rank].clone();
+            let mut done = true;
+            for row in rows[rank + 1..].iter_mut() {
+                if row[col].is_zero() {
+                    continue;
+                }
+                let q = &row[col] / &pivot_row[col];
+                for (x, p) in row[col..].iter_mut().zip(pivot_row[col..].iter()) {
+                    *x -= &q * p;
+                }
+                done &= row[col].is_zero();
+            }
+            if done {
+                rank += 1;
+                break;
+            }
+        }
+    }
+    rows[rank..]
+        .iter()
+        .fold(BigInt::zero(), |g, row| xgcd(&g, &row[vars]).0)
+}
+
+enum Solution {
+    Unique(Vec<u128>),
+    Ambiguous,
+    Inconsistent,
+}
+
+/// Solves the equations modulo `module` for the first `vars` columns.
+fn solve_mod(rows: &[Vec<BigInt>], vars: usize, module: u128) -> Solution {
+    let big_module = BigInt::from(module);
+    let mut rows: Vec<Vec<u128>> = rows
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|x| mod_red(x, &big_module).to_u128().unwrap())
+                .collect()
+        })
+        .collect();
+    // module is at most 2^64, so the products of reduced values fit
+    let sub_mul = |x: &mut u128, q: u128, y: u128| *x = (*x + module - q * y % module) % module;
+    let mut rank = 0;
+    let mut unique = true;
+    for col in 0..vars {
+        let mut found = false;
+        loop {
+            let pivot = (rank..rows.len())
+                .filter(|&i| rows[i][col] != 0)
+                .min_by_key(|&i| rows[i][col]);
+            let pivot = match pivot {
+                Some(p) => p,
+                None => break,
+            };
+            found = true;
+            rows.swap(rank, pivot);
+            let pivot_row = rows[rank].clone();
+            let mut done = true;
+            for row in rows[rank + 1..].iter_mut() {
+                if row[col] == 0 {
+                    continue;
+                }
+                let q = row[col] / pivot_row[col];
+                for (x, p) in row[col..].iter_mut().zip(pivot_row[col..].iter()) {
+                    sub_mul(x, q, *p);
+                }
+                done &= row[col] == 0;
+            }
+            if done {
+                break;
+            }
+        }
+        if !found {
+            unique = false;
+            continue;
+        }
+        let (g, (inv, _)) = xgcd(&BigInt::from(rows[rank][col]), &big_module);
+        if !g.is_one() {
+            // the pivot is a zero divisor, so there are multiple solutions if there are any
+            unique = false;
+            rank += 1;
+            continue;
+        }
+        let inv = mod_red(&inv, &big_module).to_u128().unwrap();
+        for x in rows[rank][col..].iter_mut() {
+            *x = *x * inv % module;
+        }
+        let pivot_row = rows[rank].clone();
+        for (i, row) in rows.iter_mut().enumerate() {
+            if i == rank || row[col] == 0 {
+                continue;
+            }
+            let q = row[col];
+            for (x, p) in row[col..].iter_mut().zip(pivot_row[col..].iter()) {
+                sub_mul(x, q, *p);
+            }
+        }
+        rank += 1;
+    }
+    if rows[rank..].iter().any(|row| row[vars] != 0) {
+        Solution::Inconsistent
+    } else if !unique {
+        Solution::Ambiguous
+    } else {
+        Solution::Unique(rows[..vars].iter().map(|row| row[vars]).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::prelude::*;
+    use std::str::FromStr;
+    fn files(sum: &WeightSum, count: usize, digits: bool) -> Vec<(Vec<u8>, u128)> {
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+        (0..count)
+            .map(|_| {
+                let file: Vec<u8> = (0..sum.text_len())
+                    .map(|_| match digits {
+                        true => b'0' + rng.gen_range(0, 10),
+                        false => rng.gen(),
+                    })
+                    .collect();
+                let check = u128::from(sum.checksum(&file).unwrap());
+                (file, check)
+            })
+            .collect()
+    }
+    fn reverse_all(spec: &WeightSumBuilder, files: &[(Vec<u8>, u128)]) -> Vec<WeightSum> {
+        let chk_bytes: Vec<_> = files.iter().map(|(f, s)| (f.as_slice(), *s)).collect();
+        reverse_weightsum(spec, &chk_bytes, 0)
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+    #[test]
+    fn ean() {
+        let ean = WeightSum::from_str("width=8 module=a weights=010301030103010301030103").unwrap();
+        let samples = files(&ean, 40, true);
+        let mut spec = WeightSum::with_options();
+        spec.width(8);
+        assert_eq!(reverse_all(&spec, &samples), vec![ean]);
+    }
+    #[test]
+    fn random_weights() {
+        let sum = WeightSum::from_str(
+            "width=32 module=fffffffb init=1234 weights=0badf00d00000007deadbeef12345678abcdef01",
+        )
+        .unwrap();
+        let samples = files(&sum, 12, false);
+        let mut spec = WeightSum::with_options();
+        spec.width(32);
+        assert_eq!(reverse_all(&spec, &samples), vec![sum.clone()]);
+        // with a known module, fewer files are enough
+        spec.module(0xfffffffb);
+        assert_eq!(reverse_all(&spec, &samples[..6]), vec![sum.clone()]);
+        spec.init(0x1234);
+        assert_eq!(reverse_all(&spec, &samples[..5]), vec![sum]);
+        let chk_bytes: Vec<_> = samples[..4]
+            .iter()
+            .map(|(f, s)| (f.as_slice(), *s))
+            .collect();
+        assert!(matches!(
+            reverse_weightsum(&spec, &chk_bytes, 0).next(),
+            Some(Err(CheckReverserError::UnsuitableFiles(_)))
+        ));
+    }
+}
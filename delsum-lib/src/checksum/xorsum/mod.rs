@@ -0,0 +1,321 @@
+//! A simple XOR over the bytes or words of a text, also known as longitudinal redundancy check (LRC)
+//!
+//! The text can also be xored as words of multiple bytes (e.g. `u16` in little endian),
+//! in which case a trailing partial word is padded with zero bytes.
+//! Words that are wider than the sum are cut off to their lowest `width` bits.
+//!
+//! There are a number of parameters:
+//! * width: The number of bits in the sum type, at most 64
+//! * init: The initial number
+//! * wordsize: The number of bits in a word that gets xored, a multiple of 8 (default 8)
+//! * endian: The byte order of the words, either `little` or `big` (default big)
+//! * check: The checksum of "123456789" (optional, gets checked at construction)
+//! * name: An optional name that gets used for display purposes
+//!
+//! Note that a parameter to xor at the end is not needed, since it is equivalent to `init`.
+pub mod rev;
+use crate::bitnum::BitNum;
+use crate::checksum::{CheckBuilderErr, Digest, LinearCheck};
+use crate::endian::{Endian, WordSpec};
+use crate::keyval::KeyValIter;
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// A builder to set the various parameters for the xorsum algorithm.
+///
+/// Example:
+/// ```
+/// # use delsum_lib::checksum::xorsum::XorSum;
+/// XorSum::<u8>::with_options()
+///     .width(8)
+///     .check(0x31)
+///     .build()
+///     .is_ok();
+/// ```
+#[derive(Debug, Clone)]
+pub struct XorSumBuilder<S: BitNum> {
+    width: Option<usize>,
+    init: Option<S>,
+    wordsize: Option<usize>,
+    endian: Option<Endian>,
+    check: Option<S>,
+    name: Option<String>,
+}
+
+impl<S: BitNum> XorSumBuilder<S> {
+    /// The width, in bits, of the sum. Mandatory.
+    pub fn width(&mut self, w: usize) -> &mut Self {
+        self.width = Some(w);
+        self
+    }
+    /// The initial value, optional, defaults to 0.
+    pub fn init(&mut self, i: S) -> &mut Self {
+        self.init = Some(i);
+        self
+    }
+    /// The number of bits of a word of the text, a multiple of 8, optional, defaults to 8.
+    pub fn wordsize(&mut self, w: usize) -> &mut Self {
+        self.wordsize = Some(w);
+        self
+    }
+    /// The byte order of the words of the text, optional, defaults to big endian.
+    pub fn endian(&mut self, e: Endian) -> &mut Self {
+        self.endian = Some(e);
+        self
+    }
+    /// The checksum of "123456789", gets checked on creation.
+    pub fn check(&mut self, c: S) -> &mut Self {
+        self.check = Some(c);
+        self
+    }
+    /// An optional name that gets used for display purposes.
+    pub fn name(&mut self, n: &str) -> &mut Self {
+        self.name = Some(String::from(n));
+        self
+    }
+    /// Builds the algorithm, after validating the parameters.
+    pub fn build(&self) -> Result<XorSum<S>, CheckBuilderErr> {
+        let width = self
+            .width
+            .ok_or(CheckBuilderErr::MissingParameter("width"))?;
+        if width == 0 || width > S::zero().bits() {
+            return Err(CheckBuilderErr::ValueOutOfRange("width"));
+        }
+        let mask = if width < S::zero().bits() {
+            (S::one() << width) - S::one()
+        } else {
+            !S::zero()
+        };
+        let init = self.init.unwrap_or_else(S::zero);
+        if init & !mask != S::zero() {
+            return Err(CheckBuilderErr::ValueOutOfRange("init"));
+        }
+        let wordspec = WordSpec::new(
+            self.wordsize.unwrap_or(8),
+            self.endian.unwrap_or(Endian::Big),
+        )?;
+        let s = XorSum {
+            width,
+            mask,
+            init,
+            wordspec,
+            name: self.name.clone(),
+        };
+        match self.check {
+            Some(c) => {
+                if s.digest(&b"123456789"[..]).unwrap() == c {
+                    Ok(s)
+                } else {
+                    Err(CheckBuilderErr::CheckFail)
+                }
+            }
+            None => Ok(s),
+        }
+    }
+    /// The same partially known algorithm for the lowest `bits` bits of the sums (see `LinearCheck::truncated`).
+    pub(crate) fn truncated(&self, bits: usize) -> Self {
+        let mask = if bits < S::zero().bits() {
+            (S::one() << bits) - S::one()
+        } else {
+            !S::zero()
+        };
+        XorSumBuilder {
+            width: Some(bits),
+            init: self.init.map(|i| i & mask),
+            check: self.check.map(|c| c & mask),
+            name: None,
+            ..self.clone()
+        }
+    }
+}
+
+/// A XorSum checksum algorithm.
+///
+/// Implements LinearCheck so that finding checksummed locations in a file is efficiently possible.
+#[derive(Debug, PartialEq, Eq)]
+pub struct XorSum<S: BitNum> {
+    width: usize,
+    mask: S,
+    init: S,
+    wordspec: WordSpec,
+    name: Option<String>,
+}
+
+impl<S: BitNum> XorSum<S> {
+    /// Creates a `XorSumBuilder`, for more information see its documentation.
+    pub fn with_options() -> XorSumBuilder<S> {
+        XorSumBuilder {
+            width: None,
+            init: None,
+            wordsize: None,
+            endian: None,
+            check: None,
+            name: None,
+        }
+    }
+    /// Cuts a word of the text off to the width of the sum.
+    fn reduce_word(&self, word: u64) -> S {
+        match S::from_u64(word) {
+            Some(w) => w & self.mask,
+            None => S::from_u64(word & (u64::MAX >> (64 - self.width))).unwrap(),
+        }
+    }
+}
+
+impl<Sum: BitNum> Display for XorSum<Sum> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.name {
+            Some(n) => write!(f, "{}", n),
+            None => {
+                write!(f, "xorsum width={} init={:#x}", self.width, self.init)?;
+                if self.wordspec.wordsize != 8 {
+                    write!(
+                        f,
+                        " wordsize={} endian={}",
+                        self.wordspec.wordsize, self.wordspec.endian
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<Sum: BitNum> FromStr for XorSumBuilder<Sum> {
+    /// See FromStr for XorSum<Sum>
+    fn from_str(s: &str) -> Result<XorSumBuilder<Sum>, CheckBuilderErr> {
+        let mut sum = XorSum::<Sum>::with_options();
+        for x in KeyValIter::new(s) {
+            let (current_key, current_val) = match x {
+                Err(key) => return Err(CheckBuilderErr::MalformedString(key)),
+                Ok(s) => s,
+            };
+            let xor_op = match current_key.as_str() {
+                "width" => usize::from_str(&current_val).ok().map(|x| sum.width(x)),
+                "init" => Sum::from_hex(&current_val).ok().map(|x| sum.init(x)),
+                "wordsize" => usize::from_str(&current_val).ok().map(|x| sum.wordsize(x)),
+                "endian" => Endian::from_str(&current_val).ok().map(|x| sum.endian(x)),
+                "check" => Sum::from_hex(&current_val).ok().map(|x| sum.check(x)),
+                "name" => Some(sum.name(&current_val)),
+                _ => return Err(CheckBuilderErr::UnknownKey(current_key)),
+            };
+            match xor_op {
+                Some(c) => sum = c.clone(),
+                None => return Err(CheckBuilderErr::MalformedString(current_key)),
+            }
+        }
+        Ok(sum)
+    }
+    type Err = CheckBuilderErr;
+}
+
+impl<Sum: BitNum> FromStr for XorSum<Sum> {
+    /// Construct a new xor sum from a string specification.
+    ///
+    /// Example:
+    ///
+    /// width=16 init=0 wordsize=16 endian=little
+    fn from_str(s: &str) -> Result<XorSum<Sum>, CheckBuilderErr> {
+        XorSumBuilder::from_str(s)?.build()
+    }
+    type Err = CheckBuilderErr;
+}
+
+impl<S: BitNum> Digest for XorSum<S> {
+    type Sum = S;
+    fn init(&self) -> Self::Sum {
+        self.init
+    }
+    fn dig_byte(&self, sum: Self::Sum, byte: u8) -> Self::Sum {
+        self.dig_word(sum, u64::from(byte))
+    }
+    fn dig_word(&self, sum: Self::Sum, word: u64) -> Self::Sum {
+        sum ^ self.reduce_word(word)
+    }
+    fn wordspec(&self) -> WordSpec {
+        self.wordspec
+    }
+    fn finalize(&self, sum: Self::Sum) -> Self::Sum {
+        sum
+    }
+}
+
+impl<S: BitNum> LinearCheck for XorSum<S> {
+    type Shift = ();
+    // shifts are trivial in this checksum type
+    fn init_shift(&self) -> Self::Shift {}
+    fn inc_shift(&self, _: Self::Shift) -> Self::Shift {}
+    fn shift(&self, sum: Self::Sum, _: &Self::Shift) -> Self::Sum {
+        sum
+    }
+    fn shift_n(&self, _: usize) -> Self::Shift {}
+    fn add(&self, sum_a: Self::Sum, sum_b: &Self::Sum) -> Self::Sum {
+        sum_a ^ *sum_b
+    }
+    fn negate(&self, sum: Self::Sum) -> Self::Sum {
+        sum
+    }
+    fn truncated(&self, bits: usize) -> Option<Self> {
+        let mask = if bits < S::zero().bits() {
+            (S::one() << bits) - S::one()
+        } else {
+            !S::zero()
+        };
+        Some(XorSum {
+            width: bits,
+            mask,
+            init: self.init & mask,
+            wordspec: self.wordspec,
+            name: None,
+        })
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::tests::{test_prop, test_shifts};
+    use crate::checksum::{RelativeIndex, Relativity};
+    #[test]
+    fn lrc() {
+        let s = XorSum::<u8>::with_options()
+            .width(8)
+            .check(0x31)
+            .build()
+            .unwrap();
+        test_shifts(&s);
+        test_prop(&s);
+        // NMEA sentence checksum
+        let nmea = b"GPGLL,5300.97914,N,00259.98174,E,125926,A";
+        assert_eq!(s.digest(&nmea[..]).unwrap(), 0x28);
+        let s = XorSum::<u8>::from_str("width=4 init=a").unwrap();
+        assert_eq!(s.digest(&[0x12u8, 0x34][..]).unwrap(), 0xc);
+        test_prop(&s);
+    }
+    #[test]
+    fn words() {
+        let chk = XorSum::<u16>::from_str("width=16 wordsize=16 endian=little").unwrap();
+        assert_eq!(chk.digest(&[1u8, 2, 3][..]).unwrap(), 0x0202);
+        test_prop(&chk);
+        let x = Vec::from("implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR");
+        let y = Vec::from("This program comes with ABSOLUTELY NO WARRANTY; for details typ");
+        let merchantibility = chk.digest(b"MERCHANTABILITY".as_ref()).unwrap();
+        let ith_absolutely_ = chk.digest(b"ith ABSOLUTELY ".as_ref()).unwrap();
+        assert_eq!(
+            chk.find_segments(
+                &[x, y],
+                &[merchantibility, ith_absolutely_],
+                Relativity::Start
+            ),
+            vec![(vec![20], vec![RelativeIndex::FromStart(35)])]
+        );
+        let chk = XorSum::<u32>::from_str("width=32 wordsize=32 endian=big").unwrap();
+        assert_eq!(
+            chk.to_string(),
+            "xorsum width=32 init=0x0 wordsize=32 endian=big"
+        );
+        assert_eq!(
+            chk.digest(&b"123456789"[..]).unwrap(),
+            0x31323334 ^ 0x35363738 ^ 0x39000000
+        );
+    }
+}
@@ -0,0 +1,170 @@
+//! This module contains the function(s) for reversing the parameters for a xor sum.
+//!
+//! The only unknown parameter is `init`, which is determined by a single file with its checksum.
+//! Additional files are used to verify the result.
+//!
+//! If `wordsize` is not given, every word size up to the width is tried.
+//! For xors over words bigger than a byte, both byte orders are tried if `endian` is not given,
+//! and each result contains the word size and byte order for which it fits.
+use super::{XorSum, XorSumBuilder};
+use crate::checksum::{unresult_iter, CheckReverserError};
+use crate::endian::{Endian, WordSpec};
+use std::iter::Iterator;
+/// Find the parameters of a xorsum algorithm.
+///
+/// `spec` contains the known parameters of the algorithm (by setting the corresponding values in the builder).
+/// `chk_bytes` are pairs of files and their checksums.
+/// `verbosity` makes the function output what it is doing
+///
+/// The `width` parameter of the builder has to be set.
+pub fn reverse_xorsum(
+    spec: &XorSumBuilder<u64>,
+    chk_bytes: &[(&[u8], u128)],
+    verbosity: u64,
+) -> impl Iterator<Item = Result<XorSum<u64>, CheckReverserError>> {
+    let wordsizes: Vec<_> = match spec.wordsize {
+        Some(w) => vec![w],
+        None => (8..=spec.width.unwrap_or(8).clamp(8, 64))
+            .step_by(8)
+            .collect(),
+    };
+    let res: Vec<_> = wordsizes
+        .into_iter()
+        .flat_map(|w| {
+            let endians = Endian::to_try(spec.endian, Some(w));
+            endians.into_iter().map(move |e| (w, e))
+        })
+        .map(|(w, e)| reverse(spec, chk_bytes, w, e, verbosity).map(|x| x.into_iter()))
+        .collect();
+    res.into_iter().flat_map(unresult_iter)
+}
+
+// The xor of all words of a file together with the init value is the checksum,
+// so the init value is just the checksum xor the xor of all words.
+// It has to be the same for every file, otherwise there is no solution.
+fn reverse(
+    spec: &XorSumBuilder<u64>,
+    chk_bytes: &[(&[u8], u128)],
+    wordsize: usize,
+    endian: Endian,
+    verbosity: u64,
+) -> Result<Option<XorSum<u64>>, CheckReverserError> {
+    let log = |s| {
+        if verbosity > 0 {
+            eprintln!("<xorsum> {}", s);
+        }
+    };
+    let width = spec
+        .width
+        .ok_or(CheckReverserError::MissingParameter("width"))?;
+    if width == 0 || width > 64 {
+        return Err(CheckReverserError::ValueOutOfRange("width"));
+    }
+    let wordspec = WordSpec::new(wordsize, endian)
+        .map_err(|_| CheckReverserError::ValueOutOfRange("wordsize"))?;
+    let mask = u64::MAX >> (64 - width);
+    let mut init = spec.init;
+    if init.is_none() && chk_bytes.is_empty() {
+        return Err(CheckReverserError::MissingParameter(
+            "need at least one file or init",
+        ));
+    }
+    log("xoring files");
+    for (f, chk) in chk_bytes {
+        let words = wordspec.iter_words(f).fold(0, |acc, w| acc ^ (w & mask));
+        let current_init = (*chk as u64 ^ words) & mask;
+        match init {
+            None => init = Some(current_init),
+            Some(i) if i != current_init => {
+                log("files have different init values");
+                return Ok(None);
+            }
+            Some(_) => (),
+        }
+    }
+    let mut xorsum = XorSum::with_options();
+    xorsum
+        .width(width)
+        .init(init.unwrap_or(0))
+        .wordsize(wordspec.wordsize)
+        .endian(wordspec.endian);
+    Ok(xorsum.build().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::Digest;
+    use quickcheck::{Arbitrary, TestResult};
+    use std::str::FromStr;
+    impl Arbitrary for XorSumBuilder<u64> {
+        fn arbitrary<G: quickcheck::Gen>(g: &mut G) -> Self {
+            let mut new_xorsum = XorSum::with_options();
+            let width = u8::arbitrary(g) % 64 + 1;
+            new_xorsum.width(width as usize);
+            new_xorsum.init(u64::arbitrary(g) & (u64::MAX >> (64 - width)));
+            new_xorsum.wordsize(8 << (u8::arbitrary(g) % 4));
+            new_xorsum.endian(if bool::arbitrary(g) {
+                Endian::Little
+            } else {
+                Endian::Big
+            });
+            new_xorsum
+        }
+    }
+
+    #[quickcheck]
+    fn qc_xorsum_rev(files: Vec<Vec<u8>>, xorsum_build: XorSumBuilder<u64>) -> TestResult {
+        if files.is_empty() {
+            return TestResult::discard();
+        }
+        let xorsum = xorsum_build.build().unwrap();
+        let mut naive = XorSum::<u64>::with_options();
+        naive.width(xorsum_build.width.unwrap());
+        naive.wordsize(xorsum_build.wordsize.unwrap());
+        let chk_files: Vec<_> = files
+            .iter()
+            .map(|f| {
+                let checksum = xorsum.digest(f.as_slice()).unwrap();
+                (f.as_slice(), checksum as u128)
+            })
+            .collect();
+        let mut has_appeared = false;
+        for xorsum_loop in reverse_xorsum(&naive, &chk_files, 0) {
+            let xorsum_loop = match xorsum_loop {
+                Err(_) => return TestResult::failed(),
+                Ok(x) => x,
+            };
+            if xorsum_loop == xorsum {
+                has_appeared = true;
+            }
+            for (file, original_check) in &chk_files {
+                let checksum = xorsum_loop.digest(*file).unwrap();
+                if checksum as u128 != *original_check {
+                    eprintln!("expected checksum: {:x}", original_check);
+                    eprintln!("actual checksum: {:x}", checksum);
+                    eprintln!("xorsum: {}", xorsum_loop);
+                    return TestResult::failed();
+                }
+            }
+        }
+        TestResult::from_bool(has_appeared)
+    }
+
+    #[test]
+    fn unknown_wordsize() {
+        let xorsum =
+            XorSum::<u64>::from_str("width=16 init=0x1234 wordsize=16 endian=little").unwrap();
+        let files = [&b"\x01\x02\x03\x04\x05\x06"[..], &b"\x10\x20\x30\x40"[..]];
+        let chk_files: Vec<_> = files
+            .iter()
+            .map(|f| (*f, xorsum.digest(*f).unwrap() as u128))
+            .collect();
+        let mut spec = XorSum::with_options();
+        spec.width(16);
+        let found: Vec<_> = reverse_xorsum(&spec, &chk_files, 0)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(found, vec![xorsum]);
+    }
+}
@@ -0,0 +1,333 @@
+//! Checksums which are stored inside of the files they are calculated over.
+//!
+//! Firmware images and similar files often carry their own checksum, for example in the
+//! last 4 bytes or at a fixed offset in a header.
+//! An `EmbeddedSum` describes where the checksum is stored, so that it can be read from each
+//! file and taken out of the data that was checksummed, either by cutting it out or, if the checksum
+//! was calculated with a placeholder in its place, by overwriting it with a fill byte.
+use crate::checksum::{CheckBuilderErr, RangePairs, RelativeIndex};
+use crate::endian::Endian;
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// The maximal length of an embedded checksum in bytes, which is the biggest supported width
+const MAX_LEN: usize = 64;
+
+/// The location of a checksum inside of a file.
+///
+/// It is parsed from a string `<offset>:<len>:<endian>`, where `offset` is decimal or hexadecimal
+/// with a `0x` prefix, and counts from the end of the file if it is negative.
+/// For example, `-4:4:little` is a 32-bit little endian checksum in the last 4 bytes, and
+/// `0x1c:2:big` is a 16-bit big endian checksum at offset 0x1c.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EmbeddedSum {
+    pub offset: RelativeIndex,
+    /// The length of the checksum in bytes
+    pub len: usize,
+    pub endian: Endian,
+    /// If set, the bytes of the checksum are overwritten with this byte instead of being removed from the data
+    pub fill: Option<u8>,
+}
+
+impl EmbeddedSum {
+    /// The position of the checksum in a file of length `file_len`, or `None` if it does not fit.
+    pub fn position(&self, file_len: usize) -> Option<usize> {
+        let start = match self.offset {
+            RelativeIndex::FromStart(n) => n,
+            RelativeIndex::FromEnd(n) => file_len.checked_sub(n)?,
+        };
+        match start.checked_add(self.len) {
+            Some(end) if end <= file_len => Some(start),
+            _ => None,
+        }
+    }
+    /// Reads the checksum of a file in hex, or `None` if it is not inside of the file.
+    pub fn read(&self, file: &[u8]) -> Option<String> {
+        let start = self.position(file.len())?;
+        let sum_bytes = &file[start..start + self.len];
+        let hex = |b: &u8| format!("{:02x}", b);
+        Some(match self.endian {
+            Endian::Big => sum_bytes.iter().map(hex).collect(),
+            Endian::Little => sum_bytes.iter().rev().map(hex).collect(),
+        })
+    }
+    /// Splits a file into the data the checksum is calculated over and the checksum in hex.
+    ///
+    /// Returns `None` if the checksum is not inside of the file.
+    pub fn split(&self, file: &[u8]) -> Option<(Vec<u8>, String)> {
+        let checksum = self.read(file)?;
+        let start = self.position(file.len())?;
+        let end = start + self.len;
+        let mut data = file.to_vec();
+        match self.fill {
+            Some(byte) => data[start..end].iter_mut().for_each(|b| *b = byte),
+            None => drop(data.drain(start..end)),
+        }
+        Some((data, checksum))
+    }
+    /// Maps segments found in the `data` returned by `split` back to positions in the files.
+    ///
+    /// If the checksum was cut out of the data, positions at or behind it are moved behind the checksum,
+    /// and a segment is dropped if it contains the checksum or does not end up at the same position in every file.
+    /// If `bits` is set, the positions are bit positions, like from `find_checksum_segments_bits`.
+    pub fn file_segments<B: AsRef<[u8]>>(
+        &self,
+        segs: RangePairs,
+        data: &[B],
+        bits: bool,
+    ) -> RangePairs {
+        if self.fill.is_some() {
+            return segs;
+        }
+        let unit = if bits { 8 } else { 1 };
+        let len = unit * self.len;
+        // the position of the checksum in each file, which is also where it got cut out of the data
+        let positions: Vec<_> = data
+            .iter()
+            .map(|d| {
+                let pos = self
+                    .position(d.as_ref().len() + self.len)
+                    .expect("Data was not split off a file with this checksum");
+                unit * pos
+            })
+            .collect();
+        let map_end = |start: usize, end: RelativeIndex| {
+            let ends = data.iter().zip(positions.iter()).map(|(d, &pos)| {
+                let end_pos = match end {
+                    RelativeIndex::FromStart(n) => n,
+                    RelativeIndex::FromEnd(n) => unit * d.as_ref().len() - n,
+                };
+                if start < pos && pos < end_pos {
+                    return None;
+                }
+                let end_pos = if end_pos > pos {
+                    end_pos + len
+                } else {
+                    end_pos
+                };
+                Some(match end {
+                    RelativeIndex::FromStart(_) => RelativeIndex::FromStart(end_pos),
+                    RelativeIndex::FromEnd(_) => {
+                        RelativeIndex::FromEnd(unit * d.as_ref().len() + len - end_pos)
+                    }
+                })
+            });
+            same(ends.collect::<Option<_>>()?)
+        };
+        let mut mapped: RangePairs = Vec::new();
+        for (starts, ends) in segs {
+            let first = mapped.len();
+            for start in starts {
+                let file_starts = positions.iter().map(|&pos| match start >= pos {
+                    true => start + len,
+                    false => start,
+                });
+                let file_start = match same(file_starts.collect()) {
+                    Some(s) => s,
+                    None => continue,
+                };
+                let file_ends: Vec<_> = ends.iter().filter_map(|e| map_end(start, *e)).collect();
+                if file_ends.is_empty() {
+                    continue;
+                }
+                // starts with the same ends stay together, like in the segments that were given
+                match mapped[first..].last_mut() {
+                    Some((s, e)) if *e == file_ends => s.push(file_start),
+                    _ => mapped.push((vec![file_start], file_ends)),
+                }
+            }
+        }
+        mapped
+    }
+}
+
+/// Gets the value if all `values` are the same.
+fn same<T: PartialEq + Copy>(mut values: Vec<T>) -> Option<T> {
+    values.dedup();
+    match values.as_slice() {
+        [value] => Some(*value),
+        _ => None,
+    }
+}
+
+impl FromStr for EmbeddedSum {
+    type Err = CheckBuilderErr;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        let mut next = |name: &str| {
+            parts
+                .next()
+                .ok_or_else(|| CheckBuilderErr::MalformedString(name.to_owned()))
+        };
+        let (offset, len, endian) = (next("offset")?, next("len")?, next("endian")?);
+        if parts.next().is_some() {
+            return Err(CheckBuilderErr::MalformedString(String::new()));
+        }
+        let (negative, offset) = match offset.strip_prefix('-') {
+            Some(o) => (true, o),
+            None => (false, offset),
+        };
+        let offset = match offset.strip_prefix("0x") {
+            Some(hex) => usize::from_str_radix(hex, 16),
+            None => usize::from_str(offset),
+        }
+        .map_err(|_| CheckBuilderErr::MalformedString("offset".to_owned()))?;
+        let offset = match negative {
+            true => RelativeIndex::FromEnd(offset),
+            false => RelativeIndex::FromStart(offset),
+        };
+        let len =
+            usize::from_str(len).map_err(|_| CheckBuilderErr::MalformedString("len".to_owned()))?;
+        if len == 0 || len > MAX_LEN {
+            return Err(CheckBuilderErr::ValueOutOfRange("len"));
+        }
+        Ok(EmbeddedSum {
+            offset,
+            len,
+            endian: Endian::from_str(endian)?,
+            fill: None,
+        })
+    }
+}
+
+impl Display for EmbeddedSum {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.offset {
+            RelativeIndex::FromStart(n) => write!(f, "{:#x}", n)?,
+            RelativeIndex::FromEnd(n) => write!(f, "-{:#x}", n)?,
+        }
+        write!(f, ":{}:{}", self.len, self.endian)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::Relativity;
+    use crate::{find_algorithm, find_checksum, find_checksum_segments};
+    #[test]
+    fn parse() {
+        let at = EmbeddedSum::from_str("0x1c:2:big").unwrap();
+        assert_eq!(at.offset, RelativeIndex::FromStart(0x1c));
+        assert_eq!(at.to_string(), "0x1c:2:big");
+        let at = EmbeddedSum::from_str("-4:4:little").unwrap();
+        assert_eq!(at.offset, RelativeIndex::FromEnd(4));
+        assert_eq!(at.to_string(), "-0x4:4:little");
+        assert!(EmbeddedSum::from_str("4:0:big").is_err());
+        assert!(EmbeddedSum::from_str("4:2").is_err());
+        assert!(EmbeddedSum::from_str("4:2:big:1").is_err());
+        assert!(EmbeddedSum::from_str("x:2:big").is_err());
+        assert!(EmbeddedSum::from_str("4:2:middle").is_err());
+    }
+    #[test]
+    fn split() {
+        let file = b"\x01\x02\x03\x04\x05\x06";
+        let mut at = EmbeddedSum::from_str("-2:2:little").unwrap();
+        assert_eq!(at.split(file), Some((vec![1, 2, 3, 4], "0605".to_owned())));
+        at.offset = RelativeIndex::FromStart(1);
+        at.endian = Endian::Big;
+        assert_eq!(at.split(file), Some((vec![1, 4, 5, 6], "0203".to_owned())));
+        at.fill = Some(0);
+        assert_eq!(
+            at.split(file),
+            Some((vec![1, 0, 0, 4, 5, 6], "0203".to_owned()))
+        );
+        at.offset = RelativeIndex::FromStart(5);
+        assert_eq!(at.split(file), None);
+        at.offset = RelativeIndex::FromEnd(7);
+        assert_eq!(at.split(file), None);
+    }
+    #[test]
+    fn reverse_embedded() {
+        let model = "modsum width=16 module=0xfff1 init=0x1";
+        let data: Vec<Vec<u8>> = (1..=4usize)
+            .map(|n| {
+                let mut d: Vec<u8> = (0..300 * n).map(|i| (i * n) as u8).collect();
+                d[..2].copy_from_slice(&[0, 0]);
+                d
+            })
+            .collect();
+        let sums = find_checksum(model, &data).unwrap();
+        // the checksum is in the first two bytes, little endian, and was calculated with zeroes there
+        let files: Vec<Vec<u8>> = data
+            .iter()
+            .zip(sums.iter())
+            .map(|(d, s)| {
+                let sum = u16::from_str_radix(s, 16).unwrap();
+                let mut file = d.clone();
+                file.splice(..2, sum.to_le_bytes().iter().copied());
+                file
+            })
+            .collect();
+        let mut at = EmbeddedSum::from_str("0:2:little").unwrap();
+        at.fill = Some(0);
+        let (data, sums): (Vec<_>, Vec<_>) = files.iter().map(|f| at.split(f).unwrap()).unzip();
+        let slices: Vec<_> = data.iter().map(Vec::as_slice).collect();
+        let found: Vec<_> = find_algorithm("modsum width=16", &slices, &sums.join(","), 0)
+            .unwrap()
+            .find_all()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(found, vec![model]);
+    }
+    #[test]
+    fn segments_in_file() {
+        let model =
+            "crc width=32 poly=0x04c11db7 init=0xffffffff xorout=0xffffffff refin=true refout=true";
+        // a 28 byte header, the checksum of the rest and then the data
+        let at = EmbeddedSum::from_str("0x1c:4:big").unwrap();
+        let bodies: Vec<Vec<u8>> = (1..=2usize)
+            .map(|n| (0..200 * n).map(|i| (i * n) as u8).collect())
+            .collect();
+        let sums = find_checksum(model, &bodies).unwrap();
+        let files: Vec<Vec<u8>> = bodies
+            .iter()
+            .zip(sums.iter())
+            .map(|(body, sum)| {
+                let mut file = vec![0x55u8; 0x1c];
+                file.extend(u32::from_str_radix(sum, 16).unwrap().to_be_bytes().iter());
+                file.extend(body);
+                file
+            })
+            .collect();
+        let (data, sums): (Vec<_>, Vec<_>) = files.iter().map(|f| at.split(f).unwrap()).unzip();
+        let segs = find_checksum_segments(model, &data, &sums.join(","), Relativity::End).unwrap();
+        assert_eq!(segs, vec![(vec![28], vec![RelativeIndex::FromEnd(0)])]);
+        assert_eq!(
+            at.file_segments(segs, &data, false),
+            vec![(vec![32], vec![RelativeIndex::FromEnd(0)])]
+        );
+        // segments containing the checksum are dropped
+        let segs = vec![(
+            vec![10],
+            vec![RelativeIndex::FromStart(20), RelativeIndex::FromStart(30)],
+        )];
+        assert_eq!(
+            at.file_segments(segs.clone(), &data, false),
+            vec![(vec![10], vec![RelativeIndex::FromStart(20)])]
+        );
+        assert_eq!(
+            at.file_segments(segs, &data, true),
+            vec![(
+                vec![10],
+                vec![RelativeIndex::FromStart(20), RelativeIndex::FromStart(30)]
+            )]
+        );
+        // with the checksum counted from the end, it is at different positions in the data
+        let at = EmbeddedSum::from_str("-8:4:big").unwrap();
+        let data = [vec![0u8; 100], vec![0u8; 200]];
+        let segs = vec![(
+            vec![50],
+            vec![RelativeIndex::FromEnd(2), RelativeIndex::FromEnd(10)],
+        )];
+        assert_eq!(
+            at.file_segments(segs.clone(), &data, false),
+            vec![(vec![50], vec![RelativeIndex::FromEnd(14)])]
+        );
+        let segs_start = vec![(vec![98], vec![RelativeIndex::FromStart(99)])];
+        assert_eq!(at.file_segments(segs_start, &data, false), vec![]);
+        let mut at = at;
+        at.fill = Some(0);
+        assert_eq!(at.file_segments(segs.clone(), &data, false), segs);
+    }
+}
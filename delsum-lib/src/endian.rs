@@ -0,0 +1,161 @@
+//! Helpers for reading the text as a sequence of words of some size and byte order,
+//! as opposed to just single bytes.
+use crate::checksum::CheckBuilderErr;
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// The byte order of a word.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Endian {
+    Little,
+    Big,
+}
+
+impl Endian {
+    /// Both byte orders, useful for trying out both when reversing.
+    pub fn both() -> Vec<Endian> {
+        vec![Endian::Little, Endian::Big]
+    }
+    /// The byte orders to try when reversing, given the `endian` and `wordsize` parameters.
+    ///
+    /// If no byte order is given, both are tried, except for single bytes, where it does not matter.
+    pub fn to_try(endian: Option<Endian>, wordsize: Option<usize>) -> Vec<Endian> {
+        match (endian, wordsize) {
+            (Some(e), _) => vec![e],
+            (None, None) | (None, Some(8)) => vec![Endian::Big],
+            (None, Some(_)) => Endian::both(),
+        }
+    }
+}
+
+impl FromStr for Endian {
+    type Err = CheckBuilderErr;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "little" => Ok(Endian::Little),
+            "big" => Ok(Endian::Big),
+            _ => Err(CheckBuilderErr::MalformedString(String::from("endian"))),
+        }
+    }
+}
+
+impl Display for Endian {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Endian::Little => write!(f, "little"),
+            Endian::Big => write!(f, "big"),
+        }
+    }
+}
+
+/// Describes how a text is split up into words.
+///
+/// `wordsize` is in bits and has to be a multiple of 8, at most 64.
+/// If the length of the text is not a multiple of the word size, the last word
+/// is padded with zero bytes at its end (so that, for example, the byte `0x12` as a
+/// partial 16-bit word is `0x1200` in big endian and `0x0012` in little endian).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WordSpec {
+    pub wordsize: usize,
+    pub endian: Endian,
+}
+
+impl Default for WordSpec {
+    fn default() -> Self {
+        WordSpec {
+            wordsize: 8,
+            endian: Endian::Big,
+        }
+    }
+}
+
+impl WordSpec {
+    /// Creates a new `WordSpec`, checking that the word size is valid.
+    pub fn new(wordsize: usize, endian: Endian) -> Result<Self, CheckBuilderErr> {
+        if wordsize == 0 || wordsize > 64 || wordsize & 7 != 0 {
+            return Err(CheckBuilderErr::ValueOutOfRange("wordsize"));
+        }
+        // the endian does not matter for single bytes, so we normalize it here
+        // for the sake of having equal values compare equal
+        let endian = if wordsize == 8 { Endian::Big } else { endian };
+        Ok(WordSpec { wordsize, endian })
+    }
+    /// The number of bytes in a word.
+    pub fn word_bytes(&self) -> usize {
+        self.wordsize / 8
+    }
+    /// Converts up to `word_bytes()` bytes into a word, padding missing bytes with zeros.
+    pub fn bytes_to_word(&self, bytes: &[u8]) -> u64 {
+        let wb = self.word_bytes();
+        let mut word = 0u64;
+        for i in 0..wb {
+            let byte = bytes.get(i).copied().unwrap_or(0) as u64;
+            match self.endian {
+                Endian::Big => word |= byte << (8 * (wb - 1 - i)),
+                Endian::Little => word |= byte << (8 * i),
+            }
+        }
+        word
+    }
+    /// Iterates over the words of a text, with the last word being zero-padded if needed.
+    pub fn iter_words<'a>(&self, bytes: &'a [u8]) -> impl Iterator<Item = u64> + 'a {
+        let spec = *self;
+        bytes
+            .chunks(self.word_bytes())
+            .map(move |chunk| spec.bytes_to_word(chunk))
+    }
+    /// Interprets a word as a two's complement number of `wordsize` bits.
+    pub fn to_signed(&self, word: u64) -> i64 {
+        let shift = 64 - self.wordsize;
+        ((word << shift) as i64) >> shift
+    }
+    /// Iterates over the values of the words of a text, which are sign-extended if `signed` is set.
+    pub fn iter_values<'a>(
+        &self,
+        bytes: &'a [u8],
+        signed: bool,
+    ) -> impl Iterator<Item = i128> + 'a {
+        let spec = *self;
+        self.iter_words(bytes).map(move |word| match signed {
+            true => i128::from(spec.to_signed(word)),
+            false => i128::from(word),
+        })
+    }
+    /// The number of words in a text of `len` bytes (including the padded one).
+    pub fn word_count(&self, len: usize) -> usize {
+        if len == 0 {
+            0
+        } else {
+            (len - 1) / self.word_bytes() + 1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn words() {
+        let le = WordSpec::new(16, Endian::Little).unwrap();
+        let be = WordSpec::new(16, Endian::Big).unwrap();
+        let text = [0x12u8, 0x34, 0x56];
+        assert_eq!(le.iter_words(&text).collect::<Vec<_>>(), vec![0x3412, 0x56]);
+        assert_eq!(
+            be.iter_words(&text).collect::<Vec<_>>(),
+            vec![0x1234, 0x5600]
+        );
+        assert_eq!(be.word_count(3), 2);
+        assert_eq!(
+            le.iter_values(&text, true).collect::<Vec<_>>(),
+            vec![0x3412, 0x56]
+        );
+        assert_eq!(
+            WordSpec::default()
+                .iter_values(&[0x7f, 0x80, 0xff], true)
+                .collect::<Vec<_>>(),
+            vec![127, -128, -1]
+        );
+        assert!(WordSpec::new(12, Endian::Big).is_err());
+        assert!(WordSpec::new(72, Endian::Big).is_err());
+    }
+}
@@ -1,6 +1,8 @@
 use crate::bitnum::BitNum;
 use bitvec::prelude::*;
 use lazy_static::lazy_static;
+use num_bigint::BigInt;
+use num_traits::{one, zero, Signed, Zero};
 use rand::prelude::*;
 use std::cmp::Ordering;
 use std::convert::TryFrom;
@@ -704,6 +706,47 @@ pub fn divisors_range(number: u128, low: u128, high: u128) -> Vec<u128> {
     divs
 }
 
+// modular reduction, because % is just wrong
+pub(crate) fn mod_red(n: &BigInt, module: &BigInt) -> BigInt {
+    if module.is_zero() {
+        // yes, n modulo 0 is n and i will die on this hill
+        n.clone()
+    } else {
+        let k = n % module;
+        if k < zero() {
+            module + k
+        } else {
+            k
+        }
+    }
+}
+/// Extended euclidean algorithm, returning the gcd and the bezout coefficients of `a` and `b`
+pub(crate) fn xgcd(a: &BigInt, b: &BigInt) -> (BigInt, (BigInt, BigInt)) {
+    let mut a = a.abs();
+    let mut b = b.abs();
+    if a.is_zero() {
+        return (b, (zero(), one()));
+    }
+    if b.is_zero() {
+        return (a, (one(), zero()));
+    }
+    let mut a_fac = (one(), zero());
+    let mut b_fac = (zero(), one());
+    if a < b {
+        std::mem::swap(&mut a, &mut b);
+        std::mem::swap(&mut a_fac, &mut b_fac);
+    }
+    while !b.is_zero() {
+        std::mem::swap(&mut a, &mut b);
+        std::mem::swap(&mut a_fac, &mut b_fac);
+        let fac = &b / &a;
+        let rem = &b % &a;
+        b = rem;
+        b_fac = (b_fac.0 - &fac * &a_fac.0, b_fac.1 - &fac * &a_fac.1);
+    }
+    (a, a_fac)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -63,15 +63,18 @@ impl<'a> Iterator for KeyValIter<'a> {
                 (State::Value, 'A'..='Z')
                 | (State::Value, 'a'..='z')
                 | (State::Value, '0'..='9')
+                | (State::Value, ':')
                 | (State::Equal, 'A'..='Z')
                 | (State::Equal, 'a'..='z')
-                | (State::Equal, '0'..='9') => {
+                | (State::Equal, '0'..='9')
+                | (State::Equal, '?') => {
                     current_val.push(c);
                     State::Value
                 }
                 (State::Key, '=') => State::Equal,
                 (State::Key, 'A'..='Z')
                 | (State::Key, 'a'..='z')
+                | (State::Key, '0'..='9')
                 | (State::Whitespace, 'A'..='Z')
                 | (State::Whitespace, 'a'..='z') => {
                     current_key.push(c.to_ascii_lowercase());
@@ -1,18 +1,45 @@
 mod bitnum;
 pub mod checksum;
+pub mod embedded;
+pub mod endian;
 pub(crate) mod factor;
 mod keyval;
+pub mod oracle;
 use bitnum::BitNum;
+pub use bitnum::U512;
 use checksum::{
-    crc::{CRCBuilder, CRC},
+    affine::{Affine, AffineBuilder},
+    brute::{self, BruteOptions},
+    bsdsum::BsdSum,
+    concat::{split_sum, Component, Concat},
+    crc::{rev::CrcTexts, BitCRC, CRCBuilder, LenAppend, LenAppendCRC, CRC},
     fletcher::{Fletcher, FletcherBuilder},
+    fnv::Fnv,
+    jenkins::Jenkins,
+    lanes::{Lanes, LanesBuilder},
     modsum::{ModSum, ModSumBuilder},
+    pearson::Pearson,
+    polyhash::{PolyHash, PolyHashBuilder},
+    preprocess::{InTransform, InTransformBuilder},
+    presum::PresumStrategy,
+    rotxor::{RotXor, RotXorBuilder},
+    sysvsum::SysvSum,
+    transform::{OutTransform, OutTransformBuilder, Transformed},
+    weightsum::{WeightSum, WeightSumBuilder},
+    xorsum::{XorSum, XorSumBuilder},
     Digest, LinearCheck, RangePairs, Relativity, SumStr,
 };
 use checksum::{CheckBuilderErr, CheckReverserError};
+use endian::Endian;
+use num_traits::ToPrimitive;
 #[cfg(feature = "parallel")]
-use rayon::prelude::*;
+use rayon::{iter::Either, prelude::*};
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::ops::RangeInclusive;
 use std::str::FromStr;
+use std::sync::Arc;
 #[cfg(test)]
 #[macro_use(quickcheck)]
 extern crate quickcheck_macros;
@@ -44,27 +71,281 @@ fn find_prefix_width(s: &str) -> Result<(&str, usize, &str), CheckBuilderErr> {
     Err(CheckBuilderErr::MissingParameter("width"))
 }
 
+/// The output transformation (like `negate=true`) is the same for every algorithm, so it is
+/// split off the model string here, returning the rest for the algorithm.
+fn split_transform(s: &str, width: usize) -> Result<(OutTransform, String), CheckBuilderErr> {
+    let (mut transform, rest) = OutTransformBuilder::extract(s)?;
+    Ok((transform.width(width).build()?, rest))
+}
+
+/// The lane selection (like `stride=2 lane=1`) and input transformation (like `xorin=5a`) are also the same
+/// for every algorithm, so they get split off too.
+fn split_input(s: &str) -> Result<(Lanes, InTransform, String), CheckBuilderErr> {
+    let (lanes, rest) = LanesBuilder::extract(s)?;
+    let (input, rest) = InTransformBuilder::extract(&rest)?;
+    Ok((lanes.build()?, input.build()?, rest))
+}
+
+/// Like `split_input`, but the input transformation has to be known, since it gets applied
+fn split_known_input(s: &str) -> Result<(Lanes, InTransform, String), CheckBuilderErr> {
+    let (lanes, input, rest) = split_input(s)?;
+    if !input.is_known() {
+        return Err(CheckBuilderErr::ValueOutOfRange("xorin"));
+    }
+    Ok((lanes, input, rest))
+}
+
+/// Gets the selected and transformed bytes of each file, without copying them if nothing changes
+fn preprocess<'a, B: AsRef<[u8]>>(
+    lanes: &Lanes,
+    input: &InTransform,
+    bytes: &'a [B],
+) -> Vec<Cow<'a, [u8]>> {
+    let identity = lanes.is_identity() && input.is_identity();
+    bytes
+        .iter()
+        .map(|b| match identity {
+            true => Cow::Borrowed(b.as_ref()),
+            false => Cow::Owned(input.apply(&lanes.select(b.as_ref()))),
+        })
+        .collect()
+}
+
 /// A helper function for calling the find_segments function with strings arguments
 fn find_segment_str<L>(
     spec: &str,
-    bytes: &[Vec<u8>],
+    bytes: &[Cow<[u8]>],
     sum: &str,
+    width: usize,
     rel: Relativity,
-) -> Result<RangePairs, CheckBuilderErr>
+    input: &InTransform,
+    opts: &SearchOptions,
+) -> Result<RangePairs, SegmentError>
 where
     L: LinearCheck + FromStr<Err = CheckBuilderErr>,
     L::Sum: BitNum,
 {
-    let sum_array = sum
-        .split(|x| x == ',')
-        .map(L::Sum::from_hex)
+    let (transform, spec) = split_transform(spec, width)?;
+    transformed_segments(L::from_str(&spec)?, transform, bytes, sum, rel, input, opts)
+}
+
+/// Finds the segments of an already parsed algorithm, with its sum being transformed by `transform`.
+///
+/// The bytes should already be transformed by `input`, except for the padding, which depends on the segment.
+/// If bits of the sum are cut off and the algorithm can't be truncated to the remaining ones (see `LinearCheck::truncated`),
+/// every segment gets tried like for the non-linear algorithms.
+fn transformed_segments<L>(
+    chk: L,
+    transform: OutTransform,
+    bytes: &[Cow<[u8]>],
+    sum: &str,
+    rel: Relativity,
+    input: &InTransform,
+    opts: &SearchOptions,
+) -> Result<RangePairs, SegmentError>
+where
+    L: LinearCheck + Sync,
+    L::Sum: BitNum,
+{
+    let sum_array = parse_sums::<L::Sum>(sum)?;
+    let chk = Transformed::new(chk, transform);
+    // the target sums can't be undone for the presums if the cut off bits influence the remaining ones
+    if !chk.is_searchable() {
+        let pad = input.pad();
+        return Ok(brute::find_padded_segments(
+            &chk,
+            bytes,
+            &sum_array,
+            rel,
+            pad,
+            None,
+            &opts.brute,
+        ));
+    }
+    let target = |i: usize, _| sum_array.get(i).copied();
+    Ok(match input.pad() {
+        None => chk.find_segments_with(bytes, &target, rel, opts.presums)?,
+        Some(_) => input.find_padded_segments(&chk, bytes, &sum_array, rel, &opts.brute),
+    })
+}
+
+/// Like `find_segment_str`, but for an algorithm which only implements `Digest`, so every segment gets tried.
+///
+/// Since the sums are compared directly, the transformation does not need to be invertible here.
+fn find_digest_segment_str<D>(
+    spec: &str,
+    bytes: &[Cow<[u8]>],
+    sum: &str,
+    width: usize,
+    rel: Relativity,
+    input: &InTransform,
+    opts: &SearchOptions,
+) -> Result<RangePairs, SegmentError>
+where
+    D: Digest + FromStr<Err = CheckBuilderErr> + Sync,
+    D::Sum: BitNum,
+{
+    let (transform, spec) = split_transform(spec, width)?;
+    let sum_array = parse_sums::<D::Sum>(sum)?;
+    let chk = Transformed::new(D::from_str(&spec)?, transform);
+    Ok(brute::find_padded_segments(
+        &chk,
+        bytes,
+        &sum_array,
+        rel,
+        input.pad(),
+        None,
+        &opts.brute,
+    ))
+}
+
+/// Like `find_segment_str`, but for a CRC, which may have its length appended
+fn find_crc_segment_str<S: BitNum>(
+    spec: &str,
+    bytes: &[Cow<[u8]>],
+    sum: &str,
+    width: usize,
+    rel: Relativity,
+    input: &InTransform,
+    opts: &SearchOptions,
+) -> Result<RangePairs, SegmentError> {
+    let (transform, spec) = split_transform(spec, width)?;
+    let crc = CRCBuilder::<S>::from_str(&spec)?;
+    let lenappend = match crc.get_lenappend() {
+        None => {
+            let crc = crc.build()?;
+            return transformed_segments(crc, transform, bytes, sum, rel, input, opts);
+        }
+        Some(l) => l,
+    };
+    // the appended length depends on the segment, so every segment gets tried
+    let sum_array = parse_sums::<S>(sum)?;
+    let chk = Transformed::new(crc.build_lenappend()?, transform);
+    Ok(brute::find_padded_segments(
+        &chk,
+        bytes,
+        &sum_array,
+        rel,
+        input.pad(),
+        Some(lenappend),
+        &opts.brute,
+    ))
+}
+
+/// Like `find_segment_str`, but for a CRC which is searched bit by bit
+fn find_bit_segment_str<S: BitNum>(
+    spec: &str,
+    bytes: &[Cow<[u8]>],
+    sum: &str,
+    width: usize,
+    rel: Relativity,
+) -> Result<RangePairs, CheckBuilderErr> {
+    let (transform, spec) = split_transform(spec, width)?;
+    // the higher bits of a crc get shifted into the lower ones, so it can't be truncated to `outbits`
+    if !transform.is_invertible() {
+        return Err(CheckBuilderErr::ValueOutOfRange("outbits"));
+    }
+    let sum_array = parse_sums::<S>(sum)?;
+    let crc = CRC::<S>::from_str(&spec)?;
+    let bits: Vec<_> = bytes.iter().map(|b| crc.text_bits(b)).collect();
+    Ok(Transformed::new(BitCRC(crc), transform).find_segments(&bits, &sum_array, rel))
+}
+
+/// Parses a comma-separated list of hexadecimal checksums
+fn parse_sums<S: BitNum>(sum: &str) -> Result<Vec<S>, CheckBuilderErr> {
+    sum.split(|x| x == ',')
+        .map(S::from_hex)
         .collect::<Result<Vec<_>, _>>()
-        .map_err(|_| CheckBuilderErr::MalformedString(String::default()))?;
-    Ok(L::from_str(spec)?.find_segments(bytes, &sum_array, rel))
+        .map_err(|_| CheckBuilderErr::MalformedString(String::default()))
+}
+
+/// Model strings of the form `concat(model, model, ...) {transform}` combine multiple models into one sum,
+/// so this returns the inner models and the rest of the model string, or `None` if it is not of this form.
+fn split_concat(s: &str) -> Result<Option<(Vec<&str>, &str)>, CheckBuilderErr> {
+    let inner = match s.trim_start().strip_prefix("concat(") {
+        Some(inner) => inner,
+        None => return Ok(None),
+    };
+    let mut in_quotes = false;
+    let mut parts = Vec::new();
+    let mut part_start = 0;
+    for (i, c) in inner.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => {
+                parts.push(&inner[part_start..i]);
+                part_start = i + 1;
+            }
+            ')' if !in_quotes => {
+                parts.push(&inner[part_start..i]);
+                return Ok(Some((parts, &inner[i + 1..])));
+            }
+            _ => (),
+        }
+    }
+    Err(CheckBuilderErr::MalformedString("concat".to_owned()))
+}
+
+/// Gets the transformation of the whole sum of a `concat` model, which may be the only thing after the parenthesis
+fn concat_transform(rest: &str, width: usize) -> Result<OutTransform, CheckBuilderErr> {
+    let (transform, rest) = split_transform(rest, width)?;
+    match keyval::KeyValIter::new(&rest).next() {
+        Some(Ok((key, _))) => Err(CheckBuilderErr::UnknownKey(key)),
+        Some(Err(key)) => Err(CheckBuilderErr::MalformedString(key)),
+        None => Ok(transform),
+    }
+}
+
+/// Parses a single model of a `concat`, together with its width
+fn concat_component(spec: &str) -> Result<(Transformed<Component>, usize), CheckBuilderErr> {
+    let (prefix, width, rest) = find_prefix_width(spec)?;
+    let (transform, rest) = split_transform(rest, width)?;
+    // the parts are put next to each other, so they need to keep their width
+    if !transform.is_invertible() {
+        return Err(CheckBuilderErr::ValueOutOfRange("outbits"));
+    }
+    let component = match (width, prefix) {
+        (1..=128, "crc") => Component::CRC(CRC::<u128>::from_str(&rest)?),
+        (1..=64, "modsum") => Component::ModSum(ModSum::<u64>::from_str(&rest)?),
+        (1..=128, "fletcher") => Component::Fletcher(Fletcher::<u128>::from_str(&rest)?),
+        (1..=64, "xorsum") => Component::XorSum(XorSum::<u64>::from_str(&rest)?),
+        (1..=64, "rotxor") => Component::RotXor(RotXor::<u64>::from_str(&rest)?),
+        (1..=64, "polyhash") => Component::PolyHash(PolyHash::<u64>::from_str(&rest)?),
+        _ => return Err(CheckBuilderErr::ValueOutOfRange("width")),
+    };
+    Ok((Transformed::new(component, transform), width))
+}
+
+/// Parses a whole `concat` model from the parts returned by `split_concat`
+fn build_concat(parts: &[&str], rest: &str) -> Result<(Concat, OutTransform), CheckBuilderErr> {
+    let components = parts
+        .iter()
+        .map(|part| concat_component(part))
+        .collect::<Result<Vec<_>, _>>()?;
+    let concat = Concat::new(components)?;
+    let transform = concat_transform(rest, concat.width())?;
+    Ok((concat, transform))
 }
 
 /// The available checksum types
-static PREFIXES: &[&str] = &["fletcher", "crc", "modsum"];
+static PREFIXES: &[&str] = &[
+    "fletcher",
+    "crc",
+    "modsum",
+    "xorsum",
+    "rotxor",
+    "polyhash",
+    "fnv",
+    "jenkins",
+    "pearson",
+    "bsdsum",
+    "sysvsum",
+    "affine",
+    "weightsum",
+];
+
+/// The checksum types which only implement `Digest`, so they can't be reversed
+static DIGEST_PREFIXES: &[&str] = &["fnv", "jenkins", "pearson", "bsdsum", "sysvsum"];
 
 /// A stringy function for determining which segments of a file have a given checksum.
 ///
@@ -79,60 +360,555 @@ static PREFIXES: &[&str] = &["fletcher", "crc", "modsum"];
 /// ```text
 /// [algorithm] width=[number] {more parameters}
 /// ```
-/// The `algorithm` parameter is either `fletcher`, `crc` or `modsum`.
+/// The `algorithm` parameter is either `fletcher`, `crc`, `modsum`, `xorsum`, `rotxor` or `polyhash`,
+/// or one of the non-linear `fnv`, `jenkins`, `pearson`, `bsdsum` or `sysvsum`.
 /// Parameters depend solely on what kind of algorithm is used and more information is available
 /// at the respective Builders.
-pub fn find_checksum_segments(
+/// For the non-linear algorithms, every segment is tried with the `brute` module, which takes quadratic time.
+/// The `affine` maps and `weightsum`s are only defined for files of one length, so they are accepted by `find_checksum` and
+/// `find_algorithm`, but not here.
+///
+/// Multiple models can be combined into a single sum with `concat(model, model, ...)`, see the `concat` module.
+///
+/// Additionally, each algorithm accepts the parameters of `OutTransformBuilder` (`negate`, `complement`, `outbits`
+/// and `outendian`), which transform the final sum. If `outbits` cuts off bits of the sum, the search is done for the
+/// algorithm with a width of `outbits` instead (see `LinearCheck::truncated`), but for most algorithms, the higher bits
+/// influence the lower ones, so every segment is tried like for the non-linear algorithms.
+///
+/// The parameters of `LanesBuilder` (`stride` and `lane`) are also accepted by each algorithm and select the bytes
+/// of the files the checksum is calculated over. The returned segments are still positions in the whole files.
+/// Likewise, the parameters of `InTransformBuilder` (`nibbleswap`, `xorin`, `invertin` and `pad`) transform the bytes
+/// before the checksum is calculated, where `pad` pads each segment. Since the padding depends on the length of the segments,
+/// every start is tried separately with `pad`, which takes quadratic time.
+pub fn find_checksum_segments<B: AsRef<[u8]> + Sync>(
     strspec: &str,
-    bytes: &[Vec<u8>],
+    bytes: &[B],
     sum: &str,
     rel: Relativity,
 ) -> Result<RangePairs, CheckBuilderErr> {
+    find_checksum_segments_with(strspec, bytes, sum, rel, &SearchOptions::default()).map_err(
+        |err| match err {
+            SegmentError::Model(err) => err,
+            SegmentError::Io(_) => unreachable!("the presums are sorted in memory by default"),
+        },
+    )
+}
+
+/// Options for `find_checksum_segments_with`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SearchOptions {
+    /// Restricts the segments which are tried for non-linear algorithms, `pad`, `lenappend` and `outbits`
+    /// (if the algorithm can't be truncated to it)
+    pub brute: BruteOptions,
+    /// How the presums are sorted for the linear algorithms, which consider every segment anyway
+    pub presums: PresumStrategy,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            brute: BruteOptions::default(),
+            presums: PresumStrategy::InMemory,
+        }
+    }
+}
+
+/// An error of `find_checksum_segments_with`.
+#[derive(Debug)]
+pub enum SegmentError {
+    /// The model could not be used for the search
+    Model(CheckBuilderErr),
+    /// The temporary files of `PresumStrategy::External` could not be written or read
+    Io(std::io::Error),
+}
+
+impl From<CheckBuilderErr> for SegmentError {
+    fn from(err: CheckBuilderErr) -> Self {
+        SegmentError::Model(err)
+    }
+}
+
+impl From<std::io::Error> for SegmentError {
+    fn from(err: std::io::Error) -> Self {
+        SegmentError::Io(err)
+    }
+}
+
+impl std::fmt::Display for SegmentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SegmentError::Model(err) => write!(f, "{}", err),
+            SegmentError::Io(err) => {
+                write!(f, "Could not use temporary files for the presums: {}", err)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SegmentError {}
+
+/// Like `find_checksum_segments`, but the search is done according to `opts`.
+///
+/// The brute-force options have no effect on the search for the linear algorithms without `pad`,
+/// which considers every segment anyway, and the presum strategy only has an effect on that search.
+pub fn find_checksum_segments_with<B: AsRef<[u8]> + Sync>(
+    strspec: &str,
+    bytes: &[B],
+    sum: &str,
+    rel: Relativity,
+    opts: &SearchOptions,
+) -> Result<RangePairs, SegmentError> {
+    if opts.brute.start_align == 0 {
+        return Err(CheckBuilderErr::ValueOutOfRange("start_align").into());
+    }
+    if opts.brute.end_align == 0 {
+        return Err(CheckBuilderErr::ValueOutOfRange("end_align").into());
+    }
+    let lens: Vec<_> = bytes.iter().map(|b| b.as_ref().len()).collect();
+    if let Some((parts, rest)) = split_concat(strspec)? {
+        let (lanes, input, rest) = split_known_input(rest)?;
+        let (concat, transform) = build_concat(&parts, &rest)?;
+        let bytes = preprocess(&lanes, &input.without_pad(), bytes);
+        let segs = transformed_segments(concat, transform, &bytes, sum, rel, &input, opts)?;
+        return Ok(lanes.file_segments(segs, &lens));
+    }
     let (prefix, width, rest) = find_prefix_width(strspec)?;
+    let (lanes, input, rest) = split_known_input(rest)?;
+    let rest = rest.as_str();
+    let selected = preprocess(&lanes, &input.without_pad(), bytes);
+    let bytes = selected.as_slice();
+    let input = &input;
+    let segs = match (width, prefix) {
+        (1..=8, "crc") => find_crc_segment_str::<u8>(rest, bytes, sum, width, rel, input, opts),
+        (9..=16, "crc") => find_crc_segment_str::<u16>(rest, bytes, sum, width, rel, input, opts),
+        (17..=32, "crc") => find_crc_segment_str::<u32>(rest, bytes, sum, width, rel, input, opts),
+        (33..=64, "crc") => find_crc_segment_str::<u64>(rest, bytes, sum, width, rel, input, opts),
+        (65..=128, "crc") => {
+            find_crc_segment_str::<u128>(rest, bytes, sum, width, rel, input, opts)
+        }
+        (129..=512, "crc") => {
+            find_crc_segment_str::<U512>(rest, bytes, sum, width, rel, input, opts)
+        }
+        (1..=8, "modsum") => {
+            find_segment_str::<ModSum<u8>>(rest, bytes, sum, width, rel, input, opts)
+        }
+        (9..=16, "modsum") => {
+            find_segment_str::<ModSum<u16>>(rest, bytes, sum, width, rel, input, opts)
+        }
+        (17..=32, "modsum") => {
+            find_segment_str::<ModSum<u32>>(rest, bytes, sum, width, rel, input, opts)
+        }
+        (33..=64, "modsum") => {
+            find_segment_str::<ModSum<u64>>(rest, bytes, sum, width, rel, input, opts)
+        }
+        (1..=8, "fletcher") => {
+            find_segment_str::<Fletcher<u8>>(rest, bytes, sum, width, rel, input, opts)
+        }
+        (9..=16, "fletcher") => {
+            find_segment_str::<Fletcher<u16>>(rest, bytes, sum, width, rel, input, opts)
+        }
+        (17..=32, "fletcher") => {
+            find_segment_str::<Fletcher<u32>>(rest, bytes, sum, width, rel, input, opts)
+        }
+        (33..=64, "fletcher") => {
+            find_segment_str::<Fletcher<u64>>(rest, bytes, sum, width, rel, input, opts)
+        }
+        (65..=128, "fletcher") => {
+            find_segment_str::<Fletcher<u128>>(rest, bytes, sum, width, rel, input, opts)
+        }
+        (1..=8, "xorsum") => {
+            find_segment_str::<XorSum<u8>>(rest, bytes, sum, width, rel, input, opts)
+        }
+        (9..=16, "xorsum") => {
+            find_segment_str::<XorSum<u16>>(rest, bytes, sum, width, rel, input, opts)
+        }
+        (17..=32, "xorsum") => {
+            find_segment_str::<XorSum<u32>>(rest, bytes, sum, width, rel, input, opts)
+        }
+        (33..=64, "xorsum") => {
+            find_segment_str::<XorSum<u64>>(rest, bytes, sum, width, rel, input, opts)
+        }
+        (1..=8, "rotxor") => {
+            find_segment_str::<RotXor<u8>>(rest, bytes, sum, width, rel, input, opts)
+        }
+        (9..=16, "rotxor") => {
+            find_segment_str::<RotXor<u16>>(rest, bytes, sum, width, rel, input, opts)
+        }
+        (17..=32, "rotxor") => {
+            find_segment_str::<RotXor<u32>>(rest, bytes, sum, width, rel, input, opts)
+        }
+        (33..=64, "rotxor") => {
+            find_segment_str::<RotXor<u64>>(rest, bytes, sum, width, rel, input, opts)
+        }
+        (1..=8, "polyhash") => {
+            find_segment_str::<PolyHash<u8>>(rest, bytes, sum, width, rel, input, opts)
+        }
+        (9..=16, "polyhash") => {
+            find_segment_str::<PolyHash<u16>>(rest, bytes, sum, width, rel, input, opts)
+        }
+        (17..=32, "polyhash") => {
+            find_segment_str::<PolyHash<u32>>(rest, bytes, sum, width, rel, input, opts)
+        }
+        (33..=64, "polyhash") => {
+            find_segment_str::<PolyHash<u64>>(rest, bytes, sum, width, rel, input, opts)
+        }
+        (1..=32, "fnv") => {
+            find_digest_segment_str::<Fnv<u32>>(rest, bytes, sum, width, rel, input, opts)
+        }
+        (33..=64, "fnv") => {
+            find_digest_segment_str::<Fnv<u64>>(rest, bytes, sum, width, rel, input, opts)
+        }
+        (65..=128, "fnv") => {
+            find_digest_segment_str::<Fnv<u128>>(rest, bytes, sum, width, rel, input, opts)
+        }
+        (_, "jenkins") => {
+            find_digest_segment_str::<Jenkins>(rest, bytes, sum, width, rel, input, opts)
+        }
+        (_, "pearson") => {
+            find_digest_segment_str::<Pearson>(rest, bytes, sum, width, rel, input, opts)
+        }
+        (_, "bsdsum") => {
+            find_digest_segment_str::<BsdSum>(rest, bytes, sum, width, rel, input, opts)
+        }
+        (_, "sysvsum") => {
+            find_digest_segment_str::<SysvSum>(rest, bytes, sum, width, rel, input, opts)
+        }
+        // these are only defined for one length, so segments of different lengths can't be compared
+        (_, "affine") | (_, "weightsum") => {
+            Err(CheckBuilderErr::ValueOutOfRange("algorithm").into())
+        }
+        _ => Err(CheckBuilderErr::ValueOutOfRange("width").into()),
+    }?;
+    Ok(lanes.file_segments(segs, &lens))
+}
+
+/// Like `find_checksum_segments`, but the start and end of the segments are bit positions instead of byte positions.
+///
+/// This is only supported for `crc`. The bits of a byte are ordered from the most significant to the least
+/// significant one, or the other way around if `refin` is set.
+/// Note that this needs 8 times the memory compared to `find_checksum_segments`.
+/// Selecting lanes with `stride` and padding with `pad` is not supported.
+pub fn find_checksum_segments_bits<B: AsRef<[u8]> + Sync>(
+    strspec: &str,
+    bytes: &[B],
+    sum: &str,
+    rel: Relativity,
+) -> Result<RangePairs, CheckBuilderErr> {
+    let (prefix, width, rest) = find_prefix_width(strspec)?;
+    let (lanes, input, rest) = split_known_input(rest)?;
+    if !lanes.is_identity() {
+        return Err(CheckBuilderErr::ValueOutOfRange("stride"));
+    }
+    if input.pad().is_some() {
+        return Err(CheckBuilderErr::ValueOutOfRange("pad"));
+    }
+    let rest = rest.as_str();
+    let transformed = preprocess(&lanes, &input, bytes);
+    let bytes = transformed.as_slice();
     match (width, prefix) {
-        (1..=8, "crc") => find_segment_str::<CRC<u8>>(rest, bytes, sum, rel),
-        (9..=16, "crc") => find_segment_str::<CRC<u16>>(rest, bytes, sum, rel),
-        (17..=32, "crc") => find_segment_str::<CRC<u32>>(rest, bytes, sum, rel),
-        (33..=64, "crc") => find_segment_str::<CRC<u64>>(rest, bytes, sum, rel),
-        (65..=128, "crc") => find_segment_str::<CRC<u128>>(rest, bytes, sum, rel),
-        (1..=8, "modsum") => find_segment_str::<ModSum<u8>>(rest, bytes, sum, rel),
-        (9..=16, "modsum") => find_segment_str::<ModSum<u16>>(rest, bytes, sum, rel),
-        (17..=32, "modsum") => find_segment_str::<ModSum<u32>>(rest, bytes, sum, rel),
-        (33..=64, "modsum") => find_segment_str::<ModSum<u64>>(rest, bytes, sum, rel),
-        (1..=8, "fletcher") => find_segment_str::<Fletcher<u8>>(rest, bytes, sum, rel),
-        (9..=16, "fletcher") => find_segment_str::<Fletcher<u16>>(rest, bytes, sum, rel),
-        (17..=32, "fletcher") => find_segment_str::<Fletcher<u32>>(rest, bytes, sum, rel),
-        (33..=64, "fletcher") => find_segment_str::<Fletcher<u64>>(rest, bytes, sum, rel),
-        (65..=128, "fletcher") => find_segment_str::<Fletcher<u128>>(rest, bytes, sum, rel),
-        _ => Err(CheckBuilderErr::ValueOutOfRange("width")),
+        (1..=8, "crc") => find_bit_segment_str::<u8>(rest, bytes, sum, width, rel),
+        (9..=16, "crc") => find_bit_segment_str::<u16>(rest, bytes, sum, width, rel),
+        (17..=32, "crc") => find_bit_segment_str::<u32>(rest, bytes, sum, width, rel),
+        (33..=64, "crc") => find_bit_segment_str::<u64>(rest, bytes, sum, width, rel),
+        (65..=128, "crc") => find_bit_segment_str::<u128>(rest, bytes, sum, width, rel),
+        (129..=512, "crc") => find_bit_segment_str::<U512>(rest, bytes, sum, width, rel),
+        (_, "crc") => Err(CheckBuilderErr::ValueOutOfRange("width")),
+        _ => Err(CheckBuilderErr::MalformedString("algorithm".to_owned())),
+    }
+}
+
+/// Reads a sum of `width` bits from `bytes` in the given byte order, or `None` if there are bits set beyond `width`
+fn read_sum<S: BitNum>(bytes: &[u8], endian: Endian, width: usize) -> Option<S> {
+    let mut sum: Option<S> = None;
+    let mut push = |b: &u8| {
+        sum = Some(match sum {
+            Some(s) => (s << 8) ^ S::from(*b),
+            None => S::from(*b),
+        })
+    };
+    match endian {
+        Endian::Big => bytes.iter().for_each(&mut push),
+        Endian::Little => bytes.iter().rev().for_each(&mut push),
+    }
+    let sum = sum?;
+    if width < sum.bits() && sum >> width != S::zero() {
+        return None;
+    }
+    Some(sum)
+}
+
+/// Finds the segments of an already parsed algorithm whose checksum is stored in the file `offset` bytes after their end.
+///
+/// `files` are the original files the checksums are read from, and `bytes` the transformed ones, with the same positions.
+fn scan_transformed<L>(
+    chk: L,
+    transform: OutTransform,
+    files: &[Vec<u8>],
+    bytes: &[Cow<[u8]>],
+    offset: isize,
+    endian: Endian,
+    rel: Relativity,
+) -> Result<RangePairs, CheckBuilderErr>
+where
+    L: LinearCheck,
+    L::Sum: BitNum,
+{
+    let chk = Transformed::new(chk, transform);
+    if !chk.is_searchable() {
+        return Err(CheckBuilderErr::ValueOutOfRange("outbits"));
+    }
+    let width = transform.outbits();
+    let len = (width - 1) / 8 + 1;
+    let target = |i: usize, end: usize| {
+        let pos = usize::try_from(end as isize + 1 + offset).ok()?;
+        read_sum(files[i].get(pos..pos + len)?, endian, width)
+    };
+    Ok(chk.find_segments_by(bytes, &target, rel))
+}
+
+/// A helper function for calling `scan_transformed` with a string model
+fn scan_segment_str<L>(
+    spec: &str,
+    files: &[Vec<u8>],
+    bytes: &[Cow<[u8]>],
+    width: usize,
+    offset: isize,
+    endian: Endian,
+    rel: Relativity,
+) -> Result<RangePairs, CheckBuilderErr>
+where
+    L: LinearCheck + FromStr<Err = CheckBuilderErr>,
+    L::Sum: BitNum,
+{
+    let (transform, spec) = split_transform(spec, width)?;
+    scan_transformed(
+        L::from_str(&spec)?,
+        transform,
+        files,
+        bytes,
+        offset,
+        endian,
+        rel,
+    )
+}
+
+/// Like `find_checksum_segments`, but instead of being given, the checksum of each segment is read from the file itself.
+///
+/// The checksum is expected `offset` bytes after the end of the segment (so an offset of 0 means right after it, and negative
+/// offsets are inside of the segment), stored with the given byte order in as many bytes as the width of the model needs.
+/// Segments for which the checksum would be outside of the file are not considered.
+///
+/// Since the target sum depends on the segment, this only works for the linear algorithms, without `stride` or `pad`.
+/// Note that with a lot of files, it is much more likely for a segment to be a false positive than with given checksums,
+/// since every end has its own target, and regions of zeros for example often have a checksum of zero.
+pub fn find_checksum_segments_scan(
+    strspec: &str,
+    bytes: &[Vec<u8>],
+    offset: isize,
+    endian: Endian,
+    rel: Relativity,
+) -> Result<RangePairs, CheckBuilderErr> {
+    let split_scan_input = |s| {
+        let (lanes, input, rest) = split_known_input(s)?;
+        if !lanes.is_identity() {
+            return Err(CheckBuilderErr::ValueOutOfRange("stride"));
+        }
+        if input.pad().is_some() {
+            return Err(CheckBuilderErr::ValueOutOfRange("pad"));
+        }
+        Ok((preprocess(&lanes, &input, bytes), rest))
+    };
+    if let Some((parts, rest)) = split_concat(strspec)? {
+        let (transformed, rest) = split_scan_input(rest)?;
+        let (concat, transform) = build_concat(&parts, &rest)?;
+        return scan_transformed(concat, transform, bytes, &transformed, offset, endian, rel);
+    }
+    let (prefix, width, rest) = find_prefix_width(strspec)?;
+    let (transformed, rest) = split_scan_input(rest)?;
+    let (rest, data) = (rest.as_str(), transformed.as_slice());
+    match (width, prefix) {
+        (1..=8, "crc") => {
+            scan_segment_str::<CRC<u8>>(rest, bytes, data, width, offset, endian, rel)
+        }
+        (9..=16, "crc") => {
+            scan_segment_str::<CRC<u16>>(rest, bytes, data, width, offset, endian, rel)
+        }
+        (17..=32, "crc") => {
+            scan_segment_str::<CRC<u32>>(rest, bytes, data, width, offset, endian, rel)
+        }
+        (33..=64, "crc") => {
+            scan_segment_str::<CRC<u64>>(rest, bytes, data, width, offset, endian, rel)
+        }
+        (65..=128, "crc") => {
+            scan_segment_str::<CRC<u128>>(rest, bytes, data, width, offset, endian, rel)
+        }
+        (129..=512, "crc") => {
+            scan_segment_str::<CRC<U512>>(rest, bytes, data, width, offset, endian, rel)
+        }
+        (1..=8, "modsum") => {
+            scan_segment_str::<ModSum<u8>>(rest, bytes, data, width, offset, endian, rel)
+        }
+        (9..=16, "modsum") => {
+            scan_segment_str::<ModSum<u16>>(rest, bytes, data, width, offset, endian, rel)
+        }
+        (17..=32, "modsum") => {
+            scan_segment_str::<ModSum<u32>>(rest, bytes, data, width, offset, endian, rel)
+        }
+        (33..=64, "modsum") => {
+            scan_segment_str::<ModSum<u64>>(rest, bytes, data, width, offset, endian, rel)
+        }
+        (1..=8, "fletcher") => {
+            scan_segment_str::<Fletcher<u8>>(rest, bytes, data, width, offset, endian, rel)
+        }
+        (9..=16, "fletcher") => {
+            scan_segment_str::<Fletcher<u16>>(rest, bytes, data, width, offset, endian, rel)
+        }
+        (17..=32, "fletcher") => {
+            scan_segment_str::<Fletcher<u32>>(rest, bytes, data, width, offset, endian, rel)
+        }
+        (33..=64, "fletcher") => {
+            scan_segment_str::<Fletcher<u64>>(rest, bytes, data, width, offset, endian, rel)
+        }
+        (65..=128, "fletcher") => {
+            scan_segment_str::<Fletcher<u128>>(rest, bytes, data, width, offset, endian, rel)
+        }
+        (1..=8, "xorsum") => {
+            scan_segment_str::<XorSum<u8>>(rest, bytes, data, width, offset, endian, rel)
+        }
+        (9..=16, "xorsum") => {
+            scan_segment_str::<XorSum<u16>>(rest, bytes, data, width, offset, endian, rel)
+        }
+        (17..=32, "xorsum") => {
+            scan_segment_str::<XorSum<u32>>(rest, bytes, data, width, offset, endian, rel)
+        }
+        (33..=64, "xorsum") => {
+            scan_segment_str::<XorSum<u64>>(rest, bytes, data, width, offset, endian, rel)
+        }
+        (1..=8, "rotxor") => {
+            scan_segment_str::<RotXor<u8>>(rest, bytes, data, width, offset, endian, rel)
+        }
+        (9..=16, "rotxor") => {
+            scan_segment_str::<RotXor<u16>>(rest, bytes, data, width, offset, endian, rel)
+        }
+        (17..=32, "rotxor") => {
+            scan_segment_str::<RotXor<u32>>(rest, bytes, data, width, offset, endian, rel)
+        }
+        (33..=64, "rotxor") => {
+            scan_segment_str::<RotXor<u64>>(rest, bytes, data, width, offset, endian, rel)
+        }
+        (1..=8, "polyhash") => {
+            scan_segment_str::<PolyHash<u8>>(rest, bytes, data, width, offset, endian, rel)
+        }
+        (9..=16, "polyhash") => {
+            scan_segment_str::<PolyHash<u16>>(rest, bytes, data, width, offset, endian, rel)
+        }
+        (17..=32, "polyhash") => {
+            scan_segment_str::<PolyHash<u32>>(rest, bytes, data, width, offset, endian, rel)
+        }
+        (33..=64, "polyhash") => {
+            scan_segment_str::<PolyHash<u64>>(rest, bytes, data, width, offset, endian, rel)
+        }
+        (_, "crc")
+        | (_, "modsum")
+        | (_, "fletcher")
+        | (_, "xorsum")
+        | (_, "rotxor")
+        | (_, "polyhash") => Err(CheckBuilderErr::ValueOutOfRange("width")),
+        _ => Err(CheckBuilderErr::ValueOutOfRange("algorithm")),
     }
 }
 
 fn get_checksums<A>(
     strspec: &str,
-    files: &[Vec<u8>],
+    files: &[Cow<[u8]>],
     width: usize,
 ) -> Result<Vec<String>, CheckBuilderErr>
 where
     A: Digest + FromStr<Err = CheckBuilderErr>,
+    A::Sum: BitNum,
+{
+    let (transform, strspec) = split_transform(strspec, width)?;
+    Ok(transformed_checksums(
+        A::from_str(&strspec)?,
+        transform,
+        files,
+    ))
+}
+
+/// Like `get_checksums`, but for a CRC, which may have its length appended
+fn get_crc_checksums<S: BitNum>(
+    strspec: &str,
+    files: &[Cow<[u8]>],
+    width: usize,
+) -> Result<Vec<String>, CheckBuilderErr> {
+    let (transform, strspec) = split_transform(strspec, width)?;
+    let crc = CRCBuilder::<S>::from_str(&strspec)?;
+    Ok(match crc.get_lenappend() {
+        None => transformed_checksums(crc.build()?, transform, files),
+        Some(_) => transformed_checksums(crc.build_lenappend()?, transform, files),
+    })
+}
+
+fn transformed_checksums<A>(algo: A, transform: OutTransform, files: &[Cow<[u8]>]) -> Vec<String>
+where
+    A: Digest,
+    A::Sum: BitNum,
 {
-    let algo = A::from_str(strspec)?;
+    let algo = Transformed::new(algo, transform);
     let mut sums = Vec::new();
     for file in files {
-        sums.push(algo.digest(file.as_slice()).unwrap().to_width_str(width));
+        sums.push(
+            algo.digest(file.as_ref())
+                .unwrap()
+                .to_width_str(transform.outbits()),
+        );
     }
-    Ok(sums)
+    sums
+}
+
+/// Like `get_checksums`, but for algorithms which are only defined for files of one length,
+/// like affine maps and weighted sums
+fn get_fixed_len_checksums<C: FromStr<Err = CheckBuilderErr>>(
+    strspec: &str,
+    files: &[Cow<[u8]>],
+    width: usize,
+    checksum: impl Fn(&C, &[u8]) -> Option<u128>,
+) -> Result<Vec<String>, CheckBuilderErr> {
+    let (transform, strspec) = split_transform(strspec, width)?;
+    let chk = C::from_str(&strspec)?;
+    files
+        .iter()
+        .map(|f| {
+            checksum(&chk, f)
+                .map(|sum| transform.apply(sum).to_width_str(transform.outbits()))
+                .ok_or(CheckBuilderErr::ValueOutOfRange("len"))
+        })
+        .collect()
 }
 
 pub fn find_checksum(strspec: &str, bytes: &[Vec<u8>]) -> Result<Vec<String>, CheckBuilderErr> {
+    if let Some((parts, rest)) = split_concat(strspec)? {
+        let (lanes, input, rest) = split_known_input(rest)?;
+        let (concat, transform) = build_concat(&parts, &rest)?;
+        return Ok(transformed_checksums(
+            concat,
+            transform,
+            &preprocess(&lanes, &input, bytes),
+        ));
+    }
     let (prefix, width, rest) = find_prefix_width(strspec)?;
+    let (lanes, input, rest) = split_known_input(rest)?;
+    let rest = rest.as_str();
+    let selected = preprocess(&lanes, &input, bytes);
+    let bytes = selected.as_slice();
     // look, it's not really useful to it in this case, but i really like how this looks
     match (width, prefix) {
-        (1..=8, "crc") => get_checksums::<CRC<u8>>(rest, bytes, width),
-        (9..=16, "crc") => get_checksums::<CRC<u16>>(rest, bytes, width),
-        (17..=32, "crc") => get_checksums::<CRC<u32>>(rest, bytes, width),
-        (33..=64, "crc") => get_checksums::<CRC<u64>>(rest, bytes, width),
-        (65..=128, "crc") => get_checksums::<CRC<u128>>(rest, bytes, width),
+        (1..=8, "crc") => get_crc_checksums::<u8>(rest, bytes, width),
+        (9..=16, "crc") => get_crc_checksums::<u16>(rest, bytes, width),
+        (17..=32, "crc") => get_crc_checksums::<u32>(rest, bytes, width),
+        (33..=64, "crc") => get_crc_checksums::<u64>(rest, bytes, width),
+        (65..=128, "crc") => get_crc_checksums::<u128>(rest, bytes, width),
+        (129..=512, "crc") => get_crc_checksums::<U512>(rest, bytes, width),
         (1..=8, "modsum") => get_checksums::<ModSum<u8>>(rest, bytes, width),
         (9..=16, "modsum") => get_checksums::<ModSum<u16>>(rest, bytes, width),
         (17..=32, "modsum") => get_checksums::<ModSum<u32>>(rest, bytes, width),
@@ -142,28 +918,279 @@ pub fn find_checksum(strspec: &str, bytes: &[Vec<u8>]) -> Result<Vec<String>, Ch
         (17..=32, "fletcher") => get_checksums::<Fletcher<u32>>(rest, bytes, width),
         (33..=64, "fletcher") => get_checksums::<Fletcher<u64>>(rest, bytes, width),
         (65..=128, "fletcher") => get_checksums::<Fletcher<u128>>(rest, bytes, width),
+        (1..=8, "xorsum") => get_checksums::<XorSum<u8>>(rest, bytes, width),
+        (9..=16, "xorsum") => get_checksums::<XorSum<u16>>(rest, bytes, width),
+        (17..=32, "xorsum") => get_checksums::<XorSum<u32>>(rest, bytes, width),
+        (33..=64, "xorsum") => get_checksums::<XorSum<u64>>(rest, bytes, width),
+        (1..=8, "rotxor") => get_checksums::<RotXor<u8>>(rest, bytes, width),
+        (9..=16, "rotxor") => get_checksums::<RotXor<u16>>(rest, bytes, width),
+        (17..=32, "rotxor") => get_checksums::<RotXor<u32>>(rest, bytes, width),
+        (33..=64, "rotxor") => get_checksums::<RotXor<u64>>(rest, bytes, width),
+        (1..=8, "polyhash") => get_checksums::<PolyHash<u8>>(rest, bytes, width),
+        (9..=16, "polyhash") => get_checksums::<PolyHash<u16>>(rest, bytes, width),
+        (17..=32, "polyhash") => get_checksums::<PolyHash<u32>>(rest, bytes, width),
+        (33..=64, "polyhash") => get_checksums::<PolyHash<u64>>(rest, bytes, width),
+        (1..=32, "fnv") => get_checksums::<Fnv<u32>>(rest, bytes, width),
+        (33..=64, "fnv") => get_checksums::<Fnv<u64>>(rest, bytes, width),
+        (65..=128, "fnv") => get_checksums::<Fnv<u128>>(rest, bytes, width),
+        (_, "jenkins") => get_checksums::<Jenkins>(rest, bytes, width),
+        (_, "pearson") => get_checksums::<Pearson>(rest, bytes, width),
+        (_, "bsdsum") => get_checksums::<BsdSum>(rest, bytes, width),
+        (_, "sysvsum") => get_checksums::<SysvSum>(rest, bytes, width),
+        (1..=128, "affine") => get_fixed_len_checksums(rest, bytes, width, Affine::checksum),
+        (1..=64, "weightsum") => get_fixed_len_checksums(rest, bytes, width, |w: &WeightSum, f| {
+            w.checksum(f).map(u128::from)
+        }),
         _ => Err(CheckBuilderErr::ValueOutOfRange("width")),
     }
 }
 
+/// Shows a reversed CRC, with the length appended to it like in the spec it was reversed from
+fn show_crc<S: BitNum>(crc: CRC<S>, lenappend: Option<LenAppend>) -> String {
+    match lenappend {
+        Some(l) => LenAppendCRC::new(crc, l).to_string(),
+        None => crc.to_string(),
+    }
+}
+
+#[derive(Clone)]
 enum BuilderEnum {
     CRC(CRCBuilder<u128>),
+    WideCRC(CRCBuilder<U512>),
     ModSum(ModSumBuilder<u64>),
     Fletcher(FletcherBuilder<u128>),
+    XorSum(XorSumBuilder<u64>),
+    RotXor(RotXorBuilder<u64>),
+    PolyHash(PolyHashBuilder<u64>),
+    Affine(AffineBuilder),
+    WeightSum(WeightSumBuilder),
+    // the components are reversed by the `concat_parts` of the `AlgorithmFinder`
+    Concat,
 }
 
 pub struct AlgorithmFinder<'a> {
     pairs: Vec<(&'a [u8], u128)>,
+    // only used for CRCs which are wider than 128 bits
+    wide_pairs: Vec<(&'a [u8], U512)>,
     spec: BuilderEnum,
+    transform: OutTransform,
+    lanes: Lanes,
+    // can have an unknown `xorin`, in which case every value is tried
+    input: InTransform,
+    // only used for `concat` models, one finder for each component
+    concat_parts: Vec<AlgorithmFinder<'a>>,
+    // the polynomials of the files, shared between the CRCs of `find_algorithm_auto`
+    crc_texts: Option<Arc<CrcTexts<'a>>>,
     verbosity: u64,
 }
 
 impl<'a> AlgorithmFinder<'a> {
+    /// The reversers only see the untransformed sums, so the transformation is added to their output
+    fn add_transform(&self, model: String) -> String {
+        if self.transform.is_identity() {
+            model
+        } else {
+            format!("{} {}", model, self.transform)
+        }
+    }
+
+    /// Same as `add_transform`, but for the lane selection
+    fn add_lanes(&self, model: String) -> String {
+        if self.lanes.is_identity() {
+            model
+        } else {
+            format!("{} {}", model, self.lanes)
+        }
+    }
+
+    /// The checksums of the files, for all algorithms except wide CRCs
+    fn sums(&self) -> Vec<u128> {
+        self.pairs.iter().map(|(_, s)| *s).collect()
+    }
+
+    /// Whether the files have to be preprocessed before reversing
+    fn is_direct(&self) -> bool {
+        self.lanes.is_identity() && self.input.is_identity()
+    }
+
+    /// The files the finder was created with
+    fn files(&self) -> Vec<&'a [u8]> {
+        if let Some(part) = self.concat_parts.first() {
+            return part.files();
+        }
+        self.pairs
+            .iter()
+            .map(|(f, _)| *f)
+            .chain(self.wide_pairs.iter().map(|(f, _)| *f))
+            .collect()
+    }
+
+    /// Gets the same finder for other files with the same checksums, which do not get preprocessed anymore
+    fn with_files<'b>(&self, files: &[&'b [u8]]) -> AlgorithmFinder<'b> {
+        AlgorithmFinder {
+            pairs: files
+                .iter()
+                .zip(self.pairs.iter())
+                .map(|(f, (_, s))| (*f, *s))
+                .collect(),
+            wide_pairs: files
+                .iter()
+                .zip(self.wide_pairs.iter())
+                .map(|(f, (_, s))| (*f, *s))
+                .collect(),
+            spec: self.spec.clone(),
+            transform: self.transform,
+            lanes: Lanes::default(),
+            input: InTransform::default(),
+            concat_parts: self
+                .concat_parts
+                .iter()
+                .map(|p| p.with_files(files))
+                .collect(),
+            crc_texts: None,
+            verbosity: self.verbosity,
+        }
+    }
+
+    /// Gets the lane-selected bytes of the files, to be transformed by the candidates of the input transformation
+    fn selected_files(&self) -> Vec<Vec<u8>> {
+        self.files().iter().map(|f| self.lanes.select(f)).collect()
+    }
+
+    /// The input transformation is not part of the reversed model, so it is added to the solutions like `add_transform`
+    fn add_input(input: &InTransform, model: String) -> String {
+        if input.is_identity() {
+            model
+        } else {
+            format!("{} {}", model, input)
+        }
+    }
+
+    /// The candidates for an unknown `xorin` usually fail for the same reasons, so each error is only kept once
+    fn dedup_errors(
+        solutions: Vec<Result<String, CheckReverserError>>,
+    ) -> Vec<Result<String, CheckReverserError>> {
+        let mut errors = Vec::new();
+        let mut ret = Vec::new();
+        for solution in solutions {
+            match &solution {
+                Err(e) if errors.contains(e) => continue,
+                Err(e) => errors.push(e.clone()),
+                Ok(_) => (),
+            }
+            ret.push(solution);
+        }
+        ret
+    }
+
+    /// Finds the solutions for the preprocessed bytes, which only live inside of this function, so they get collected
+    fn find_preprocessed(&self) -> Vec<Result<String, CheckReverserError>> {
+        let selected = self.selected_files();
+        let mut solutions = Vec::new();
+        for input in self.input.candidates() {
+            let files: Vec<_> = selected.iter().map(|f| input.apply(f)).collect();
+            let slices: Vec<_> = files.iter().map(Vec::as_slice).collect();
+            let finder = self.with_files(&slices);
+            solutions.extend(
+                finder
+                    .find_direct()
+                    .map(|x| x.map(|y| Self::add_input(&input, y))),
+            );
+        }
+        Self::dedup_errors(solutions)
+    }
+
+    #[cfg(feature = "parallel")]
+    fn find_preprocessed_para(&self) -> Vec<Result<String, CheckReverserError>> {
+        let selected = self.selected_files();
+        let solutions = self
+            .input
+            .candidates()
+            .into_par_iter()
+            .flat_map_iter(|input| {
+                let files: Vec<_> = selected.iter().map(|f| input.apply(f)).collect();
+                let slices: Vec<_> = files.iter().map(Vec::as_slice).collect();
+                let solutions: Vec<_> = self.with_files(&slices).find_direct_para().collect();
+                solutions
+                    .into_iter()
+                    .map(move |x| x.map(|y| Self::add_input(&input, y)))
+            })
+            .collect();
+        Self::dedup_errors(solutions)
+    }
+
+    /// Combines the solutions for each component of a `concat` model into solutions for the whole model.
+    ///
+    /// Every combination of the solutions of the components is a solution, errors are passed through.
+    fn combine_concat(
+        parts: Vec<Vec<Result<String, CheckReverserError>>>,
+    ) -> Vec<Result<String, CheckReverserError>> {
+        let mut errors = Vec::new();
+        let mut combined = vec![Vec::new()];
+        for part in parts {
+            let mut models = Vec::new();
+            for model in part {
+                match model {
+                    Ok(m) => models.push(m),
+                    Err(e) => errors.push(Err(e)),
+                }
+            }
+            combined = combined
+                .into_iter()
+                .flat_map(|prefix: Vec<String>| {
+                    models.iter().map(move |m| {
+                        let mut with_m = prefix.clone();
+                        with_m.push(m.clone());
+                        with_m
+                    })
+                })
+                .collect();
+        }
+        errors
+            .into_iter()
+            .chain(
+                combined
+                    .into_iter()
+                    .map(|models| Ok(format!("concat({})", models.join(", ")))),
+            )
+            .collect()
+    }
+
     pub fn find_all<'b>(&'b self) -> impl Iterator<Item = Result<String, CheckReverserError>> + 'b {
+        // the iterator of the reversers is quite big, so it is boxed to not use too much stack space
+        let solutions: Box<dyn Iterator<Item = _> + 'b> = if self.is_direct() {
+            Box::new(self.find_direct())
+        } else {
+            Box::new(self.find_preprocessed().into_iter())
+        };
+        solutions.map(move |x| x.map(|y| self.add_lanes(y)))
+    }
+
+    fn find_direct<'b>(&'b self) -> impl Iterator<Item = Result<String, CheckReverserError>> + 'b {
         let maybe_crc = if let BuilderEnum::CRC(crc) = &self.spec {
+            let solutions: Box<dyn Iterator<Item = _> + 'b> = match &self.crc_texts {
+                Some(texts) => Box::new(checksum::crc::rev::reverse_crc_shared(
+                    crc,
+                    texts,
+                    &self.sums(),
+                    self.verbosity,
+                )),
+                None => Box::new(checksum::crc::rev::reverse_crc(
+                    crc,
+                    self.pairs.as_slice(),
+                    self.verbosity,
+                )),
+            };
+            let lenappend = crc.get_lenappend();
+            Some(solutions.map(move |x| x.map(|y| show_crc(y, lenappend))))
+        } else {
+            None
+        };
+        let maybe_wide_crc = if let BuilderEnum::WideCRC(crc) = &self.spec {
+            let lenappend = crc.get_lenappend();
             Some(
-                checksum::crc::rev::reverse_crc(crc, self.pairs.as_slice(), self.verbosity)
-                    .map(|x| x.map(|y| y.to_string())),
+                checksum::crc::rev::reverse_crc(crc, self.wide_pairs.as_slice(), self.verbosity)
+                    .map(move |x| x.map(|y| show_crc(y, lenappend))),
             )
         } else {
             None
@@ -192,21 +1219,135 @@ impl<'a> AlgorithmFinder<'a> {
         } else {
             None
         };
+        let maybe_xorsum = if let BuilderEnum::XorSum(xorsum) = &self.spec {
+            Some(
+                checksum::xorsum::rev::reverse_xorsum(
+                    xorsum,
+                    self.pairs.as_slice(),
+                    self.verbosity,
+                )
+                .map(|x| x.map(|y| y.to_string())),
+            )
+        } else {
+            None
+        };
+        let maybe_rotxor = if let BuilderEnum::RotXor(rotxor) = &self.spec {
+            Some(
+                checksum::rotxor::rev::reverse_rotxor(
+                    rotxor,
+                    self.pairs.as_slice(),
+                    self.verbosity,
+                )
+                .map(|x| x.map(|y| y.to_string())),
+            )
+        } else {
+            None
+        };
+        let maybe_polyhash = if let BuilderEnum::PolyHash(polyhash) = &self.spec {
+            Some(
+                checksum::polyhash::rev::reverse_polyhash(
+                    polyhash,
+                    self.pairs.as_slice(),
+                    self.verbosity,
+                )
+                .map(|x| x.map(|y| y.to_string())),
+            )
+        } else {
+            None
+        };
+        let maybe_affine = if let BuilderEnum::Affine(affine) = &self.spec {
+            Some(
+                checksum::affine::rev::reverse_affine(
+                    affine,
+                    self.pairs.as_slice(),
+                    self.verbosity,
+                )
+                .map(|x| x.map(|y| y.to_string())),
+            )
+        } else {
+            None
+        };
+        let maybe_weightsum = if let BuilderEnum::WeightSum(weightsum) = &self.spec {
+            Some(
+                checksum::weightsum::rev::reverse_weightsum(
+                    weightsum,
+                    self.pairs.as_slice(),
+                    self.verbosity,
+                )
+                .map(|x| x.map(|y| y.to_string())),
+            )
+        } else {
+            None
+        };
+        let maybe_concat = if let BuilderEnum::Concat = &self.spec {
+            Some(Self::combine_concat(
+                self.concat_parts
+                    .iter()
+                    .map(|p| p.find_all().collect())
+                    .collect(),
+            ))
+        } else {
+            None
+        };
         maybe_crc
             .into_iter()
             .flatten()
+            .chain(maybe_wide_crc.into_iter().flatten())
             .chain(maybe_modsum.into_iter().flatten())
             .chain(maybe_fletcher.into_iter().flatten())
+            .chain(maybe_xorsum.into_iter().flatten())
+            .chain(maybe_rotxor.into_iter().flatten())
+            .chain(maybe_polyhash.into_iter().flatten())
+            .chain(maybe_affine.into_iter().flatten())
+            .chain(maybe_weightsum.into_iter().flatten())
+            .chain(maybe_concat.into_iter().flatten())
+            .map(move |x| x.map(|y| self.add_transform(y)))
     }
 
     #[cfg(feature = "parallel")]
     pub fn find_all_para<'b>(
         &'b self,
+    ) -> impl ParallelIterator<Item = Result<String, CheckReverserError>> + 'b {
+        let solutions = if self.is_direct() {
+            Either::Left(self.find_direct_para())
+        } else {
+            Either::Right(self.find_preprocessed_para().into_par_iter())
+        };
+        solutions.map(move |x| x.map(|y| self.add_lanes(y)))
+    }
+
+    #[cfg(feature = "parallel")]
+    fn find_direct_para<'b>(
+        &'b self,
     ) -> impl ParallelIterator<Item = Result<String, CheckReverserError>> + 'b {
         let maybe_crc = if let BuilderEnum::CRC(crc) = &self.spec {
+            let solutions = match &self.crc_texts {
+                Some(texts) => Either::Left(checksum::crc::rev::reverse_crc_shared_para(
+                    crc,
+                    texts,
+                    &self.sums(),
+                    self.verbosity,
+                )),
+                None => Either::Right(checksum::crc::rev::reverse_crc_para(
+                    crc,
+                    self.pairs.as_slice(),
+                    self.verbosity,
+                )),
+            };
+            let lenappend = crc.get_lenappend();
+            Some(solutions.map(move |x| x.map(|y| show_crc(y, lenappend))))
+        } else {
+            None
+        };
+        let maybe_wide_crc = if let BuilderEnum::WideCRC(crc) = &self.spec {
+            let lenappend = crc.get_lenappend();
             Some(
-                checksum::crc::rev::reverse_crc_para(crc, self.pairs.as_slice(), self.verbosity)
-                    .map(|x| x.map(|y| y.to_string())),
+                checksum::crc::rev::reverse_crc_para(
+                    crc,
+                    self.wide_pairs.as_slice(),
+                    self.verbosity,
+                )
+                .map(move |x| x.map(|y| show_crc(y, lenappend))),
             )
         } else {
             None
@@ -236,40 +1377,363 @@ impl<'a> AlgorithmFinder<'a> {
         } else {
             None
         };
+        let maybe_xorsum = if let BuilderEnum::XorSum(xorsum) = &self.spec {
+            Some(
+                checksum::xorsum::rev::reverse_xorsum(
+                    xorsum,
+                    self.pairs.as_slice(),
+                    self.verbosity,
+                )
+                .map(|x| x.map(|y| y.to_string()))
+                .par_bridge(),
+            )
+        } else {
+            None
+        };
+        let maybe_rotxor = if let BuilderEnum::RotXor(rotxor) = &self.spec {
+            Some(
+                checksum::rotxor::rev::reverse_rotxor_para(
+                    rotxor,
+                    self.pairs.as_slice(),
+                    self.verbosity,
+                )
+                .map(|x| x.map(|y| y.to_string())),
+            )
+        } else {
+            None
+        };
+        let maybe_polyhash = if let BuilderEnum::PolyHash(polyhash) = &self.spec {
+            Some(
+                checksum::polyhash::rev::reverse_polyhash(
+                    polyhash,
+                    self.pairs.as_slice(),
+                    self.verbosity,
+                )
+                .map(|x| x.map(|y| y.to_string()))
+                .par_bridge(),
+            )
+        } else {
+            None
+        };
+        let maybe_affine = if let BuilderEnum::Affine(affine) = &self.spec {
+            Some(
+                checksum::affine::rev::reverse_affine(
+                    affine,
+                    self.pairs.as_slice(),
+                    self.verbosity,
+                )
+                .map(|x| x.map(|y| y.to_string()))
+                .par_bridge(),
+            )
+        } else {
+            None
+        };
+        let maybe_weightsum = if let BuilderEnum::WeightSum(weightsum) = &self.spec {
+            Some(
+                checksum::weightsum::rev::reverse_weightsum(
+                    weightsum,
+                    self.pairs.as_slice(),
+                    self.verbosity,
+                )
+                .map(|x| x.map(|y| y.to_string()))
+                .par_bridge(),
+            )
+        } else {
+            None
+        };
+        let maybe_concat = if let BuilderEnum::Concat = &self.spec {
+            Some(Self::combine_concat(
+                self.concat_parts
+                    .iter()
+                    .map(|p| p.find_all_para().collect())
+                    .collect(),
+            ))
+        } else {
+            None
+        };
         maybe_crc
             .into_par_iter()
             .flatten()
+            .chain(maybe_wide_crc.into_par_iter().flatten())
             .chain(maybe_modsum.into_par_iter().flatten())
             .chain(maybe_fletcher.into_par_iter().flatten())
+            .chain(maybe_xorsum.into_par_iter().flatten())
+            .chain(maybe_rotxor.into_par_iter().flatten())
+            .chain(maybe_polyhash.into_par_iter().flatten())
+            .chain(maybe_affine.into_par_iter().flatten())
+            .chain(maybe_weightsum.into_par_iter().flatten())
+            .chain(maybe_concat.into_par_iter().flatten())
+            .map(move |x| x.map(|y| self.add_transform(y)))
     }
 }
 
+/// Gets the files to give to an oracle for reversing the given model string, see the `oracle` module.
+///
+/// For `concat` models, the combined width of the components is used.
+pub fn oracle_files(strspec: &str) -> Result<Vec<Vec<u8>>, CheckBuilderErr> {
+    let width = match split_concat(strspec)? {
+        Some((parts, _)) => parts
+            .iter()
+            .map(|part| find_prefix_width(part).map(|(_, width, _)| width))
+            .sum::<Result<usize, _>>()?,
+        None => find_prefix_width(strspec)?.1,
+    };
+    if width == 0 {
+        return Err(CheckBuilderErr::ValueOutOfRange("width"));
+    }
+    Ok(oracle::chosen_files(width))
+}
+
+/// The families tried by `find_algorithm_auto`, together with their maximal width
+static AUTO_FAMILIES: &[(&str, usize)] = &[
+    ("crc", 128),
+    ("modsum", 64),
+    ("fletcher", 128),
+    ("xorsum", 64),
+    ("rotxor", 64),
+    ("polyhash", 64),
+];
+
+/// Guesses the widths of the checksums from how they are written.
+///
+/// The checksums are assumed to be written with leading zeros, so the widths are the ones with the
+/// same number of hexadecimal digits as the longest checksum, but at least the number of bits of the largest one.
+fn auto_widths(sum: &str) -> Result<RangeInclusive<usize>, CheckBuilderErr> {
+    // only for validation, since the leading zeros get lost
+    parse_sums::<U512>(sum)?;
+    let digits = sum.split(',').map(str::len).max().unwrap_or(0);
+    let bits = sum
+        .split(',')
+        .map(|s| {
+            let s = s.trim_start_matches('0');
+            match s.chars().next().and_then(|c| c.to_digit(16)) {
+                Some(first) => 4 * (s.len() - 1) + (32 - first.leading_zeros() as usize),
+                None => 0,
+            }
+        })
+        .max()
+        .unwrap_or(0);
+    let low = (4 * digits.saturating_sub(1) + 1).max(bits);
+    let high = (4 * digits).min(128);
+    if low > high {
+        return Err(CheckBuilderErr::ValueOutOfRange("width"));
+    }
+    Ok(low..=high)
+}
+
+/// Finds the algorithms of all families and widths that fit the files, see `find_algorithm_auto`.
+pub struct AutoFinder<'a> {
+    finders: Vec<(String, AlgorithmFinder<'a>)>,
+}
+
+impl<'a> AutoFinder<'a> {
+    /// The models that are tried, like `crc width=16`
+    pub fn models(&self) -> impl Iterator<Item = &str> {
+        self.finders.iter().map(|(model, _)| model.as_str())
+    }
+
+    /// Tries all models, returning the results together with the model they came from.
+    ///
+    /// Solutions which were already found with another model are left out.
+    pub fn find_all(&self) -> Vec<(&str, Result<String, CheckReverserError>)> {
+        let results = self
+            .finders
+            .iter()
+            .flat_map(|(model, finder)| finder.find_all().map(move |x| (model.as_str(), x)))
+            .collect();
+        Self::merge(results)
+    }
+
+    /// Parallel version of `find_all`, which tries the models at the same time.
+    #[cfg(feature = "parallel")]
+    pub fn find_all_para(&self) -> Vec<(&str, Result<String, CheckReverserError>)> {
+        let results = self
+            .finders
+            .par_iter()
+            .flat_map_iter(|(model, finder)| {
+                let solutions: Vec<_> = finder.find_all_para().collect();
+                solutions.into_iter().map(move |x| (model.as_str(), x))
+            })
+            .collect();
+        Self::merge(results)
+    }
+
+    fn merge(
+        results: Vec<(&str, Result<String, CheckReverserError>)>,
+    ) -> Vec<(&str, Result<String, CheckReverserError>)> {
+        let mut seen = HashSet::new();
+        results
+            .into_iter()
+            .filter(|(_, x)| match x {
+                Ok(solution) => seen.insert(solution.clone()),
+                Err(_) => true,
+            })
+            .collect()
+    }
+}
+
+/// Like `find_algorithm`, but without a model string: every linear family
+/// (`crc`, `modsum`, `fletcher`, `xorsum`, `rotxor` and `polyhash`) is tried with every width the checksums could have.
+///
+/// The checksums should be written with leading zeros, since the widths are guessed from the number of their digits.
+/// The files only get converted to polynomials once for the CRCs of all widths.
+pub fn find_algorithm_auto<'a>(
+    bytes: &'a [&[u8]],
+    sum: &str,
+    verbosity: u64,
+) -> Result<AutoFinder<'a>, CheckBuilderErr> {
+    let widths = auto_widths(sum)?;
+    let texts = Arc::new(CrcTexts::new(bytes));
+    let mut finders = Vec::new();
+    for &(family, max_width) in AUTO_FAMILIES {
+        for width in widths.clone() {
+            // the two halves of a fletcher need to have the same width
+            if width > max_width || (family == "fletcher" && width & 1 == 1) {
+                continue;
+            }
+            let model = format!("{} width={}", family, width);
+            let mut finder = find_algorithm(&model, bytes, sum, verbosity)?;
+            if family == "crc" {
+                finder.crc_texts = Some(Arc::clone(&texts));
+            }
+            finders.push((model, finder));
+        }
+    }
+    Ok(AutoFinder { finders })
+}
+
 pub fn find_algorithm<'a>(
     strspec: &str,
     bytes: &'a [&[u8]],
     sum: &str,
     verbosity: u64,
 ) -> Result<AlgorithmFinder<'a>, CheckBuilderErr> {
-    let (prefix, _, rest) = find_prefix_width(strspec)?;
+    if let Some((parts, rest)) = split_concat(strspec)? {
+        return find_concat_algorithm(&parts, rest, bytes, sum, verbosity);
+    }
+    let (prefix, width, rest) = find_prefix_width(strspec)?;
+    if DIGEST_PREFIXES.contains(&prefix) {
+        return Err(CheckBuilderErr::ValueOutOfRange("algorithm"));
+    }
     let prefix = prefix.to_ascii_lowercase();
+    let (lanes, input, rest) = split_input(rest)?;
+    // only for these families, the solutions for the different values of `xorin` are few enough to list them
+    if !input.is_known() && !matches!(prefix.as_str(), "crc" | "xorsum" | "rotxor") {
+        return Err(CheckBuilderErr::ValueOutOfRange("xorin"));
+    }
+    let (transform, rest) = split_transform(&rest, width)?;
     let spec = match prefix.as_str() {
-        "crc" => BuilderEnum::CRC(CRCBuilder::<u128>::from_str(rest)?),
-        "modsum" => BuilderEnum::ModSum(ModSumBuilder::<u64>::from_str(rest)?),
-        "fletcher" => BuilderEnum::Fletcher(FletcherBuilder::<u128>::from_str(rest)?),
+        "crc" if width > 128 => BuilderEnum::WideCRC(CRCBuilder::<U512>::from_str(&rest)?),
+        "crc" => BuilderEnum::CRC(CRCBuilder::<u128>::from_str(&rest)?),
+        "modsum" => BuilderEnum::ModSum(ModSumBuilder::<u64>::from_str(&rest)?),
+        "fletcher" => BuilderEnum::Fletcher(FletcherBuilder::<u128>::from_str(&rest)?),
+        "xorsum" => BuilderEnum::XorSum(XorSumBuilder::<u64>::from_str(&rest)?),
+        "rotxor" => BuilderEnum::RotXor(RotXorBuilder::<u64>::from_str(&rest)?),
+        "polyhash" => BuilderEnum::PolyHash(PolyHashBuilder::<u64>::from_str(&rest)?),
+        "affine" => BuilderEnum::Affine(AffineBuilder::from_str(&rest)?),
+        "weightsum" => BuilderEnum::WeightSum(WeightSumBuilder::from_str(&rest)?),
         _ => unimplemented!(),
     };
+    // if bits are cut off, the remaining ones are the sums of the same algorithm with a width of `outbits`
+    let (spec, transform) = if transform.is_invertible() {
+        (spec, transform)
+    } else {
+        (
+            truncated_spec(&spec, transform.outbits())?,
+            transform.truncated(),
+        )
+    };
     let sums = sum
         .split(|x| x == ',')
-        .map(u128::from_hex)
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|_| CheckBuilderErr::MalformedString(String::default()))?;
+        .map(|x| U512::from_hex(x).ok().and_then(|y| transform.invert(y)))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| CheckBuilderErr::MalformedString(String::default()))?;
     if sums.len() != bytes.len() {
         panic!("Help how do I error handle this?")
     }
-    let pairs: Vec<_> = bytes.iter().cloned().zip(sums.into_iter()).collect();
+    let wide_pairs: Vec<_> = bytes.iter().cloned().zip(sums.into_iter()).collect();
+    // everything that is not a wide crc uses u128 for the sums
+    let (pairs, wide_pairs) = if let BuilderEnum::WideCRC(_) = spec {
+        (Vec::new(), wide_pairs)
+    } else {
+        let pairs = wide_pairs
+            .into_iter()
+            .map(|(b, s)| s.to_u128().map(|s| (b, s)))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| CheckBuilderErr::MalformedString(String::default()))?;
+        (pairs, Vec::new())
+    };
     Ok(AlgorithmFinder {
         spec,
+        transform,
         pairs,
+        wide_pairs,
+        lanes,
+        input,
+        concat_parts: Vec::new(),
+        crc_texts: None,
+        verbosity,
+    })
+}
+
+/// The spec of the same algorithm for the lowest `bits` bits of the sums (see `LinearCheck::truncated`).
+///
+/// For the other algorithms and for modules which are not a power of two (but above `2^bits`),
+/// the higher bits influence the lower ones, so their parameters can't be found from the lower bits alone.
+fn truncated_spec(spec: &BuilderEnum, bits: usize) -> Result<BuilderEnum, CheckBuilderErr> {
+    let truncated = match spec {
+        BuilderEnum::ModSum(m) => m.truncated(bits).map(BuilderEnum::ModSum),
+        BuilderEnum::XorSum(x) => Some(BuilderEnum::XorSum(x.truncated(bits))),
+        BuilderEnum::PolyHash(p) => p.truncated(bits).map(BuilderEnum::PolyHash),
+        _ => None,
+    };
+    truncated.ok_or(CheckBuilderErr::ValueOutOfRange("outbits"))
+}
+
+/// For a `concat` model, the checksums get split up and each component is reversed on its own.
+fn find_concat_algorithm<'a>(
+    parts: &[&str],
+    rest: &str,
+    bytes: &'a [&[u8]],
+    sum: &str,
+    verbosity: u64,
+) -> Result<AlgorithmFinder<'a>, CheckBuilderErr> {
+    let widths = parts
+        .iter()
+        .map(|part| find_prefix_width(part).map(|(_, width, _)| width))
+        .collect::<Result<Vec<_>, _>>()?;
+    let width = widths.iter().sum();
+    if width > 128 {
+        return Err(CheckBuilderErr::ValueOutOfRange("width"));
+    }
+    let (lanes, input, rest) = split_known_input(rest)?;
+    let transform = concat_transform(&rest, width)?;
+    let sums = parse_sums::<u128>(sum)?
+        .into_iter()
+        .map(|s| transform.invert(s).map(|s| split_sum(s, &widths)))
+        .collect::<Option<Vec<_>>>()
+        .ok_or(CheckBuilderErr::ValueOutOfRange("outbits"))?;
+    let concat_parts = parts
+        .iter()
+        .enumerate()
+        .map(|(i, part)| {
+            let part_sums: Vec<_> = sums.iter().map(|s| format!("{:x}", s[i])).collect();
+            find_algorithm(part, bytes, &part_sums.join(","), verbosity)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    // the bytes can only be preprocessed for the whole model, like with `check` and `part`
+    if concat_parts.iter().any(|p| !p.is_direct()) {
+        return Err(CheckBuilderErr::ValueOutOfRange("stride"));
+    }
+    Ok(AlgorithmFinder {
+        spec: BuilderEnum::Concat,
+        transform,
+        pairs: Vec::new(),
+        wide_pairs: Vec::new(),
+        lanes,
+        input,
+        concat_parts,
+        crc_texts: None,
         verbosity,
     })
 }
@@ -0,0 +1,95 @@
+//! Chosen inputs for reversing a checksum when something can calculate it for arbitrary files.
+//!
+//! If there is an oracle for the checksum, like a device emulator or a vendor tool, the files for
+//! reversing don't have to be collected from somewhere, but can be chosen to make the reversers' job easy:
+//! * zero files of different lengths only depend on `init`, `xorout` and the length
+//! * unit impulses of the same length differ from the zero file by a single bit or byte, which
+//!   gives the polynomial of a CRC or the weight of a position directly
+//! * files of `0xff` bytes that are long enough for the sum to wrap around give the module of sums,
+//!   with one of them having the highest bit of the sum set
+//!
+//! All lengths are multiples of 8 bytes, so every word size up to 64 bits divides them.
+
+/// The length of the zero file the unit impulses are put into
+const IMPULSE_LEN: usize = 16;
+
+/// The maximal length of the `0xff` files, so that wide sums don't need huge files
+const MAX_FILL_LEN: usize = 1 << 20;
+
+/// Gets the files to give to an oracle for a checksum with `width` bits.
+///
+/// The files only depend on the width, so the same files work for every family of algorithms.
+/// A width of 0 is treated like a width of 1.
+pub fn chosen_files(width: usize) -> Vec<Vec<u8>> {
+    let width = width.max(1);
+    let mut files: Vec<Vec<u8>> = (1..=4).map(|n| vec![0u8; 8 * n]).collect();
+    for pos in 0..IMPULSE_LEN {
+        for bit in [0x01, 0x80] {
+            let mut impulse = vec![0u8; IMPULSE_LEN];
+            impulse[pos] = bit;
+            files.push(impulse);
+        }
+    }
+    // enough 0xff bytes for the plain sum to exceed 2^width once and twice
+    let wrap = ((1u128 << width.min(64)) / 0xff + 1).min(MAX_FILL_LEN as u128) as usize;
+    let wrap = ((wrap - 1) / 8 + 1) * 8;
+    let mut fill_lens = vec![8, 16, 24, wrap, (2 * wrap).min(MAX_FILL_LEN)];
+    // a sum with the highest bit set after wrapping, so that a module of 2^width can't be mistaken
+    // for a smaller power of two
+    let top_bit = (1..=MAX_FILL_LEN / 8)
+        .map(|n| 8 * n)
+        .find(|len| (*len as u128 * 0xff) >> (width.min(64) - 1) & 1 == 1);
+    fill_lens.extend(top_bit);
+    fill_lens.sort_unstable();
+    fill_lens.dedup();
+    files.extend(fill_lens.into_iter().map(|len| vec![0xffu8; len]));
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{find_algorithm, find_checksum, oracle_files};
+    fn reverse_chosen(model: &str, reverse_model: &str) -> Vec<String> {
+        let files = chosen_files(16);
+        let sums = find_checksum(model, &files).unwrap().join(",");
+        let slices: Vec<_> = files.iter().map(Vec::as_slice).collect();
+        find_algorithm(reverse_model, &slices, &sums, 0)
+            .unwrap()
+            .find_all()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+    #[test]
+    fn lengths() {
+        let files = chosen_files(16);
+        assert!(files.iter().all(|f| !f.is_empty() && f.len() & 7 == 0));
+        let longest = files.iter().map(Vec::len).max().unwrap();
+        assert!(longest * 0xff >= 2 * (1 << 16));
+        assert_eq!(
+            chosen_files(128).iter().map(Vec::len).max(),
+            Some(MAX_FILL_LEN)
+        );
+        assert!(oracle_files("crc width=0").is_err());
+        assert!(oracle_files("concat(modsum width=0, xorsum width=0)").is_err());
+        assert_eq!(chosen_files(0), chosen_files(1));
+    }
+    #[test]
+    fn unique_solutions() {
+        // polynomials divisible by x + 1 can't distinguish one bit of init from xorout for any lengths
+        let crc = "crc width=16 poly=0x8bb7 init=0x1234 xorout=0x5678 refin=true refout=true";
+        assert_eq!(reverse_chosen(crc, "crc width=16"), vec![crc]);
+        let modsum = "modsum width=16 module=0xfff1 init=0x1";
+        assert_eq!(reverse_chosen(modsum, "modsum width=16"), vec![modsum]);
+        // a plain byte sum needs a sum above 2^15 to rule out the smaller powers of two as module
+        assert_eq!(
+            reverse_chosen("modsum width=16", "modsum width=16"),
+            vec!["modsum width=16 module=0x10000 init=0x0"]
+        );
+        let fletcher = "fletcher width=16 module=0xff init=0x0 addout=0x0 swap=false";
+        assert_eq!(
+            reverse_chosen(fletcher, "fletcher width=16"),
+            vec![fletcher]
+        );
+    }
+}
@@ -1,60 +1,175 @@
-use delsum_lib::checksum::{RelativeIndex, Relativity};
-use delsum_lib::{find_algorithm, find_checksum, find_checksum_segments};
+use delsum_lib::checksum::{
+    brute::BruteOptions, presum::PresumStrategy, RangePairs, RelativeIndex, Relativity,
+};
+use delsum_lib::embedded::EmbeddedSum;
+use delsum_lib::endian::Endian;
+use delsum_lib::{
+    find_algorithm, find_algorithm_auto, find_checksum, find_checksum_segments_bits,
+    find_checksum_segments_scan, find_checksum_segments_with, oracle_files, SearchOptions,
+    SegmentError,
+};
+use memmap2::Mmap;
 #[cfg(feature = "parallel")]
 use rayon::prelude::*;
+use std::borrow::Cow;
 use std::ffi::OsString;
 use std::fs::File;
-use std::io::Read;
-use std::process::exit;
+use std::io::{ErrorKind, Read, Write};
+use std::num::ParseIntError;
+use std::ops::Range;
+use std::process::{exit, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
 use structopt::StructOpt;
 
 fn main() {
     let opt = Opt::from_args();
     match opt {
         Opt::Part(p) => part(&p),
+        Opt::Scan(s) => scan(&s),
         Opt::Reverse(r) => reverse(&r),
         Opt::Check(c) => check(&c),
     }
 }
 
 fn reverse(opts: &Reverse) {
-    let files = read_files(&opts.files);
+    // with an oracle, the files are chosen for each model
+    let (files, embedded_sums) = match opts.oracle {
+        Some(_) => (Vec::new(), None),
+        None => read_checksummed(&opts.files, opts.checksum_at, opts.checksum_fill),
+    };
+    // structopt makes sure that the checksums are given somehow without an oracle
+    let given_sums = embedded_sums
+        .as_deref()
+        .or(opts.checksums.as_deref())
+        .unwrap_or_default();
+    if opts.auto {
+        return reverse_auto(opts, &files, given_sums);
+    }
     let models = read_models(&opts.model, &opts.model_file);
-    let byte_slices: Vec<_> = files.iter().map(Vec::<u8>::as_slice).collect();
-    let algorithms = |model: &str| {
-        find_algorithm(&model, &byte_slices, &opts.checksums, opts.verbose).unwrap_or_else(|err| {
-            eprintln!("Could not process model '{}': {}", model, err);
-            exit(1);
-        })
+    let model_err = |model: &str, err| -> ! {
+        eprintln!("Could not process model '{}': {}", model, err);
+        exit(1);
+    };
+    let inputs = |model: &str| match &opts.oracle {
+        Some(cmd) => {
+            let files = oracle_files(model).unwrap_or_else(|err| model_err(model, err));
+            let checksums = ask_oracle(cmd, &files, opts.verbose);
+            (Cow::Owned(files), Cow::Owned(checksums))
+        }
+        None => (Cow::Borrowed(&files), Cow::Borrowed(given_sums)),
     };
     #[cfg(feature = "parallel")]
     let parallel = opts.parallel;
     #[cfg(not(feature = "parallel"))]
     let parallel = false;
+    let print_algorithms = |model: &String| {
+        let (files, checksums) = inputs(model);
+        let byte_slices: Vec<_> = files.iter().map(Vec::<u8>::as_slice).collect();
+        let algorithms = find_algorithm(model, &byte_slices, &checksums, opts.verbose)
+            .unwrap_or_else(|err| model_err(model, err));
+        let print = |algo| match algo {
+            Ok(a) => println!("{}", a),
+            Err(e) => eprintln!("Error on {}: {}", model, e),
+        };
+        match parallel {
+            true => {
+                #[cfg(feature = "parallel")]
+                algorithms.find_all_para().for_each(print);
+            }
+            false => algorithms.find_all().for_each(print),
+        }
+    };
 
     match parallel {
         true => {
             #[cfg(feature = "parallel")]
-            models.par_iter().for_each(|x| {
-                algorithms(x).find_all_para().for_each(|algo| match algo {
-                    Ok(a) => println!("{}", a),
-                    Err(e) => eprintln!("Error on {}: {}", x, e),
-                })
-            });
+            models.par_iter().for_each(print_algorithms);
         }
         false => {
-            models.iter().for_each(|x| {
-                algorithms(x).find_all().for_each(|algo| match algo {
-                    Ok(a) => println!("{}", a),
-                    Err(e) => eprintln!("Error on {}: {}", x, e),
-                })
-            });
+            models.iter().for_each(print_algorithms);
         }
     }
 }
 
+/// Tries every family and every width fitting the checksums, instead of the given models
+fn reverse_auto(opts: &Reverse, files: &[Vec<u8>], checksums: &str) {
+    let byte_slices: Vec<_> = files.iter().map(Vec::<u8>::as_slice).collect();
+    let finder = find_algorithm_auto(&byte_slices, checksums, opts.verbose).unwrap_or_else(|err| {
+        eprintln!("Could not guess the models from the checksums: {}", err);
+        exit(1);
+    });
+    #[cfg(feature = "parallel")]
+    let solutions = match opts.parallel {
+        true => finder.find_all_para(),
+        false => finder.find_all(),
+    };
+    #[cfg(not(feature = "parallel"))]
+    let solutions = finder.find_all();
+    for (model, solution) in solutions {
+        match solution {
+            Ok(a) => println!("{}", a),
+            // most models don't fit, so the errors are only interesting when asked for
+            Err(e) if opts.verbose > 0 => eprintln!("Error on {}: {}", model, e),
+            Err(_) => (),
+        }
+    }
+}
+
+/// Runs the oracle command once for every file, which gets the file on stdin and has to print its checksum in hex
+fn ask_oracle(cmd: &str, files: &[Vec<u8>], verbose: u64) -> String {
+    if verbose > 0 {
+        eprintln!(
+            "<oracle> asking '{}' for the checksums of {} files",
+            cmd,
+            files.len()
+        );
+    }
+    let oracle_err = |msg: String| -> ! {
+        eprintln!("Oracle '{}' failed: {}", cmd, msg);
+        exit(1);
+    };
+    let mut checksums = Vec::new();
+    for file in files {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(cmd)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap_or_else(|err| oracle_err(err.to_string()));
+        let mut stdin = child.stdin.take().unwrap();
+        // the oracle might not need to read everything, in which case the pipe gets closed early
+        match stdin.write_all(file) {
+            Err(err) if err.kind() != ErrorKind::BrokenPipe => oracle_err(err.to_string()),
+            _ => drop(stdin),
+        }
+        let output = child
+            .wait_with_output()
+            .unwrap_or_else(|err| oracle_err(err.to_string()));
+        if !output.status.success() {
+            oracle_err(output.status.to_string());
+        }
+        let out = String::from_utf8_lossy(&output.stdout);
+        let out = out.trim();
+        let sum = out
+            .strip_prefix("0x")
+            .or_else(|| out.strip_prefix("0X"))
+            .unwrap_or(out);
+        if sum.is_empty() || !sum.chars().all(|c| c.is_ascii_hexdigit()) {
+            oracle_err(format!("expected a hexadecimal checksum, got '{}'", out));
+        }
+        checksums.push(sum.to_owned());
+    }
+    checksums.join(",")
+}
+
 fn part(opts: &Part) {
-    let files = read_files(&opts.files);
+    let (files, embedded_sums) = map_checksummed(&opts.files, opts.checksum_at, opts.checksum_fill);
+    // structopt makes sure that the checksums are given somehow
+    let checksums = embedded_sums
+        .as_deref()
+        .or(opts.checksums.as_deref())
+        .unwrap_or_default();
     let models = read_models(&opts.model, &opts.model_file);
     let rel = if opts.start {
         Relativity::Start
@@ -65,34 +180,42 @@ fn part(opts: &Part) {
     let parallel = opts.parallel;
     #[cfg(not(feature = "parallel"))]
     let parallel = false;
-    let subsum_print = |model| {
-        let segs =
-            find_checksum_segments(model, &files, &opts.checksums, rel).unwrap_or_else(|err| {
-                eprintln!("Could not process model '{}': {}", model, err);
-                exit(1);
-            });
-        if !segs.is_empty() {
-            let mut list = String::new();
-            list.push_str(&format!("{}:\n", model));
-            for (a, b) in segs {
-                let a_list = a
-                    .iter()
-                    .map(|x| format!("{}", x))
-                    .collect::<Vec<_>>()
-                    .join(",");
-                let b_list = b
-                    .iter()
-                    .map(|x| match x {
-                        RelativeIndex::FromStart(n) => format!("{}", n),
-                        RelativeIndex::FromEnd(n) => format!("-{}", n),
-                    })
-                    .collect::<Vec<_>>()
-                    .join(",");
-                list.push_str(&format!("\t{}:{}\n", a_list, b_list));
-            }
-            print!("{}", list);
+    let search_opts = SearchOptions {
+        brute: BruteOptions {
+            max_len: opts.max_len,
+            start_align: opts.start_align,
+            end_align: opts.end_align,
+            parallel,
+        },
+        presums: match (opts.spill, opts.max_memory) {
+            (Some(run_len), _) => PresumStrategy::External { run_len },
+            (None, Some(max_memory)) => PresumStrategy::Auto { max_memory },
+            (None, None) => PresumStrategy::InMemory,
+        },
+    };
+    let find_segments = |model: &str, files: &[FileBytes], checksums: &str, rel| {
+        if opts.bits {
+            find_checksum_segments_bits(model, files, checksums, rel).map_err(SegmentError::from)
+        } else {
+            find_checksum_segments_with(model, files, checksums, rel, &search_opts)
         }
     };
+    // the segments are found in the data, so they get moved to where they are in the files
+    let checksum_at = opts.checksum_at.map(|at| EmbeddedSum {
+        fill: opts.checksum_fill,
+        ..at
+    });
+    let subsum_print = |model| {
+        let segs = find_segments(model, &files, checksums, rel).unwrap_or_else(|err| {
+            eprintln!("Could not process model '{}': {}", model, err);
+            exit(1);
+        });
+        let segs = match checksum_at {
+            Some(at) => at.file_segments(segs, &files, opts.bits),
+            None => segs,
+        };
+        print_segments(model, segs);
+    };
     match parallel {
         true => {
             #[cfg(feature = "parallel")]
@@ -104,10 +227,82 @@ fn part(opts: &Part) {
     };
 }
 
-fn check(opts: &Check) {
+fn scan(opts: &Scan) {
     let files = read_files(&opts.files);
     let models = read_models(&opts.model, &opts.model_file);
+    let rel = if opts.end || !opts.start {
+        Relativity::End
+    } else {
+        Relativity::Start
+    };
+    let endians = match opts.endian {
+        Some(e) => vec![e],
+        None => Endian::both(),
+    };
+    #[cfg(feature = "parallel")]
+    let parallel = opts.parallel;
+    #[cfg(not(feature = "parallel"))]
+    let parallel = false;
+    let scan_print = |model: &str| {
+        for endian in &endians {
+            if opts.verbose > 0 {
+                eprintln!("<scan> {} with {} endian checksums", model, endian);
+            }
+            let segs = find_checksum_segments_scan(model, &files, opts.offset, *endian, rel)
+                .unwrap_or_else(|err| {
+                    eprintln!("Could not process model '{}': {}", model, err);
+                    exit(1);
+                });
+            let header = format!(
+                "{} ({} endian checksum at end{:+})",
+                model, endian, opts.offset
+            );
+            print_segments(&header, segs);
+        }
+    };
+    match parallel {
+        true => {
+            #[cfg(feature = "parallel")]
+            models.par_iter().map(|x| x.as_str()).for_each(scan_print);
+        }
+        false => {
+            models.iter().map(|x| x.as_str()).for_each(scan_print);
+        }
+    };
+}
+
+/// Prints the segments found for a model, if there are any
+fn print_segments(header: &str, segs: RangePairs) {
+    if segs.is_empty() {
+        return;
+    }
+    let mut list = String::new();
+    list.push_str(&format!("{}:\n", header));
+    for (a, b) in segs {
+        let a_list = a
+            .iter()
+            .map(|x| format!("{}", x))
+            .collect::<Vec<_>>()
+            .join(",");
+        let b_list = b
+            .iter()
+            .map(|x| match x {
+                RelativeIndex::FromStart(n) => format!("{}", n),
+                RelativeIndex::FromEnd(n) => format!("-{}", n),
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        list.push_str(&format!("\t{}:{}\n", a_list, b_list));
+    }
+    print!("{}", list);
+}
+
+fn check(opts: &Check) {
+    let (files, embedded_sums) =
+        read_checksummed(&opts.files, opts.checksum_at, opts.checksum_fill);
+    let models = read_models(&opts.model, &opts.model_file);
     let is_single = models.len() <= 1;
+    let mismatch = AtomicBool::new(false);
     #[cfg(feature = "parallel")]
     let parallel = opts.parallel;
     #[cfg(not(feature = "parallel"))]
@@ -122,6 +317,23 @@ fn check(opts: &Check) {
         } else {
             println!("{}: {}", model, checksums.join(","))
         }
+        let embedded = match &embedded_sums {
+            Some(sums) => sums.split(','),
+            None => return,
+        };
+        for ((sum, expected), name) in checksums.iter().zip(embedded).zip(&opts.files) {
+            let trim = |s: &str| s.trim_start_matches('0').to_ascii_lowercase();
+            if trim(sum) != trim(expected) {
+                eprintln!(
+                    "{}: checksum {} of '{}' does not match the embedded checksum {}",
+                    model,
+                    sum,
+                    name.to_string_lossy(),
+                    expected
+                );
+                mismatch.store(true, Ordering::Relaxed);
+            }
+        }
     };
     match parallel {
         true => {
@@ -132,11 +344,15 @@ fn check(opts: &Check) {
             models.iter().for_each(|x| print_sums(x));
         }
     }
+    if mismatch.load(Ordering::Relaxed) {
+        exit(1);
+    }
 }
 
 #[derive(Debug, StructOpt)]
 enum Opt {
     Part(Part),
+    Scan(Scan),
     Reverse(Reverse),
     Check(Check),
 }
@@ -154,6 +370,24 @@ struct Part {
     /// Sets the end of the checksum segments to be relative to the end of the file (default)
     #[structopt(short, long)]
     end: bool,
+    /// Search with bit granularity, printing bit positions instead of byte positions (only for crc)
+    #[structopt(short, long)]
+    bits: bool,
+    /// The maximum length of the segments (only for non-linear algorithms, pad and lenappend)
+    #[structopt(long)]
+    max_len: Option<usize>,
+    /// Only search segments starting at a multiple of this (only for non-linear algorithms, pad and lenappend)
+    #[structopt(long, default_value = "1")]
+    start_align: usize,
+    /// Only search segments ending at a multiple of this (only for non-linear algorithms, pad and lenappend)
+    #[structopt(long, default_value = "1")]
+    end_align: usize,
+    /// Sort the presums of this many bytes at a time in temporary files instead of all in memory (only for linear algorithms)
+    #[structopt(long)]
+    spill: Option<usize>,
+    /// Choose how the presums are sorted so they take at most this many bytes, with an optional K, M, G or T suffix (only for linear algorithms)
+    #[structopt(long, conflicts_with = "spill", parse(try_from_str = parse_size))]
+    max_memory: Option<usize>,
     /// Do more parallelism, in turn using more memory
     #[structopt(short, long)]
     parallel: bool,
@@ -164,8 +398,46 @@ struct Part {
     #[structopt(short = "M", long)]
     model_file: Option<OsString>,
     /// A comma separated list of checksums, each corresponding to a file
+    #[structopt(short, long, required_unless = "checksum-at")]
+    checksums: Option<String>,
+    /// Read the checksum from each file at <offset>:<len>:<endian>, with a negative offset counting from the end
+    #[structopt(long, conflicts_with = "checksums", allow_hyphen_values = true)]
+    checksum_at: Option<EmbeddedSum>,
+    /// Overwrite the embedded checksum with this hex byte instead of removing it from the data
+    #[structopt(long, requires = "checksum-at", parse(try_from_str = parse_byte))]
+    checksum_fill: Option<u8>,
+    /// The files of which to find checksummed parts
+    files: Vec<OsString>,
+}
+
+/// With given checksum algorithms, find parts of the files whose checksum is stored right after them
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+struct Scan {
+    /// Print some messages indicating progress
+    #[structopt(short, long, parse(from_occurrences))]
+    verbose: u64,
+    /// Sets the end of the checksum segments to be relative to the start of the file
     #[structopt(short, long)]
-    checksums: String,
+    start: bool,
+    /// Sets the end of the checksum segments to be relative to the end of the file (default)
+    #[structopt(short, long, conflicts_with = "start")]
+    end: bool,
+    /// The position of the checksum relative to the end of the segments, where 0 is right after them
+    #[structopt(short, long, default_value = "0", allow_hyphen_values = true)]
+    offset: isize,
+    /// Only look for checksums stored with this byte order (little or big), instead of both
+    #[structopt(long)]
+    endian: Option<Endian>,
+    /// Do more parallelism, in turn using more memory
+    #[structopt(short, long)]
+    parallel: bool,
+    /// Use the checksum algorithm given by the model string
+    #[structopt(short, long)]
+    model: Option<String>,
+    /// Read model strings line-by-line from given file
+    #[structopt(short = "M", long)]
+    model_file: Option<OsString>,
     /// The files of which to find checksummed parts
     files: Vec<OsString>,
 }
@@ -180,6 +452,9 @@ struct Reverse {
     /// Do more parallelism, in turn using more memory
     #[structopt(short, long)]
     parallel: bool,
+    /// Try all linear algorithm families with all widths fitting the checksums, instead of a model
+    #[structopt(short, long, conflicts_with_all = &["model", "model-file", "oracle"])]
+    auto: bool,
     /// Use the checksum algorithm given by the model string
     #[structopt(short, long)]
     model: Option<String>,
@@ -187,8 +462,17 @@ struct Reverse {
     #[structopt(short = "M", long)]
     model_file: Option<OsString>,
     /// A comma separated list of checksums, each corresponding to a file
-    #[structopt(short, long)]
-    checksums: String,
+    #[structopt(short, long, required_unless_one = &["oracle", "checksum-at"])]
+    checksums: Option<String>,
+    /// Read the checksum from each file at <offset>:<len>:<endian>, with a negative offset counting from the end
+    #[structopt(long, conflicts_with = "checksums", allow_hyphen_values = true)]
+    checksum_at: Option<EmbeddedSum>,
+    /// Overwrite the embedded checksum with this hex byte instead of removing it from the data
+    #[structopt(long, requires = "checksum-at", parse(try_from_str = parse_byte))]
+    checksum_fill: Option<u8>,
+    /// A shell command which reads a file from stdin and prints its checksum in hex, used instead of files and checksums
+    #[structopt(short, long, conflicts_with_all = &["checksums", "checksum-at", "files"])]
+    oracle: Option<String>,
     /// The files of which to find checksummed parts
     files: Vec<OsString>,
 }
@@ -209,9 +493,31 @@ struct Check {
     /// Read model strings line-by-line from given file
     #[structopt(short = "M", long)]
     model_file: Option<OsString>,
+    /// Compare with the checksum in each file at <offset>:<len>:<endian>, with a negative offset counting from the end
+    #[structopt(long, allow_hyphen_values = true)]
+    checksum_at: Option<EmbeddedSum>,
+    /// Overwrite the embedded checksum with this hex byte instead of removing it from the data
+    #[structopt(long, requires = "checksum-at", parse(try_from_str = parse_byte))]
+    checksum_fill: Option<u8>,
     /// The files of which to find checksummed parts
     files: Vec<OsString>,
 }
+
+fn parse_byte(s: &str) -> Result<u8, ParseIntError> {
+    u8::from_str_radix(s.strip_prefix("0x").unwrap_or(s), 16)
+}
+
+fn parse_size(s: &str) -> Result<usize, String> {
+    let units = [('K', 10), ('M', 20), ('G', 30), ('T', 40)];
+    let (num, shift) = units
+        .iter()
+        .find_map(|(unit, shift)| Some((s.strip_suffix(*unit)?, *shift)))
+        .unwrap_or((s, 0));
+    let num: usize = num.parse().map_err(|e: ParseIntError| e.to_string())?;
+    num.checked_mul(1 << shift)
+        .ok_or_else(|| String::from("size is too large"))
+}
+
 fn read_models(model: &Option<String>, model_file: &Option<OsString>) -> Vec<String> {
     model_file.clone().map_or_else(
         || {
@@ -259,3 +565,130 @@ fn read_files(files: &[OsString]) -> Vec<Vec<u8>> {
     }
     bytes
 }
+
+/// The bytes of a file, which are memory-mapped if possible, so that the file does not have to fit into memory
+enum FileBytes {
+    /// The given range of a mapped file
+    Mapped(Mmap, Range<usize>),
+    /// A file which could not be mapped, like a pipe, or data which had to be changed
+    Read(Vec<u8>),
+}
+
+impl FileBytes {
+    /// Only keeps the bytes in `range`
+    fn slice(self, range: Range<usize>) -> FileBytes {
+        match self {
+            FileBytes::Mapped(map, r) => {
+                FileBytes::Mapped(map, r.start + range.start..r.start + range.end)
+            }
+            FileBytes::Read(mut bytes) => {
+                bytes.truncate(range.end);
+                bytes.drain(..range.start);
+                FileBytes::Read(bytes)
+            }
+        }
+    }
+}
+
+impl AsRef<[u8]> for FileBytes {
+    fn as_ref(&self) -> &[u8] {
+        match self {
+            FileBytes::Mapped(map, range) => &map[range.clone()],
+            FileBytes::Read(bytes) => bytes,
+        }
+    }
+}
+
+/// Like `read_files`, but the files get memory-mapped if possible
+fn map_files(files: &[OsString]) -> Vec<FileBytes> {
+    let mut bytes = Vec::new();
+    for file in files {
+        let mut f = File::open(file).unwrap_or_else(|err| {
+            eprintln!("Could not open file '{}': {}", file.to_string_lossy(), err);
+            exit(1);
+        });
+        // the files are only read, and changing them while delsum runs gives wrong results anyway
+        if let Ok(map) = unsafe { Mmap::map(&f) } {
+            let len = map.len();
+            bytes.push(FileBytes::Mapped(map, 0..len));
+            continue;
+        }
+        let mut current_bytes = Vec::new();
+        f.read_to_end(&mut current_bytes).unwrap_or_else(|err| {
+            eprintln!("Could not read file '{}': {}", file.to_string_lossy(), err);
+            exit(1);
+        });
+        bytes.push(FileBytes::Read(current_bytes));
+    }
+    bytes
+}
+
+/// Like `read_checksummed`, but the files get memory-mapped if possible.
+///
+/// The data is only copied if the checksum is not at the start or end of a file or gets overwritten by `fill`.
+fn map_checksummed(
+    files: &[OsString],
+    checksum_at: Option<EmbeddedSum>,
+    fill: Option<u8>,
+) -> (Vec<FileBytes>, Option<String>) {
+    let bytes = map_files(files);
+    let mut embedded = match checksum_at {
+        Some(e) => e,
+        None => return (bytes, None),
+    };
+    embedded.fill = fill;
+    let mut checksums = Vec::new();
+    let mut data = Vec::new();
+    for (file, name) in bytes.into_iter().zip(files) {
+        let len = file.as_ref().len();
+        let (pos, sum) = match (embedded.position(len), embedded.read(file.as_ref())) {
+            (Some(pos), Some(sum)) => (pos, sum),
+            _ => {
+                eprintln!(
+                    "File '{}' is too short for a checksum at {}",
+                    name.to_string_lossy(),
+                    embedded
+                );
+                exit(1);
+            }
+        };
+        let end = pos + embedded.len;
+        data.push(match embedded.fill {
+            None if pos == 0 => file.slice(end..len),
+            None if end == len => file.slice(0..pos),
+            _ => FileBytes::Read(embedded.split(file.as_ref()).unwrap().0),
+        });
+        checksums.push(sum);
+    }
+    (data, Some(checksums.join(",")))
+}
+
+/// Reads the files, and with `--checksum-at` also the checksums embedded in them,
+/// which are taken out of the data and returned as a comma separated list
+fn read_checksummed(
+    files: &[OsString],
+    checksum_at: Option<EmbeddedSum>,
+    fill: Option<u8>,
+) -> (Vec<Vec<u8>>, Option<String>) {
+    let bytes = read_files(files);
+    let mut embedded = match checksum_at {
+        Some(e) => e,
+        None => return (bytes, None),
+    };
+    embedded.fill = fill;
+    let mut checksums = Vec::new();
+    let mut data = Vec::new();
+    for (file, name) in bytes.iter().zip(files) {
+        let (d, sum) = embedded.split(file).unwrap_or_else(|| {
+            eprintln!(
+                "File '{}' is too short for a checksum at {}",
+                name.to_string_lossy(),
+                embedded
+            );
+            exit(1);
+        });
+        data.push(d);
+        checksums.push(sum);
+    }
+    (data, Some(checksums.join(",")))
+}
@@ -0,0 +1,35 @@
+//! Runs `delsum reverse --oracle` with small shell commands as oracles.
+use std::process::{Command, Output};
+
+/// Sums up the bytes on stdin and prints the sum modulo 2^16 with a `0x` prefix
+const BYTE_SUM: &str = "od -An -v -tu1 | awk '{ for (i = 1; i <= NF; i++) s += $i } \
+                        END { printf \"0x%x\\n\", s % 65536 }'";
+
+fn reverse_with_oracle(model: &str, oracle: &str) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_delsum"))
+        .args(["reverse", "-m", model, "--oracle", oracle])
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn byte_sum() {
+    let output = reverse_with_oracle("modsum width=16", BYTE_SUM);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "modsum width=16 module=0x10000 init=0x0\n"
+    );
+}
+
+#[test]
+fn failing_oracle() {
+    // a failing exit status (without reading stdin), a non-hexadecimal and an empty output
+    for oracle in &["exit 3", "echo nope", "true"] {
+        let output = reverse_with_oracle("modsum width=16", oracle);
+        assert_eq!(output.status.code(), Some(1));
+        assert!(output.stdout.is_empty());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.starts_with(&format!("Oracle '{}' failed", oracle)));
+    }
+}